@@ -109,3 +109,20 @@ export type CommitState =
 	| { readonly type: 'Integrated' };
 
 export type CommitStateType = CommitState['type'];
+
+/** The outcome of verifying the signature of a commit, similar to what `git log --format=%G?` would show. */
+export type SignatureStatus =
+	/** The commit isn't signed. */
+	| { readonly type: 'Unsigned' }
+	/** The signature is valid and made by a trusted key, owned by the signer in `subject`. */
+	| { readonly type: 'Good'; readonly subject: { readonly signer: string } }
+	/** The signature is valid, but the key that made it isn't trusted or, for SSH signatures, not in the allowed signers. */
+	| { readonly type: 'Untrusted'; readonly subject: { readonly signer: string | null } }
+	/** The signature was valid, but it or its key expired. */
+	| { readonly type: 'Expired'; readonly subject: { readonly signer: string } }
+	/** The signature was made by a key that was revoked. */
+	| { readonly type: 'Revoked'; readonly subject: { readonly signer: string } }
+	/** The signature doesn't match the commit. */
+	| { readonly type: 'Bad' }
+	/** The signature couldn't be checked, for instance because the key isn't available. */
+	| { readonly type: 'Unverifiable'; readonly subject: { readonly reason: string } };
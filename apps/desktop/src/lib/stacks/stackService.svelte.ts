@@ -13,7 +13,7 @@ import {
 import { createEntityAdapter, type EntityState } from '@reduxjs/toolkit';
 import type { PostHogWrapper } from '$lib/analytics/posthog';
 import type { BranchPushResult, SeriesIntegrationStrategy } from '$lib/branches/branchController';
-import type { Commit, StackBranch, UpstreamCommit } from '$lib/branches/v3';
+import type { Commit, SignatureStatus, StackBranch, UpstreamCommit } from '$lib/branches/v3';
 import type { CommitKey } from '$lib/commits/commit';
 import type { DefaultForgeFactory } from '$lib/forge/forgeFactory.svelte';
 import type { TreeChange } from '$lib/hunks/change';
@@ -349,6 +349,13 @@ export class StackService {
 		return result;
 	}
 
+	commitSignatureStatus(projectId: string, commitId: string) {
+		const result = $derived(
+			this.api.endpoints.commitSignatureStatus.useQuery({ projectId, commitId })
+		);
+		return result;
+	}
+
 	branchChanges(projectId: string, stackId: string, branchName: string) {
 		return this.api.endpoints.branchChanges.useQuery(
 			{ projectId, stackId, branchName },
@@ -618,6 +625,12 @@ function injectEndpoints(api: ClientState['backendApi']) {
 					return commitChangesAdapter.addMany(commitChangesAdapter.getInitialState(), changes);
 				}
 			}),
+			commitSignatureStatus: build.query<SignatureStatus, { projectId: string; commitId: string }>({
+				query: ({ projectId, commitId }) => ({
+					command: 'commit_signature_status',
+					params: { projectId, commitId }
+				})
+			}),
 			branchChanges: build.query<
 				EntityState<TreeChange, string>,
 				{ projectId: string; stackId: string; branchName: string }
@@ -19,11 +19,26 @@ pub struct Args {
     /// The production version is used if unset.
     #[clap(short = 's', long)]
     pub app_suffix: Option<String>,
+    /// How to print the results of a command.
+    ///
+    /// `json` wraps the result or the error into a versioned envelope, and is meant to be consumed by scripts.
+    #[clap(long, short = 'f', value_enum, default_value_t = OutputFormat::Human, global = true)]
+    pub format: OutputFormat,
 
     #[clap(subcommand)]
     pub cmd: Subcommands,
 }
 
+/// The way results of commands are printed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Output for humans, which isn't stable and may change at any time.
+    #[default]
+    Human,
+    /// A JSON object with `version` and either `data` or `error` on a single line.
+    Json,
+}
+
 #[derive(Debug, clap::Subcommand)]
 pub enum Subcommands {
     /// Commit or amend all worktree changes to a new commit.
@@ -59,6 +74,10 @@ pub enum Subcommands {
         /// The revspec to create the commit on top of, or the commit to amend to.
         #[clap(long)]
         parent: Option<String>,
+        /// A file with a unified diff, as produced by `git diff` or `git format-patch`, to commit instead of
+        /// worktree changes. Use `-` to read it from standard input.
+        #[clap(long, conflicts_with_all = ["current_path", "previous_path", "hunk_headers"])]
+        patch: Option<PathBuf>,
     },
     /// List all uncommitted working tree changes.
     Status {
@@ -101,6 +120,202 @@ pub enum Subcommands {
     StackBranches { id: String },
     /// Returns all commits for the branch with the given `name` in the stack with the given `id`.
     StackBranchCommits { id: String, name: String },
+    /// Fold all `fixup!`, `squash!` and `amend!` commits of the stack with the given `id` into the commits they refer to.
+    Autosquash { id: String },
+    /// Create, rename, reorder, apply and unapply stacks of the workspace.
+    Stack {
+        #[clap(subcommand)]
+        cmd: stack::Subcommands,
+    },
+    /// List, inspect, diff and restore snapshots of the operations log.
+    Oplog {
+        #[clap(subcommand)]
+        cmd: oplog::Subcommands,
+    },
+}
+
+pub mod oplog {
+    use std::path::PathBuf;
+
+    #[derive(Debug, clap::Subcommand)]
+    pub enum Subcommands {
+        /// List snapshots, most recent first.
+        List {
+            /// The maximum amount of snapshots to list, after filtering.
+            #[clap(long, short = 'l', default_value_t = 100)]
+            limit: usize,
+            /// Only list snapshots with the given operation kind, like `CreateCommit`. May be repeated.
+            #[clap(long = "kind", short = 'k', value_name = "OPERATION")]
+            kinds: Vec<String>,
+            /// Only list snapshots created at or after the given date, like `2 days ago` or `2025-01-31`.
+            #[clap(long)]
+            since: Option<String>,
+            /// Only list snapshots created at or before the given date, like `1 hour ago` or `2025-01-31`.
+            #[clap(long)]
+            until: Option<String>,
+            /// Only list snapshots with a trailer `KEY`, or one with `KEY=VALUE`. May be repeated.
+            #[clap(long = "trailer", short = 't', value_name = "KEY[=VALUE]")]
+            trailers: Vec<String>,
+            /// Only list pinned snapshots.
+            #[clap(long)]
+            pinned: bool,
+            /// Print the snapshots as JSON instead of one line per snapshot, the same as `--format json`.
+            #[clap(long)]
+            json: bool,
+        },
+        /// Show all details of a single snapshot.
+        Show {
+            /// The revspec of the snapshot commit, like its (abbreviated) hash.
+            snapshot: String,
+            /// Print the snapshot as JSON, the same as `--format json`.
+            #[clap(long)]
+            json: bool,
+        },
+        /// Show the worktree changes that a snapshot recorded compared to its predecessor.
+        ///
+        /// If `other` is given, show how the worktree, stacks, branches and target changed from `snapshot` to `other` instead.
+        Diff {
+            /// The revspec of the snapshot commit, like its (abbreviated) hash.
+            snapshot: String,
+            /// The revspec of a second snapshot to compare `snapshot` with.
+            other: Option<String>,
+            /// Print the changes as JSON, the same as `--format json`.
+            #[clap(long)]
+            json: bool,
+        },
+        /// Restore the worktree and GitButler state to what it was in the given snapshot.
+        ///
+        /// A new snapshot of the state before the restore is recorded so it can be undone.
+        Restore {
+            /// The revspec of the snapshot commit, like its (abbreviated) hash.
+            snapshot: String,
+        },
+        /// Remove old snapshots, keeping those selected by any of the given rules.
+        ///
+        /// Without rules, the retention policy configured for the project is used.
+        Gc {
+            /// Keep this many of the most recent snapshots.
+            #[clap(long)]
+            keep_last: Option<usize>,
+            /// Keep all snapshots created after the given date, like `2 weeks ago`.
+            #[clap(long, value_name = "DATE")]
+            keep_newer_than: Option<String>,
+            /// Keep the most recent snapshot of each of the last N days with snapshots.
+            #[clap(long, value_name = "N")]
+            daily: Option<usize>,
+            /// Keep the most recent snapshot of each of the last N weeks with snapshots.
+            #[clap(long, value_name = "N")]
+            weekly: Option<usize>,
+        },
+        /// Create a snapshot of the current state with the given title, to be able to return to it later.
+        Checkpoint {
+            /// The title of the checkpoint, like `before big refactor`.
+            title: String,
+            /// A longer description of the checkpoint.
+            #[clap(long, short = 'm')]
+            body: Option<String>,
+            /// Pin the checkpoint so it is never removed when pruning the operations log.
+            #[clap(long)]
+            pin: bool,
+        },
+        /// Pin a snapshot so it is never removed when pruning the operations log.
+        Pin {
+            /// The revspec of the snapshot commit, like its (abbreviated) hash.
+            snapshot: String,
+        },
+        /// Remove the pin of a snapshot so it can be pruned again.
+        Unpin {
+            /// The revspec of the snapshot commit, like its (abbreviated) hash.
+            snapshot: String,
+        },
+        /// Write the most recent snapshots into a git bundle, with a manifest next to it.
+        Export {
+            /// The path to write the bundle to.
+            bundle: PathBuf,
+            /// The amount of most recent snapshots to export, or all of them if unset.
+            #[clap(long)]
+            depth: Option<usize>,
+        },
+        /// Add the snapshots of a bundle created with `export` on top of the operations log.
+        Import {
+            /// The path to the bundle to read.
+            bundle: PathBuf,
+        },
+    }
+}
+
+pub mod stack {
+    use std::path::PathBuf;
+
+    #[derive(Debug, clap::Subcommand)]
+    pub enum Subcommands {
+        /// Create a new branch and add it to the workspace as a stack without commits.
+        Create {
+            /// The name of the branch, like `feature`.
+            name: String,
+            /// The revspec of the commit to create the branch at, defaulting to the tip of the target branch.
+            #[clap(long)]
+            base: Option<String>,
+            /// Only create the branch and its metadata, but don't add it to the workspace.
+            #[clap(long)]
+            unapplied: bool,
+        },
+        /// Rename the branch of a stack, along with its metadata.
+        Rename {
+            /// The current name of the branch.
+            name: String,
+            /// The new name of the branch.
+            new_name: String,
+        },
+        /// Change the order of the stacks in the workspace.
+        ///
+        /// Stacks that aren't mentioned keep their relative order and are placed after the given ones.
+        Reorder {
+            /// The names of branches of the stacks, in the order the stacks should have.
+            #[clap(required = true)]
+            names: Vec<String>,
+        },
+        /// Add the given branch as stack to the workspace, merging it into the workspace commit.
+        Apply {
+            /// The name of the branch to apply.
+            name: String,
+        },
+        /// Remove the stack with the given branch from the workspace, keeping the branch and its metadata.
+        Unapply {
+            /// The name of the branch at the top of the stack to unapply.
+            name: String,
+        },
+        /// Write the commits of a branch as a series of patches in mbox format, like `git format-patch`.
+        ///
+        /// The description of the branch is used as cover letter.
+        FormatPatch {
+            /// The name of the branch whose commits to export.
+            name: String,
+            /// The revspec of the commit after which to start, defaulting to the branch below it in its stack,
+            /// or to the merge-base with the target branch.
+            #[clap(long)]
+            base: Option<String>,
+            /// The file to write the mailbox to, or `-` for stdout, which is the default.
+            /// With `--format json`, the mailbox written to stdout is part of the JSON output.
+            #[clap(long, short = 'o')]
+            output: Option<PathBuf>,
+        },
+        /// Create a new branch from the patches in a mailbox, like `git am`, and add it to the workspace as a stack.
+        ///
+        /// A cover letter becomes the description of the branch.
+        Am {
+            /// The name of the branch to create, like `feature`.
+            name: String,
+            /// The mailbox to read the patches from, or `-` to read from stdin.
+            mbox: PathBuf,
+            /// The revspec of the commit to apply the patches to, defaulting to the tip of the target branch.
+            #[clap(long)]
+            base: Option<String>,
+            /// Only create the branch and its metadata, but don't add it to the workspace.
+            #[clap(long)]
+            unapplied: bool,
+        },
+    }
 }
 
 #[cfg(test)]
@@ -112,4 +327,31 @@ mod tests {
         use clap::CommandFactory;
         Args::command().debug_assert();
     }
+
+    #[test]
+    fn commit_patch_conflicts_with_worktree_changes() {
+        use clap::Parser;
+        for args in [
+            &["but-cli", "commit", "--patch", "-", "file"][..],
+            &["but-cli", "commit", "--patch", "-", "file", "previous"],
+            &[
+                "but-cli",
+                "commit",
+                "--patch",
+                "-",
+                "--hunk-headers",
+                "1",
+                "1",
+                "1",
+                "1",
+            ],
+        ] {
+            assert_eq!(
+                Args::try_parse_from(args).unwrap_err().kind(),
+                clap::error::ErrorKind::ArgumentConflict,
+                "{args:?}"
+            );
+        }
+        assert!(Args::try_parse_from(["but-cli", "commit", "--patch", "-"]).is_ok());
+    }
 }
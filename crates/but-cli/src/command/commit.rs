@@ -1,14 +1,28 @@
+use crate::args::OutputFormat;
 use crate::command::discard_change::IndicesOrHeaders;
-use crate::command::{debug_print, indices_or_headers_to_hunk_headers, path_to_rela_path};
+use crate::command::{
+    debug_print, indices_or_headers_to_hunk_headers, json_print, path_to_rela_path,
+};
 use anyhow::bail;
 use but_core::TreeChange;
 use but_workspace::commit_engine::{
-    DiffSpec, ReferenceFrame, StackSegmentId, create_commit_and_update_refs,
+    CreateCommitOutcome, DiffSpec, ReferenceFrame, StackSegmentId, create_commit_and_update_refs,
+    create_commit_from_patch_and_update_refs,
+    create_commit_from_patch_and_update_refs_with_project,
 };
 use gitbutler_project::Project;
 use gitbutler_stack::{VirtualBranchesHandle, VirtualBranchesState};
+use std::io::Read;
 use std::path::Path;
 
+/// The source of the changes to commit.
+enum Changes {
+    /// Selected changes in the worktree.
+    Worktree(Vec<DiffSpec>),
+    /// A unified diff to apply.
+    Patch(Vec<u8>),
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn commit(
     repo: gix::Repository,
@@ -21,6 +35,8 @@ pub fn commit(
     current_rela_path: Option<&Path>,
     previous_rela_path: Option<&Path>,
     headers: Option<&[u32]>,
+    patch: Option<&Path>,
+    format: OutputFormat,
 ) -> anyhow::Result<()> {
     if message.is_none() && !amend {
         bail!("Need a message when creating a new commit");
@@ -30,27 +46,31 @@ pub fn commit(
         .unwrap_or_else(|| Ok(repo.head_id()?))?
         .detach();
 
-    let changes = match (current_rela_path, previous_rela_path, headers) {
-        (None, None, None) => {
-            to_whole_file_diffspec(but_core::diff::worktree_changes(&repo)?.changes)
-        }
-        (Some(current_path), previous_path, Some(headers)) => {
-            let path = path_to_rela_path(current_path)?;
-            let previous_path = previous_path.map(path_to_rela_path).transpose()?;
-            let hunk_headers = indices_or_headers_to_hunk_headers(
-                &repo,
-                Some(IndicesOrHeaders::Headers(headers)),
-                &path,
-                previous_path.as_ref(),
-            )?;
+    let changes = if let Some(patch) = patch {
+        Changes::Patch(read_patch(patch)?)
+    } else {
+        match (current_rela_path, previous_rela_path, headers) {
+            (None, None, None) => Changes::Worktree(to_whole_file_diffspec(
+                but_core::diff::worktree_changes(&repo)?.changes,
+            )),
+            (Some(current_path), previous_path, Some(headers)) => {
+                let path = path_to_rela_path(current_path)?;
+                let previous_path = previous_path.map(path_to_rela_path).transpose()?;
+                let hunk_headers = indices_or_headers_to_hunk_headers(
+                    &repo,
+                    Some(IndicesOrHeaders::Headers(headers)),
+                    &path,
+                    previous_path.as_ref(),
+                )?;
 
-            vec![DiffSpec {
-                previous_path,
-                path,
-                hunk_headers,
-            }]
+                Changes::Worktree(vec![DiffSpec {
+                    previous_path,
+                    path,
+                    hunk_headers,
+                }])
+            }
+            _ => unreachable!("BUG: specifying this shouldn't be possible"),
         }
-        _ => unreachable!("BUG: specifying this shouldn't be possible"),
     };
     if let Some(project) = project.as_ref() {
         let destination = if amend {
@@ -80,18 +100,29 @@ pub fn commit(
             }
         };
         let mut guard = project.exclusive_worktree_access();
-        debug_print(
-            but_workspace::commit_engine::create_commit_and_update_refs_with_project(
+        let outcome = match changes {
+            Changes::Worktree(changes) => {
+                but_workspace::commit_engine::create_commit_and_update_refs_with_project(
+                    &repo,
+                    project,
+                    None,
+                    destination,
+                    None,
+                    changes,
+                    0, /* context-lines */
+                    guard.write_permission(),
+                )?
+            }
+            Changes::Patch(patch) => create_commit_from_patch_and_update_refs_with_project(
                 &repo,
                 project,
                 None,
                 destination,
-                None,
-                changes,
-                0, /* context-lines */
+                &patch,
                 guard.write_permission(),
             )?,
-        )?;
+        };
+        print_outcome(outcome, format)?;
     } else {
         let destination = if amend {
             if message.is_some() {
@@ -105,30 +136,56 @@ pub fn commit(
                 stack_segment: None,
             }
         };
-        debug_print(create_commit_and_update_refs(
-            &repo,
-            ReferenceFrame {
-                workspace_tip: workspace_tip
-                    .map(|spec| repo.rev_parse_single(spec))
+        let frame = ReferenceFrame {
+            workspace_tip: workspace_tip
+                .map(|spec| repo.rev_parse_single(spec))
+                .transpose()?
+                .map(|id| id.detach()),
+            branch_tip: Some(
+                stack_segment_ref
+                    .map(|name| repo.find_reference(name).map(|r| r.id().detach()))
                     .transpose()?
-                    .map(|id| id.detach()),
-                branch_tip: Some(
-                    stack_segment_ref
-                        .map(|name| repo.find_reference(name).map(|r| r.id().detach()))
-                        .transpose()?
-                        .unwrap_or(repo.head_id()?.detach()),
-                ),
-            },
-            &mut VirtualBranchesState::default(),
-            destination,
-            None,
-            changes,
-            0,
-        )?)?;
+                    .unwrap_or(repo.head_id()?.detach()),
+            ),
+        };
+        let mut vb = VirtualBranchesState::default();
+        let outcome = match changes {
+            Changes::Worktree(changes) => {
+                create_commit_and_update_refs(&repo, frame, &mut vb, destination, None, changes, 0)?
+            }
+            Changes::Patch(patch) => create_commit_from_patch_and_update_refs(
+                &repo,
+                frame,
+                &mut vb,
+                destination,
+                &patch,
+            )?,
+        };
+        print_outcome(outcome, format)?;
     }
     Ok(())
 }
 
+fn print_outcome(outcome: CreateCommitOutcome, format: OutputFormat) -> anyhow::Result<()> {
+    match format {
+        OutputFormat::Human => debug_print(outcome),
+        OutputFormat::Json => json_print(
+            but_workspace::commit_engine::ui::CreateCommitOutcome::from(outcome),
+        ),
+    }
+}
+
+/// Read the patch at `path`, or from standard input if it is `-`.
+pub(crate) fn read_patch(path: &Path) -> anyhow::Result<Vec<u8>> {
+    if path == Path::new("-") {
+        let mut buf = Vec::new();
+        std::io::stdin().read_to_end(&mut buf)?;
+        Ok(buf)
+    } else {
+        Ok(std::fs::read(path)?)
+    }
+}
+
 fn to_whole_file_diffspec(changes: Vec<TreeChange>) -> Vec<DiffSpec> {
     changes
         .into_iter()
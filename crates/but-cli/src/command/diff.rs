@@ -1,6 +1,8 @@
-use crate::command::{UI_CONTEXT_LINES, debug_print, project_from_path, project_repo};
+use crate::args::OutputFormat;
+use crate::command::{UI_CONTEXT_LINES, debug_print, json_print, project_from_path, project_repo};
 use gix::bstr::BString;
 use itertools::Itertools;
+use serde::Serialize;
 use std::path::Path;
 
 pub fn commit_changes(
@@ -8,6 +10,7 @@ pub fn commit_changes(
     current_commit: &str,
     previous_commit: Option<&str>,
     unified_diff: bool,
+    format: OutputFormat,
 ) -> anyhow::Result<()> {
     let repo = project_repo(current_dir)?;
     let previous_commit = previous_commit
@@ -17,28 +20,61 @@ pub fn commit_changes(
     let changes =
         but_core::diff::commit_changes(&repo, previous_commit.map(Into::into), commit.into())?;
 
-    if unified_diff {
-        debug_print(unified_diff_for_changes(&repo, changes, UI_CONTEXT_LINES)?)
-    } else {
-        debug_print(changes)
+    match (unified_diff, format) {
+        (true, OutputFormat::Human) => {
+            debug_print(unified_diff_for_changes(&repo, changes, UI_CONTEXT_LINES)?)
+        }
+        (true, OutputFormat::Json) => json_print(ui_changes_with_diffs(unified_diff_for_changes(
+            &repo,
+            changes,
+            UI_CONTEXT_LINES,
+        )?)),
+        (false, OutputFormat::Human) => debug_print(changes),
+        (false, OutputFormat::Json) => json_print(
+            changes
+                .into_iter()
+                .map(but_core::ui::TreeChange::from)
+                .collect::<Vec<_>>(),
+        ),
     }
 }
 
-pub fn status(current_dir: &Path, unified_diff: bool, context_lines: u32) -> anyhow::Result<()> {
+pub fn status(
+    current_dir: &Path,
+    unified_diff: bool,
+    context_lines: u32,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
     let repo = project_repo(current_dir)?;
     let worktree = but_core::diff::worktree_changes(&repo)?;
-    if unified_diff {
-        debug_print((
+    match (unified_diff, format) {
+        (true, OutputFormat::Human) => debug_print((
             unified_diff_for_changes(&repo, worktree.changes, context_lines)?,
             worktree.ignored_changes,
-        ))
-    } else {
-        debug_print(worktree)
+        )),
+        (true, OutputFormat::Json) => json_print(WorktreeChangesWithDiffs {
+            changes: ui_changes_with_diffs(unified_diff_for_changes(
+                &repo,
+                worktree.changes,
+                context_lines,
+            )?),
+            ignored_changes: worktree.ignored_changes,
+        }),
+        (false, OutputFormat::Human) => debug_print(worktree),
+        (false, OutputFormat::Json) => json_print(but_core::ui::WorktreeChanges::from(worktree)),
     }
 }
 
-pub fn locks(current_dir: &Path) -> anyhow::Result<()> {
+pub fn locks(current_dir: &Path, format: OutputFormat) -> anyhow::Result<()> {
     let project = project_from_path(current_dir)?;
+    if format == OutputFormat::Json {
+        return json_print(
+            but_hunk_dependency::ui::hunk_dependencies_for_workspace_changes_by_worktree_dir(
+                &project.worktree_path(),
+                &project.gb_dir(),
+            )?,
+        );
+    }
     let repo = gix::open(project.worktree_path())?;
     let worktree_changes = but_core::diff::worktree_changes(&repo)?;
     let input_stacks = but_hunk_dependency::workspace_stacks_to_input_stacks(
@@ -54,6 +90,33 @@ pub fn locks(current_dir: &Path) -> anyhow::Result<()> {
     )?)
 }
 
+/// A tree-change along with its unified diff, for use in JSON output.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChangeWithDiff {
+    change: but_core::ui::TreeChange,
+    diff: but_core::UnifiedDiff,
+}
+
+/// Like [`but_core::ui::WorktreeChanges`], but with the unified diff of each change.
+#[derive(Serialize)]
+struct WorktreeChangesWithDiffs {
+    changes: Vec<ChangeWithDiff>,
+    ignored_changes: Vec<but_core::IgnoredWorktreeChange>,
+}
+
+fn ui_changes_with_diffs(
+    changes: Vec<(but_core::TreeChange, but_core::UnifiedDiff)>,
+) -> Vec<ChangeWithDiff> {
+    changes
+        .into_iter()
+        .map(|(change, diff)| ChangeWithDiff {
+            change: change.into(),
+            diff,
+        })
+        .collect()
+}
+
 fn unified_diff_for_changes(
     repo: &gix::Repository,
     changes: Vec<but_core::TreeChange>,
@@ -1,6 +1,8 @@
+use crate::args::OutputFormat;
 use anyhow::{Context, anyhow, bail};
 use but_core::UnifiedDiff;
-use but_workspace::commit_engine::HunkHeader;
+use but_workspace::commit_engine::{DiffSpec, HunkHeader};
+use gitbutler_error::envelope::Envelope;
 use gitbutler_project::Project;
 use gix::bstr::{BString, ByteSlice};
 use std::path::Path;
@@ -68,6 +70,29 @@ fn debug_print(this: impl std::fmt::Debug) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Print `this` as `data` of the versioned JSON envelope.
+fn json_print(this: impl serde::Serialize) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string(&Envelope::data(this))?);
+    Ok(())
+}
+
+/// Print `this` in the given `format`, assuming that it is a UI type that is equally suitable for humans.
+fn print<T>(this: T, format: OutputFormat) -> anyhow::Result<()>
+where
+    T: std::fmt::Debug + serde::Serialize,
+{
+    match format {
+        OutputFormat::Human => debug_print(this),
+        OutputFormat::Json => json_print(this),
+    }
+}
+
+/// Print `err` as `error` of the versioned JSON envelope, so scripts can learn about the kind of failure.
+pub fn json_print_error(err: &anyhow::Error) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string(&Envelope::error(err))?);
+    Ok(())
+}
+
 fn project_controller(
     app_suffix: Option<&str>,
     app_data_dir: Option<&Path>,
@@ -103,41 +128,72 @@ use crate::command::discard_change::IndicesOrHeaders;
 pub use commit::commit;
 
 pub mod diff;
+pub mod oplog;
+pub mod stack;
 
 pub mod stacks {
     use std::path::Path;
+    use std::str::FromStr;
 
     use but_settings::AppSettings;
     use but_workspace::{
         stack_branch_local_and_remote_commits, stack_branch_upstream_only_commits, stack_branches,
     };
     use gitbutler_command_context::CommandContext;
+    use gitbutler_stack::StackId;
 
-    use crate::command::{debug_print, project_from_path};
+    use crate::args::OutputFormat;
+    use crate::command::{debug_print, json_print, print, project_from_path};
 
-    pub fn list(current_dir: &Path) -> anyhow::Result<()> {
+    pub fn list(current_dir: &Path, format: OutputFormat) -> anyhow::Result<()> {
         let project = project_from_path(current_dir)?;
         let ctx = CommandContext::open(&project, AppSettings::default())?;
         let repo = ctx.gix_repo()?;
-        debug_print(but_workspace::stacks(&project.gb_dir(), &repo))
+        print(but_workspace::stacks(&project.gb_dir(), &repo)?, format)
     }
 
-    pub fn branches(id: &str, current_dir: &Path) -> anyhow::Result<()> {
+    pub fn branches(id: &str, current_dir: &Path, format: OutputFormat) -> anyhow::Result<()> {
         let project = project_from_path(current_dir)?;
         let ctx = CommandContext::open(&project, AppSettings::default())?;
-        debug_print(stack_branches(id.to_string(), &ctx))
+        print(stack_branches(id.to_string(), &ctx)?, format)
     }
 
-    pub fn branch_commits(id: &str, name: &str, current_dir: &Path) -> anyhow::Result<()> {
+    pub fn branch_commits(
+        id: &str,
+        name: &str,
+        current_dir: &Path,
+        format: OutputFormat,
+    ) -> anyhow::Result<()> {
         let project = project_from_path(current_dir)?;
         let ctx = CommandContext::open(&project, AppSettings::default())?;
         let repo = ctx.gix_repo()?;
         let local_and_remote =
-            stack_branch_local_and_remote_commits(id.to_string(), name.to_string(), &ctx, &repo);
-        debug_print(local_and_remote)?;
+            stack_branch_local_and_remote_commits(id.to_string(), name.to_string(), &ctx, &repo)?;
         let upstream_only =
-            stack_branch_upstream_only_commits(id.to_string(), name.to_string(), &ctx, &repo);
-        debug_print(upstream_only)
+            stack_branch_upstream_only_commits(id.to_string(), name.to_string(), &ctx, &repo)?;
+        match format {
+            OutputFormat::Human => {
+                debug_print(local_and_remote)?;
+                debug_print(upstream_only)
+            }
+            OutputFormat::Json => json_print(serde_json::json!({
+                "localAndRemote": local_and_remote,
+                "upstreamOnly": upstream_only,
+            })),
+        }
+    }
+
+    pub fn autosquash(id: &str, current_dir: &Path, format: OutputFormat) -> anyhow::Result<()> {
+        let project = project_from_path(current_dir)?;
+        let ctx = CommandContext::open(&project, AppSettings::default())?;
+        let folded = gitbutler_branch_actions::autosquash_stack(&ctx, StackId::from_str(id)?)?;
+        match format {
+            OutputFormat::Human => {
+                println!("Folded {folded} commit(s)");
+                Ok(())
+            }
+            OutputFormat::Json => json_print(serde_json::json!({ "folded": folded })),
+        }
     }
 }
 
@@ -152,6 +208,7 @@ pub(crate) fn discard_change(
     current_rela_path: &Path,
     previous_rela_path: Option<&Path>,
     indices_or_headers: Option<discard_change::IndicesOrHeaders<'_>>,
+    format: OutputFormat,
 ) -> anyhow::Result<()> {
     let repo = configured_repo(gix::discover(cwd)?, RepositoryOpenMode::Merge)?;
 
@@ -163,16 +220,22 @@ pub(crate) fn discard_change(
         &path,
         previous_path.as_ref(),
     )?;
-    let spec = but_workspace::commit_engine::DiffSpec {
+    let spec = DiffSpec {
         previous_path,
         path,
         hunk_headers,
     };
-    debug_print(but_workspace::discard_workspace_changes(
-        &repo,
-        Some(spec.into()),
-        UI_CONTEXT_LINES,
-    )?)
+    let dropped =
+        but_workspace::discard_workspace_changes(&repo, Some(spec.into()), UI_CONTEXT_LINES)?;
+    match format {
+        OutputFormat::Human => debug_print(dropped),
+        OutputFormat::Json => json_print(
+            dropped
+                .into_iter()
+                .map(|spec| but_workspace::discard::ui::DiscardSpec::from(DiffSpec::from(spec)))
+                .collect::<Vec<_>>(),
+        ),
+    }
 }
 
 fn indices_or_headers_to_hunk_headers(
@@ -0,0 +1,690 @@
+use crate::args::OutputFormat;
+use crate::command::{RepositoryOpenMode, json_print, project_from_path, repo_and_maybe_project};
+use anyhow::{Context, bail};
+use but_core::{TreeChange, TreeStatus, TreeStatusKind};
+use gitbutler_oplog::OplogExt;
+use gitbutler_oplog::compare::HeadChange;
+use gitbutler_oplog::entry::{OperationKind, PINNED_TRAILER_KEY, Snapshot};
+use gitbutler_oxidize::ObjectIdExt;
+use gitbutler_project::{Project, SnapshotRetention};
+use gix::bstr::BStr;
+use itertools::Itertools;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The amount of snapshots to obtain at once while looking for ones that pass the filter.
+const PAGE_SIZE: usize = 100;
+
+/// Criteria that all listed snapshots must fulfil.
+#[derive(Debug, Default)]
+pub struct Filter {
+    /// If not empty, the snapshot operation must be one of these.
+    kinds: Vec<OperationKind>,
+    /// The snapshot must have been created at or after this time, in seconds since epoch.
+    since: Option<i64>,
+    /// The snapshot must have been created at or before this time, in seconds since epoch.
+    until: Option<i64>,
+    /// Each trailer key must be present, and if a value is given, it must match as well.
+    trailers: Vec<(String, Option<String>)>,
+    /// If `true`, the snapshot must be pinned.
+    pinned: bool,
+}
+
+impl Filter {
+    pub fn from_args(
+        kinds: &[String],
+        since: Option<&str>,
+        until: Option<&str>,
+        trailers: &[String],
+        pinned: bool,
+    ) -> anyhow::Result<Self> {
+        let kinds = kinds
+            .iter()
+            .map(|kind| {
+                OperationKind::from_str(kind)
+                    .with_context(|| format!("Unknown operation kind '{kind}'"))
+            })
+            .collect::<Result<_, _>>()?;
+        let parse_date = |date: &str| -> anyhow::Result<i64> {
+            Ok(gix::date::parse(date, Some(std::time::SystemTime::now()))
+                .with_context(|| format!("Could not parse '{date}' as date"))?
+                .seconds)
+        };
+        let trailers = trailers
+            .iter()
+            .map(|trailer| match trailer.split_once('=') {
+                Some((key, value)) => (key.to_owned(), Some(value.to_owned())),
+                None => (trailer.to_owned(), None),
+            })
+            .collect();
+        Ok(Filter {
+            kinds,
+            since: since.map(parse_date).transpose()?,
+            until: until.map(parse_date).transpose()?,
+            trailers,
+            pinned,
+        })
+    }
+
+    fn matches(&self, snapshot: &Snapshot) -> bool {
+        let created_at = snapshot.created_at.seconds();
+        if self.since.is_some_and(|since| created_at < since)
+            || self.until.is_some_and(|until| created_at > until)
+        {
+            return false;
+        }
+        if self.kinds.is_empty() && self.trailers.is_empty() && !self.pinned {
+            return true;
+        }
+        let Some(details) = snapshot.details.as_ref() else {
+            return false;
+        };
+        if self.pinned && !details.is_pinned() {
+            return false;
+        }
+        if !self.kinds.is_empty() && !self.kinds.contains(&details.operation) {
+            return false;
+        }
+        self.trailers.iter().all(|(key, value)| {
+            details
+                .trailers
+                .iter()
+                .any(|t| t.key == *key && value.as_ref().is_none_or(|value| t.value == *value))
+        })
+    }
+
+    /// Return `true` if no snapshot older than `snapshot` can match anymore.
+    fn is_past_range(&self, snapshot: &Snapshot) -> bool {
+        self.since
+            .is_some_and(|since| snapshot.created_at.seconds() < since)
+    }
+}
+
+pub fn list(
+    current_dir: &Path,
+    limit: usize,
+    filter: Filter,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let project = project_from_path(current_dir)?;
+    let snapshots = filtered_snapshots(&project, limit, &filter)?;
+    if format == OutputFormat::Json {
+        return json_print(snapshots);
+    }
+    for snapshot in snapshots {
+        println!("{}", snapshot_line(&snapshot));
+    }
+    Ok(())
+}
+
+pub fn show(current_dir: &Path, snapshot: &str, format: OutputFormat) -> anyhow::Result<()> {
+    let project = project_from_path(current_dir)?;
+    let snapshot_id = resolve_snapshot_id(&project, snapshot)?;
+    let snapshot = project
+        .list_snapshots(1, Some(snapshot_id))?
+        .into_iter()
+        .next()
+        .with_context(|| format!("'{snapshot}' is not an oplog snapshot"))?;
+    let excluded_files = project.excluded_files(snapshot_id)?;
+    let changes = snapshot_changes(&project, snapshot_id)?;
+    if format == OutputFormat::Json {
+        return json_print(serde_json::json!({
+            "snapshot": snapshot,
+            "changes": changes
+                .iter()
+                .map(|change| serde_json::json!({
+                    "path": change.path.to_string(),
+                    "previousPath": previous_path(change).map(ToString::to_string),
+                    "status": status_letter(change.status.kind()).to_string(),
+                }))
+                .collect::<Vec<_>>(),
+            "excludedFiles": excluded_files,
+        }));
+    }
+
+    println!("snapshot {}", snapshot.commit_id);
+    println!("Date:    {}", format_time(&snapshot));
+    println!(
+        "Changes: {} file(s), +{} -{}",
+        changes.len(),
+        snapshot.lines_added,
+        snapshot.lines_removed
+    );
+    if let Some(details) = &snapshot.details {
+        println!("Version: {}", details.version);
+        println!("Operation: {}", details.operation);
+        println!("\n    {}", details.title);
+        if let Some(body) = &details.body {
+            println!();
+            for line in body.lines() {
+                println!("    {line}");
+            }
+        }
+        if !details.trailers.is_empty() {
+            println!();
+            for trailer in &details.trailers {
+                println!("    {trailer}");
+            }
+        }
+    }
+    if !changes.is_empty() {
+        println!();
+        for change in &changes {
+            let letter = status_letter(change.status.kind());
+            match previous_path(change) {
+                Some(previous_path) => println!("{letter} {previous_path} -> {}", change.path),
+                None => println!("{letter} {}", change.path),
+            }
+        }
+    }
+    if !excluded_files.is_empty() {
+        println!("\nExcluded untracked files:");
+        for file in &excluded_files {
+            println!(
+                "  {} ({} bytes, {:?}, {})",
+                file.path.display(),
+                file.size,
+                file.reason,
+                file.hash
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn diff(current_dir: &Path, snapshot: &str, format: OutputFormat) -> anyhow::Result<()> {
+    let project = project_from_path(current_dir)?;
+    let snapshot_id = resolve_snapshot_id(&project, snapshot)?;
+    let diff = project.snapshot_diff(snapshot_id)?;
+    if format == OutputFormat::Json {
+        return json_print(diff);
+    }
+    for (path, file_diff) in diff.into_iter().sorted_by(|a, b| a.0.cmp(&b.0)) {
+        println!("--- {}", path.display());
+        if file_diff.skipped {
+            println!("(skipped as it is too large)");
+            continue;
+        }
+        if file_diff.binary {
+            println!("(binary)");
+            continue;
+        }
+        for hunk in file_diff.hunks {
+            println!(
+                "@@ -{},{} +{},{} @@",
+                hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines
+            );
+            print!("{}", hunk.diff_lines);
+        }
+    }
+    Ok(())
+}
+
+pub fn compare(
+    current_dir: &Path,
+    previous: &str,
+    current: &str,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let project = project_from_path(current_dir)?;
+    let previous_id = resolve_snapshot_id(&project, previous)?;
+    let current_id = resolve_snapshot_id(&project, current)?;
+    let comparison = project.compare_snapshots(previous_id, current_id)?;
+    if format == OutputFormat::Json {
+        return json_print(comparison);
+    }
+
+    let short = |id: Option<git2::Oid>| {
+        id.map(|id| id.to_string()[..7].to_owned())
+            .unwrap_or_else(|| "none".into())
+    };
+    if let Some(target) = &comparison.target {
+        println!(
+            "target: {} {} -> {} {}",
+            target.previous_branch.as_deref().unwrap_or("none"),
+            short(target.previous_sha),
+            target.branch.as_deref().unwrap_or("none"),
+            short(target.sha)
+        );
+    }
+    for stack in &comparison.stacks_added {
+        println!("+ stack {} [{}]", stack.name, stack.heads.join(", "));
+    }
+    for stack in &comparison.stacks_removed {
+        println!("- stack {} [{}]", stack.name, stack.heads.join(", "));
+    }
+    for stack in &comparison.stacks_changed {
+        print!("~ stack {}", stack.name);
+        if let Some(previous_name) = &stack.renamed_from {
+            print!(" (renamed from {previous_name})");
+        }
+        match stack.in_workspace {
+            Some(true) => print!(" (applied)"),
+            Some(false) => print!(" (unapplied)"),
+            None => {}
+        }
+        println!();
+        for head in &stack.heads {
+            match head {
+                HeadChange::Added { name, head } => println!("    + {name} {}", short(*head)),
+                HeadChange::Removed { name, head } => println!("    - {name} {}", short(*head)),
+                HeadChange::Moved {
+                    name,
+                    previous,
+                    current,
+                } => println!("    ~ {name} {} -> {}", short(*previous), short(*current)),
+            }
+        }
+    }
+    for change in comparison.worktree_changes {
+        println!("{:?} {}", change.status.kind(), change.path);
+    }
+    Ok(())
+}
+
+pub fn restore(current_dir: &Path, snapshot: &str, format: OutputFormat) -> anyhow::Result<()> {
+    let project = project_from_path(current_dir)?;
+    let snapshot_id = resolve_snapshot_id(&project, snapshot)?;
+    let _guard = project.try_exclusive_access()?;
+    let mut guard = project.exclusive_worktree_access();
+    let unrestorable_files: Vec<_> = project
+        .excluded_files(snapshot_id)?
+        .into_iter()
+        .filter(|file| !file.is_present_in(&project.path))
+        .collect();
+    let restore_snapshot_id = project.restore_snapshot(snapshot_id, guard.write_permission())?;
+    if format == OutputFormat::Json {
+        return json_print(serde_json::json!({
+            "restored": snapshot_id.to_string(),
+            "snapshot": restore_snapshot_id.to_string(),
+            "unrestorableFiles": unrestorable_files,
+        }));
+    }
+    println!("Restored {snapshot_id}, the previous state is in snapshot {restore_snapshot_id}");
+    if !unrestorable_files.is_empty() {
+        println!(
+            "These untracked files were excluded from the snapshot and could not be restored:"
+        );
+        for file in unrestorable_files {
+            println!("  {} ({} bytes)", file.path.display(), file.size);
+        }
+    }
+    Ok(())
+}
+
+pub fn gc(
+    args: &crate::args::Args,
+    keep_last: Option<usize>,
+    keep_newer_than: Option<&str>,
+    daily: Option<usize>,
+    weekly: Option<usize>,
+) -> anyhow::Result<()> {
+    let (_repo, stored_project) = repo_and_maybe_project(args, RepositoryOpenMode::General)?;
+    let project = match stored_project {
+        Some(project) => project,
+        None => project_from_path(&args.current_dir)?,
+    };
+    let keep_within_seconds = keep_newer_than
+        .map(|date| -> anyhow::Result<u64> {
+            let time = gix::date::parse(date, Some(std::time::SystemTime::now()))
+                .with_context(|| format!("Could not parse '{date}' as date"))?;
+            let now = gix::date::Time::now_utc().seconds;
+            Ok(now.saturating_sub(time.seconds).max(0) as u64)
+        })
+        .transpose()?;
+    let mut retention = SnapshotRetention {
+        keep_last,
+        keep_within_seconds,
+        daily,
+        weekly,
+    };
+    if retention.keeps_everything() {
+        retention = project
+            .snapshot_retention
+            .filter(|retention| !retention.keeps_everything())
+            .context("No retention rules given, and the project doesn't configure any")?;
+    }
+
+    let _guard = project.try_exclusive_access()?;
+    let mut guard = project.exclusive_worktree_access();
+    let outcome = project.prune_snapshots(&retention, guard.write_permission())?;
+    if args.format == OutputFormat::Json {
+        return json_print(serde_json::json!({
+            "kept": outcome.kept,
+            "removed": outcome.removed.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            "rewritten": outcome
+                .rewritten
+                .iter()
+                .map(|(old, new)| [old.to_string(), new.to_string()])
+                .collect::<Vec<_>>(),
+        }));
+    }
+    println!(
+        "Kept {} snapshot(s), removed {}, rewrote {}",
+        outcome.kept,
+        outcome.removed.len(),
+        outcome.rewritten.len()
+    );
+    Ok(())
+}
+
+pub fn checkpoint(
+    current_dir: &Path,
+    title: &str,
+    body: Option<&str>,
+    pin: bool,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let project = project_from_path(current_dir)?;
+    let _guard = project.try_exclusive_access()?;
+    let mut guard = project.exclusive_worktree_access();
+    let snapshot_id = project.create_checkpoint(
+        title.to_owned(),
+        body.map(ToOwned::to_owned),
+        pin,
+        guard.write_permission(),
+    )?;
+    if format == OutputFormat::Json {
+        return json_print(serde_json::json!({ "snapshot": snapshot_id.to_string() }));
+    }
+    println!("Created checkpoint {snapshot_id}");
+    Ok(())
+}
+
+pub fn set_pinned(
+    current_dir: &Path,
+    snapshot: &str,
+    pinned: bool,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let project = project_from_path(current_dir)?;
+    let snapshot_id = resolve_snapshot_id(&project, snapshot)?;
+    let _guard = project.try_exclusive_access()?;
+    let mut guard = project.exclusive_worktree_access();
+    let new_snapshot_id =
+        project.set_snapshot_pinned(snapshot_id, pinned, guard.write_permission())?;
+    if format == OutputFormat::Json {
+        return json_print(serde_json::json!({
+            "snapshot": new_snapshot_id.to_string(),
+            "pinned": pinned,
+        }));
+    }
+    println!(
+        "{} {new_snapshot_id}",
+        if pinned { "Pinned" } else { "Unpinned" }
+    );
+    Ok(())
+}
+
+pub fn export(
+    current_dir: &Path,
+    bundle: &Path,
+    depth: Option<usize>,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let project = project_from_path(current_dir)?;
+    let guard = project.exclusive_worktree_access();
+    let manifest = project.export_oplog(bundle, depth, guard.read_permission())?;
+    if format == OutputFormat::Json {
+        return json_print(manifest);
+    }
+    println!(
+        "Exported {} snapshot(s) to {}{}",
+        manifest.snapshot_count,
+        bundle.display(),
+        if manifest.truncated {
+            ", older snapshots were left out"
+        } else {
+            ""
+        }
+    );
+    Ok(())
+}
+
+pub fn import(current_dir: &Path, bundle: &Path, format: OutputFormat) -> anyhow::Result<()> {
+    let project = project_from_path(current_dir)?;
+    let _guard = project.try_exclusive_access()?;
+    let mut guard = project.exclusive_worktree_access();
+    let outcome = project.import_oplog(bundle, guard.write_permission())?;
+    if format == OutputFormat::Json {
+        return json_print(serde_json::json!({
+            "head": outcome.head.to_string(),
+            "imported": outcome.imported,
+        }));
+    }
+    println!(
+        "Imported {} snapshot(s), the most recent one is {}",
+        outcome.imported, outcome.head
+    );
+    Ok(())
+}
+
+/// Obtain up to `limit` snapshots that pass `filter`, most recent first.
+fn filtered_snapshots(
+    project: &Project,
+    limit: usize,
+    filter: &Filter,
+) -> anyhow::Result<Vec<Snapshot>> {
+    let mut out = Vec::new();
+    let mut next_root = None;
+    while out.len() < limit {
+        let mut page = project.list_snapshots(PAGE_SIZE, next_root)?;
+        let is_last_page = page.len() < PAGE_SIZE;
+        if next_root.is_some() && !page.is_empty() {
+            // The traversal root was already seen as last item of the previous page.
+            page.remove(0);
+        }
+        let Some(last) = page.last() else {
+            break;
+        };
+        next_root = Some(last.commit_id);
+
+        for snapshot in page {
+            if filter.is_past_range(&snapshot) {
+                return Ok(out);
+            }
+            if filter.matches(&snapshot) {
+                out.push(snapshot);
+                if out.len() == limit {
+                    break;
+                }
+            }
+        }
+        if is_last_page {
+            break;
+        }
+    }
+    Ok(out)
+}
+
+fn resolve_snapshot_id(project: &Project, spec: &str) -> anyhow::Result<git2::Oid> {
+    let repo = gix::open(project.worktree_path())?;
+    let id = repo
+        .rev_parse_single(spec)
+        .with_context(|| format!("Could not find snapshot '{spec}'"))?;
+    if id.object()?.kind != gix::object::Kind::Commit {
+        bail!("'{spec}' does not point to a snapshot commit");
+    }
+    let id = id.detach().to_git2();
+    if !project.is_snapshot(id)? {
+        bail!("'{spec}' is not a snapshot in the oplog");
+    }
+    Ok(id)
+}
+
+/// The changes to the worktree that were recorded by `snapshot_id`, compared to the snapshot before it.
+fn snapshot_changes(project: &Project, snapshot_id: git2::Oid) -> anyhow::Result<Vec<TreeChange>> {
+    let repo = git2::Repository::open(&project.path)?;
+    let Ok(parent_id) = repo.find_commit(snapshot_id)?.parent_id(0) else {
+        // The first snapshot has nothing to compare with.
+        return Ok(Vec::new());
+    };
+    Ok(project
+        .compare_snapshots(parent_id, snapshot_id)?
+        .worktree_changes)
+}
+
+/// The letter `git status --short` uses for changes of `kind`.
+fn status_letter(kind: TreeStatusKind) -> char {
+    match kind {
+        TreeStatusKind::Addition => 'A',
+        TreeStatusKind::Deletion => 'D',
+        TreeStatusKind::Modification => 'M',
+        TreeStatusKind::Rename => 'R',
+    }
+}
+
+fn previous_path(change: &TreeChange) -> Option<&BStr> {
+    match &change.status {
+        TreeStatus::Rename { previous_path, .. } => Some(previous_path.as_ref()),
+        _ => None,
+    }
+}
+
+fn format_time(snapshot: &Snapshot) -> String {
+    chrono::DateTime::from_timestamp(snapshot.created_at.seconds(), 0)
+        .map(|ts| ts.to_string())
+        .unwrap_or_else(|| snapshot.created_at.seconds().to_string())
+}
+
+fn snapshot_line(snapshot: &Snapshot) -> String {
+    let mut line = format!(
+        "{} {} {}",
+        &snapshot.commit_id.to_string()[..7],
+        format_time(snapshot),
+        snapshot
+            .details
+            .as_ref()
+            .map(|d| d.operation)
+            .unwrap_or_default()
+    );
+    if let Some(details) = &snapshot.details {
+        if details.title != details.operation.to_string() {
+            line.push_str(&format!(" \"{}\"", details.title));
+        }
+        if details.is_pinned() {
+            line.push_str(" [pinned]");
+        }
+        for trailer in details
+            .trailers
+            .iter()
+            .filter(|t| t.key != PINNED_TRAILER_KEY)
+        {
+            line.push_str(&format!(
+                " {}={}",
+                trailer.key,
+                trailer.value.replace('\n', "\\n")
+            ));
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gitbutler_oplog::entry::{SnapshotDetails, Trailer};
+
+    fn snapshot(seconds: i64, details: Option<SnapshotDetails>) -> Snapshot {
+        Snapshot {
+            commit_id: git2::Oid::zero(),
+            created_at: git2::Time::new(seconds, 0),
+            lines_added: 0,
+            lines_removed: 0,
+            files_changed: Vec::new(),
+            details,
+        }
+    }
+
+    #[test]
+    fn status_letters_match_git() {
+        assert_eq!(status_letter(TreeStatusKind::Addition), 'A');
+        assert_eq!(status_letter(TreeStatusKind::Deletion), 'D');
+        assert_eq!(status_letter(TreeStatusKind::Modification), 'M');
+        assert_eq!(status_letter(TreeStatusKind::Rename), 'R');
+    }
+
+    #[test]
+    fn filter_from_args() -> anyhow::Result<()> {
+        let filter = Filter::from_args(
+            &["CreateCommit".into()],
+            Some("100"),
+            None,
+            &["restored_from".into(), "key=a=b".into()],
+            true,
+        )?;
+        assert_eq!(filter.kinds, [OperationKind::CreateCommit]);
+        assert_eq!(filter.since, Some(100));
+        assert_eq!(filter.until, None);
+        assert_eq!(
+            filter.trailers,
+            [
+                ("restored_from".to_owned(), None),
+                ("key".to_owned(), Some("a=b".to_owned()))
+            ],
+            "only the first `=` separates key and value"
+        );
+        assert!(filter.pinned);
+
+        let err = Filter::from_args(&["NoSuchKind".into()], None, None, &[], false).unwrap_err();
+        assert_eq!(err.to_string(), "Unknown operation kind 'NoSuchKind'");
+        Ok(())
+    }
+
+    #[test]
+    fn filter_matches() {
+        let details =
+            SnapshotDetails::new(OperationKind::CreateCommit).with_trailers(vec![Trailer {
+                key: "restored_from".into(),
+                value: "abc".into(),
+            }]);
+        let commit = snapshot(100, Some(details.clone()));
+
+        assert!(Filter::default().matches(&commit), "no criteria match all");
+        assert!(
+            Filter::default().matches(&snapshot(100, None)),
+            "even snapshots without details"
+        );
+
+        let filter = Filter {
+            kinds: vec![OperationKind::CreateBranch],
+            ..Default::default()
+        };
+        assert!(!filter.matches(&commit));
+        assert!(
+            !filter.matches(&snapshot(100, None)),
+            "without details, the kind is unknown"
+        );
+
+        let filter = Filter {
+            since: Some(101),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&commit));
+        assert!(filter.is_past_range(&commit));
+        let filter = Filter {
+            until: Some(99),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&commit));
+        assert!(!filter.is_past_range(&commit));
+
+        let trailer_filter = |key: &str, value: Option<&str>| Filter {
+            trailers: vec![(key.into(), value.map(Into::into))],
+            ..Default::default()
+        };
+        assert!(trailer_filter("restored_from", None).matches(&commit));
+        assert!(trailer_filter("restored_from", Some("abc")).matches(&commit));
+        assert!(!trailer_filter("restored_from", Some("def")).matches(&commit));
+        assert!(!trailer_filter("other", None).matches(&commit));
+
+        let filter = Filter {
+            pinned: true,
+            ..Default::default()
+        };
+        assert!(!filter.matches(&commit));
+        let mut pinned_details = details;
+        pinned_details.set_pinned(true);
+        assert!(filter.matches(&snapshot(100, Some(pinned_details))));
+    }
+}
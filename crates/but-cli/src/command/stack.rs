@@ -0,0 +1,432 @@
+use crate::args::{Args, OutputFormat};
+use crate::command::commit::read_patch;
+use crate::command::{RepositoryOpenMode, json_print, repo_and_maybe_project};
+use anyhow::{Context, bail};
+use but_core::RefMetadata;
+use but_core::ref_metadata::{WorkspaceStack, WorkspaceStackBranch};
+use but_workspace::VirtualBranchesTomlMetadata;
+use gix::bstr::ByteSlice as _;
+use gix::prelude::ObjectIdExt;
+use gix::refs::transaction::{Change, LogChange, PreviousValue, RefEdit, RefLog};
+use std::io::Write;
+use std::path::Path;
+
+/// The reference pointing to the workspace commit, which is the only workspace supported by the metadata backend.
+const WORKSPACE_REF: &str = "refs/heads/gitbutler/workspace";
+
+pub fn create(args: &Args, name: &str, base: Option<&str>, apply: bool) -> anyhow::Result<()> {
+    let (repo, mut meta) = repo_and_metadata(args)?;
+    let ref_name = branch_ref_name(name)?;
+    let base_id = match base {
+        Some(base) => repo.rev_parse_single(base)?.detach(),
+        None => target_tip(&repo, &meta)?.context(
+            "There is no target branch to create the stack on, please provide the base with --base",
+        )?,
+    };
+    repo.reference(
+        ref_name.as_ref(),
+        base_id,
+        PreviousValue::MustNotExist,
+        "GitButler: create stack",
+    )
+    .with_context(|| format!("Could not create branch '{}'", ref_name.shorten()))?;
+    let branch = meta.branch(ref_name.as_ref())?;
+    meta.set_branch(&branch)?;
+
+    if apply {
+        apply_ref(&repo, &mut meta, ref_name.clone())?;
+    }
+    if args.format == OutputFormat::Json {
+        return json_print(serde_json::json!({
+            "refName": ref_name.to_string(),
+            "base": base_id.to_string(),
+            "applied": apply,
+        }));
+    }
+    println!("Created {} at {base_id}", ref_name.shorten());
+    if apply {
+        println!("Applied {}", ref_name.shorten());
+    }
+    Ok(())
+}
+
+pub fn rename(args: &Args, name: &str, new_name: &str) -> anyhow::Result<()> {
+    let (repo, mut meta) = repo_and_metadata(args)?;
+    let ref_name = branch_ref_name(name)?;
+    let new_ref_name = branch_ref_name(new_name)?;
+    let mut reference = repo.find_reference(ref_name.as_ref())?;
+    let tip = reference.peel_to_id_in_place()?.detach();
+
+    repo.edit_references([
+        RefEdit {
+            change: Change::Update {
+                log: LogChange {
+                    mode: RefLog::AndReference,
+                    force_create_reflog: false,
+                    message: format!("GitButler: rename {} to {new_name}", ref_name.shorten())
+                        .into(),
+                },
+                expected: PreviousValue::MustNotExist,
+                new: gix::refs::Target::Object(tip),
+            },
+            name: new_ref_name.clone(),
+            deref: false,
+        },
+        RefEdit {
+            change: Change::Delete {
+                expected: PreviousValue::MustExistAndMatch(gix::refs::Target::Object(tip)),
+                log: RefLog::AndReference,
+            },
+            name: ref_name.clone(),
+            deref: false,
+        },
+    ])?;
+
+    // The branch keeps its place in its stack, along with all data of the stack.
+    meta.rename_branch(ref_name.as_ref(), new_ref_name.as_ref())?;
+    if args.format == OutputFormat::Json {
+        return json_print(serde_json::json!({
+            "refName": new_ref_name.to_string(),
+            "previousRefName": ref_name.to_string(),
+        }));
+    }
+    println!(
+        "Renamed {} to {}",
+        ref_name.shorten(),
+        new_ref_name.shorten()
+    );
+    Ok(())
+}
+
+pub fn reorder(args: &Args, names: &[String]) -> anyhow::Result<()> {
+    let (repo, mut meta) = repo_and_metadata(args)?;
+    let mut ws = meta.workspace(WORKSPACE_REF.try_into()?)?;
+    let mut stacks_in_order = Vec::with_capacity(ws.stacks.len());
+    for name in names {
+        let ref_name = branch_ref_name(name)?;
+        let idx = ws
+            .stacks
+            .iter()
+            .position(|stack| stack.branches.iter().any(|b| b.ref_name == ref_name))
+            .with_context(|| format!("'{name}' isn't part of a stack in the workspace"))?;
+        stacks_in_order.push(ws.stacks.remove(idx));
+    }
+    stacks_in_order.append(&mut ws.stacks);
+    ws.stacks = stacks_in_order;
+    meta.set_workspace(&ws)?;
+
+    // The order of stacks is also the order of the parents of the workspace commit.
+    let mut workspace_ref = repo.find_reference(WORKSPACE_REF)?;
+    let workspace_tip = workspace_ref.peel_to_id_in_place()?.detach();
+    let workspace_commit = but_workspace::WorkspaceCommit::from_id(workspace_tip.attach(&repo))?;
+    if workspace_commit.is_managed() {
+        let mut commit = workspace_commit.inner.clone();
+        let stack_position = |parent: &gix::ObjectId| {
+            ws.stacks.iter().position(|stack| {
+                stack.ref_name().is_some_and(|ref_name| {
+                    repo.try_find_reference(ref_name.as_ref())
+                        .ok()
+                        .flatten()
+                        .and_then(|mut r| r.peel_to_id_in_place().ok())
+                        .is_some_and(|id| id == *parent)
+                })
+            })
+        };
+        commit.parents.sort_by_key(stack_position);
+        if commit.parents != workspace_commit.parents {
+            let new_tip = repo.write_object(&commit)?.detach();
+            repo.reference(
+                WORKSPACE_REF,
+                new_tip,
+                PreviousValue::MustExistAndMatch(gix::refs::Target::Object(workspace_tip)),
+                "GitButler: reorder stacks",
+            )?;
+        }
+    }
+    let stack_names = ws.stacks.iter().filter_map(|stack| stack.ref_name());
+    if args.format == OutputFormat::Json {
+        return json_print(serde_json::json!({
+            "stacks": stack_names.map(ToString::to_string).collect::<Vec<_>>(),
+        }));
+    }
+    for ref_name in stack_names {
+        println!("{}", ref_name.shorten());
+    }
+    Ok(())
+}
+
+pub fn apply(args: &Args, name: &str) -> anyhow::Result<()> {
+    let (repo, mut meta) = repo_and_metadata(args)?;
+    let ref_name = branch_ref_name(name)?;
+    apply_ref(&repo, &mut meta, ref_name.clone())?;
+    if args.format == OutputFormat::Json {
+        return json_print(serde_json::json!({ "refName": ref_name.to_string() }));
+    }
+    println!("Applied {}", ref_name.shorten());
+    Ok(())
+}
+
+pub fn unapply(args: &Args, name: &str) -> anyhow::Result<()> {
+    let (repo, mut meta) = repo_and_metadata(args)?;
+    let ref_name = branch_ref_name(name)?;
+    let branch_tip = repo
+        .find_reference(ref_name.as_ref())?
+        .peel_to_id_in_place()?
+        .detach();
+    let workspace_tip = repo
+        .find_reference(WORKSPACE_REF)?
+        .peel_to_id_in_place()?
+        .detach();
+    let workspace_commit = but_workspace::WorkspaceCommit::from_id(workspace_tip.attach(&repo))?;
+
+    if workspace_commit.is_managed() && workspace_commit.parents.contains(&branch_tip) {
+        assure_clean_worktree(&repo)?;
+        let outcome = but_workspace::branch::remove_branch_from_workspace_and_update_refs(
+            &repo,
+            ref_name.as_bstr().try_into()?,
+            WORKSPACE_REF.try_into()?,
+            target_ref(&meta)?,
+            &mut meta,
+        )?;
+        checkout_workspace(&repo, outcome.workspace_tip)?;
+    } else {
+        // Stacks without commits of their own aren't part of the workspace commit.
+        let mut ws = meta.workspace(WORKSPACE_REF.try_into()?)?;
+        let num_stacks = ws.stacks.len();
+        ws.stacks
+            .retain(|stack| !stack.branches.iter().any(|b| b.ref_name == ref_name));
+        if ws.stacks.len() == num_stacks {
+            bail!("'{name}' isn't part of a stack in the workspace");
+        }
+        meta.set_workspace(&ws)?;
+    }
+    if args.format == OutputFormat::Json {
+        return json_print(serde_json::json!({ "refName": ref_name.to_string() }));
+    }
+    println!("Unapplied {}", ref_name.shorten());
+    Ok(())
+}
+
+pub fn format_patch(
+    args: &Args,
+    name: &str,
+    base: Option<&str>,
+    output: Option<&Path>,
+) -> anyhow::Result<()> {
+    let (repo, meta) = repo_and_metadata(args)?;
+    let ref_name = branch_ref_name(name)?;
+    let base = match base {
+        Some(base) => Some(repo.rev_parse_single(base)?.detach()),
+        None => default_patch_base(&repo, &meta, ref_name.as_ref())?,
+    };
+    let mbox =
+        but_workspace::mbox::format_patches_for_branch(&repo, &meta, ref_name.as_ref(), base)?;
+    let Some(output) = output.filter(|path| *path != Path::new("-")) else {
+        if args.format == OutputFormat::Json {
+            return json_print(serde_json::json!({
+                "refName": ref_name.to_string(),
+                "base": base.map(|id| id.to_string()),
+                "mbox": mbox.to_str_lossy(),
+            }));
+        }
+        std::io::stdout().write_all(&mbox)?;
+        return Ok(());
+    };
+    std::fs::write(output, &mbox)?;
+    if args.format == OutputFormat::Json {
+        return json_print(serde_json::json!({
+            "refName": ref_name.to_string(),
+            "base": base.map(|id| id.to_string()),
+            "output": output,
+        }));
+    }
+    println!(
+        "Wrote patches of {} to {}",
+        ref_name.shorten(),
+        output.display()
+    );
+    Ok(())
+}
+
+pub fn am(
+    args: &Args,
+    name: &str,
+    mbox: &Path,
+    base: Option<&str>,
+    apply: bool,
+) -> anyhow::Result<()> {
+    let (repo, mut meta) = repo_and_metadata(args)?;
+    let ref_name = branch_ref_name(name)?;
+    let base_id = match base {
+        Some(base) => repo.rev_parse_single(base)?.detach(),
+        None => target_tip(&repo, &meta)?.context(
+            "There is no target branch to apply the patches to, please provide the base with --base",
+        )?,
+    };
+    let commit_ids = but_workspace::mbox::create_stack_from_mbox(
+        &repo,
+        &mut meta,
+        ref_name.as_ref(),
+        base_id,
+        &read_patch(mbox)?,
+    )?;
+
+    if apply {
+        apply_ref(&repo, &mut meta, ref_name.clone())?;
+    }
+    if args.format == OutputFormat::Json {
+        return json_print(serde_json::json!({
+            "refName": ref_name.to_string(),
+            "base": base_id.to_string(),
+            "commits": commit_ids.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            "applied": apply,
+        }));
+    }
+    println!(
+        "Created {} with {} commit(s) on top of {base_id}",
+        ref_name.shorten(),
+        commit_ids.len()
+    );
+    if apply {
+        println!("Applied {}", ref_name.shorten());
+    }
+    Ok(())
+}
+
+/// Patches of a branch start after the branch below it in its stack, or after the target branch for the
+/// bottom-most branch. Without either, all of its commits are included.
+fn default_patch_base(
+    repo: &gix::Repository,
+    meta: &VirtualBranchesTomlMetadata,
+    ref_name: &gix::refs::FullNameRef,
+) -> anyhow::Result<Option<gix::ObjectId>> {
+    let ws = meta.workspace(WORKSPACE_REF.try_into()?)?;
+    let branch_below = ws.stacks.iter().find_map(|stack| {
+        stack
+            .branches
+            .iter()
+            .skip_while(|branch| branch.ref_name.as_bstr() != ref_name.as_bstr())
+            .nth(1)
+    });
+    if let Some(branch_below) = branch_below {
+        return Ok(Some(
+            repo.find_reference(branch_below.ref_name.as_ref())?
+                .peel_to_id_in_place()?
+                .detach(),
+        ));
+    }
+    let Some(target_tip) = target_tip(repo, meta)? else {
+        return Ok(None);
+    };
+    let tip = repo.find_reference(ref_name)?.peel_to_id_in_place()?;
+    Ok(Some(repo.merge_base(tip, target_tip)?.detach()))
+}
+
+fn apply_ref(
+    repo: &gix::Repository,
+    meta: &mut VirtualBranchesTomlMetadata,
+    ref_name: gix::refs::FullName,
+) -> anyhow::Result<()> {
+    let branch_tip = repo
+        .find_reference(ref_name.as_ref())?
+        .peel_to_id_in_place()?
+        .detach();
+    let workspace_tip = repo
+        .find_reference(WORKSPACE_REF)?
+        .peel_to_id_in_place()?
+        .detach();
+
+    if repo.merge_base(branch_tip, workspace_tip)? == branch_tip {
+        // Nothing to merge, the stack has no commits of its own yet.
+        let mut ws = meta.workspace(WORKSPACE_REF.try_into()?)?;
+        if ws.contains_ref(ref_name.as_ref()) {
+            bail!("'{}' is already in the workspace", ref_name.shorten());
+        }
+        ws.stacks.push(WorkspaceStack {
+            branches: vec![WorkspaceStackBranch {
+                ref_name: ref_name.clone(),
+                archived: false,
+            }],
+        });
+        meta.set_workspace(&ws)?;
+    } else {
+        assure_clean_worktree(repo)?;
+        let outcome = but_workspace::branch::add_branch_to_workspace_and_update_refs(
+            repo,
+            ref_name.as_bstr().try_into()?,
+            WORKSPACE_REF.try_into()?,
+            target_ref(meta)?,
+            meta,
+        )?;
+        checkout_workspace(repo, outcome.workspace_tip)?;
+    }
+    Ok(())
+}
+
+/// Open the repository along with the metadata of its project, or the metadata in the repository if there is no project.
+fn repo_and_metadata(
+    args: &Args,
+) -> anyhow::Result<(gix::Repository, VirtualBranchesTomlMetadata)> {
+    let (repo, project) = repo_and_maybe_project(args, RepositoryOpenMode::Merge)?;
+    let gb_dir = match project {
+        Some(project) => project.gb_dir(),
+        None => repo.path().join("gitbutler"),
+    };
+    let meta = VirtualBranchesTomlMetadata::from_path(gb_dir.join("virtual_branches.toml"))?;
+    Ok((repo, meta))
+}
+
+/// Turn a short branch `name` like `feature` into a full reference name, or keep it if it is one already.
+fn branch_ref_name(name: &str) -> anyhow::Result<gix::refs::FullName> {
+    Ok(if name.starts_with("refs/") {
+        name.try_into()?
+    } else {
+        format!("refs/heads/{name}").try_into()?
+    })
+}
+
+fn target_ref(
+    meta: &VirtualBranchesTomlMetadata,
+) -> anyhow::Result<Option<gix::refs::PartialName>> {
+    let ws = meta.workspace(WORKSPACE_REF.try_into()?)?;
+    Ok(ws
+        .target_ref
+        .as_ref()
+        .map(|name| name.as_bstr().try_into())
+        .transpose()?)
+}
+
+fn target_tip(
+    repo: &gix::Repository,
+    meta: &VirtualBranchesTomlMetadata,
+) -> anyhow::Result<Option<gix::ObjectId>> {
+    let ws = meta.workspace(WORKSPACE_REF.try_into()?)?;
+    let Some(target_ref) = ws.target_ref.as_ref() else {
+        return Ok(None);
+    };
+    Ok(Some(
+        repo.find_reference(target_ref.as_ref())?
+            .peel_to_id_in_place()?
+            .detach(),
+    ))
+}
+
+/// Changing the workspace commit means changing the worktree, which we only do if there is nothing to lose.
+fn assure_clean_worktree(repo: &gix::Repository) -> anyhow::Result<()> {
+    let changes = but_core::diff::worktree_changes(repo)?;
+    if !changes.changes.is_empty() || !changes.ignored_changes.is_empty() {
+        bail!("Please commit or discard all worktree changes first");
+    }
+    Ok(())
+}
+
+/// Make index and worktree match the tree of the new `workspace_tip`, which `HEAD` already points to.
+fn checkout_workspace(repo: &gix::Repository, workspace_tip: gix::ObjectId) -> anyhow::Result<()> {
+    use gitbutler_oxidize::ObjectIdExt as _;
+    let git2_repo = git2::Repository::open(repo.path())?;
+    let tree = git2_repo.find_commit(workspace_tip.to_git2())?.tree()?;
+    let mut checkout = git2::build::CheckoutBuilder::new();
+    checkout.force();
+    git2_repo.checkout_tree(tree.as_object(), Some(&mut checkout))?;
+    Ok(())
+}
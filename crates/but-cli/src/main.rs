@@ -4,7 +4,7 @@ use anyhow::Result;
 
 mod args;
 use crate::command::{RepositoryOpenMode, repo_and_maybe_project};
-use args::Args;
+use args::{Args, OutputFormat};
 
 mod command;
 
@@ -16,6 +16,19 @@ fn main() -> Result<()> {
     }
     let _op_span = tracing::info_span!("cli-op").entered();
 
+    match (run(&args), args.format) {
+        (Err(err), OutputFormat::Json) => {
+            command::json_print_error(&err)?;
+            std::process::exit(1);
+        }
+        (res, _) => res,
+    }
+}
+
+fn run(args: &Args) -> Result<()> {
+    let format = args.format;
+    // Some commands had a `--json` flag before `--format` existed.
+    let format_or_json = |json: bool| if json { OutputFormat::Json } else { format };
     match &args.cmd {
         args::Subcommands::DiscardChange {
             hunk_indices,
@@ -37,6 +50,7 @@ fn main() -> Result<()> {
             } else {
                 None
             },
+            format,
         ),
         args::Subcommands::Commit {
             current_path,
@@ -47,8 +61,9 @@ fn main() -> Result<()> {
             parent,
             workspace_tip,
             stack_segment_ref,
+            patch,
         } => {
-            let (repo, project) = repo_and_maybe_project(&args, RepositoryOpenMode::Merge)?;
+            let (repo, project) = repo_and_maybe_project(args, RepositoryOpenMode::Merge)?;
             command::commit(
                 repo,
                 project,
@@ -64,13 +79,15 @@ fn main() -> Result<()> {
                 } else {
                     None
                 },
+                patch.as_deref(),
+                format,
             )
         }
-        args::Subcommands::HunkDependency => command::diff::locks(&args.current_dir),
+        args::Subcommands::HunkDependency => command::diff::locks(&args.current_dir, format),
         args::Subcommands::Status {
             unified_diff,
             context_lines,
-        } => command::diff::status(&args.current_dir, *unified_diff, *context_lines),
+        } => command::diff::status(&args.current_dir, *unified_diff, *context_lines, format),
         args::Subcommands::CommitChanges {
             unified_diff,
             current_commit,
@@ -80,12 +97,108 @@ fn main() -> Result<()> {
             current_commit,
             previous_commit.as_deref(),
             *unified_diff,
+            format,
         ),
-        args::Subcommands::Stacks => command::stacks::list(&args.current_dir),
-        args::Subcommands::StackBranches { id } => command::stacks::branches(id, &args.current_dir),
+        args::Subcommands::Stacks => command::stacks::list(&args.current_dir, format),
+        args::Subcommands::StackBranches { id } => {
+            command::stacks::branches(id, &args.current_dir, format)
+        }
         args::Subcommands::StackBranchCommits { id, name } => {
-            command::stacks::branch_commits(id, name, &args.current_dir)
+            command::stacks::branch_commits(id, name, &args.current_dir, format)
+        }
+        args::Subcommands::Autosquash { id } => {
+            command::stacks::autosquash(id, &args.current_dir, format)
         }
+        args::Subcommands::Stack { cmd } => match cmd {
+            args::stack::Subcommands::Create {
+                name,
+                base,
+                unapplied,
+            } => command::stack::create(args, name, base.as_deref(), !*unapplied),
+            args::stack::Subcommands::Rename { name, new_name } => {
+                command::stack::rename(args, name, new_name)
+            }
+            args::stack::Subcommands::Reorder { names } => command::stack::reorder(args, names),
+            args::stack::Subcommands::Apply { name } => command::stack::apply(args, name),
+            args::stack::Subcommands::Unapply { name } => command::stack::unapply(args, name),
+            args::stack::Subcommands::FormatPatch { name, base, output } => {
+                command::stack::format_patch(args, name, base.as_deref(), output.as_deref())
+            }
+            args::stack::Subcommands::Am {
+                name,
+                mbox,
+                base,
+                unapplied,
+            } => command::stack::am(args, name, mbox, base.as_deref(), !*unapplied),
+        },
+        args::Subcommands::Oplog { cmd } => match cmd {
+            args::oplog::Subcommands::List {
+                limit,
+                kinds,
+                since,
+                until,
+                trailers,
+                pinned,
+                json,
+            } => command::oplog::list(
+                &args.current_dir,
+                *limit,
+                command::oplog::Filter::from_args(
+                    kinds,
+                    since.as_deref(),
+                    until.as_deref(),
+                    trailers,
+                    *pinned,
+                )?,
+                format_or_json(*json),
+            ),
+            args::oplog::Subcommands::Show { snapshot, json } => {
+                command::oplog::show(&args.current_dir, snapshot, format_or_json(*json))
+            }
+            args::oplog::Subcommands::Diff {
+                snapshot,
+                other,
+                json,
+            } => match other {
+                Some(other) => command::oplog::compare(
+                    &args.current_dir,
+                    snapshot,
+                    other,
+                    format_or_json(*json),
+                ),
+                None => command::oplog::diff(&args.current_dir, snapshot, format_or_json(*json)),
+            },
+            args::oplog::Subcommands::Restore { snapshot } => {
+                command::oplog::restore(&args.current_dir, snapshot, format)
+            }
+            args::oplog::Subcommands::Gc {
+                keep_last,
+                keep_newer_than,
+                daily,
+                weekly,
+            } => command::oplog::gc(
+                args,
+                *keep_last,
+                keep_newer_than.as_deref(),
+                *daily,
+                *weekly,
+            ),
+            args::oplog::Subcommands::Checkpoint { title, body, pin } => {
+                command::oplog::checkpoint(&args.current_dir, title, body.as_deref(), *pin, format)
+            }
+            args::oplog::Subcommands::Pin { snapshot } => {
+                command::oplog::set_pinned(&args.current_dir, snapshot, true, format)
+            }
+            args::oplog::Subcommands::Unpin { snapshot } => {
+                command::oplog::set_pinned(&args.current_dir, snapshot, false, format)
+            }
+            args::oplog::Subcommands::Export { bundle, depth } => {
+                command::oplog::export(&args.current_dir, bundle, *depth, format)
+            }
+            args::oplog::Subcommands::Import { bundle } => {
+                command::oplog::import(&args.current_dir, bundle, format)
+            }
+        },
     }
 }
 
@@ -17,16 +17,26 @@ pub fn commit_changes(
 ) -> anyhow::Result<Vec<TreeChange>> {
     let lhs_tree = lhs_commit
         .map(|commit_id| {
-            Commit::from_id(commit_id.attach(repo)).and_then(|commit| {
-                let id = commit.tree_id()?;
-                Ok(id.object()?.into_tree())
-            })
+            Commit::from_id(commit_id.attach(repo)).and_then(|commit| commit.tree_id())
         })
         .transpose()?;
-    let rhs_tree = Commit::from_id(rhs_commit.attach(repo))?
-        .tree_id()?
-        .object()
-        .map(|obj| obj.into_tree())?;
+    let rhs_tree = Commit::from_id(rhs_commit.attach(repo))?.tree_id()?;
+    tree_changes(repo, lhs_tree.map(|id| id.detach()), rhs_tree.detach())
+}
+
+/// Produce all changes that are needed to turn `lhs_tree` into `rhs_tree`.
+/// If `lhs_tree` is `None`, it will be treated like an empty tree.
+///
+/// They are sorted by their current path.
+pub fn tree_changes(
+    repo: &gix::Repository,
+    lhs_tree: Option<gix::ObjectId>,
+    rhs_tree: gix::ObjectId,
+) -> anyhow::Result<Vec<TreeChange>> {
+    let lhs_tree = lhs_tree
+        .map(|id| id.attach(repo).object().map(|obj| obj.into_tree()))
+        .transpose()?;
+    let rhs_tree = rhs_tree.attach(repo).object()?.into_tree();
 
     let changes = repo.diff_tree_to_tree(lhs_tree.as_ref(), &rhs_tree, None)?;
     let mut out: Vec<TreeChange> = changes
@@ -0,0 +1,211 @@
+use crate::RebaseStep;
+use anyhow::Result;
+use bstr::{BStr, BString, ByteSlice};
+use std::collections::HashMap;
+
+/// The kind of commit that is meant to be folded into a previous commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// `fixup! <target>` squashes the changes and keeps the message of the target.
+    Fixup,
+    /// `squash! <target>` squashes the changes and appends its message body to the message of the target.
+    Squash,
+    /// `amend! <target>` squashes the changes and replaces the message of the target with its message body.
+    Amend,
+}
+
+/// Parse the `subject` of a commit message into the [kind](Kind) of fold it requests and the
+/// text identifying its target, or return `None` if it's a normal commit.
+///
+/// Repeated prefixes like in `fixup! squash! <target>` are skipped, with the first one determining the kind.
+pub fn parse_subject(subject: &BStr) -> Option<(Kind, &BStr)> {
+    let (kind, mut target) = strip_prefix(subject)?;
+    while let Some((_, rest)) = strip_prefix(target) {
+        target = rest;
+    }
+    Some((kind, target.trim().as_bstr()))
+}
+
+fn strip_prefix(subject: &BStr) -> Option<(Kind, &BStr)> {
+    [
+        (Kind::Fixup, "fixup! "),
+        (Kind::Squash, "squash! "),
+        (Kind::Amend, "amend! "),
+    ]
+    .into_iter()
+    .find_map(|(kind, prefix)| {
+        subject
+            .strip_prefix(prefix.as_bytes())
+            .map(|rest| (kind, rest.as_bstr()))
+    })
+}
+
+/// Reorder `steps` so that each picked `fixup!`, `squash!` or `amend!` commit is [squashed](RebaseStep::SquashIntoPreceding)
+/// into the picked commit it refers to, with the message adjusted accordingly.
+///
+/// The target is searched among the commits picked before it, within the same sequence of picks, and is the first one that
+/// matches, in order of preference:
+///
+/// - its subject is the same as the target text,
+/// - its [Change-Id](but_core::commit::HeadersV2::change_id) is the same as the target text,
+/// - its subject starts with the target text.
+///
+/// Commits for which no target can be found, and merge commits, are picked as before.
+pub fn plan(
+    repo: &gix::Repository,
+    steps: impl IntoIterator<Item = RebaseStep>,
+) -> Result<Vec<RebaseStep>> {
+    let steps: Vec<_> = steps.into_iter().collect();
+
+    // Pass 1: figure out which picks should be folded into which target.
+    let mut folds = HashMap::<gix::ObjectId, Vec<(Kind, gix::ObjectId)>>::new();
+    let mut folded = Vec::new();
+    let mut candidates = Vec::<Candidate>::new();
+    for step in &steps {
+        let RebaseStep::Pick {
+            commit_id, base, ..
+        } = step
+        else {
+            continue;
+        };
+        if base.is_some() {
+            candidates.clear();
+        }
+        let commit = repo.find_commit(*commit_id)?.decode()?.to_owned();
+        let subject = first_line(commit.message.as_ref());
+        if commit.parents.len() < 2 {
+            if let Some((kind, target)) = parse_subject(subject) {
+                if let Some(target) = find_target(&candidates, target) {
+                    folds.entry(target).or_default().push((kind, *commit_id));
+                    folded.push(*commit_id);
+                    continue;
+                }
+            }
+        }
+        candidates.push(Candidate {
+            id: *commit_id,
+            subject: subject.to_owned(),
+            change_id: but_core::commit::HeadersV2::try_from_commit(&commit)
+                .map(|headers| headers.change_id),
+        });
+    }
+    if folded.is_empty() {
+        return Ok(steps);
+    }
+
+    // Pass 2: emit all steps, with the folded ones right after their target and after squashes that were already there.
+    let mut out = Vec::with_capacity(steps.len());
+    let mut pending: Option<(gix::ObjectId, BString)> = None;
+    for step in steps {
+        match &step {
+            RebaseStep::Pick { commit_id, .. } if folded.contains(commit_id) => continue,
+            RebaseStep::SquashIntoPreceding {
+                commit_id,
+                new_message,
+            } => {
+                if let Some((_, message)) = pending.as_mut() {
+                    *message = match new_message {
+                        Some(new_message) => new_message.clone(),
+                        None => message_of(repo, *commit_id)?,
+                    };
+                }
+                out.push(step);
+                continue;
+            }
+            _ => {}
+        }
+        flush(repo, &mut out, pending.take(), &folds)?;
+        if let RebaseStep::Pick {
+            commit_id,
+            new_message,
+            ..
+        } = &step
+        {
+            if folds.contains_key(commit_id) {
+                let message = match new_message {
+                    Some(new_message) => new_message.clone(),
+                    None => message_of(repo, *commit_id)?,
+                };
+                pending = Some((*commit_id, message));
+            }
+        }
+        out.push(step);
+    }
+    flush(repo, &mut out, pending, &folds)?;
+    Ok(out)
+}
+
+/// A commit that fixups may refer to.
+struct Candidate {
+    id: gix::ObjectId,
+    subject: BString,
+    change_id: Option<String>,
+}
+
+fn find_target(candidates: &[Candidate], target: &BStr) -> Option<gix::ObjectId> {
+    candidates
+        .iter()
+        .find(|c| c.subject == target)
+        .or_else(|| {
+            candidates.iter().find(|c| {
+                c.change_id
+                    .as_deref()
+                    .is_some_and(|id| id.as_bytes() == target)
+            })
+        })
+        .or_else(|| candidates.iter().find(|c| c.subject.starts_with(target)))
+        .map(|c| c.id)
+}
+
+/// Emit the squash steps for all commits folded into the `pending` target, whose current message is also given.
+fn flush(
+    repo: &gix::Repository,
+    out: &mut Vec<RebaseStep>,
+    pending: Option<(gix::ObjectId, BString)>,
+    folds: &HashMap<gix::ObjectId, Vec<(Kind, gix::ObjectId)>>,
+) -> Result<()> {
+    let Some((target, mut message)) = pending else {
+        return Ok(());
+    };
+    for (kind, commit_id) in folds.get(&target).into_iter().flatten() {
+        match kind {
+            Kind::Fixup => {}
+            Kind::Squash => {
+                let body = body_of(&message_of(repo, *commit_id)?);
+                if !body.is_empty() {
+                    let mut combined = message.trim_end().as_bstr().to_owned();
+                    combined.extend_from_slice(b"\n\n");
+                    combined.extend_from_slice(&body);
+                    message = combined;
+                }
+            }
+            Kind::Amend => {
+                let body = body_of(&message_of(repo, *commit_id)?);
+                if !body.is_empty() {
+                    message = body;
+                }
+            }
+        }
+        out.push(RebaseStep::SquashIntoPreceding {
+            commit_id: *commit_id,
+            new_message: Some(message.clone()),
+        });
+    }
+    Ok(())
+}
+
+fn message_of(repo: &gix::Repository, id: gix::ObjectId) -> Result<BString> {
+    Ok(repo.find_commit(id)?.message_raw()?.to_owned())
+}
+
+fn first_line(message: &BStr) -> &BStr {
+    message.lines().next().unwrap_or_default().trim().as_bstr()
+}
+
+/// Return everything after the subject of `message`, without leading empty lines.
+fn body_of(message: &BStr) -> BString {
+    message
+        .find_byte(b'\n')
+        .map(|pos| message[pos + 1..].trim_start().as_bstr().to_owned())
+        .unwrap_or_default()
+}
@@ -22,6 +22,8 @@ pub enum EmptyCommit {
     UsePrevious,
 }
 
+pub use function::ConflictEntries;
+
 pub(crate) mod function {
     use crate::cherry_pick::{EmptyCommit, PickMode};
     use crate::commit::CommitterMode;
@@ -30,7 +32,7 @@ pub(crate) mod function {
     use but_core::commit::{HEADERS_CONFLICTED_FIELD, HeadersV2, TreeKind};
     use gix::object::tree::EntryKind;
     use gix::prelude::ObjectIdExt;
-    use serde::Serialize;
+    use serde::{Deserialize, Serialize};
     use std::collections::HashSet;
     use std::path::PathBuf;
 
@@ -195,56 +197,68 @@ pub(crate) mod function {
         treat_as_unresolved: gix::merge::tree::TreatAsUnresolved,
     ) -> anyhow::Result<gix::Id<'repo>> {
         let repo = resolved_tree_id.repo;
+        let conflicted_files =
+            extract_conflicted_files(resolved_tree_id, cherry_pick, treat_as_unresolved)?;
+        let (base_tree_id, ours_tree_id, theirs_tree_id) =
+            find_cherry_pick_trees(&head, &to_rebase)?;
+        let tree = write_conflicted_tree(
+            repo,
+            base_tree_id.detach(),
+            ours_tree_id.detach(),
+            theirs_tree_id.detach(),
+            resolved_tree_id.detach(),
+            &conflicted_files,
+        )?;
+
+        let mut headers = to_rebase.headers().unwrap_or_default();
+        headers.conflicted = conflicted_files.conflicted_header_field();
+        to_rebase.tree = tree;
+        set_parent(&mut to_rebase, head.id.detach())?;
+
+        to_rebase.set_headers(&headers);
+        Ok(crate::commit::create(repo, to_rebase.inner, CommitterMode::Update)?.attach(repo))
+    }
+
+    /// Write the special tree that records a conflict between `ours` and `theirs` with their common `base`, along with
+    /// the `resolved` tree that is used in place of the conflicting ones, and `conflicted_files`, returning its id.
+    pub(crate) fn write_conflicted_tree(
+        repo: &gix::Repository,
+        base: gix::ObjectId,
+        ours: gix::ObjectId,
+        theirs: gix::ObjectId,
+        resolved: gix::ObjectId,
+        conflicted_files: &ConflictEntries,
+    ) -> anyhow::Result<gix::ObjectId> {
         // in case someone checks this out with vanilla Git, we should warn why it looks like this
         let readme_content =
             b"You have checked out a GitButler Conflicted commit. You probably didn't mean to do this.";
         let readme_blob = repo.write_blob(readme_content)?;
 
-        let conflicted_files =
-            extract_conflicted_files(resolved_tree_id, cherry_pick, treat_as_unresolved)?;
-
         // convert files into a string and save as a blob
-        let conflicted_files_string = toml::to_string(&conflicted_files)?;
+        let conflicted_files_string = toml::to_string(conflicted_files)?;
         let conflicted_files_blob = repo.write_blob(conflicted_files_string.as_bytes())?;
 
         let mut tree = repo.empty_tree().edit()?;
 
         // save the state of the conflict, so we can recreate it later
-        let (base_tree_id, ours_tree_id, theirs_tree_id) =
-            find_cherry_pick_trees(&head, &to_rebase)?;
-        tree.upsert(
-            TreeKind::Ours.as_tree_entry_name(),
-            EntryKind::Tree,
-            ours_tree_id,
-        )?;
+        tree.upsert(TreeKind::Ours.as_tree_entry_name(), EntryKind::Tree, ours)?;
         tree.upsert(
             TreeKind::Theirs.as_tree_entry_name(),
             EntryKind::Tree,
-            theirs_tree_id,
-        )?;
-        tree.upsert(
-            TreeKind::Base.as_tree_entry_name(),
-            EntryKind::Tree,
-            base_tree_id,
+            theirs,
         )?;
+        tree.upsert(TreeKind::Base.as_tree_entry_name(), EntryKind::Tree, base)?;
         tree.upsert(
             TreeKind::AutoResolution.as_tree_entry_name(),
             EntryKind::Tree,
-            resolved_tree_id,
+            resolved,
         )?;
         tree.upsert(".conflict-files", EntryKind::Blob, conflicted_files_blob)?;
         tree.upsert("README.txt", EntryKind::Blob, readme_blob)?;
-
-        let mut headers = to_rebase.headers().unwrap_or_default();
-        headers.conflicted = conflicted_files.conflicted_header_field();
-        to_rebase.tree = tree.write().context("failed to write tree")?.detach();
-        set_parent(&mut to_rebase, head.id.detach())?;
-
-        to_rebase.set_headers(&headers);
-        Ok(crate::commit::create(repo, to_rebase.inner, CommitterMode::Update)?.attach(repo))
+        Ok(tree.write().context("failed to write tree")?.detach())
     }
 
-    fn extract_conflicted_files(
+    pub(crate) fn extract_conflicted_files(
         merged_tree_id: gix::Id<'_>,
         merge_result: gix::merge::tree::Outcome<'_>,
         treat_as_unresolved: gix::merge::tree::TreatAsUnresolved,
@@ -310,15 +324,37 @@ pub(crate) mod function {
         Ok(out)
     }
 
-    #[derive(Default, Debug, Clone, Serialize, PartialEq)]
+    /// The paths that conflicted in a commit, as stored in the `.conflict-files` entry of its tree.
+    #[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
     #[serde(rename_all = "camelCase")]
     pub struct ConflictEntries {
-        ancestor_entries: Vec<PathBuf>,
-        our_entries: Vec<PathBuf>,
-        their_entries: Vec<PathBuf>,
+        /// The conflicting paths as seen in the *base* tree.
+        #[serde(default)]
+        pub ancestor_entries: Vec<PathBuf>,
+        /// The conflicting paths as seen in the *ours* tree.
+        #[serde(default)]
+        pub our_entries: Vec<PathBuf>,
+        /// The conflicting paths as seen in the *theirs* tree.
+        #[serde(default)]
+        pub their_entries: Vec<PathBuf>,
     }
 
     impl ConflictEntries {
+        /// Add all entries of `other` that aren't present yet.
+        pub(crate) fn extend(&mut self, other: ConflictEntries) {
+            for (entries, other_entries) in [
+                (&mut self.ancestor_entries, other.ancestor_entries),
+                (&mut self.our_entries, other.our_entries),
+                (&mut self.their_entries, other.their_entries),
+            ] {
+                for entry in other_entries {
+                    if !entries.contains(&entry) {
+                        entries.push(entry);
+                    }
+                }
+            }
+        }
+
         fn has_entries(&self) -> bool {
             !self.ancestor_entries.is_empty()
                 || !self.our_entries.is_empty()
@@ -337,7 +373,7 @@ pub(crate) mod function {
         }
 
         /// Return the `conflicted` header field value.
-        fn conflicted_header_field(&self) -> Option<u64> {
+        pub(crate) fn conflicted_header_field(&self) -> Option<u64> {
             let entries = self.total_entries();
             Some(if entries > 0 { entries as u64 } else { 1 })
         }
@@ -4,6 +4,7 @@ use but_core::cmd::prepare_with_shell;
 use but_core::{GitConfigSettings, RepositoryExt};
 use gitbutler_error::error::Code;
 use gix::objs::WriteTo;
+use serde::Serialize;
 use std::borrow::Cow;
 use std::io::Write;
 use std::path::Path;
@@ -92,43 +93,113 @@ pub(crate) fn update_committer(
     Ok(())
 }
 
+/// The signature formats Git knows, as configured with `gpg.format`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SignatureFormat {
+    /// OpenPGP signatures produced by `gpg`, the default.
+    OpenPgp,
+    /// X.509 signatures produced by `gpgsm`.
+    X509,
+    /// SSH signatures produced by `ssh-keygen`.
+    Ssh,
+}
+
+impl SignatureFormat {
+    /// Read the format to sign with from `gpg.format` in `config`, falling back to OpenPGP if it's unset or unknown.
+    pub fn from_config(config: &gix::config::Snapshot<'_>) -> Self {
+        match config.string("gpg.format") {
+            None => SignatureFormat::OpenPgp,
+            Some(format) => match format.as_bstr().as_bytes() {
+                b"x509" => SignatureFormat::X509,
+                b"ssh" => SignatureFormat::Ssh,
+                b"openpgp" => SignatureFormat::OpenPgp,
+                _ => {
+                    tracing::warn!(
+                        "Unknown signature format in 'gpg.format': {format}, using 'openpgp'"
+                    );
+                    SignatureFormat::OpenPgp
+                }
+            },
+        }
+    }
+
+    /// Determine the format of the armored `signature`, or `None` if it isn't known.
+    pub fn from_signature(signature: &[u8]) -> Option<Self> {
+        let signature = signature.trim_start();
+        if signature.starts_with(b"-----BEGIN PGP SIGNATURE-----")
+            || signature.starts_with(b"-----BEGIN PGP MESSAGE-----")
+        {
+            Some(SignatureFormat::OpenPgp)
+        } else if signature.starts_with(b"-----BEGIN SIGNED MESSAGE-----") {
+            Some(SignatureFormat::X509)
+        } else if signature.starts_with(b"-----BEGIN SSH SIGNATURE-----") {
+            Some(SignatureFormat::Ssh)
+        } else {
+            None
+        }
+    }
+
+    /// The name of the format as used in `gpg.format`.
+    fn config_name(self) -> &'static str {
+        match self {
+            SignatureFormat::OpenPgp => "openpgp",
+            SignatureFormat::X509 => "x509",
+            SignatureFormat::Ssh => "ssh",
+        }
+    }
+
+    /// The program to sign and verify with, which is overridden with `gpg.<format>.program`,
+    /// or with `gpg.program` for OpenPGP.
+    fn program<'a>(self, config: &'a gix::config::Snapshot<'_>) -> (Cow<'a, Path>, String) {
+        let key = format!("gpg.{}.program", self.config_name());
+        let configured = config
+            .trusted_program(key.as_str())
+            .filter(|program| !program.is_empty())
+            .map(|program| (program, key))
+            .or_else(|| {
+                (self == SignatureFormat::OpenPgp)
+                    .then(|| config.trusted_program("gpg.program"))
+                    .flatten()
+                    .filter(|program| !program.is_empty())
+                    .map(|program| (program, "gpg.program".to_owned()))
+            });
+        match configured {
+            Some((program, key)) => (Cow::Owned(program.into_owned().into()), key),
+            None => {
+                let default = match self {
+                    SignatureFormat::OpenPgp => "gpg",
+                    SignatureFormat::X509 => "gpgsm",
+                    SignatureFormat::Ssh => "ssh-keygen",
+                };
+                (Path::new(default).into(), key)
+            }
+        }
+    }
+}
+
 /// Sign the given `buffer` using configuration from `repo`, just like Git would.
+///
+/// The signing key is `user.signingkey`. If it's not set, `gpg.ssh.defaultKeyCommand` provides it for SSH signatures,
+/// while the committer identity is used for OpenPGP and X.509 signatures.
 pub fn sign_buffer(repo: &gix::Repository, buffer: &[u8]) -> anyhow::Result<BString> {
-    // TODO: support gpg.ssh.defaultKeyCommand to get the signing key if this value doesn't exist
     let config = repo.config_snapshot();
-    let Some(signing_key) = config.string("user.signingkey") else {
-        bail!("No signing key found");
-    };
-    let signing_key = signing_key.to_str().context("non-utf8 signing key")?;
-    let sign_format = config.string("gpg.format");
-    let is_ssh = if let Some(sign_format) = sign_format {
-        sign_format.as_ref() == "ssh"
-    } else {
-        false
-    };
+    let format = SignatureFormat::from_config(&config);
+    let signing_key = signing_key(repo, &config, format)?;
+    let (program, program_key) = format.program(&config);
 
-    if is_ssh {
+    if format == SignatureFormat::Ssh {
         // write commit data to a temp file so we can sign it
         let mut signature_storage = tempfile::NamedTempFile::new()?;
         signature_storage.write_all(buffer)?;
         let buffer_file_to_sign_path = signature_storage.into_temp_path();
 
-        let gpg_program = config
-            .trusted_program("gpg.ssh.program")
-            .filter(|program| !program.is_empty())
-            .map_or_else(
-                || Path::new("ssh-keygen").into(),
-                |program| Cow::Owned(program.into_owned().into()),
-            );
-
-        let cmd =
-            prepare_with_shell(gpg_program.into_owned()).args(["-Y", "sign", "-n", "git", "-f"]);
+        let cmd = prepare_with_shell(program.into_owned()).args(["-Y", "sign", "-n", "git", "-f"]);
 
         // Write the key to a temp file. This is needs to be created in the
         // same scope where its used; IE: in the command, otherwise the
         // tmpfile will get removed too early.
         let mut key_storage = tempfile::NamedTempFile::new()?;
-        let signing_cmd = if let Some(signing_key) = as_literal_key(signing_key) {
+        let signing_cmd = if let Some(signing_key) = as_literal_key(&signing_key) {
             key_storage.write_all(signing_key.as_bytes())?;
 
             // if on unix
@@ -171,36 +242,17 @@ pub fn sign_buffer(repo: &gix::Repository, buffer: &[u8]) -> anyhow::Result<BStr
             bail!("Failed to sign SSH: {}", std_both);
         }
     } else {
-        let gpg_program = config
-            .trusted_program("gpg.program")
-            .filter(|program| !program.is_empty())
-            .map_or_else(
-                || Path::new("gpg").into(),
-                |program| Cow::Owned(program.into_owned().into()),
-            );
-
-        let mut cmd = into_command(prepare_with_shell(gpg_program.as_ref()).args([
+        let mut cmd = into_command(prepare_with_shell(program.as_ref()).args([
             "--status-fd=2",
             "-bsau",
-            signing_key,
+            signing_key.as_str(),
             "-",
         ]));
         cmd.stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .stdin(Stdio::piped());
 
-        let mut child = match cmd.spawn() {
-            Ok(child) => child,
-            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
-                bail!(
-                    "Could not find '{}'. Please make sure it is in your `PATH` or configure the full path using `gpg.program` in the Git configuration",
-                    gpg_program.display()
-                )
-            }
-            Err(err) => {
-                return Err(err).context(format!("Could not execute GPG program using {:?}", cmd));
-            }
-        };
+        let mut child = spawn(&mut cmd, &program, &program_key)?;
         child.stdin.take().expect("configured").write_all(buffer)?;
 
         let output = child.wait_with_output()?;
@@ -212,11 +264,296 @@ pub fn sign_buffer(repo: &gix::Repository, buffer: &[u8]) -> anyhow::Result<BStr
             let stderr = BString::new(output.stderr);
             let stdout = BString::new(output.stdout);
             let std_both = format!("{} {}", stdout, stderr);
-            bail!("Failed to sign GPG: {}", std_both);
+            match format {
+                SignatureFormat::X509 => bail!("Failed to sign X.509: {}", std_both),
+                _ => bail!("Failed to sign GPG: {}", std_both),
+            }
+        }
+    }
+}
+
+/// The outcome of verifying the signature of a commit, similar to what `git log --format=%G?` would show.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", content = "subject")]
+pub enum SignatureStatus {
+    /// The commit isn't signed.
+    Unsigned,
+    /// The signature is valid and made by a trusted key, owned by `signer`.
+    Good {
+        /// The identity of the owner of the key, or the principal of SSH signatures.
+        signer: String,
+    },
+    /// The signature is valid, but the key that made it isn't trusted or, for SSH signatures, not in the allowed signers.
+    Untrusted {
+        /// The identity of the owner of the key, if known.
+        signer: Option<String>,
+    },
+    /// The signature was valid, but it or its key expired.
+    Expired {
+        /// The identity of the owner of the key.
+        signer: String,
+    },
+    /// The signature was made by a key that was revoked.
+    Revoked {
+        /// The identity of the owner of the key.
+        signer: String,
+    },
+    /// The signature doesn't match the commit.
+    Bad,
+    /// The signature couldn't be checked, for instance because the key isn't available.
+    Unverifiable {
+        /// Why the signature couldn't be checked.
+        reason: String,
+    },
+}
+
+/// Verify the signature of `commit` using the configuration of `repo`, just like Git would.
+///
+/// SSH signatures are checked against `gpg.ssh.allowedSignersFile` and `gpg.ssh.revocationFile`, while OpenPGP and X.509
+/// signatures are checked against the keyring of the respective program.
+/// Signatures that can't be checked for any reason are reported as [`SignatureStatus::Unverifiable`].
+pub fn verify_signature(repo: &gix::Repository, commit: &gix::objs::Commit) -> SignatureStatus {
+    verify_signature_inner(repo, commit).unwrap_or_else(|err| SignatureStatus::Unverifiable {
+        reason: err.to_string(),
+    })
+}
+
+fn verify_signature_inner(
+    repo: &gix::Repository,
+    commit: &gix::objs::Commit,
+) -> anyhow::Result<SignatureStatus> {
+    let Some(pos) = commit
+        .extra_headers()
+        .find_pos(gix::objs::commit::SIGNATURE_FIELD_NAME)
+    else {
+        return Ok(SignatureStatus::Unsigned);
+    };
+    let mut payload_commit = commit.clone();
+    let (_, signature) = payload_commit.extra_headers.remove(pos);
+    let mut payload = Vec::new();
+    payload_commit.write_to(&mut payload)?;
+
+    let format = SignatureFormat::from_signature(&signature)
+        .context("The signature is in an unknown format")?;
+    let config = repo.config_snapshot();
+    let (program, program_key) = format.program(&config);
+    let mut signature_storage = tempfile::NamedTempFile::new()?;
+    signature_storage.write_all(&signature)?;
+    let signature_path = signature_storage.into_temp_path();
+
+    if format == SignatureFormat::Ssh {
+        let Some(allowed_signers) = config
+            .trusted_path("gpg.ssh.allowedSignersFile")
+            .transpose()?
+        else {
+            bail!("'gpg.ssh.allowedSignersFile' needs to be configured to verify SSH signatures");
+        };
+        let principals = into_command(
+            prepare_with_shell(program.as_ref())
+                .args(["-Y", "find-principals", "-f"])
+                .arg(allowed_signers.as_ref())
+                .arg("-s")
+                .arg(signature_path.to_path_buf()),
+        )
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()?;
+        let principal = principals
+            .status
+            .success()
+            .then(|| {
+                principals
+                    .stdout
+                    .lines()
+                    .map(|line| line.trim())
+                    .find(|line| !line.is_empty())
+                    .map(|line| line.to_str_lossy().into_owned())
+            })
+            .flatten();
+
+        let Some(principal) = principal else {
+            let good = run_with_input(
+                prepare_with_shell(program.as_ref())
+                    .args(["-Y", "check-novalidate", "-n", "git", "-s"])
+                    .arg(signature_path.to_path_buf()),
+                &program,
+                &program_key,
+                &payload,
+            )?
+            .status
+            .success();
+            return Ok(if good {
+                SignatureStatus::Untrusted { signer: None }
+            } else {
+                SignatureStatus::Bad
+            });
+        };
+
+        let mut cmd = prepare_with_shell(program.as_ref())
+            .args(["-Y", "verify", "-n", "git", "-f"])
+            .arg(allowed_signers.as_ref())
+            .arg("-I")
+            .arg(principal.as_str())
+            .arg("-s")
+            .arg(signature_path.to_path_buf());
+        if let Some(revocations) = config.trusted_path("gpg.ssh.revocationFile").transpose()? {
+            cmd = cmd.arg("-r").arg(revocations.as_ref());
+        }
+        let good = run_with_input(cmd, &program, &program_key, &payload)?
+            .status
+            .success();
+        Ok(if good {
+            SignatureStatus::Good { signer: principal }
+        } else {
+            SignatureStatus::Bad
+        })
+    } else {
+        let output = run_with_input(
+            prepare_with_shell(program.as_ref())
+                .args(["--status-fd=1", "--verify"])
+                .arg(signature_path.to_path_buf())
+                .arg("-"),
+            &program,
+            &program_key,
+            &payload,
+        )?;
+        parse_gpg_status(&output.stdout).with_context(|| {
+            format!(
+                "Could not verify signature: {}",
+                output.stderr.to_str_lossy().trim()
+            )
+        })
+    }
+}
+
+/// Interpret the `--status-fd` output of `gpg` or `gpgsm`, or return `None` if it doesn't contain a verdict.
+fn parse_gpg_status(status: &[u8]) -> Option<SignatureStatus> {
+    let mut verdict = None;
+    let mut trusted = false;
+    for line in status.lines() {
+        let Some(line) = line.strip_prefix(b"[GNUPG:] ") else {
+            continue;
+        };
+        let mut tokens = line.splitn_str(3, " ");
+        let keyword = tokens.next().unwrap_or_default();
+        // The key-id is followed by the user-id of the signer.
+        let mut signer = || {
+            tokens
+                .nth(1)
+                .map(|uid| uid.to_str_lossy().into_owned())
+                .unwrap_or_default()
+        };
+        match keyword {
+            b"GOODSIG" => verdict = Some(SignatureStatus::Good { signer: signer() }),
+            b"EXPSIG" | b"EXPKEYSIG" => {
+                verdict = Some(SignatureStatus::Expired { signer: signer() })
+            }
+            b"REVKEYSIG" => verdict = Some(SignatureStatus::Revoked { signer: signer() }),
+            b"BADSIG" => verdict = Some(SignatureStatus::Bad),
+            b"ERRSIG" | b"NO_PUBKEY" => {
+                verdict.get_or_insert(SignatureStatus::Unverifiable {
+                    reason: "The key that made the signature isn't available".into(),
+                });
+            }
+            b"TRUST_MARGINAL" | b"TRUST_FULLY" | b"TRUST_ULTIMATE" => trusted = true,
+            _ => {}
+        }
+    }
+    verdict.map(|verdict| match verdict {
+        SignatureStatus::Good { signer } if !trusted => SignatureStatus::Untrusted {
+            signer: Some(signer),
+        },
+        verdict => verdict,
+    })
+}
+
+/// Obtain the key to sign with for `format`.
+fn signing_key(
+    repo: &gix::Repository,
+    config: &gix::config::Snapshot<'_>,
+    format: SignatureFormat,
+) -> anyhow::Result<String> {
+    if let Some(signing_key) = config.string("user.signingkey") {
+        return Ok(signing_key
+            .to_str()
+            .context("non-utf8 signing key")?
+            .to_owned());
+    }
+    match format {
+        SignatureFormat::Ssh => {
+            let Some(key_command) = config.string("gpg.ssh.defaultKeyCommand") else {
+                bail!("No signing key found");
+            };
+            let key_command = gix::path::from_bstr(key_command.as_bstr()).into_owned();
+            let output = into_command(prepare_with_shell(key_command.clone()))
+                .stdin(Stdio::null())
+                .stderr(Stdio::piped())
+                .stdout(Stdio::piped())
+                .output()?;
+            if !output.status.success() {
+                bail!(
+                    "'gpg.ssh.defaultKeyCommand' {} failed: {}",
+                    key_command.display(),
+                    BString::new(output.stderr)
+                );
+            }
+            output
+                .stdout
+                .lines()
+                .map(|line| line.trim())
+                .find(|line| !line.is_empty())
+                .and_then(|line| line.to_str().ok())
+                .filter(|line| as_literal_key(line).is_some())
+                .map(ToOwned::to_owned)
+                .with_context(|| {
+                    format!(
+                        "'gpg.ssh.defaultKeyCommand' {} didn't provide a usable key",
+                        key_command.display()
+                    )
+                })
+        }
+        SignatureFormat::OpenPgp | SignatureFormat::X509 => {
+            let Some(committer) = repo.committer().transpose()? else {
+                bail!("No signing key found");
+            };
+            Ok(format!("{} <{}>", committer.name, committer.email))
+        }
+    }
+}
+
+fn spawn(
+    cmd: &mut std::process::Command,
+    program: &Path,
+    program_key: &str,
+) -> anyhow::Result<std::process::Child> {
+    match cmd.spawn() {
+        Ok(child) => Ok(child),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            bail!(
+                "Could not find '{}'. Please make sure it is in your `PATH` or configure the full path using `{program_key}` in the Git configuration",
+                program.display()
+            )
         }
+        Err(err) => Err(err).context(format!("Could not execute program using {:?}", cmd)),
     }
 }
 
+/// Run `prepare` with `input` on stdin, and collect its output.
+fn run_with_input(
+    prepare: gix::command::Prepare,
+    program: &Path,
+    program_key: &str,
+    input: &[u8],
+) -> anyhow::Result<std::process::Output> {
+    let mut cmd = into_command(prepare);
+    cmd.stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .stdin(Stdio::piped());
+    let mut child = spawn(&mut cmd, program, program_key)?;
+    child.stdin.take().expect("configured").write_all(input)?;
+    Ok(child.wait_with_output()?)
+}
+
 fn into_command(prepare: gix::command::Prepare) -> std::process::Command {
     let cmd: std::process::Command = prepare.into();
     tracing::debug!(?cmd, "command to produce commit signature");
@@ -5,9 +5,11 @@
 
 use crate::commit::CommitterMode;
 use anyhow::{Context, Ok, Result, anyhow, bail};
-use bstr::BString;
+use bstr::{BString, ByteSlice};
 use gix::objs::Exists;
 use gix::prelude::ObjectIdExt;
+use std::collections::VecDeque;
+use std::process::Stdio;
 use tracing::instrument;
 
 /// Types for use with cherry-picking
@@ -20,18 +22,31 @@ pub mod commit;
 /// Utilities around merging
 pub mod merge;
 
+/// Plan how to fold `fixup!`, `squash!` and `amend!` commits into the commits they refer to.
+pub mod autosquash;
+
+/// Types to pause a rebase and to continue or abort it later, even after a restart.
+pub mod paused;
+use crate::paused::{PauseReason, PausedRebase};
+
 /// An instruction for [`RebaseBuilder::rebase()`].
 #[derive(Debug, Clone)]
 pub enum RebaseStep {
     /// Pick an existing commit and place it on top of `base` and optionally reword it.
+    ///
+    /// If the commit is a merge commit, the merge is repeated with each of its parents replaced by the rewritten
+    /// tip of the sequence that picked it, if any.
     Pick {
         /// Id of an already existing commit
         commit_id: gix::ObjectId,
         /// Optional message to use for newly produced commit
         new_message: Option<BString>,
-        // TODO: add `base: Option<ObjectId>` to allow restarting the sequence at a new base
-        //       for multi-branch rebasing. It would keep the previous cursor, to allow the last
-        //       branch to contain a pick of the merge commit on top, which it can then correctly re-merge.
+        /// If set, restart the sequence at this commit, placing this pick and all steps after it on top of it.
+        ///
+        /// That way, multiple branches can be rebased at once, each starting at its own base. The tip of the
+        /// previous sequence is remembered so that a merge commit picked later can re-merge it.
+        /// It must not be set when picking merge commits.
+        base: Option<gix::ObjectId>,
     },
     /// Squashes an existing commit into the one in the first `Pick` or `Merge` RebaseStep that precedes it.
     ///
@@ -50,16 +65,53 @@ pub enum RebaseStep {
     /// If this is the first step in the list, the reference will be to the `base` commit.
     /// If the step before this one is another `Reference` step, this reference will point to the same commit.
     Reference(but_core::Reference),
+    /// Pick an existing commit just like [`Pick`](RebaseStep::Pick) and pause right after, so the picked commit
+    /// can be changed before the rebase is [resumed](PausedRebase::resume()).
+    ///
+    /// This step is only possible with [`Rebase::start()`].
+    Edit {
+        /// Id of an already existing commit
+        commit_id: gix::ObjectId,
+        /// Optional message to use for newly produced commit
+        new_message: Option<BString>,
+    },
+    /// Run `command` in a shell once all preceding steps are done, and abort the rebase if it fails.
+    ///
+    /// As the worktree isn't changed by the rebase, the command runs in a temporary worktree with the most recently
+    /// rewritten commit checked out, which is also passed in the `GITBUTLER_REBASE_HEAD` environment variable.
+    /// `GIT_DIR` points to the repository, so `git` can be used as usual.
+    Exec {
+        /// The command to run, interpreted by a shell.
+        command: String,
+    },
+    /// Leave out an existing commit, and list it as [dropped](RebaseOutput::dropped).
+    ///
+    /// This is the same as not mentioning the commit at all, but makes the intent explicit.
+    Drop {
+        /// Id of an already existing commit
+        commit_id: gix::ObjectId,
+    },
+    /// Pause the rebase once all preceding steps are done so the latest rewritten commit can be inspected, or changed,
+    /// before the rebase is [resumed](PausedRebase::resume()).
+    ///
+    /// This step is only possible with [`Rebase::start()`].
+    Break,
 }
 
 impl RebaseStep {
     fn commit_id(&self) -> Option<&gix::oid> {
         match self {
             RebaseStep::Pick { commit_id, .. }
-            | RebaseStep::SquashIntoPreceding { commit_id, .. } => Some(commit_id),
-            RebaseStep::Reference { .. } => None,
+            | RebaseStep::SquashIntoPreceding { commit_id, .. }
+            | RebaseStep::Edit { commit_id, .. }
+            | RebaseStep::Drop { commit_id } => Some(commit_id),
+            RebaseStep::Reference { .. } | RebaseStep::Exec { .. } | RebaseStep::Break => None,
         }
     }
+
+    fn pauses(&self) -> bool {
+        matches!(self, RebaseStep::Edit { .. } | RebaseStep::Break)
+    }
 }
 
 /// Setup a list of [instructions](RebaseStep) for the actual [rebase operation](RebaseBuilder::rebase).
@@ -70,6 +122,7 @@ pub struct Rebase<'repo> {
     base_substitute: Option<gix::ObjectId>,
     steps: Vec<RebaseStep>,
     rebase_noops: bool,
+    merge_conflicts: merge::ConflictMode,
 }
 
 impl<'repo> Rebase<'repo> {
@@ -98,6 +151,7 @@ impl<'repo> Rebase<'repo> {
             base_substitute,
             steps: Vec::new(),
             rebase_noops: true, // default to always rebasing
+            merge_conflicts: merge::ConflictMode::default(),
         })
     }
 
@@ -120,6 +174,15 @@ impl<'repo> Rebase<'repo> {
         self
     }
 
+    /// Configures what happens if a picked merge commit can't be re-merged without conflicts.
+    /// With [`ConflictMode::Record`](merge::ConflictMode::Record), the conflicts are recorded in the merge commit just like
+    /// it's done for conflicting cherry-picks, so the rebase can continue.
+    /// Default is [`ConflictMode::Fail`](merge::ConflictMode::Fail).
+    pub fn merge_conflicts(&mut self, mode: merge::ConflictMode) -> &mut Self {
+        self.merge_conflicts = mode;
+        self
+    }
+
     /// Performs a rebase on top of a given base, according to the provided steps, or fails if no step was provided.
     /// It does not actually create new git references nor does it update existing ones, it only deals with
     /// altering commits and providing the information needed to update refs.
@@ -132,23 +195,51 @@ impl<'repo> Rebase<'repo> {
     ///  - rewrite the history at will
     ///
     /// **However, note that it will also make all input commits sequential, so the caller must assure
-    /// these actually form a 'line'.**
+    /// these actually form a 'line'.** To rebase multiple lines at once, like all stacks in a workspace,
+    /// start each of them with a [pick](RebaseStep::Pick) that has its own `base`, and finish with a pick of the
+    /// merge commit that should be re-created with the new tips of all lines.
+    ///
+    /// Steps that pause the rebase, like [`Edit`](RebaseStep::Edit) and [`Break`](RebaseStep::Break), need
+    /// [`start()`](Self::start()) instead.
     pub fn rebase(&mut self) -> Result<RebaseOutput> {
+        let state = self.take_state()?;
+        if state.steps.iter().any(RebaseStep::pauses) {
+            bail!("Steps that pause the rebase need `Rebase::start()`");
+        }
+        match run(self.repo, state)? {
+            Progress::Done(output) => Ok(output),
+            Progress::Paused(_) => unreachable!("there are no steps that pause"),
+        }
+    }
+
+    /// Like [`rebase()`](Self::rebase()), but supports steps that pause the rebase.
+    ///
+    /// If the rebase pauses, it is persisted in the `.git/gitbutler` directory until it is
+    /// [resumed](PausedRebase::resume()) to completion or [aborted](PausedRebase::abort()), which can also
+    /// happen after [loading](PausedRebase::load()) it in a new process.
+    /// Only one rebase can be paused at a time.
+    pub fn start(&mut self) -> Result<Progress> {
+        if PausedRebase::load(self.repo)?.is_some() {
+            bail!("A paused rebase must be continued or aborted before starting a new one");
+        }
+        let progress = run(self.repo, self.take_state()?)?;
+        if let Progress::Paused(paused) = &progress {
+            paused.persist(self.repo)?;
+        }
+        Ok(progress)
+    }
+
+    fn take_state(&mut self) -> Result<State> {
         if self.steps.is_empty() {
             return Err(anyhow!("No rebase steps provided"));
         }
-        let pick_mode = if self.rebase_noops {
-            PickMode::Unconditionally
-        } else {
-            PickMode::SkipIfNoop
-        };
-        rebase(
-            self.repo,
+        Ok(State::new(
             self.base,
             self.base_substitute,
             std::mem::take(&mut self.steps),
-            pick_mode,
-        )
+            self.rebase_noops,
+            self.merge_conflicts,
+        ))
     }
 }
 
@@ -159,31 +250,54 @@ impl Rebase<'_> {
     ///
     /// Pick and Merge operations:
     /// - The commit must not be a commit that is already in a pick, merge or fixup step
+    /// - If a pick has a base, it must exist and the picked commit must not be a merge commit
     ///
     /// Fixup operations:
     /// - Must not be a reference step immediately before it
-    /// - Must not be the first operation
+    /// - Must have a pick, edit or fixup step before it, ignoring drop, exec and break steps
     ///
     /// Reference operations:
     /// - The refname must be a valid reference name
+    ///
+    /// Edit and Drop operations are validated like Pick operations, and Exec operations must have a command.
     fn validate_step(&self, step: &RebaseStep) -> Result<()> {
         match step {
             RebaseStep::Pick {
                 commit_id,
                 new_message: _,
+                base,
             } => {
                 self.assure_unique_step_and_existing_non_base(commit_id, "Picked")?;
+                if let Some(base) = base {
+                    if self.repo.find_commit(*base).is_err() {
+                        bail!("Base commit of picked commit {commit_id} must exist: {base}");
+                    }
+                    if self.repo.find_commit(*commit_id)?.parent_ids().count() > 1 {
+                        bail!(
+                            "Merge-commit {commit_id} can't be picked onto a new base, \
+                            it would be re-merged with the tips of the rewritten sequences instead"
+                        );
+                    }
+                }
             }
             RebaseStep::SquashIntoPreceding {
                 commit_id,
                 new_message: _,
             } => {
                 self.assure_unique_step_and_existing_non_base(commit_id, "Fixup")?;
-                if matches!(self.steps.last(), Some(RebaseStep::Reference { .. })) {
-                    bail!("Fixup commit must not come after a reference step");
-                }
-                if self.steps.is_empty() {
-                    bail!("Fixup must have a commit to work on");
+                // Dropped commits and steps that don't rewrite commits can't be squashed into.
+                let preceding_step = self.steps.iter().rev().find(|step| {
+                    !matches!(
+                        step,
+                        RebaseStep::Drop { .. } | RebaseStep::Exec { .. } | RebaseStep::Break
+                    )
+                });
+                match preceding_step {
+                    Some(RebaseStep::Reference { .. }) => {
+                        bail!("Fixup commit must not come after a reference step");
+                    }
+                    None => bail!("Fixup must have a commit to work on"),
+                    Some(_) => {}
                 }
             }
             RebaseStep::Reference(name) => {
@@ -193,6 +307,21 @@ impl Rebase<'_> {
                     ));
                 }
             }
+            RebaseStep::Edit {
+                commit_id,
+                new_message: _,
+            } => {
+                self.assure_unique_step_and_existing_non_base(commit_id, "Edited")?;
+            }
+            RebaseStep::Drop { commit_id } => {
+                self.assure_unique_step_and_existing_non_base(commit_id, "Dropped")?;
+            }
+            RebaseStep::Exec { command } => {
+                if command.trim().is_empty() {
+                    bail!("Exec step must have a non-empty command");
+                }
+            }
+            RebaseStep::Break => {}
         }
         Ok(())
     }
@@ -213,92 +342,114 @@ impl Rebase<'_> {
     }
 }
 
-#[instrument(level = tracing::Level::DEBUG, skip(repo))]
-fn rebase(
-    repo: &gix::Repository,
-    base: Option<gix::ObjectId>,
+/// The result of a rebase that can pause, as [started](Rebase::start()) or [resumed](PausedRebase::resume()).
+#[derive(Debug, Clone)]
+pub enum Progress {
+    /// All steps were performed.
+    Done(RebaseOutput),
+    /// The rebase paused, and was persisted so it can be resumed or aborted later.
+    Paused(PausedRebase),
+}
+
+/// Everything needed to perform the remaining steps of a rebase, along with the results so far.
+#[derive(Debug, Clone)]
+struct State {
     base_substitute: Option<gix::ObjectId>,
-    steps: Vec<RebaseStep>,
-    pick_mode: PickMode,
-) -> Result<RebaseOutput> {
-    let (mut references, mut commit_mapping) = (
-        vec![],
-        Vec::<(Option<gix::ObjectId>, gix::ObjectId, gix::ObjectId)>::new(),
-    );
-    let (mut cursor, mut last_seen_commit) = (base, base);
+    rebase_noops: bool,
+    merge_conflicts: merge::ConflictMode,
+    /// The steps that are yet to be performed.
+    steps: VecDeque<RebaseStep>,
+    /// The most recently rewritten commit.
+    cursor: Option<gix::ObjectId>,
+    /// The most recently picked or squashed original commit.
+    last_seen_commit: Option<gix::ObjectId>,
+    /// The base of the current sequence, which changes with each pick that has its own base.
+    sequence_base: Option<gix::ObjectId>,
+    /// All commits seen in the current sequence.
+    seen: Vec<gix::ObjectId>,
+    /// All previous sequences that were completed.
+    sequences: Vec<Sequence>,
+    references: Vec<ReferenceSpec>,
+    commit_mapping: Vec<(Option<gix::ObjectId>, gix::ObjectId, gix::ObjectId)>,
+    dropped: Vec<gix::ObjectId>,
+}
+
+impl State {
+    fn new(
+        base: Option<gix::ObjectId>,
+        base_substitute: Option<gix::ObjectId>,
+        steps: Vec<RebaseStep>,
+        rebase_noops: bool,
+        merge_conflicts: merge::ConflictMode,
+    ) -> Self {
+        State {
+            base_substitute,
+            rebase_noops,
+            merge_conflicts,
+            steps: steps.into(),
+            cursor: base,
+            last_seen_commit: base,
+            sequence_base: base,
+            seen: Vec::new(),
+            sequences: Vec::new(),
+            references: Vec::new(),
+            commit_mapping: Vec::new(),
+            dropped: Vec::new(),
+        }
+    }
+}
+
+#[instrument(level = tracing::Level::DEBUG, skip(repo, state))]
+fn run(repo: &gix::Repository, mut state: State) -> Result<Progress> {
+    let pick_mode = if state.rebase_noops {
+        PickMode::Unconditionally
+    } else {
+        PickMode::SkipIfNoop
+    };
     let cache = repo.commit_graph_if_enabled()?;
     let mut graph = repo.revision_graph(cache.as_ref());
-    for step in steps {
+    while let Some(step) = state.steps.pop_front() {
+        let mut pause = None;
         match step {
             RebaseStep::Pick {
                 commit_id,
                 new_message,
+                base,
             } => {
-                let commit = to_commit(repo, commit_id)?;
-                if commit.parents.len() > 1 {
-                    let mut merge_commit = commit;
-                    if let Some(new_message) = new_message {
-                        merge_commit.message = new_message;
-                    }
-                    // Find any parent that we have seen during picking.
-                    let Some(parent_to_replace) = merge_commit.parents.iter_mut().find(|id| {
-                        (Some(**id) == base_substitute)
-                            || commit_mapping.iter().any(|(mapping_base, old, _new)| {
-                                *mapping_base == base && (*id == old)
-                            })
-                    }) else {
-                        bail!(
-                            "Merge-commit {commit_id} can't be remerged if none of \
-                                its parents was seen in the rebase (to \
-                                be replaced with this new commit)"
-                        )
-                    };
-                    *parent_to_replace = cursor.context("Expecting a base for any merge")?;
-                    cursor = merge::octopus(repo, merge_commit, &mut graph)
-                        .context(
-                            "The rebase failed as a merge could not be repeated without conflicts",
-                        )?
-                        .into();
-                } else {
-                    match &mut cursor {
-                        Some(cursor) => {
-                            let mut new_commit = cherry_pick_one(
-                                repo,
-                                *cursor,
-                                commit_id,
-                                pick_mode,
-                                EmptyCommit::Keep,
-                            )?;
-                            if let Some(new_message) = new_message {
-                                new_commit = reword_commit(repo, new_commit, new_message.clone())?;
-                            }
-                            *cursor = new_commit;
-                        }
-                        None if commit.parents.is_empty() => {
-                            let mut new_commit = commit;
-                            if let Some(new_message) = new_message {
-                                new_commit.message = new_message;
-                            }
-                            cursor = Some(commit::create(repo, new_commit, CommitterMode::Update)?);
-                        }
-                        None => {
-                            // TODO: should this be supported? This would be as easy as forgetting its parents.
-                            bail!(
-                                "Cannot currently rebase a commit so that it becomes the first commit in the history"
-                            )
-                        }
-                    }
-                }
-                last_seen_commit = Some(commit_id);
+                pick(
+                    repo,
+                    &mut graph,
+                    &mut state,
+                    commit_id,
+                    new_message,
+                    base,
+                    pick_mode,
+                )?;
+            }
+            RebaseStep::Edit {
+                commit_id,
+                new_message,
+            } => {
+                pick(
+                    repo,
+                    &mut graph,
+                    &mut state,
+                    commit_id,
+                    new_message,
+                    None,
+                    pick_mode,
+                )?;
+                pause = Some(PauseReason::Edit { commit_id });
             }
             RebaseStep::SquashIntoPreceding {
                 commit_id,
                 new_message,
             } => {
-                let Some(cursor) = &mut cursor else {
+                let Some(cursor) = &mut state.cursor else {
                     bail!("Can't squash if previous commit is missing");
                 };
-                last_seen_commit = Some(commit_id);
+                state.last_seen_commit = Some(commit_id);
+                state.seen.push(commit_id);
                 let base_commit = repo.find_commit(*cursor)?;
                 let new_commit = cherry_pick_one(
                     repo,
@@ -317,25 +468,208 @@ fn rebase(
                 *cursor = commit::create(repo, new_commit, CommitterMode::Update)?;
             }
             RebaseStep::Reference(reference) => {
-                references.push(ReferenceSpec {
+                state.references.push(ReferenceSpec {
                     reference,
-                    commit_id: cursor
+                    commit_id: state
+                        .cursor
                         .expect("Validation assures there is a rewritten commit prior"),
-                    previous_commit_id: last_seen_commit
+                    previous_commit_id: state
+                        .last_seen_commit
                         .expect("Validation assures there is a commit prior"),
                 });
             }
+            RebaseStep::Drop { commit_id } => {
+                // Merges that have the dropped commit as parent should use the sequence tip instead.
+                state.seen.push(commit_id);
+                state.dropped.push(commit_id);
+                continue;
+            }
+            RebaseStep::Exec { command } => {
+                exec(repo, &command, state.cursor)?;
+                continue;
+            }
+            RebaseStep::Break => {
+                return Ok(Progress::Paused(PausedRebase {
+                    reason: PauseReason::Break,
+                    state,
+                }));
+            }
+        }
+        if let Some((old, new)) = state.last_seen_commit.zip(state.cursor) {
+            state.commit_mapping.push((state.sequence_base, old, new));
+        }
+        if let Some(reason) = pause {
+            return Ok(Progress::Paused(PausedRebase { reason, state }));
+        }
+    }
+
+    Ok(Progress::Done(RebaseOutput {
+        top_commit: state
+            .cursor
+            .context("There must be at least one commit to rebase if there is no base")?,
+        references: state.references,
+        commit_mapping: state.commit_mapping,
+        dropped: state.dropped,
+    }))
+}
+
+fn pick(
+    repo: &gix::Repository,
+    graph: &mut gix::revwalk::Graph<
+        '_,
+        '_,
+        gix::revwalk::graph::Commit<gix::revision::plumbing::merge_base::Flags>,
+    >,
+    state: &mut State,
+    commit_id: gix::ObjectId,
+    new_message: Option<BString>,
+    new_base: Option<gix::ObjectId>,
+    pick_mode: PickMode,
+) -> Result<()> {
+    let commit = to_commit(repo, commit_id)?;
+    if let Some(new_base) = new_base {
+        state.sequences.push(Sequence {
+            tip: state.cursor,
+            seen: std::mem::take(&mut state.seen),
+        });
+        (state.cursor, state.sequence_base) = (Some(new_base), Some(new_base));
+    }
+    if commit.parents.len() > 1 {
+        let mut merge_commit = commit;
+        if let Some(new_message) = new_message {
+            merge_commit.message = new_message;
+        }
+        // Replace the first parent seen in the current sequence with its tip, and
+        // all parents seen in previous sequences with their respective tips.
+        let mut replaced_with_cursor = false;
+        for parent in merge_commit.parents.iter_mut() {
+            if !replaced_with_cursor
+                && (Some(*parent) == state.base_substitute || state.seen.contains(parent))
+            {
+                *parent = state.cursor.context("Expecting a base for any merge")?;
+                replaced_with_cursor = true;
+            } else if let Some(tip) = state
+                .sequences
+                .iter()
+                .rev()
+                .find(|sequence| sequence.seen.contains(parent))
+                .and_then(|sequence| sequence.tip)
+            {
+                *parent = tip;
+            }
         }
-        if let Some((old, new)) = last_seen_commit.zip(cursor) {
-            commit_mapping.push((base, old, new));
+        if !replaced_with_cursor {
+            bail!(
+                "Merge-commit {commit_id} can't be remerged if none of \
+                    its parents was seen in the rebase (to \
+                    be replaced with this new commit)"
+            )
+        };
+        state.cursor = merge::octopus(repo, merge_commit, graph, state.merge_conflicts)
+            .context("The rebase failed as a merge could not be repeated without conflicts")?
+            .into();
+    } else {
+        match &mut state.cursor {
+            Some(cursor) => {
+                let mut new_commit =
+                    cherry_pick_one(repo, *cursor, commit_id, pick_mode, EmptyCommit::Keep)?;
+                if let Some(new_message) = new_message {
+                    new_commit = reword_commit(repo, new_commit, new_message.clone())?;
+                }
+                *cursor = new_commit;
+            }
+            None if commit.parents.is_empty() => {
+                let mut new_commit = commit;
+                if let Some(new_message) = new_message {
+                    new_commit.message = new_message;
+                }
+                state.cursor = Some(commit::create(repo, new_commit, CommitterMode::Update)?);
+            }
+            None => {
+                // TODO: should this be supported? This would be as easy as forgetting its parents.
+                bail!(
+                    "Cannot currently rebase a commit so that it becomes the first commit in the history"
+                )
+            }
         }
     }
+    state.last_seen_commit = Some(commit_id);
+    state.seen.push(commit_id);
+    Ok(())
+}
+
+/// Run `command` in a temporary worktree with `head` checked out, and fail if it doesn't succeed.
+fn exec(repo: &gix::Repository, command: &str, head: Option<gix::ObjectId>) -> Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let (worktree_dir, index_path) = (tmp.path().join("worktree"), tmp.path().join("index"));
+    std::fs::create_dir(&worktree_dir)?;
+    if let Some(head) = head {
+        checkout_into(repo, head, &worktree_dir, &index_path)?;
+    }
+
+    let mut cmd: std::process::Command = gix::command::prepare(command)
+        .command_may_be_shell_script()
+        .with_shell()
+        .into();
+    cmd.current_dir(&worktree_dir)
+        .env("GIT_DIR", repo.path())
+        .env("GIT_WORK_TREE", &worktree_dir)
+        .env("GIT_INDEX_FILE", &index_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(head) = head {
+        cmd.env("GITBUTLER_REBASE_HEAD", head.to_string());
+    }
+    tracing::debug!(?cmd, "running exec step");
+    let output = cmd
+        .output()
+        .with_context(|| format!("Could not run '{command}'"))?;
+    if !output.status.success() {
+        bail!(
+            "The rebase was aborted as '{command}' failed with {status}: {stderr}",
+            status = output.status,
+            stderr = output.stderr.as_bstr().trim().as_bstr()
+        );
+    }
+    Ok(())
+}
 
-    Ok(RebaseOutput {
-        top_commit: cursor.expect("validation assures we have at least one commit to process"),
-        references,
-        commit_mapping,
-    })
+/// Write the files of `commit_id` into the empty `worktree_dir`, along with a matching index at `index_path`.
+/// For conflicted commits, the auto-resolved files are written.
+fn checkout_into(
+    repo: &gix::Repository,
+    commit_id: gix::ObjectId,
+    worktree_dir: &std::path::Path,
+    index_path: &std::path::Path,
+) -> Result<()> {
+    let tree_id = but_core::Commit::from_id(commit_id.attach(repo))?
+        .tree_id_by_kind_or_ours(but_core::commit::TreeKind::AutoResolution)?;
+    let mut index = repo.index_from_tree(&tree_id)?;
+    index.set_path(index_path);
+    let mut opts =
+        repo.checkout_options(gix::worktree::stack::state::attributes::Source::IdMapping)?;
+    opts.destination_is_initially_empty = true;
+    gix::worktree::state::checkout(
+        &mut index,
+        worktree_dir.to_owned(),
+        repo.clone(),
+        &gix::progress::Discard,
+        &gix::progress::Discard,
+        &gix::interrupt::IS_INTERRUPTED,
+        opts,
+    )?;
+    index.write(Default::default())?;
+    Ok(())
+}
+
+/// A line of commits that was completed when a pick restarted the rebase at a new base.
+#[derive(Debug, Clone)]
+struct Sequence {
+    /// The rewritten commit at the top of the sequence.
+    tip: Option<gix::ObjectId>,
+    /// The original commits that were picked or squashed in the sequence.
+    seen: Vec<gix::ObjectId>,
 }
 
 fn to_commit(repo: &gix::Repository, commit_id: gix::ObjectId) -> Result<gix::objs::Commit> {
@@ -377,9 +711,11 @@ pub struct RebaseOutput {
     pub top_commit: gix::ObjectId,
     /// The list of references along with their new locations, ordered from the least recent to the most recent.
     pub references: Vec<ReferenceSpec>,
-    /// A listing of all commits `(base, old, new)` in order of [steps](RebaseBuilder::step()), with its base followed by
+    /// A listing of all commits `(base, old, new)` in order of [steps](RebaseBuilder::step()), with the base of its sequence followed by
     /// the initial commit hash on the left and the rewritten version of it on the right side of each tuple.
     ///
     /// That way programmatic users may perform their own remapping without having to deal with [references](RebaseStep::Reference).
     pub commit_mapping: Vec<(Option<gix::ObjectId>, gix::ObjectId, gix::ObjectId)>,
+    /// The commits that were left out by [drop steps](RebaseStep::Drop), in order of steps.
+    pub dropped: Vec<gix::ObjectId>,
 }
@@ -1,9 +1,22 @@
+use crate::cherry_pick::function::{
+    ConflictEntries, extract_conflicted_files, write_conflicted_tree,
+};
 use crate::commit::CommitterMode;
 use anyhow::{Result, bail};
 use but_core::commit::TreeKind;
 use gitbutler_oxidize::GixRepositoryExt;
 use gix::prelude::ObjectIdExt;
 
+/// Determines what happens if the parents of a merge commit conflict with each other.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum ConflictMode {
+    /// Fail the merge on the first conflict.
+    #[default]
+    Fail,
+    /// Auto-resolve conflicts and record them in the merge commit, marking it as conflicted.
+    Record,
+}
+
 /// Perform a three-base merge for each of the parents in `target_merge_commit` which serves as template for the merge.
 /// This means that after merging, we will use it unchanged to create a new, possibly signed commit, after adjusting its
 /// tree to point to the merge result of its parents.
@@ -13,9 +26,13 @@ use gix::prelude::ObjectIdExt;
 /// and then re-used when merging subsequent `parent-commit^{tree}` into each other in a three-way merge, reusing the previous
 /// result as *ours* until all parents are merged in.
 ///
-/// Conflicts will cause the operation to fail, there is no hiding of conflicts as merge commits typically are
-/// workspace tips which are implicit in the application. For consistency, there is no special treatment of merge-commits
-/// which are part of the branches or stacks.
+/// With [`ConflictMode::Fail`], conflicts will cause the operation to fail, there is no hiding of conflicts as merge commits
+/// typically are workspace tips which are implicit in the application. For consistency, there is no special treatment of
+/// merge-commits which are part of the branches or stacks.
+///
+/// With [`ConflictMode::Record`], conflicts are auto-resolved in favor of *ours*, and the merge commit is marked as conflicted
+/// with a tree just like the one of conflicting cherry-picks. Its *ours* and *theirs* sides are the trees that were merged when
+/// the first conflict occurred, while the conflicting paths of all merges are recorded.
 ///
 /// ### About Signing
 ///
@@ -31,6 +48,7 @@ pub fn octopus(
         '_,
         gix::revwalk::graph::Commit<gix::revision::plumbing::merge_base::Flags>,
     >,
+    mode: ConflictMode,
 ) -> Result<gix::ObjectId> {
     if target_merge_commit.parents.len() < 2 {
         bail!("An octopus merge commits must have at least two parents");
@@ -54,7 +72,15 @@ pub fn octopus(
         .collect::<Result<Vec<_>, _>>()?
         .into_iter();
     let mut ours = trees_to_merge.next().expect("two or more trees");
-    let (merge_options, unresolved) = repo.merge_options_fail_fast()?;
+    let (merge_options, unresolved) = match mode {
+        ConflictMode::Fail => repo.merge_options_fail_fast()?,
+        ConflictMode::Record => (
+            repo.merge_options_force_ours()?,
+            gix::merge::tree::TreatAsUnresolved::forced_resolution(),
+        ),
+    };
+    let mut first_conflict = None;
+    let mut conflicted_files = ConflictEntries::default();
     for tree_to_merge in trees_to_merge {
         let mut merge = repo.merge_trees(
             merge_base,
@@ -63,20 +89,54 @@ pub fn octopus(
             repo.default_merge_labels(),
             merge_options.clone(),
         )?;
+        let previous_ours = ours;
+        ours = merge.tree.write()?.detach();
         if merge.has_unresolved_conflicts(unresolved) {
-            bail!(
-                "Encountered conflict when merging commits {}",
-                parents_to_merge
-                    .map(|id| id.to_string())
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            )
+            if let ConflictMode::Fail = mode {
+                bail!(
+                    "Encountered conflict when merging commits {}",
+                    parents_to_merge
+                        .map(|id| id.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            }
+            first_conflict.get_or_insert((previous_ours, tree_to_merge));
+            conflicted_files.extend(extract_conflicted_files(
+                ours.attach(repo),
+                merge,
+                unresolved,
+            )?);
         }
-        ours = merge.tree.write()?.detach();
     }
-    target_merge_commit.tree = ours;
-    if but_core::commit::HeadersV2::try_from_commit(&target_merge_commit).is_none() {
-        but_core::commit::HeadersV2::default().set_in_commit(&mut target_merge_commit);
+    let existing_headers = but_core::commit::HeadersV2::try_from_commit(&target_merge_commit);
+    match first_conflict {
+        Some((conflicting_ours, conflicting_theirs)) => {
+            target_merge_commit.tree = write_conflicted_tree(
+                repo,
+                merge_base,
+                conflicting_ours,
+                conflicting_theirs,
+                ours,
+                &conflicted_files,
+            )?;
+            let mut headers = existing_headers.unwrap_or_default();
+            headers.conflicted = conflicted_files.conflicted_header_field();
+            headers.set_in_commit(&mut target_merge_commit);
+        }
+        None => {
+            target_merge_commit.tree = ours;
+            match existing_headers {
+                None => {
+                    but_core::commit::HeadersV2::default().set_in_commit(&mut target_merge_commit)
+                }
+                Some(mut headers) if headers.is_conflicted() => {
+                    headers.conflicted = None;
+                    headers.set_in_commit(&mut target_merge_commit);
+                }
+                Some(_) => {}
+            }
+        }
     }
     if target_merge_commit
         .extra_headers()
@@ -0,0 +1,417 @@
+use crate::merge::ConflictMode;
+use crate::{Progress, RebaseStep, ReferenceSpec, Sequence, State, run};
+use anyhow::{Context, Result, bail};
+use bstr::{BString, ByteSlice};
+use std::path::PathBuf;
+
+/// The reason for a rebase to pause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    /// An [edit step](RebaseStep::Edit) picked `commit_id` so it can be changed.
+    Edit {
+        /// The original commit that was picked.
+        commit_id: gix::ObjectId,
+    },
+    /// A [break step](RebaseStep::Break) was reached.
+    Break,
+}
+
+/// A rebase that was paused by an [edit](RebaseStep::Edit) or [break](RebaseStep::Break) step.
+///
+/// As long as it's paused, it's persisted in `.git/gitbutler/rebase.toml` so that it can be [resumed](Self::resume())
+/// or [aborted](Self::abort()) after [loading](Self::load()) it in another process.
+#[derive(Debug, Clone)]
+pub struct PausedRebase {
+    /// Why the rebase was paused.
+    pub reason: PauseReason,
+    pub(crate) state: State,
+}
+
+impl PausedRebase {
+    /// The most recently rewritten commit, i.e. the one to change if the rebase was paused by an edit step,
+    /// or `None` if there is no commit yet.
+    pub fn head(&self) -> Option<gix::ObjectId> {
+        self.state.cursor
+    }
+
+    /// Load the rebase that is currently paused in `repo`, or `None` if there is none.
+    pub fn load(repo: &gix::Repository) -> Result<Option<Self>> {
+        let path = state_path(repo);
+        let toml = match std::fs::read_to_string(&path) {
+            Ok(toml) => toml,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+        let persisted: persisted::PausedRebase =
+            toml::from_str(&toml).context("Failed to parse the paused rebase")?;
+        Ok(Some(persisted.try_into()?))
+    }
+
+    /// Continue the rebase with the remaining steps, after replacing [`head()`](Self::head()) with `edited_head`
+    /// if it is set, which typically is an amended version of it.
+    ///
+    /// The persisted state is removed, and only written again if the rebase pauses once more.
+    /// If a step fails, the rebase is aborted.
+    pub fn resume(
+        mut self,
+        repo: &gix::Repository,
+        edited_head: Option<gix::ObjectId>,
+    ) -> Result<Progress> {
+        if let Some(edited_head) = edited_head {
+            repo.find_commit(edited_head)
+                .with_context(|| format!("Edited commit {edited_head} must exist"))?;
+            let previous_head = self.state.cursor.replace(edited_head);
+            for (_base, _old, new) in &mut self.state.commit_mapping {
+                if Some(*new) == previous_head {
+                    *new = edited_head;
+                }
+            }
+            for reference in &mut self.state.references {
+                if Some(reference.commit_id) == previous_head {
+                    reference.commit_id = edited_head;
+                }
+            }
+        }
+        remove(repo)?;
+        let progress = run(repo, self.state)?;
+        if let Progress::Paused(paused) = &progress {
+            paused.persist(repo)?;
+        }
+        Ok(progress)
+    }
+
+    /// Stop the rebase and forget about it.
+    ///
+    /// As the rebase never alters references or the worktree, there is nothing else to undo.
+    pub fn abort(self, repo: &gix::Repository) -> Result<()> {
+        remove(repo)
+    }
+
+    pub(crate) fn persist(&self, repo: &gix::Repository) -> Result<()> {
+        let persisted = persisted::PausedRebase::try_from(self)?;
+        let toml = toml::to_string(&persisted).context("Failed to serialize the paused rebase")?;
+        gitbutler_fs::create_dirs_then_write(state_path(repo), toml)
+            .context("Failed to write the paused rebase")?;
+        Ok(())
+    }
+}
+
+fn state_path(repo: &gix::Repository) -> PathBuf {
+    repo.path().join("gitbutler").join("rebase.toml")
+}
+
+fn remove(repo: &gix::Repository) -> Result<()> {
+    match std::fs::remove_file(state_path(repo)) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+            Err(err).context("Failed to remove the paused rebase")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The on-disk representation of a paused rebase, with messages as strings to keep it readable.
+mod persisted {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct PausedRebase {
+        pub reason: PauseReason,
+        #[serde(with = "gitbutler_serde::object_id_opt", default)]
+        pub base_substitute: Option<gix::ObjectId>,
+        pub rebase_noops: bool,
+        #[serde(default)]
+        pub record_merge_conflicts: bool,
+        #[serde(with = "gitbutler_serde::object_id_opt", default)]
+        pub cursor: Option<gix::ObjectId>,
+        #[serde(with = "gitbutler_serde::object_id_opt", default)]
+        pub last_seen_commit: Option<gix::ObjectId>,
+        #[serde(with = "gitbutler_serde::object_id_opt", default)]
+        pub sequence_base: Option<gix::ObjectId>,
+        #[serde(with = "gitbutler_serde::object_id_vec")]
+        pub seen: Vec<gix::ObjectId>,
+        #[serde(with = "gitbutler_serde::object_id_vec")]
+        pub dropped: Vec<gix::ObjectId>,
+        pub sequences: Vec<Sequence>,
+        pub references: Vec<ReferenceSpec>,
+        pub commit_mapping: Vec<Mapping>,
+        pub steps: Vec<Step>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(tag = "kind", rename_all = "camelCase")]
+    pub enum PauseReason {
+        Edit {
+            #[serde(with = "gitbutler_serde::object_id")]
+            commit_id: gix::ObjectId,
+        },
+        Break,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Sequence {
+        #[serde(with = "gitbutler_serde::object_id_opt", default)]
+        pub tip: Option<gix::ObjectId>,
+        #[serde(with = "gitbutler_serde::object_id_vec")]
+        pub seen: Vec<gix::ObjectId>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct ReferenceSpec {
+        pub reference: Reference,
+        #[serde(with = "gitbutler_serde::object_id")]
+        pub commit_id: gix::ObjectId,
+        #[serde(with = "gitbutler_serde::object_id")]
+        pub previous_commit_id: gix::ObjectId,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub enum Reference {
+        Git(String),
+        Virtual(String),
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Mapping {
+        #[serde(with = "gitbutler_serde::object_id_opt", default)]
+        pub base: Option<gix::ObjectId>,
+        #[serde(with = "gitbutler_serde::object_id")]
+        pub old: gix::ObjectId,
+        #[serde(with = "gitbutler_serde::object_id")]
+        pub new: gix::ObjectId,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(tag = "kind", rename_all = "camelCase")]
+    pub enum Step {
+        Pick {
+            #[serde(with = "gitbutler_serde::object_id")]
+            commit_id: gix::ObjectId,
+            new_message: Option<String>,
+            #[serde(with = "gitbutler_serde::object_id_opt", default)]
+            base: Option<gix::ObjectId>,
+        },
+        SquashIntoPreceding {
+            #[serde(with = "gitbutler_serde::object_id")]
+            commit_id: gix::ObjectId,
+            new_message: Option<String>,
+        },
+        Reference {
+            reference: Reference,
+        },
+        Edit {
+            #[serde(with = "gitbutler_serde::object_id")]
+            commit_id: gix::ObjectId,
+            new_message: Option<String>,
+        },
+        Exec {
+            command: String,
+        },
+        Drop {
+            #[serde(with = "gitbutler_serde::object_id")]
+            commit_id: gix::ObjectId,
+        },
+        Break,
+    }
+}
+
+impl TryFrom<&PausedRebase> for persisted::PausedRebase {
+    type Error = anyhow::Error;
+
+    fn try_from(PausedRebase { reason, state }: &PausedRebase) -> Result<Self> {
+        Ok(persisted::PausedRebase {
+            reason: match *reason {
+                PauseReason::Edit { commit_id } => persisted::PauseReason::Edit { commit_id },
+                PauseReason::Break => persisted::PauseReason::Break,
+            },
+            base_substitute: state.base_substitute,
+            rebase_noops: state.rebase_noops,
+            record_merge_conflicts: state.merge_conflicts == ConflictMode::Record,
+            cursor: state.cursor,
+            last_seen_commit: state.last_seen_commit,
+            sequence_base: state.sequence_base,
+            seen: state.seen.clone(),
+            dropped: state.dropped.clone(),
+            sequences: state
+                .sequences
+                .iter()
+                .map(|sequence| persisted::Sequence {
+                    tip: sequence.tip,
+                    seen: sequence.seen.clone(),
+                })
+                .collect(),
+            references: state
+                .references
+                .iter()
+                .map(|spec| persisted::ReferenceSpec {
+                    reference: to_persisted_reference(&spec.reference),
+                    commit_id: spec.commit_id,
+                    previous_commit_id: spec.previous_commit_id,
+                })
+                .collect(),
+            commit_mapping: state
+                .commit_mapping
+                .iter()
+                .map(|&(base, old, new)| persisted::Mapping { base, old, new })
+                .collect(),
+            steps: state
+                .steps
+                .iter()
+                .map(to_persisted_step)
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl TryFrom<persisted::PausedRebase> for PausedRebase {
+    type Error = anyhow::Error;
+
+    fn try_from(persisted: persisted::PausedRebase) -> Result<Self> {
+        Ok(PausedRebase {
+            reason: match persisted.reason {
+                persisted::PauseReason::Edit { commit_id } => PauseReason::Edit { commit_id },
+                persisted::PauseReason::Break => PauseReason::Break,
+            },
+            state: State {
+                base_substitute: persisted.base_substitute,
+                rebase_noops: persisted.rebase_noops,
+                merge_conflicts: if persisted.record_merge_conflicts {
+                    ConflictMode::Record
+                } else {
+                    ConflictMode::Fail
+                },
+                steps: persisted
+                    .steps
+                    .into_iter()
+                    .map(from_persisted_step)
+                    .collect::<Result<_>>()?,
+                cursor: persisted.cursor,
+                last_seen_commit: persisted.last_seen_commit,
+                sequence_base: persisted.sequence_base,
+                seen: persisted.seen,
+                sequences: persisted
+                    .sequences
+                    .into_iter()
+                    .map(|sequence| Sequence {
+                        tip: sequence.tip,
+                        seen: sequence.seen,
+                    })
+                    .collect(),
+                references: persisted
+                    .references
+                    .into_iter()
+                    .map(|spec| {
+                        Ok(ReferenceSpec {
+                            reference: from_persisted_reference(spec.reference)?,
+                            commit_id: spec.commit_id,
+                            previous_commit_id: spec.previous_commit_id,
+                        })
+                    })
+                    .collect::<Result<_>>()?,
+                commit_mapping: persisted
+                    .commit_mapping
+                    .into_iter()
+                    .map(|mapping| (mapping.base, mapping.old, mapping.new))
+                    .collect(),
+                dropped: persisted.dropped,
+            },
+        })
+    }
+}
+
+fn to_persisted_step(step: &RebaseStep) -> Result<persisted::Step> {
+    Ok(match step {
+        RebaseStep::Pick {
+            commit_id,
+            new_message,
+            base,
+        } => persisted::Step::Pick {
+            commit_id: *commit_id,
+            new_message: to_persisted_message(new_message.as_ref())?,
+            base: *base,
+        },
+        RebaseStep::SquashIntoPreceding {
+            commit_id,
+            new_message,
+        } => persisted::Step::SquashIntoPreceding {
+            commit_id: *commit_id,
+            new_message: to_persisted_message(new_message.as_ref())?,
+        },
+        RebaseStep::Reference(reference) => persisted::Step::Reference {
+            reference: to_persisted_reference(reference),
+        },
+        RebaseStep::Edit {
+            commit_id,
+            new_message,
+        } => persisted::Step::Edit {
+            commit_id: *commit_id,
+            new_message: to_persisted_message(new_message.as_ref())?,
+        },
+        RebaseStep::Exec { command } => persisted::Step::Exec {
+            command: command.clone(),
+        },
+        RebaseStep::Drop { commit_id } => persisted::Step::Drop {
+            commit_id: *commit_id,
+        },
+        RebaseStep::Break => persisted::Step::Break,
+    })
+}
+
+fn from_persisted_step(step: persisted::Step) -> Result<RebaseStep> {
+    Ok(match step {
+        persisted::Step::Pick {
+            commit_id,
+            new_message,
+            base,
+        } => RebaseStep::Pick {
+            commit_id,
+            new_message: new_message.map(BString::from),
+            base,
+        },
+        persisted::Step::SquashIntoPreceding {
+            commit_id,
+            new_message,
+        } => RebaseStep::SquashIntoPreceding {
+            commit_id,
+            new_message: new_message.map(BString::from),
+        },
+        persisted::Step::Reference { reference } => {
+            RebaseStep::Reference(from_persisted_reference(reference)?)
+        }
+        persisted::Step::Edit {
+            commit_id,
+            new_message,
+        } => RebaseStep::Edit {
+            commit_id,
+            new_message: new_message.map(BString::from),
+        },
+        persisted::Step::Exec { command } => RebaseStep::Exec { command },
+        persisted::Step::Drop { commit_id } => RebaseStep::Drop { commit_id },
+        persisted::Step::Break => RebaseStep::Break,
+    })
+}
+
+fn to_persisted_message(message: Option<&BString>) -> Result<Option<String>> {
+    message
+        .map(|message| match message.to_str() {
+            Ok(message) => Ok(message.to_owned()),
+            Err(_) => bail!("Can't pause a rebase with commit messages that aren't valid UTF-8"),
+        })
+        .transpose()
+}
+
+fn to_persisted_reference(reference: &but_core::Reference) -> persisted::Reference {
+    match reference {
+        but_core::Reference::Git(name) => persisted::Reference::Git(name.to_string()),
+        but_core::Reference::Virtual(name) => persisted::Reference::Virtual(name.clone()),
+    }
+}
+
+fn from_persisted_reference(reference: persisted::Reference) -> Result<but_core::Reference> {
+    Ok(match reference {
+        persisted::Reference::Git(name) => but_core::Reference::Git(name.as_str().try_into()?),
+        persisted::Reference::Virtual(name) => but_core::Reference::Virtual(name),
+    })
+}
@@ -31,3 +31,30 @@ git init three-branches-merged
   git checkout main
   git merge A B C
 )
+
+git init fixups
+(cd fixups
+  echo "base" >base && git add . && git commit -m "base"
+  echo "a" >a && git add . && git commit -m "a"
+  echo "b" >b && git add . && git commit -m "b: first line"
+  echo "a2" >>a && git add . && git commit -m "fixup! a"
+  echo "b2" >>b && git add . && git commit -m "squash! b: first" -m "more about b"
+  echo "a3" >>a && git add . && git commit -m "amend! fixup! a" -m "a: amended"
+  echo "c" >c && git add . && git commit -m "fixup! c"
+)
+
+git init merge-conflict-after-drop
+(cd merge-conflict-after-drop
+  printf '1\n2\n3\n' >file && git add . && git commit -m "base" && git tag base
+  git branch B
+
+  git checkout -b A
+  printf '1\na\n3\n' >file && git commit -am "A: change second line"
+
+  git checkout B
+  printf '1\nb\n3\n' >file && git commit -am "B: change second line"
+  printf '1\n2\n3\n' >file && git commit -am "B: revert second line"
+
+  git checkout main
+  git merge A B
+)
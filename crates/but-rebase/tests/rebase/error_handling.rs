@@ -25,6 +25,7 @@ fn non_existing_commit_in_pick_step() -> anyhow::Result<()> {
     let result = builder.steps([RebaseStep::Pick {
         commit_id: non_existing_commit(),
         new_message: None,
+        base: None,
     }]);
     assert_eq!(
         result.unwrap_err().to_string(),
@@ -86,10 +87,12 @@ fn using_picked_commit_in_a_pick_step() -> anyhow::Result<()> {
         RebaseStep::Pick {
             commit_id: commits.a,
             new_message: None,
+            base: None,
         },
         RebaseStep::Pick {
             commit_id: commits.a,
             new_message: None,
+            base: None,
         },
     ]);
     assert_eq!(
@@ -107,6 +110,7 @@ fn using_fixup_commit_in_a_pick_step() -> anyhow::Result<()> {
         RebaseStep::Pick {
             commit_id: commits.a,
             new_message: None,
+            base: None,
         },
         RebaseStep::SquashIntoPreceding {
             commit_id: commits.b,
@@ -115,6 +119,7 @@ fn using_fixup_commit_in_a_pick_step() -> anyhow::Result<()> {
         RebaseStep::Pick {
             commit_id: commits.b,
             new_message: None,
+            base: None,
         },
     ]);
     assert_eq!(
@@ -132,6 +137,7 @@ fn using_picked_commit_in_a_fixup_step() -> anyhow::Result<()> {
         RebaseStep::Pick {
             commit_id: commits.a,
             new_message: None,
+            base: None,
         },
         RebaseStep::SquashIntoPreceding {
             commit_id: commits.a,
@@ -153,6 +159,7 @@ fn using_fixup_commit_in_a_fixup_step() -> anyhow::Result<()> {
         RebaseStep::Pick {
             commit_id: commits.a,
             new_message: None,
+            base: None,
         },
         RebaseStep::SquashIntoPreceding {
             commit_id: commits.b,
@@ -185,6 +192,27 @@ fn fixup_is_first_step() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn fixup_is_only_preceeded_by_a_dropped_commit() -> anyhow::Result<()> {
+    let (repo, commits) = four_commits()?;
+    let mut builder = Rebase::new(&repo, commits.base, None)?;
+    let result = builder.steps([
+        RebaseStep::Drop {
+            commit_id: commits.a,
+        },
+        RebaseStep::SquashIntoPreceding {
+            commit_id: commits.b,
+            new_message: None,
+        },
+    ]);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "Fixup must have a commit to work on",
+        "it must not be squashed into the base instead"
+    );
+    Ok(())
+}
+
 #[test]
 fn fixup_is_only_preceeded_by_a_reference_step() -> anyhow::Result<()> {
     let (repo, commits) = four_commits()?;
@@ -216,3 +244,120 @@ fn empty_reference_step() -> anyhow::Result<()> {
     );
     Ok(())
 }
+
+#[test]
+fn non_existing_pick_base() -> anyhow::Result<()> {
+    let (repo, commits) = four_commits()?;
+    let mut builder = Rebase::new(&repo, commits.base, None)?;
+    let result = builder.steps([RebaseStep::Pick {
+        commit_id: commits.a,
+        new_message: None,
+        base: Some(non_existing_commit()),
+    }]);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        format!(
+            "Base commit of picked commit {} must exist: {}",
+            commits.a,
+            non_existing_commit()
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn merge_commit_with_pick_base() -> anyhow::Result<()> {
+    let repo = fixture("three-branches-merged")?;
+    let base = repo.rev_parse_single("base")?.detach();
+    let merge = repo.rev_parse_single("main")?.detach();
+    let mut builder = Rebase::new(&repo, base, None)?;
+    let result = builder.steps([RebaseStep::Pick {
+        commit_id: merge,
+        new_message: None,
+        base: Some(base),
+    }]);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        format!(
+            "Merge-commit {merge} can't be picked onto a new base, \
+            it would be re-merged with the tips of the rewritten sequences instead"
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn nothing_to_rebase_without_base() -> anyhow::Result<()> {
+    let (repo, commits) = four_commits()?;
+    let mut builder = Rebase::new(&repo, None, None)?;
+    let result = builder
+        .steps([RebaseStep::Drop {
+            commit_id: commits.a,
+        }])?
+        .rebase();
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "There must be at least one commit to rebase if there is no base"
+    );
+    Ok(())
+}
+
+#[test]
+fn pausing_steps_need_start() -> anyhow::Result<()> {
+    let (repo, commits) = four_commits()?;
+    let mut builder = Rebase::new(&repo, commits.base, None)?;
+    let result = builder
+        .steps([
+            RebaseStep::Pick {
+                commit_id: commits.a,
+                new_message: None,
+                base: None,
+            },
+            RebaseStep::Break,
+        ])?
+        .rebase();
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "Steps that pause the rebase need `Rebase::start()`"
+    );
+    Ok(())
+}
+
+#[test]
+fn empty_exec_step() -> anyhow::Result<()> {
+    let (repo, commits) = four_commits()?;
+    let mut builder = Rebase::new(&repo, commits.base, None)?;
+    let result = builder.steps([RebaseStep::Exec {
+        command: " ".into(),
+    }]);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "Exec step must have a non-empty command"
+    );
+    Ok(())
+}
+
+#[test]
+fn failing_exec_aborts_the_rebase() -> anyhow::Result<()> {
+    let (repo, commits) = four_commits()?;
+    let mut builder = Rebase::new(&repo, commits.base, None)?;
+    let result = builder
+        .steps([
+            RebaseStep::Pick {
+                commit_id: commits.a,
+                new_message: None,
+                base: None,
+            },
+            RebaseStep::Exec {
+                command: "echo 'tests failed' >&2; exit 3".into(),
+            },
+        ])?
+        .rebase();
+    let err = result.unwrap_err().to_string();
+    assert!(
+        err.starts_with("The rebase was aborted as 'echo 'tests failed' >&2; exit 3' failed with"),
+        "{err}"
+    );
+    assert!(err.ends_with(": tests failed"), "{err}");
+    Ok(())
+}
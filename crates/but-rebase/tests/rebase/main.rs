@@ -1,9 +1,12 @@
 use crate::utils::{
-    assure_nonconflicting, conflicted, fixture_writable, four_commits_writable, visualize_tree,
+    assure_nonconflicting, conflicted, fixture, fixture_writable, four_commits_writable,
+    visualize_tree,
 };
 use anyhow::Result;
 use bstr::ByteSlice;
-use but_rebase::{Rebase, RebaseStep};
+use but_core::commit::TreeKind;
+use but_rebase::paused::{PauseReason, PausedRebase};
+use but_rebase::{Progress, Rebase, RebaseStep};
 use but_testsupport::{assure_stable_env, visualize_commit_graph};
 use gix::prelude::ObjectIdExt;
 
@@ -19,6 +22,7 @@ fn single_stack_journey() -> Result<()> {
             RebaseStep::Pick {
                 commit_id: commits.a,
                 new_message: Some("first step: pick a".into()),
+                base: None,
             },
             RebaseStep::SquashIntoPreceding {
                 commit_id: commits.b,
@@ -76,6 +80,7 @@ fn single_stack_journey() -> Result<()> {
                 Sha1(a466bf82eed2e6aa725eb61a85cc73281fc02960),
             ),
         ],
+        dropped: [],
     }
     "#);
     assure_nonconflicting(&repo, &out)?;
@@ -112,12 +117,14 @@ fn amended_commit() -> Result<()> {
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("C")?.into(),
                 new_message: Some("C: add another 10 lines to new file - amended".into()),
+                base: None,
             },
             // Picking a merge commit means to repeat the merge with the latest rewritten commit
             // from the previous step.
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("main")?.into(),
                 new_message: Some("Merge branches 'A', 'B' and 'C' - rewritten".into()),
+                base: None,
             },
         ])?
         .rebase()?;
@@ -156,12 +163,122 @@ fn amended_commit() -> Result<()> {
                 Sha1(7997ae52819cc4ceb88e2e675453bbfb4dd8cd46),
             ),
         ],
+        dropped: [],
     }
     ");
     assure_nonconflicting(&repo, &out)?;
     Ok(())
 }
 
+#[test]
+fn rewrite_all_stacks_and_remerge_them_in_one_go() -> Result<()> {
+    assure_stable_env();
+    let (repo, _tmp) = fixture_writable("three-branches-merged")?;
+    let id = |spec: &str| -> Result<gix::ObjectId> { Ok(repo.rev_parse_single(spec)?.detach()) };
+    let base = id("base")?;
+    let mut builder = Rebase::new(&repo, base, None)?;
+    let out = builder
+        .steps([
+            RebaseStep::Pick {
+                commit_id: id("A")?,
+                new_message: Some("A: rewritten".into()),
+                base: None,
+            },
+            RebaseStep::Reference(but_core::Reference::Virtual("A".into())),
+            // Each stack starts a new sequence on top of its own base…
+            RebaseStep::Pick {
+                commit_id: id("B~1")?,
+                new_message: None,
+                base: Some(base),
+            },
+            RebaseStep::Pick {
+                commit_id: id("B")?,
+                new_message: Some("B: rewritten".into()),
+                base: None,
+            },
+            RebaseStep::Reference(but_core::Reference::Virtual("B".into())),
+            RebaseStep::Pick {
+                commit_id: id("C~2")?,
+                new_message: None,
+                base: Some(id("A")?),
+            },
+            RebaseStep::Pick {
+                commit_id: id("C~1")?,
+                new_message: None,
+                base: None,
+            },
+            RebaseStep::Pick {
+                commit_id: id("C")?,
+                new_message: Some("C: rewritten".into()),
+                base: None,
+            },
+            RebaseStep::Reference(but_core::Reference::Virtual("C".into())),
+            // …and the merge is redone with the tips of all of them.
+            RebaseStep::Pick {
+                commit_id: id("main")?,
+                new_message: Some("Merge branches 'A', 'B' and 'C' - rewritten".into()),
+                base: None,
+            },
+        ])?
+        .rebase()?;
+
+    let stack_tips: Vec<_> = out.references.iter().map(|r| r.commit_id).collect();
+    assert_eq!(stack_tips.len(), 3, "one tip per stack");
+    let merge = repo.find_commit(out.top_commit)?;
+    assert_eq!(
+        merge.parent_ids().map(|id| id.detach()).collect::<Vec<_>>(),
+        stack_tips,
+        "the merge uses the rewritten tips of each sequence, in the original parent order"
+    );
+    assert_eq!(
+        merge.tree_id()?,
+        id("main^{tree}")?,
+        "nothing but messages changed, so the merge result is the same"
+    );
+    for ((tip, expected_base), expected_base_distance) in
+        stack_tips.iter().zip([base, base, id("A")?]).zip([1, 2, 3])
+    {
+        let mut commit = repo.find_commit(*tip)?;
+        for _ in 0..expected_base_distance {
+            commit = repo.find_commit(commit.parent_ids().next().expect("parent"))?;
+        }
+        assert_eq!(
+            commit.id, expected_base,
+            "each stack was rebased onto its own base"
+        );
+    }
+    assert_eq!(
+        out.commit_mapping
+            .iter()
+            .map(|(mapping_base, old, _new)| (*mapping_base, *old))
+            .collect::<Vec<_>>(),
+        [
+            (Some(base), id("A")?),
+            (Some(base), id("B~1")?),
+            (Some(base), id("B")?),
+            (Some(id("A")?), id("C~2")?),
+            (Some(id("A")?), id("C~1")?),
+            (Some(id("A")?), id("C")?),
+            (Some(id("A")?), id("main")?),
+        ],
+        "all commits are mapped relative to the base of their sequence, \
+        and the merge is mapped relative to the base of the last one"
+    );
+    let rewritten: Vec<_> = out.commit_mapping.iter().map(|(_, _, new)| *new).collect();
+    assert_eq!(
+        [rewritten[0], rewritten[2], rewritten[5]],
+        stack_tips.as_slice(),
+        "the rewritten stack tips are mapped"
+    );
+    assert_eq!(
+        rewritten.last(),
+        Some(&out.top_commit),
+        "the merge is mapped as well"
+    );
+    assure_nonconflicting(&repo, &out)?;
+    Ok(())
+}
+
 #[test]
 fn reorder_with_conflict_and_remerge_and_pick_from_conflicts() -> Result<()> {
     assure_stable_env();
@@ -187,19 +304,23 @@ fn reorder_with_conflict_and_remerge_and_pick_from_conflicts() -> Result<()> {
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("C~2")?.into(),
                 new_message: Some("C~2".into()),
+                base: None,
             },
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("C")?.into(),
                 new_message: Some("C".into()),
+                base: None,
             },
             RebaseStep::Pick {
                 // This will conflict,
                 commit_id: repo.rev_parse_single("C~1")?.into(),
                 new_message: Some("C~1".into()),
+                base: None,
             },
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("main")?.into(),
                 new_message: Some("Re-merge branches 'A', 'B' and 'C'".into()),
+                base: None,
             },
         ])?
         .rebase()?;
@@ -237,6 +358,7 @@ fn reorder_with_conflict_and_remerge_and_pick_from_conflicts() -> Result<()> {
                 Sha1(49915cc7bbd6cf82a009f34b66272766441bc392),
             ),
         ],
+        dropped: [],
     }
     ");
     insta::assert_snapshot!(visualize_commit_graph(&repo, out.top_commit)?, @r"
@@ -339,6 +461,7 @@ fn reorder_with_conflict_and_remerge_and_pick_from_conflicts() -> Result<()> {
         .steps([RebaseStep::Pick {
             commit_id: repo.rev_parse_single("C~2")?.into(),
             new_message: Some("picked on top of conflicted base".into()),
+            base: None,
         }])?
         .rebase()?;
 
@@ -377,18 +500,22 @@ fn reversible_conflicts() -> anyhow::Result<()> {
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("C~2")?.into(),
                 new_message: Some("C~2".into()),
+                base: None,
             },
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("C")?.into(),
                 new_message: Some("C".into()),
+                base: None,
             },
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("C~1")?.into(),
                 new_message: Some("C~1".into()),
+                base: None,
             },
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("main")?.into(),
                 new_message: Some("Re-merge branches 'A', 'B' and 'C'".into()),
+                base: None,
             },
         ])?
         .rebase()?;
@@ -405,18 +532,22 @@ fn reversible_conflicts() -> anyhow::Result<()> {
                 RebaseStep::Pick {
                     commit_id: repo.rev_parse_single("C~2")?.into(),
                     new_message: Some("C~2".into()),
+                    base: None,
                 },
                 RebaseStep::Pick {
                     commit_id: repo.rev_parse_single("C~1")?.into(),
                     new_message: Some("C~1".into()),
+                    base: None,
                 },
                 RebaseStep::Pick {
                     commit_id: repo.rev_parse_single("C")?.into(),
                     new_message: Some("C".into()),
+                    base: None,
                 },
                 RebaseStep::Pick {
                     commit_id: repo.rev_parse_single("main")?.into(),
                     new_message: Some("Re-merge branches 'A', 'B' and 'C'".into()),
+                    base: None,
                 },
             ])?
             .rebase()?;
@@ -437,6 +568,7 @@ fn reversible_conflicts() -> anyhow::Result<()> {
             .steps([RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("C")?.into(),
                 new_message: Some("C~1".into()),
+                base: None,
             }])?
             .rebase()?;
         assert_eq!(conflicted(&repo, &out), [false]);
@@ -475,18 +607,22 @@ fn reversible_conflicts() -> anyhow::Result<()> {
                     .rev_parse_single(format!("{conflict_tip}~2").as_str())?
                     .into(),
                 new_message: Some("C~2 is first".into()),
+                base: None,
             },
             RebaseStep::Pick {
                 commit_id: conflict_tip.detach(),
                 new_message: Some("This commit is now unconflicted".into()),
+                base: None,
             },
             RebaseStep::Pick {
                 commit_id: repo.rev_parse_single("C")?.into(),
                 new_message: Some("The original C will fit right on top".into()),
+                base: None,
             },
             RebaseStep::Pick {
                 commit_id: out.top_commit,
                 new_message: Some("Re-merge branches 'A', 'B' and 'C'".into()),
+                base: None,
             },
         ])?
         .rebase()?;
@@ -514,6 +650,7 @@ fn pick_the_first_commit_with_no_parents_for_squashing() -> Result<()> {
             RebaseStep::Pick {
                 commit_id: commits.base,
                 new_message: Some("reword base".into()),
+                base: None,
             },
             RebaseStep::SquashIntoPreceding {
                 commit_id: commits.a,
@@ -538,12 +675,454 @@ fn pick_the_first_commit_with_no_parents_for_squashing() -> Result<()> {
                 Sha1(9078131ba71afab019afd55f9dbce97c80858a42),
             ),
         ],
+        dropped: [],
     }
     ");
     assure_nonconflicting(&repo, &out)?;
     Ok(())
 }
 
+#[test]
+fn drop_and_exec() -> Result<()> {
+    assure_stable_env();
+    let (repo, commits, _tmp) = four_commits_writable()?;
+    let mut builder = Rebase::new(&repo, commits.base, None)?;
+    let out = builder
+        .steps([
+            RebaseStep::Pick {
+                commit_id: commits.a,
+                new_message: None,
+                base: None,
+            },
+            RebaseStep::Drop {
+                commit_id: commits.b,
+            },
+            // Commands see the rewritten commit, even though the worktree is never touched.
+            RebaseStep::Exec {
+                command: r#"git cat-file -e "$GITBUTLER_REBASE_HEAD:a""#.into(),
+            },
+            // They run in a temporary worktree that has it checked out.
+            RebaseStep::Exec {
+                command: "test -f a && test ! -e b".into(),
+            },
+            RebaseStep::Pick {
+                commit_id: commits.c,
+                new_message: None,
+                base: None,
+            },
+            RebaseStep::Exec {
+                command: r#"! git cat-file -e "$GITBUTLER_REBASE_HEAD:b""#.into(),
+            },
+        ])?
+        .rebase()?;
+    assert_eq!(out.dropped, [commits.b], "drops are listed explicitly");
+    assert_eq!(
+        out.commit_mapping
+            .iter()
+            .map(|(_base, old, _new)| *old)
+            .collect::<Vec<_>>(),
+        [commits.a, commits.c],
+        "dropped commits aren't mapped"
+    );
+    let tree = repo.find_commit(out.top_commit)?.tree()?;
+    assert!(tree.lookup_entry_by_path("b")?.is_none());
+    assert!(tree.lookup_entry_by_path("c")?.is_some());
+    assure_nonconflicting(&repo, &out)?;
+    Ok(())
+}
+
+#[test]
+fn edit_pauses_until_resumed_from_persisted_state() -> Result<()> {
+    assure_stable_env();
+    let (repo, commits, _tmp) = four_commits_writable()?;
+    let mut builder = Rebase::new(&repo, commits.base, None)?;
+    let Progress::Paused(paused) = builder
+        .steps([
+            RebaseStep::Pick {
+                commit_id: commits.a,
+                new_message: None,
+                base: None,
+            },
+            RebaseStep::Edit {
+                commit_id: commits.b,
+                new_message: Some("b: to be edited".into()),
+            },
+            RebaseStep::Pick {
+                commit_id: commits.c,
+                new_message: None,
+                base: None,
+            },
+            RebaseStep::Reference(but_core::Reference::Virtual("anchor".into())),
+        ])?
+        .start()?
+    else {
+        unreachable!("the edit step pauses the rebase")
+    };
+    assert_eq!(
+        paused.reason,
+        PauseReason::Edit {
+            commit_id: commits.b
+        }
+    );
+    let head = paused.head().expect("b was picked");
+    assert_eq!(repo.find_commit(head)?.message_raw()?, "b: to be edited");
+
+    let mut other = Rebase::new(&repo, commits.base, None)?;
+    assert_eq!(
+        other
+            .steps([RebaseStep::Pick {
+                commit_id: commits.a,
+                new_message: None,
+                base: None,
+            }])?
+            .start()
+            .unwrap_err()
+            .to_string(),
+        "A paused rebase must be continued or aborted before starting a new one",
+        "only one rebase can be paused at a time"
+    );
+
+    // Pretend the application was restarted.
+    drop(paused);
+    let paused = PausedRebase::load(&repo)?.expect("the paused rebase was persisted");
+    assert_eq!(
+        paused.head(),
+        Some(head),
+        "the state survives the round-trip"
+    );
+
+    let mut edited = repo.find_commit(head)?.decode()?.to_owned();
+    edited.message = "b: edited".into();
+    let edited = repo.write_object(&edited)?.detach();
+    let Progress::Done(out) = paused.resume(&repo, Some(edited))? else {
+        unreachable!("there are no more steps that pause")
+    };
+    let top = repo.find_commit(out.top_commit)?;
+    assert_eq!(
+        top.parent_ids().map(|id| id.detach()).collect::<Vec<_>>(),
+        [edited],
+        "the remaining steps are performed on top of the edited commit"
+    );
+    assert_eq!(out.references.len(), 1);
+    assert_eq!(out.references[0].commit_id, out.top_commit);
+    assert!(
+        out.commit_mapping
+            .iter()
+            .any(|(base, old, new)| *base == Some(commits.base)
+                && *old == commits.b
+                && *new == edited),
+        "the edited commit replaces the picked one"
+    );
+    assert!(
+        PausedRebase::load(&repo)?.is_none(),
+        "completed rebases aren't persisted"
+    );
+    assure_nonconflicting(&repo, &out)?;
+    Ok(())
+}
+
+#[test]
+fn break_and_abort() -> Result<()> {
+    assure_stable_env();
+    let (repo, commits, _tmp) = four_commits_writable()?;
+    let mut builder = Rebase::new(&repo, commits.base, None)?;
+    let Progress::Paused(paused) = builder
+        .steps([
+            RebaseStep::Pick {
+                commit_id: commits.a,
+                new_message: Some("a: reworded".into()),
+                base: None,
+            },
+            RebaseStep::Break,
+            RebaseStep::Pick {
+                commit_id: commits.b,
+                new_message: None,
+                base: None,
+            },
+        ])?
+        .start()?
+    else {
+        unreachable!("the break step pauses the rebase")
+    };
+    assert_eq!(paused.reason, PauseReason::Break);
+    let head = paused.head().expect("a was picked");
+    assert_eq!(repo.find_commit(head)?.message_raw()?, "a: reworded");
+
+    paused.abort(&repo)?;
+    assert!(
+        PausedRebase::load(&repo)?.is_none(),
+        "aborting forgets the paused rebase"
+    );
+    assert_eq!(
+        repo.head_id()?,
+        commits.c,
+        "nothing but the commit graph is ever changed"
+    );
+    Ok(())
+}
+
+#[test]
+fn conflicting_remerge_can_be_recorded() -> Result<()> {
+    assure_stable_env();
+    let repo = fixture("merge-conflict-after-drop")?;
+    let steps = || -> Result<Vec<RebaseStep>> {
+        Ok(vec![
+            RebaseStep::Pick {
+                commit_id: repo.rev_parse_single("A")?.into(),
+                new_message: None,
+                base: None,
+            },
+            RebaseStep::Pick {
+                commit_id: repo.rev_parse_single("B~1")?.into(),
+                new_message: None,
+                base: Some(repo.rev_parse_single("base")?.into()),
+            },
+            // Without the revert, B conflicts with A.
+            RebaseStep::Drop {
+                commit_id: repo.rev_parse_single("B")?.into(),
+            },
+            RebaseStep::Pick {
+                commit_id: repo.rev_parse_single("main")?.into(),
+                new_message: None,
+                base: None,
+            },
+        ])
+    };
+    let base = repo.rev_parse_single("base")?.detach();
+
+    let mut builder = Rebase::new(&repo, base, None)?;
+    let err = builder.steps(steps()?)?.rebase().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "The rebase failed as a merge could not be repeated without conflicts"
+    );
+
+    let mut builder = Rebase::new(&repo, base, None)?;
+    let out = builder
+        .steps(steps()?)?
+        .merge_conflicts(but_rebase::merge::ConflictMode::Record)
+        .rebase()?;
+    let merge = but_core::Commit::from_id(out.top_commit.attach(&repo))?;
+    assert!(merge.is_conflicted(), "the conflict is recorded");
+    assert_eq!(merge.inner.parents.len(), 2, "it's still a merge");
+    let tree = repo.find_tree(merge.inner.tree)?;
+    let conflict_files = tree
+        .find_entry(".conflict-files")
+        .expect("conflicting paths are recorded")
+        .object()?
+        .detach()
+        .data;
+    assert_eq!(
+        conflict_files.as_bstr(),
+        "ancestorEntries = [\"file\"]\nourEntries = [\"file\"]\ntheirEntries = [\"file\"]\n"
+    );
+    for (kind, content) in [
+        (TreeKind::Base, "1\n2\n3\n"),
+        (TreeKind::Ours, "1\na\n3\n"),
+        (TreeKind::Theirs, "1\nb\n3\n"),
+        (TreeKind::AutoResolution, "1\na\n3\n"),
+    ] {
+        let side = merge.tree_id_by_kind(kind)?.expect("all sides are present");
+        let blob = side
+            .object()?
+            .peel_to_tree()?
+            .find_entry("file")
+            .expect("file on each side")
+            .object()?
+            .detach()
+            .data;
+        assert_eq!(blob.as_bstr(), content, "{kind:?}");
+    }
+    Ok(())
+}
+
+mod autosquash {
+    use crate::utils::{assure_nonconflicting, fixture, fixture_writable};
+    use anyhow::Result;
+    use bstr::{BStr, ByteSlice};
+    use but_rebase::autosquash::{Kind, parse_subject, plan};
+    use but_rebase::{Rebase, RebaseStep};
+    use but_testsupport::assure_stable_env;
+
+    #[test]
+    fn subjects() {
+        assert_eq!(parse_subject("a".into()), None);
+        assert_eq!(
+            parse_subject("fixup! a".into()),
+            Some((Kind::Fixup, "a".into()))
+        );
+        assert_eq!(
+            parse_subject("squash! fixup! amend! a b ".into()),
+            Some((Kind::Squash, "a b".into())),
+            "the first prefix determines the kind"
+        );
+        assert_eq!(
+            parse_subject("amend!a".into()),
+            None,
+            "the space is required"
+        );
+    }
+
+    #[test]
+    fn fold_by_subject() -> Result<()> {
+        assure_stable_env();
+        let (repo, _tmp) = fixture_writable("fixups")?;
+        let mut steps = (0..6)
+            .rev()
+            .map(|n| {
+                Ok(RebaseStep::Pick {
+                    commit_id: repo.rev_parse_single(format!("@~{n}").as_str())?.detach(),
+                    new_message: None,
+                    base: None,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        steps.push(RebaseStep::Reference(but_core::Reference::Virtual(
+            "anchor".into(),
+        )));
+
+        let steps = plan(&repo, steps)?;
+        assert_eq!(
+            describe(&repo, &steps)?,
+            [
+                "pick a",
+                r#"squash 'fixup! a' with "a\n""#,
+                r#"squash 'amend! fixup! a' with "a: amended\n""#,
+                "pick b: first line",
+                r#"squash 'squash! b: first' with "b: first line\n\nmore about b\n""#,
+                "pick fixup! c",
+                "reference anchor",
+            ],
+            "fixups without target remain picks, and references stay in place"
+        );
+
+        let base = repo.rev_parse_single("@~6")?.detach();
+        let mut builder = Rebase::new(&repo, base, None)?;
+        let out = builder.steps(steps)?.rebase()?;
+        assure_nonconflicting(&repo, &out)?;
+
+        let top = repo.find_commit(out.top_commit)?;
+        let tree = top.tree()?;
+        for (path, content) in [("a", "a\na2\na3\n"), ("b", "b\nb2\n"), ("c", "c\n")] {
+            let blob = tree
+                .lookup_entry_by_path(path)?
+                .expect("file exists")
+                .object()?;
+            assert_eq!(blob.data.as_bstr(), content, "{path}");
+        }
+        let b = repo.find_commit(top.parent_ids().next().expect("parent"))?;
+        assert_eq!(b.message_raw()?, "b: first line\n\nmore about b\n");
+        let a = repo.find_commit(b.parent_ids().next().expect("parent"))?;
+        assert_eq!(a.message_raw()?, "a: amended\n");
+        assert_eq!(
+            a.parent_ids().next().map(|id| id.detach()),
+            Some(base),
+            "three commits remain"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fold_by_change_id() -> Result<()> {
+        let repo = fixture("fixups")?;
+        let mut target = repo
+            .find_commit(repo.rev_parse_single("@~5")?)?
+            .decode()?
+            .to_owned();
+        but_core::commit::HeadersV2 {
+            change_id: "change-of-a".into(),
+            conflicted: None,
+        }
+        .set_in_commit(&mut target);
+        let target = repo.write_object(&target)?.detach();
+        let mut fixup = repo
+            .find_commit(repo.rev_parse_single("@~3")?)?
+            .decode()?
+            .to_owned();
+        fixup.parents = [target].into_iter().collect();
+        fixup.message = "fixup! change-of-a\n".into();
+        let fixup = repo.write_object(&fixup)?.detach();
+
+        let steps = plan(
+            &repo,
+            [fixup, target]
+                .into_iter()
+                .rev()
+                .map(|commit_id| RebaseStep::Pick {
+                    commit_id,
+                    new_message: None,
+                    base: None,
+                }),
+        )?;
+        assert_eq!(
+            describe(&repo, &steps)?,
+            ["pick a", r#"squash 'fixup! change-of-a' with "a\n""#]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn no_fold_across_sequences() -> Result<()> {
+        let repo = fixture("fixups")?;
+        let base = repo.rev_parse_single("@~6")?.detach();
+        let steps = plan(
+            &repo,
+            [
+                RebaseStep::Pick {
+                    commit_id: repo.rev_parse_single("@~5")?.detach(),
+                    new_message: None,
+                    base: None,
+                },
+                RebaseStep::Pick {
+                    commit_id: repo.rev_parse_single("@~3")?.detach(),
+                    new_message: None,
+                    base: Some(base),
+                },
+            ],
+        )?;
+        assert_eq!(
+            describe(&repo, &steps)?,
+            ["pick a", "pick fixup! a"],
+            "the target has to be in the same sequence"
+        );
+        Ok(())
+    }
+
+    fn describe(repo: &gix::Repository, steps: &[RebaseStep]) -> Result<Vec<String>> {
+        let subject = |id: &gix::ObjectId| -> Result<String> {
+            let commit = repo.find_commit(*id)?;
+            let message: &BStr = commit.message_raw()?;
+            Ok(message
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_str_lossy()
+                .into_owned())
+        };
+        steps
+            .iter()
+            .map(|step| {
+                Ok(match step {
+                    RebaseStep::Pick { commit_id, .. } => format!("pick {}", subject(commit_id)?),
+                    RebaseStep::SquashIntoPreceding {
+                        commit_id,
+                        new_message,
+                    } => format!(
+                        "squash '{}' with {:?}",
+                        subject(commit_id)?,
+                        new_message
+                            .as_ref()
+                            .map(|m| m.to_str_lossy().into_owned())
+                            .unwrap_or_default()
+                    ),
+                    RebaseStep::Reference(reference) => format!("reference {reference}"),
+                    other => unreachable!("not produced here: {other:?}"),
+                })
+            })
+            .collect()
+    }
+}
+
 pub mod utils {
     use anyhow::Result;
     use but_rebase::RebaseOutput;
@@ -414,7 +414,8 @@
 use crate::StashStatus;
 use anyhow::{Context, bail};
 use bstr::BString;
-use but_core::RefMetadata;
+use but_core::{RefMetadata, ref_metadata};
+use gitbutler_oxidize::GixRepositoryExt;
 use gix::prelude::ObjectIdExt;
 
 /// The result of [`add_branch_to_workspace`].
@@ -454,15 +455,35 @@ pub fn add_branch_to_workspace(
     let merge_base = repo
         .merge_base_with_graph(workspace_tip, branch_tip, &mut graph)
         .context("Branch and workspace must have a merge-base")?;
-    if merge_base == branch_tip || merge_base == workspace_tip {
+    if merge_base == branch_tip {
         bail!("Cannot add branch that is already integrated")
     }
-    todo!()
+    if let Some(target_tip) = target_tip {
+        if repo
+            .merge_base_with_graph(target_tip, branch_tip, &mut graph)
+            .is_ok_and(|merge_base| merge_base == branch_tip)
+        {
+            bail!("Cannot add branch that is already integrated into the target branch")
+        }
+    }
+
+    let mut stack_tips = workspace_stack_tips(repo, workspace_tip, target_tip)?;
+    // A plain branch that is contained in the new branch is superseded by it.
+    if merge_base == workspace_tip {
+        stack_tips.retain(|tip| *tip != workspace_tip);
+    }
+    stack_tips.push(branch_tip);
+    Ok(ApplyOutcome {
+        workspace_tip: write_workspace_commit(repo, &stack_tips, target_tip)?,
+        rebase_output: None,
+    })
 }
 
 /// Like [`add_branch_to_workspace`], but will also update the involved references, and change `HEAD` to point to possibly newly
 /// created `workspace_tip`.
 /// `workspace_tip` can also be a detached `HEAD`, that's valid.
+///
+/// The stack of `branch_tip` is added to the workspace `metadata` as well, so it's known as applied.
 // TODO: maybe rather work with refs and add dry-run?
 pub fn add_branch_to_workspace_and_update_refs(
     repo: &gix::Repository,
@@ -471,7 +492,38 @@ pub fn add_branch_to_workspace_and_update_refs(
     target_tip: Option<gix::refs::PartialName>,
     metadata: &mut impl RefMetadata,
 ) -> anyhow::Result<ApplyOutcome> {
-    todo!()
+    let mut branch_ref = repo.find_reference(branch_tip.as_ref())?;
+    let branch_tip_id = branch_ref.peel_to_id_in_place()?.detach();
+    let branch_ref_name = branch_ref.name().to_owned();
+    let previous_workspace_tip = peel_workspace_tip(repo, workspace_tip.as_ref())?;
+    let target_tip = peel_target_tip(repo, target_tip)?;
+
+    let outcome = add_branch_to_workspace(
+        repo,
+        branch_tip_id,
+        previous_workspace_tip,
+        target_tip,
+        metadata,
+    )?;
+    update_workspace_ref(
+        repo,
+        workspace_tip.as_ref(),
+        previous_workspace_tip,
+        outcome.workspace_tip,
+        "GitButler: add branch to workspace",
+    )?;
+
+    let mut ws = metadata.workspace(workspace_tip.as_ref())?;
+    if !ws.contains_ref(branch_ref_name.as_ref()) {
+        ws.stacks.push(ref_metadata::WorkspaceStack {
+            branches: vec![ref_metadata::WorkspaceStackBranch {
+                ref_name: branch_ref_name,
+                archived: false,
+            }],
+        });
+        metadata.set_workspace(&ws)?;
+    }
+    Ok(outcome)
 }
 
 /// The inverse of [`add_branch_to_workspace`] where `workspace_tip` is the current `HEAD` and `branch_tip` is the tip to *not* include in the
@@ -483,7 +535,196 @@ pub fn remove_branch_from_workspace(
     target_tip: Option<gix::ObjectId>,
     metadata: &mut impl RefMetadata,
 ) -> anyhow::Result<ApplyOutcome> {
-    todo!()
+    let mut stack_tips = workspace_stack_tips(repo, workspace_tip, target_tip)?;
+    let num_stacks = stack_tips.len();
+    stack_tips.retain(|tip| *tip != branch_tip);
+    if stack_tips.len() == num_stacks {
+        bail!("The branch at {branch_tip} isn't a stack of the workspace at {workspace_tip}")
+    }
+    Ok(ApplyOutcome {
+        workspace_tip: write_workspace_commit(repo, &stack_tips, target_tip)?,
+        rebase_output: None,
+    })
+}
+
+/// Like [`remove_branch_from_workspace`], but will also update the `workspace_tip` reference to the new workspace commit,
+/// and remove the stack of `branch_tip` from the workspace `metadata`.
+pub fn remove_branch_from_workspace_and_update_refs(
+    repo: &gix::Repository,
+    branch_tip: gix::refs::PartialName,
+    workspace_tip: gix::refs::FullName,
+    target_tip: Option<gix::refs::PartialName>,
+    metadata: &mut impl RefMetadata,
+) -> anyhow::Result<ApplyOutcome> {
+    let mut branch_ref = repo.find_reference(branch_tip.as_ref())?;
+    let branch_tip_id = branch_ref.peel_to_id_in_place()?.detach();
+    let branch_ref_name = branch_ref.name().to_owned();
+    let previous_workspace_tip = peel_workspace_tip(repo, workspace_tip.as_ref())?;
+    let target_tip = peel_target_tip(repo, target_tip)?;
+
+    let outcome = remove_branch_from_workspace(
+        repo,
+        branch_tip_id,
+        previous_workspace_tip,
+        target_tip,
+        metadata,
+    )?;
+    update_workspace_ref(
+        repo,
+        workspace_tip.as_ref(),
+        previous_workspace_tip,
+        outcome.workspace_tip,
+        "GitButler: remove branch from workspace",
+    )?;
+
+    let mut ws = metadata.workspace(workspace_tip.as_ref())?;
+    let num_stacks = ws.stacks.len();
+    ws.stacks.retain(|stack| {
+        !stack
+            .branches
+            .iter()
+            .any(|branch| branch.ref_name == branch_ref_name)
+    });
+    if ws.stacks.len() != num_stacks {
+        metadata.set_workspace(&ws)?;
+    }
+    Ok(outcome)
+}
+
+/// Return the tips of all stacks merged by `workspace_tip`, which is just `workspace_tip` itself if it's not a managed
+/// workspace commit. `target_tip` is never considered a stack.
+fn workspace_stack_tips(
+    repo: &gix::Repository,
+    workspace_tip: gix::ObjectId,
+    target_tip: Option<gix::ObjectId>,
+) -> anyhow::Result<Vec<gix::ObjectId>> {
+    let workspace_commit = crate::WorkspaceCommit::from_id(workspace_tip.attach(repo))?;
+    let mut tips: Vec<_> = if workspace_commit.is_managed() {
+        workspace_commit.parents.iter().copied().collect()
+    } else {
+        vec![workspace_tip]
+    };
+    tips.retain(|tip| Some(*tip) != target_tip);
+    Ok(tips)
+}
+
+/// Merge the trees of all `stack_tips` and write a new workspace commit with them as parents, in order.
+/// If there are no stacks, the workspace commit will sit on top of `target_tip`.
+fn write_workspace_commit(
+    repo: &gix::Repository,
+    stack_tips: &[gix::ObjectId],
+    target_tip: Option<gix::ObjectId>,
+) -> anyhow::Result<gix::ObjectId> {
+    let parents: Vec<_> = if stack_tips.is_empty() {
+        target_tip.into_iter().collect()
+    } else {
+        stack_tips.to_vec()
+    };
+    let Some(first_parent) = parents.first() else {
+        bail!("Cannot create a workspace without stacks if there is no target branch")
+    };
+
+    let mut tree_id = first_parent.attach(repo).object()?.peel_to_tree()?.id;
+    if let Some(other_parents) = parents.get(1..).filter(|parents| !parents.is_empty()) {
+        let merge_base = repo.merge_base_octopus(parents.iter().copied())?;
+        let base_tree_id = merge_base.object()?.peel_to_tree()?.id;
+        let (merge_options, conflict_kind) = repo.merge_options_fail_fast()?;
+        for parent in other_parents {
+            let parent_tree_id = parent.attach(repo).object()?.peel_to_tree()?.id;
+            let mut merge = repo.merge_trees(
+                base_tree_id,
+                tree_id,
+                parent_tree_id,
+                repo.default_merge_labels(),
+                merge_options.clone(),
+            )?;
+            if merge.has_unresolved_conflicts(conflict_kind) {
+                bail!("The branch at {parent} conflicts with other stacks in the workspace");
+            }
+            tree_id = merge.tree.write()?.detach();
+        }
+    }
+
+    let mut commit = crate::WorkspaceCommit::create_commit_from_stack_tips(
+        stack_tips.iter().map(|tip| (None, *tip)),
+        repo.object_hash(),
+    );
+    commit.parents = parents.into();
+    commit.tree = tree_id;
+    Ok(repo.write_object(&commit)?.detach())
+}
+
+/// Return the commit that `workspace_ref` points to, which may also be `HEAD`.
+fn peel_workspace_tip(
+    repo: &gix::Repository,
+    workspace_ref: &gix::refs::FullNameRef,
+) -> anyhow::Result<gix::ObjectId> {
+    Ok(if workspace_ref.as_bstr() == "HEAD" {
+        repo.head_id()?.detach()
+    } else {
+        repo.find_reference(workspace_ref)?
+            .peel_to_id_in_place()?
+            .detach()
+    })
+}
+
+fn peel_target_tip(
+    repo: &gix::Repository,
+    target_ref: Option<gix::refs::PartialName>,
+) -> anyhow::Result<Option<gix::ObjectId>> {
+    target_ref
+        .map(|name| -> anyhow::Result<_> {
+            Ok(repo
+                .find_reference(name.as_ref())?
+                .peel_to_id_in_place()?
+                .detach())
+        })
+        .transpose()
+}
+
+/// Point `workspace_ref` from `previous_tip` to `new_tip`, and make `HEAD` point to it unless
+/// `workspace_ref` is a detached `HEAD` itself.
+fn update_workspace_ref(
+    repo: &gix::Repository,
+    workspace_ref: &gix::refs::FullNameRef,
+    previous_tip: gix::ObjectId,
+    new_tip: gix::ObjectId,
+    message: &str,
+) -> anyhow::Result<()> {
+    use gix::refs::Target;
+    use gix::refs::transaction::{Change, LogChange, PreviousValue, RefEdit, RefLog};
+
+    let log = LogChange {
+        mode: RefLog::AndReference,
+        force_create_reflog: false,
+        message: message.into(),
+    };
+    let mut edits = vec![RefEdit {
+        change: Change::Update {
+            log: log.clone(),
+            expected: PreviousValue::MustExistAndMatch(Target::Object(previous_tip)),
+            new: Target::Object(new_tip),
+        },
+        name: workspace_ref.to_owned(),
+        deref: false,
+    }];
+    let head = repo.head()?;
+    let head_points_to_workspace_ref = head
+        .referent_name()
+        .is_some_and(|name| name == workspace_ref);
+    if workspace_ref.as_bstr() != "HEAD" && !head_points_to_workspace_ref {
+        edits.push(RefEdit {
+            change: Change::Update {
+                log,
+                expected: PreviousValue::Any,
+                new: Target::Symbolic(workspace_ref.to_owned()),
+            },
+            name: "HEAD".try_into()?,
+            deref: false,
+        });
+    }
+    repo.edit_references(edits)?;
+    Ok(())
 }
 
 /// An even more minimal version of the [`StackEntry](crate::StackEntry) with enough information to query
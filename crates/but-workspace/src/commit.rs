@@ -1,5 +1,5 @@
 use crate::{StackEntry, WorkspaceCommit};
-use bstr::ByteSlice;
+use bstr::{BStr, ByteSlice};
 
 /// Construction
 impl<'repo> WorkspaceCommit<'repo> {
@@ -25,6 +25,19 @@ impl<'repo> WorkspaceCommit<'repo> {
         stacks: &[StackEntry],
         object_hash: gix::hash::Kind,
     ) -> gix::objs::Commit {
+        Self::create_commit_from_stack_tips(
+            stacks.iter().map(|stack| (stack.name(), stack.tip)),
+            object_hash,
+        )
+    }
+
+    /// Like [`Self::create_commit_from_vb_state()`], but only needs the optional name and the tip of each stack
+    /// to merge, in the order in which they should become parents of the commit.
+    pub fn create_commit_from_stack_tips<'a>(
+        stacks: impl IntoIterator<Item = (Option<&'a BStr>, gix::ObjectId)>,
+        object_hash: gix::hash::Kind,
+    ) -> gix::objs::Commit {
+        let stacks: Vec<_> = stacks.into_iter().collect();
         // message that says how to get back to where they were
         let mut message = Self::GITBUTLER_WORKSPACE_COMMIT_TITLE.to_string();
         message.push_str("\n\n");
@@ -44,15 +57,15 @@ impl<'repo> WorkspaceCommit<'repo> {
         message.push_str("If you commit on this branch, GitButler will throw it away.\n\n");
         if !stacks.is_empty() {
             message.push_str("Here are the branches that are currently applied:\n");
-            for branch in stacks {
-                if let Some(name) = branch.name() {
+            for (name, tip) in &stacks {
+                if let Some(name) = name {
                     message.push_str(" - ");
                     message.push_str(name.to_str_lossy().as_ref());
                     message.push('\n');
                 }
 
                 message.push_str("   branch head: ");
-                message.push_str(&branch.tip.to_string());
+                message.push_str(&tip.to_string());
                 message.push('\n');
             }
         }
@@ -67,7 +80,7 @@ impl<'repo> WorkspaceCommit<'repo> {
         };
         gix::objs::Commit {
             tree: gix::ObjectId::empty_tree(object_hash),
-            parents: stacks.iter().map(|s| s.tip).collect(),
+            parents: stacks.iter().map(|(_, tip)| *tip).collect(),
             committer: author.clone(),
             author,
             encoding: Some("UTF-8".into()),
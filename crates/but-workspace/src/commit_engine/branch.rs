@@ -0,0 +1,267 @@
+use crate::commit_engine::tree::{PossibleChange, apply_tree_changes, into_err_spec};
+use crate::commit_engine::{
+    CreateCommitOutcome, DiffSpec, RejectionReason, UpdatedReference,
+    create_possibly_signed_commit, from_patch::patch,
+};
+use anyhow::bail;
+use bstr::BString;
+use but_core::RepositoryExt;
+use gitbutler_oxidize::{ObjectIdExt as _, OidExt};
+use gitbutler_project::access::WorktreeWritePermission;
+use gitbutler_stack::{CommitOrChangeId, VirtualBranchesHandle, VirtualBranchesState};
+use gix::merge::tree::TreatAsUnresolved;
+use gix::prelude::ObjectIdExt as _;
+use gix::refs::transaction::PreviousValue;
+
+/// The changes to commit onto a branch with [`create_commit_on_branch()`].
+#[derive(Debug, Clone)]
+pub enum BranchChanges {
+    /// A selection of the changes that turn `previous_tree` into `tree`, with hunks as seen in the diff between them.
+    ///
+    /// If `previous_tree` isn't the tree of the branch tip, the selected changes will be cherry-picked onto it.
+    Trees {
+        /// The tree the `changes` are based on.
+        previous_tree: gix::ObjectId,
+        /// The tree with the new state of all `changes`.
+        tree: gix::ObjectId,
+        /// The changes to commit.
+        changes: Vec<DiffSpec>,
+    },
+    /// A unified diff as produced by `git diff` or `git format-patch`, to apply to the tree of the branch tip.
+    Patch(BString),
+}
+
+/// Create a new commit with `message` and `changes` on top of `branch`, and set `branch` to point to it.
+/// This works for any branch that isn't checked out or applied to the workspace, as the commit is created in memory.
+///
+/// `context_lines` is the amount of lines of context included in each [`HunkHeader`](crate::commit_engine::HunkHeader)
+/// of `changes`, and the value that will be used to recover their hunks.
+///
+/// If `branch` is a segment of an unapplied stack in `vb`, the segments above it are rebased onto the new commit
+/// and their heads are updated, along with the one of `branch`.
+///
+/// Return additional information that helps to understand to what extent the commit was created, as the commit might
+/// not contain all changes that were requested if they failed to apply.
+pub fn create_commit_on_branch(
+    repo: &gix::Repository,
+    vb: &mut VirtualBranchesState,
+    branch: &gix::refs::FullNameRef,
+    message: &str,
+    changes: BranchChanges,
+    context_lines: u32,
+) -> anyhow::Result<CreateCommitOutcome> {
+    if repo.head_name()?.as_ref().map(|name| name.as_ref()) == Some(branch) {
+        bail!(
+            "Branch '{}' is checked out and can't be committed to without the worktree",
+            branch.as_bstr()
+        );
+    }
+    let short_name = branch.shorten();
+    if vb
+        .branches
+        .values()
+        .filter(|stack| stack.in_workspace)
+        .any(|stack| stack.heads.iter().any(|head| head.name() == short_name))
+    {
+        bail!(
+            "Branch '{}' is applied to the workspace and can't be committed to without it",
+            branch.as_bstr()
+        );
+    }
+
+    let tip = repo.find_reference(branch)?.peel_to_commit()?.id;
+    let tip_tree = but_core::Commit::from_id(tip.attach(repo))?
+        .tree_id()?
+        .detach();
+    let (new_tree, changed_tree_pre_cherry_pick, rejected_specs) = match changes {
+        BranchChanges::Trees {
+            previous_tree,
+            tree,
+            changes,
+        } => {
+            let mut changes: Vec<PossibleChange> = changes.into_iter().map(Ok).collect();
+            let (new_tree, changed_tree_pre_cherry_pick) = 'retry: loop {
+                let Some(tree_with_changes) =
+                    apply_tree_changes(repo, previous_tree, tree, &mut changes, context_lines)?
+                else {
+                    break 'retry (None, None);
+                };
+                if previous_tree == tip_tree {
+                    break 'retry (Some(tree_with_changes), Some(tree_with_changes));
+                }
+                let mut merge_result = repo.merge_trees(
+                    previous_tree,
+                    tip_tree,
+                    tree_with_changes,
+                    repo.default_merge_labels(),
+                    repo.tree_merge_options()?,
+                )?;
+                let unresolved_conflicts: Vec<_> = merge_result
+                    .conflicts
+                    .iter()
+                    .filter_map(|c| {
+                        c.is_unresolved(TreatAsUnresolved::git())
+                            .then(|| c.theirs.location().to_owned())
+                    })
+                    .collect();
+                if !unresolved_conflicts.is_empty() {
+                    for change in changes.iter_mut().filter(|c| {
+                        c.as_ref()
+                            .ok()
+                            .is_some_and(|change| unresolved_conflicts.contains(&change.path))
+                    }) {
+                        into_err_spec(change, RejectionReason::CherryPickMergeConflict);
+                    }
+                    continue 'retry;
+                }
+                break 'retry (
+                    Some(merge_result.tree.write()?.detach()),
+                    Some(tree_with_changes),
+                );
+            };
+            let new_tree = new_tree.filter(|new_tree| *new_tree != tip_tree);
+            if new_tree.is_none() {
+                changes
+                    .iter_mut()
+                    .for_each(|c| into_err_spec(c, RejectionReason::NoEffectiveChanges));
+            }
+            (
+                new_tree,
+                changed_tree_pre_cherry_pick,
+                changes.into_iter().filter_map(Result::err).collect(),
+            )
+        }
+        BranchChanges::Patch(patch) => {
+            let files = patch::parse(&patch)?;
+            if files.is_empty() {
+                bail!("The patch didn't contain any changes");
+            }
+            let (new_tree, rejected_specs) = patch::apply(repo, tip_tree, files)?;
+            (new_tree, new_tree, rejected_specs)
+        }
+    };
+
+    let mut out = CreateCommitOutcome {
+        rejected_specs,
+        new_commit: None,
+        changed_tree_pre_cherry_pick: None,
+        references: Vec::new(),
+        rebase_output: None,
+        index: None,
+    };
+    let Some(new_tree) = new_tree else {
+        return Ok(out);
+    };
+    let (author, committer) = repo.commit_signatures()?;
+    let new_commit =
+        create_possibly_signed_commit(repo, author, committer, message, new_tree, [tip], None)?;
+    out.new_commit = Some(new_commit);
+    out.changed_tree_pre_cherry_pick = changed_tree_pre_cherry_pick;
+
+    let stack = vb.branches.values_mut().find(|stack| {
+        !stack.in_workspace && stack.heads.iter().any(|head| head.name() == short_name)
+    });
+    let Some(stack) = stack else {
+        repo.reference(
+            branch,
+            new_commit,
+            PreviousValue::MustExistAndMatch(tip.into()),
+            format!(
+                "commit: {title}",
+                title = message.lines().next().unwrap_or_default()
+            ),
+        )?;
+        out.references.push(UpdatedReference {
+            reference: but_core::Reference::Git(branch.to_owned()),
+            old_commit_id: tip,
+            new_commit_id: new_commit,
+        });
+        return Ok(out);
+    };
+
+    // Rebase all segments above `branch`, and move their heads along with the one of `branch`.
+    let heads_to_update: Vec<_> = stack
+        .heads
+        .iter_mut()
+        .skip_while(|head| head.name() != short_name)
+        .collect();
+    let stack_tip = match heads_to_update.last() {
+        Some(head) => head.head_oid(repo)?.to_gix(),
+        None => tip,
+    };
+    let mut commit_mapping = vec![(tip, new_commit)];
+    if stack_tip != tip {
+        let commits_to_rebase: Vec<_> = stack_tip
+            .attach(repo)
+            .ancestors()
+            .first_parent_only()
+            .with_hidden(Some(tip))
+            .all()?
+            .map(|info| info.map(|info| info.id))
+            .collect::<Result<_, _>>()?;
+        let mut builder = but_rebase::Rebase::new(repo, new_commit, Some(tip))?;
+        builder.steps(commits_to_rebase.into_iter().rev().map(|commit_id| {
+            but_rebase::RebaseStep::Pick {
+                commit_id,
+                new_message: None,
+                base: None,
+            }
+        }))?;
+        let rebase = builder.rebase()?;
+        commit_mapping.extend(
+            rebase
+                .commit_mapping
+                .iter()
+                .map(|(_base, old, new)| (*old, *new)),
+        );
+        out.rebase_output = Some(rebase);
+    }
+    let rewritten = |old: gix::ObjectId| {
+        commit_mapping
+            .iter()
+            .find_map(|(old_id, new_id)| (*old_id == old).then_some(*new_id))
+    };
+    for head in heads_to_update {
+        let old = head.head_oid(repo)?.to_gix();
+        let Some(new) = rewritten(old) else {
+            continue;
+        };
+        head.set_head(CommitOrChangeId::CommitId(new.to_string()), repo)?;
+        out.references.push(UpdatedReference {
+            reference: but_core::Reference::Git(head.full_name()?),
+            old_commit_id: old,
+            new_commit_id: new,
+        });
+    }
+    // The stack is unapplied, so its head and tree are the ones of its top-most segment.
+    if let Some(new_stack_tip) = rewritten(stack_tip) {
+        let new_stack_tree = but_core::Commit::from_id(new_stack_tip.attach(repo))?
+            .tree_id()?
+            .detach();
+        stack.set_stack_head_without_persisting(
+            repo,
+            new_stack_tip.to_git2(),
+            Some(new_stack_tree.to_git2()),
+        )?;
+    }
+    Ok(out)
+}
+
+/// Like [`create_commit_on_branch()`], but integrates with an existing GitButler `project`.
+/// Note that virtual branches will be updated and written back after this call.
+pub fn create_commit_on_branch_with_project(
+    repo: &gix::Repository,
+    project: &gitbutler_project::Project,
+    branch: &gix::refs::FullNameRef,
+    message: &str,
+    changes: BranchChanges,
+    context_lines: u32,
+    _perm: &mut WorktreeWritePermission,
+) -> anyhow::Result<CreateCommitOutcome> {
+    let vbh = VirtualBranchesHandle::new(project.gb_dir());
+    let mut vb = vbh.read_file()?;
+    let out = create_commit_on_branch(repo, &mut vb, branch, message, changes, context_lines)?;
+
+    vbh.write_file(&vb)?;
+    Ok(out)
+}
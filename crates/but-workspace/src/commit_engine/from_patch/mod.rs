@@ -0,0 +1,259 @@
+use crate::commit_engine::reference_frame::InferenceMode;
+use crate::commit_engine::{
+    CreateCommitOutcome, Destination, ReferenceFrame, commit_tree_to_destination,
+    destination_parents, update_refs_and_index,
+};
+use crate::discard::file::{
+    RestoreMode, index::mark_entry_for_deletion, restore_state_to_worktree,
+};
+use anyhow::{Context, bail};
+use bstr::{BStr, ByteSlice};
+use but_core::TreeStatus;
+use gitbutler_project::access::WorktreeWritePermission;
+use gitbutler_stack::{StackId, VirtualBranchesHandle, VirtualBranchesState};
+use gix::prelude::ObjectIdExt as _;
+
+pub(crate) mod patch;
+
+/// Alter the single `destination` with all changes in `patch`, a unified diff as produced by `git diff` or
+/// `git format-patch`, and write new objects into `repo`, but only if the commit succeeds.
+///
+/// The patch is applied to the tree of the parent of the new commit, or to the tree of the commit to amend.
+/// Hunks are applied where they are expected, or where their context lines match if the file changed since
+/// the patch was created. Hunks that can't be placed are returned as [rejected specs](CreateCommitOutcome::rejected_specs)
+/// with the hunk headers of the patch, while all other hunks are still committed.
+///
+/// Note that no [`index`](CreateCommitOutcome::index) is produced here as the `HEAD` isn't queried and doesn't play a role.
+///
+/// No reference is touched in the process.
+pub fn create_commit_from_patch(
+    repo: &gix::Repository,
+    destination: Destination,
+    patch: &[u8],
+) -> anyhow::Result<CreateCommitOutcome> {
+    let files = patch::parse(patch)?;
+    create_commit_from_files(repo, destination, files)
+}
+
+fn create_commit_from_files(
+    repo: &gix::Repository,
+    destination: Destination,
+    files: Vec<patch::FilePatch>,
+) -> anyhow::Result<CreateCommitOutcome> {
+    if files.is_empty() {
+        bail!("The patch didn't contain any changes");
+    }
+    let parents = destination_parents(repo, &destination)?;
+    let base_commit = match &destination {
+        Destination::NewCommit {
+            parent_commit_id, ..
+        } => *parent_commit_id,
+        Destination::AmendCommit(commit_id) => Some(*commit_id),
+    };
+    let base_tree = match base_commit {
+        None => repo.empty_tree().id,
+        Some(commit_id) => {
+            let commit = but_core::Commit::from_id(commit_id.attach(repo))?;
+            if commit.is_conflicted() {
+                bail!("Cannot apply a patch to commit {commit_id} as it is conflicted");
+            }
+            commit.tree_id()?.detach()
+        }
+    };
+
+    let (new_tree, rejected_specs) = patch::apply(repo, base_tree, files)?;
+    let new_commit = new_tree
+        .map(|new_tree| commit_tree_to_destination(repo, destination, parents, new_tree))
+        .transpose()?;
+    Ok(CreateCommitOutcome {
+        rejected_specs,
+        new_commit,
+        changed_tree_pre_cherry_pick: new_tree,
+        references: Vec::new(),
+        rebase_output: None,
+        index: None,
+    })
+}
+
+/// Like [`create_commit_from_patch()`], but allows to also update virtual branches and git references pointing to commits
+/// after rebasing all descendants, along with re-merging possible workspace merge commits.
+///
+/// `frame` and `vb` are used exactly like in [`create_commit_and_update_refs()`](crate::commit_engine::create_commit_and_update_refs()),
+/// with the stack segment of a [new commit](Destination::NewCommit) identifying the branch the commit goes into.
+///
+/// If the stack that receives the commit is applied to the workspace, the changes of the patch are also written to the worktree
+/// and the index, so they don't show up as uncommitted changes. As this would overwrite them, it's an error if any of the files
+/// touched by the patch have uncommitted changes in that case.
+/// If the stack isn't applied, the worktree and index remain untouched.
+pub fn create_commit_from_patch_and_update_refs(
+    repo: &gix::Repository,
+    frame: ReferenceFrame,
+    vb: &mut VirtualBranchesState,
+    destination: Destination,
+    patch: &[u8],
+) -> anyhow::Result<CreateCommitOutcome> {
+    let files = patch::parse(patch)?;
+    if is_in_workspace(repo, vb, &destination) {
+        let worktree_changes = but_core::diff::worktree_changes(repo)?.changes;
+        let touched_paths = files
+            .iter()
+            .flat_map(|file| [file.previous_path.as_ref(), file.path.as_ref()])
+            .flatten();
+        for path in touched_paths {
+            if worktree_changes.iter().any(|change| {
+                change.path == *path || change.previous_path() == Some(path.as_bstr())
+            }) {
+                bail!(
+                    "Cannot apply patch as '{path}' has uncommitted changes in the worktree that would be overwritten"
+                );
+            }
+        }
+    }
+
+    let mut out = create_commit_from_files(repo, destination.clone(), files)?;
+    update_refs_and_index(repo, frame, vb, &destination, &mut out, true)?;
+    Ok(out)
+}
+
+/// Like [`create_commit_from_patch_and_update_refs()`], but integrates with an existing GitButler `project`
+/// if present. Alternatively it uses the current `HEAD` as only reference point.
+/// Note that virtual branches will be updated and written back after this call.
+pub fn create_commit_from_patch_and_update_refs_with_project(
+    repo: &gix::Repository,
+    project: &gitbutler_project::Project,
+    maybe_stackid: Option<StackId>,
+    destination: Destination,
+    patch: &[u8],
+    _perm: &mut WorktreeWritePermission,
+) -> anyhow::Result<CreateCommitOutcome> {
+    let vbh = VirtualBranchesHandle::new(project.gb_dir());
+    let mut vb = vbh.read_file()?;
+    let stack_id = maybe_stackid.or_else(|| {
+        destination
+            .stack_segment()
+            .map(|stack_segment| stack_segment.stack_id)
+    });
+    let mut frame = match stack_id {
+        None => {
+            let maybe_commit_id = match &destination {
+                Destination::NewCommit {
+                    parent_commit_id, ..
+                } => *parent_commit_id,
+                Destination::AmendCommit(commit_id) => Some(*commit_id),
+            };
+            match maybe_commit_id {
+                None => ReferenceFrame::default(),
+                Some(commit_id) => {
+                    ReferenceFrame::infer(repo, &vb, InferenceMode::CommitIdInStack(commit_id))?
+                }
+            }
+        }
+        Some(stack_id) => ReferenceFrame::infer(repo, &vb, InferenceMode::StackId(stack_id))?,
+    };
+    if !is_in_workspace(repo, &vb, &destination) {
+        // The workspace commit must not be rebased onto stacks that aren't part of it.
+        frame.workspace_tip = None;
+    }
+    let out = create_commit_from_patch_and_update_refs(repo, frame, &mut vb, destination, patch)?;
+
+    vbh.write_file(&vb)?;
+    Ok(out)
+}
+
+/// Return `true` if the commit created for `destination` will be visible at `HEAD`, so that the worktree
+/// has to be updated with its changes.
+fn is_in_workspace(
+    repo: &gix::Repository,
+    vb: &VirtualBranchesState,
+    destination: &Destination,
+) -> bool {
+    if let Some(stack_segment) = destination.stack_segment() {
+        if let Some(stack) = vb.branches.get(&stack_segment.stack_id) {
+            return stack.in_workspace;
+        }
+    }
+    let commit_in_graph = match destination {
+        Destination::NewCommit {
+            parent_commit_id, ..
+        } => *parent_commit_id,
+        Destination::AmendCommit(commit_id) => Some(*commit_id),
+    };
+    let (Some(commit_in_graph), Some(head_id)) = (commit_in_graph, repo.head_id().ok()) else {
+        // An unborn `HEAD` will point to the new commit.
+        return true;
+    };
+    head_id == commit_in_graph
+        || repo
+            .merge_base(commit_in_graph, head_id)
+            .is_ok_and(|base| base == commit_in_graph)
+}
+
+/// Write all changes that turn `previous_tree` into `tree` to the worktree of `repo`, and update `index` to match.
+pub(super) fn checkout_tree_changes(
+    repo: &gix::Repository,
+    previous_tree: gix::ObjectId,
+    tree: gix::ObjectId,
+    index: &mut gix::index::State,
+) -> anyhow::Result<()> {
+    let (mut pipeline, _) = repo.filter_pipeline(Some(repo.empty_tree().id))?;
+    let mut path_check = gix::status::plumbing::SymlinkCheck::new(
+        repo.workdir().context("non-bare repository")?.into(),
+    );
+    let mut num_sorted_entries = index.entries().len();
+    for change in but_core::diff::tree_changes(repo, Some(previous_tree), tree)? {
+        let (state, mode) = match change.status {
+            TreeStatus::Deletion { .. } => {
+                remove_from_worktree(
+                    index,
+                    change.path.as_bstr(),
+                    &mut path_check,
+                    num_sorted_entries,
+                )?;
+                continue;
+            }
+            TreeStatus::Addition { state, .. } => (state, RestoreMode::Deleted),
+            TreeStatus::Modification { state, .. } => (state, RestoreMode::Update),
+            TreeStatus::Rename {
+                ref previous_path,
+                state,
+                ..
+            } => {
+                remove_from_worktree(
+                    index,
+                    previous_path.as_bstr(),
+                    &mut path_check,
+                    num_sorted_entries,
+                )?;
+                (state, RestoreMode::Deleted)
+            }
+        };
+        restore_state_to_worktree(
+            &mut pipeline,
+            index,
+            change.path.as_bstr(),
+            state,
+            mode,
+            &mut path_check,
+            &mut num_sorted_entries,
+        )?;
+    }
+    index.sort_entries();
+    Ok(())
+}
+
+fn remove_from_worktree(
+    index: &mut gix::index::State,
+    rela_path: &BStr,
+    path_check: &mut gix::status::plumbing::SymlinkCheck,
+    num_sorted_entries: usize,
+) -> anyhow::Result<()> {
+    mark_entry_for_deletion(index, rela_path, num_sorted_entries);
+    std::fs::remove_file(path_check.verified_path(rela_path)?).or_else(|err| {
+        if err.kind() == std::io::ErrorKind::NotFound {
+            Ok(())
+        } else {
+            Err(err)
+        }
+    })?;
+    Ok(())
+}
@@ -0,0 +1,425 @@
+//! Parse unified diffs as produced by `git diff` or `git format-patch`, and apply them to trees.
+use crate::commit_engine::{DiffSpec, HunkHeader, RejectionReason};
+use anyhow::{Context, bail};
+use bstr::{BStr, BString, ByteSlice};
+use gix::object::tree::EntryKind;
+use gix::prelude::ObjectIdExt;
+
+/// The change to a single file as described by a unified diff.
+#[derive(Debug, Default)]
+pub(crate) struct FilePatch {
+    /// The path of the file before the change, or `None` if the file is added.
+    pub previous_path: Option<BString>,
+    /// The path of the file after the change, or `None` if the file is deleted.
+    pub path: Option<BString>,
+    /// The kind of the file after the change, if the patch specifies it.
+    pub new_kind: Option<EntryKind>,
+    /// If `true`, the patch contains binary changes that we can't apply.
+    pub is_binary: bool,
+    /// The hunks to apply, from the top to the bottom of the file.
+    pub hunks: Vec<Hunk>,
+}
+
+/// A single hunk of a [`FilePatch`].
+#[derive(Debug)]
+pub(crate) struct Hunk {
+    pub header: HunkHeader,
+    /// The lines the hunk replaces, including their line terminator if they have one.
+    old: Vec<BString>,
+    /// The lines the hunk replaces the `old` lines with, including their line terminator if they have one.
+    new: Vec<BString>,
+}
+
+impl FilePatch {
+    /// Return the specification of the change described by this patch, limited to `hunk_headers`.
+    pub fn to_spec(&self, hunk_headers: Vec<HunkHeader>) -> DiffSpec {
+        let path = self
+            .path
+            .clone()
+            .or_else(|| self.previous_path.clone())
+            .unwrap_or_default();
+        DiffSpec {
+            previous_path: self
+                .previous_path
+                .clone()
+                .filter(|previous_path| self.path.is_some() && *previous_path != path),
+            path,
+            hunk_headers,
+        }
+    }
+
+    fn hunk_headers(&self) -> Vec<HunkHeader> {
+        self.hunks.iter().map(|hunk| hunk.header).collect()
+    }
+}
+
+/// Parse `patch` into a patch for each file it changes.
+///
+/// Everything that isn't part of a file patch, like the headers of emails produced by `git format-patch`, is ignored.
+pub(crate) fn parse(patch: &[u8]) -> anyhow::Result<Vec<FilePatch>> {
+    let mut files = Vec::new();
+    let mut current: Option<FilePatch> = None;
+    let mut lines = patch.lines_with_terminator().peekable();
+    while let Some(line) = lines.next() {
+        if let Some(paths) = line.strip_prefix(b"diff --git ") {
+            files.extend(current.take());
+            let (previous_path, path) = parse_git_header_paths(paths)?;
+            current = Some(FilePatch {
+                previous_path: Some(previous_path),
+                path: Some(path),
+                ..Default::default()
+            });
+            continue;
+        }
+        if let Some(previous_path) = line.strip_prefix(b"--- ") {
+            if let Some(path) = lines.peek().and_then(|line| line.strip_prefix(b"+++ ")) {
+                let previous_path = parse_path(previous_path, b"a/")?;
+                let path = parse_path(path, b"b/")?;
+                lines.next();
+                match current.as_mut() {
+                    Some(file) if file.hunks.is_empty() => {
+                        file.previous_path = previous_path;
+                        file.path = path;
+                    }
+                    _ => {
+                        files.extend(current.take());
+                        current = Some(FilePatch {
+                            previous_path,
+                            path,
+                            ..Default::default()
+                        });
+                    }
+                }
+                continue;
+            }
+        }
+
+        let Some(file) = current.as_mut() else {
+            continue;
+        };
+        if line.starts_with(b"@@ ") {
+            let header = parse_hunk_header(line)?;
+            file.hunks.push(parse_hunk(header, &mut lines)?);
+        } else if let Some(mode) = line.strip_prefix(b"new file mode ") {
+            file.previous_path = None;
+            file.new_kind = Some(parse_mode(mode)?);
+        } else if line.starts_with(b"deleted file mode ") {
+            file.path = None;
+        } else if let Some(mode) = line.strip_prefix(b"new mode ") {
+            file.new_kind = Some(parse_mode(mode)?);
+        } else if let Some(path) = line.strip_prefix(b"rename from ") {
+            file.previous_path = Some(parse_path(path, b"")?.context("invalid rename source")?);
+        } else if let Some(path) = line.strip_prefix(b"rename to ") {
+            file.path = Some(parse_path(path, b"")?.context("invalid rename destination")?);
+        } else if line.starts_with(b"Binary files ") || line.starts_with(b"GIT binary patch") {
+            file.is_binary = true;
+        }
+    }
+    files.extend(current);
+    Ok(files)
+}
+
+/// Apply `files` to `base_tree` and return the new tree, or `None` if nothing changed, along with all changes that
+/// couldn't be applied.
+///
+/// Hunks are applied where they are expected, or where their context lines match if the file changed in the meantime.
+/// Hunks that can't be placed are rejected, while the other hunks of the same file are still applied.
+pub(crate) fn apply(
+    repo: &gix::Repository,
+    base_tree: gix::ObjectId,
+    files: Vec<FilePatch>,
+) -> anyhow::Result<(Option<gix::ObjectId>, Vec<(RejectionReason, DiffSpec)>)> {
+    let tree = base_tree.attach(repo).object()?.peel_to_tree()?;
+    let mut editor = tree.edit()?;
+    let mut rejected = Vec::new();
+    for file in files {
+        if file.is_binary {
+            rejected.push((
+                RejectionReason::FileToLargeOrBinary,
+                file.to_spec(file.hunk_headers()),
+            ));
+            continue;
+        }
+        let previous = match &file.previous_path {
+            None => None,
+            Some(previous_path) => match tree.lookup_entry(previous_path.split(|b| *b == b'/'))? {
+                Some(entry) => Some((entry.mode().kind(), entry.object_id())),
+                None => {
+                    rejected.push((
+                        RejectionReason::PathNotFoundInBaseTree,
+                        file.to_spec(file.hunk_headers()),
+                    ));
+                    continue;
+                }
+            },
+        };
+        let old_data = match previous {
+            None => {
+                let is_present = match &file.path {
+                    Some(path) => tree.lookup_entry(path.split(|b| *b == b'/'))?.is_some(),
+                    None => false,
+                };
+                if is_present {
+                    rejected.push((
+                        RejectionReason::PatchDoesNotApply,
+                        file.to_spec(file.hunk_headers()),
+                    ));
+                    continue;
+                }
+                Vec::new()
+            }
+            Some((EntryKind::Tree | EntryKind::Commit, _)) => {
+                rejected.push((
+                    RejectionReason::UnsupportedTreeEntry,
+                    file.to_spec(file.hunk_headers()),
+                ));
+                continue;
+            }
+            Some((_, id)) => repo.find_blob(id)?.detach().data,
+        };
+
+        let (new_data, rejected_hunks) = apply_hunks(old_data.as_bstr(), &file.hunks);
+        let all_rejected = !file.hunks.is_empty() && rejected_hunks.len() == file.hunks.len();
+        match &file.path {
+            None => {
+                if !rejected_hunks.is_empty() {
+                    // Deleting a file that doesn't match exactly would lose changes.
+                    rejected.push((
+                        RejectionReason::PatchDoesNotApply,
+                        file.to_spec(file.hunk_headers()),
+                    ));
+                    continue;
+                }
+                let previous_path = file
+                    .previous_path
+                    .as_ref()
+                    .expect("deletions have a source");
+                editor.remove(previous_path.as_bstr())?;
+            }
+            Some(path) => {
+                if !rejected_hunks.is_empty() {
+                    rejected.push((
+                        RejectionReason::PatchDoesNotApply,
+                        file.to_spec(rejected_hunks),
+                    ));
+                }
+                if all_rejected {
+                    continue;
+                }
+                if let Some(previous_path) = file.previous_path.as_ref().filter(|p| *p != path) {
+                    editor.remove(previous_path.as_bstr())?;
+                }
+                let kind = file
+                    .new_kind
+                    .or(previous.map(|(kind, _)| kind))
+                    .unwrap_or(EntryKind::Blob);
+                let id = match previous {
+                    Some((_, id)) if file.hunks.is_empty() => id,
+                    _ => repo.write_blob(new_data.as_slice())?.detach(),
+                };
+                editor.upsert(path.as_bstr(), kind, id)?;
+            }
+        }
+    }
+    let new_tree = editor.write()?.detach();
+    Ok(((new_tree != base_tree).then_some(new_tree), rejected))
+}
+
+/// Apply `hunks` to `old_image`, and return the new image along with the headers of the hunks that couldn't be applied.
+fn apply_hunks(old_image: &BStr, hunks: &[Hunk]) -> (BString, Vec<HunkHeader>) {
+    let mut lines: Vec<BString> = old_image
+        .lines_with_terminator()
+        .map(|line| line.into())
+        .collect();
+    let mut rejected = Vec::new();
+    // The difference between where hunks were expected and where they were placed, in lines.
+    let mut offset = 0isize;
+    // Hunks must not be placed before the previous one.
+    let mut min_pos = 0;
+    for hunk in hunks {
+        // An empty old range denotes the line after which to insert.
+        let expected_pos = if hunk.header.old_lines == 0 {
+            hunk.header.old_start as isize
+        } else {
+            hunk.header.old_start as isize - 1
+        };
+        let Some(pos) = find_hunk_position(
+            &lines,
+            &hunk.old,
+            (expected_pos + offset).max(0) as usize,
+            min_pos,
+        ) else {
+            rejected.push(hunk.header);
+            continue;
+        };
+        lines.splice(pos..pos + hunk.old.len(), hunk.new.iter().cloned());
+        offset = pos as isize - expected_pos + hunk.new.len() as isize - hunk.old.len() as isize;
+        min_pos = pos + hunk.new.len();
+    }
+    let mut new_image = BString::default();
+    for line in lines {
+        new_image.extend_from_slice(&line);
+    }
+    (new_image, rejected)
+}
+
+/// Find the position closest to `expected_pos` at which `old` matches `lines`, at or after `min_pos`.
+fn find_hunk_position(
+    lines: &[BString],
+    old: &[BString],
+    expected_pos: usize,
+    min_pos: usize,
+) -> Option<usize> {
+    let max_pos = lines.len().checked_sub(old.len())?;
+    let matches_at = |pos: usize| {
+        (min_pos..=max_pos).contains(&pos) && lines.get(pos..pos + old.len()) == Some(old)
+    };
+    (0..=lines.len()).find_map(|distance| {
+        let after = expected_pos + distance;
+        let before = expected_pos.checked_sub(distance);
+        if matches_at(after) {
+            Some(after)
+        } else {
+            before.filter(|pos| matches_at(*pos))
+        }
+    })
+}
+
+/// Parse the lines of a hunk with `header` from `lines`.
+fn parse_hunk<'a>(
+    header: HunkHeader,
+    lines: &mut std::iter::Peekable<impl Iterator<Item = &'a [u8]>>,
+) -> anyhow::Result<Hunk> {
+    let mut old = Vec::<BString>::new();
+    let mut new = Vec::<BString>::new();
+    let (mut old_remaining, mut new_remaining) = (header.old_lines, header.new_lines);
+    let mut last_kind = b' ';
+    loop {
+        // A marker for a missing newline refers to the previous line.
+        if let Some(b'\\') = lines.peek().and_then(|line| line.first()) {
+            lines.next();
+            let trim_newline = |lines: &mut Vec<BString>| {
+                if let Some(line) = lines.last_mut() {
+                    let len = trim_newline(line).len();
+                    line.truncate(len);
+                }
+            };
+            match last_kind {
+                b'-' => trim_newline(&mut old),
+                b'+' => trim_newline(&mut new),
+                _ => {
+                    trim_newline(&mut old);
+                    trim_newline(&mut new);
+                }
+            }
+            continue;
+        }
+        if old_remaining == 0 && new_remaining == 0 {
+            break;
+        }
+        let line = lines
+            .next()
+            .with_context(|| format!("The patch ended within the hunk {header:?}"))?;
+        let (kind, content) = match line.split_first() {
+            // Some tools strip the trailing whitespace of empty context lines.
+            None | Some((b'\n' | b'\r', _)) => (b' ', line),
+            Some((kind, content)) => (*kind, content),
+        };
+        match kind {
+            b' ' if old_remaining > 0 && new_remaining > 0 => {
+                old.push(content.into());
+                new.push(content.into());
+                old_remaining -= 1;
+                new_remaining -= 1;
+            }
+            b'-' if old_remaining > 0 => {
+                old.push(content.into());
+                old_remaining -= 1;
+            }
+            b'+' if new_remaining > 0 => {
+                new.push(content.into());
+                new_remaining -= 1;
+            }
+            _ => bail!(
+                "Unexpected line in hunk {header:?}: {line:?}",
+                line = line.as_bstr()
+            ),
+        }
+        last_kind = kind;
+    }
+    Ok(Hunk { header, old, new })
+}
+
+/// Parse a line like `@@ -1,2 +1,3 @@ optional context`.
+fn parse_hunk_header(line: &[u8]) -> anyhow::Result<HunkHeader> {
+    let invalid = || format!("Invalid hunk header: {:?}", line.as_bstr());
+    let mut tokens = line
+        .strip_prefix(b"@@ ")
+        .with_context(invalid)?
+        .splitn_str(3, " ");
+    let old = tokens
+        .next()
+        .and_then(|range| range.strip_prefix(b"-"))
+        .with_context(invalid)?;
+    let new = tokens
+        .next()
+        .and_then(|range| range.strip_prefix(b"+"))
+        .with_context(invalid)?;
+    let parse_range = |range: &[u8]| -> Option<(u32, u32)> {
+        let range = range.to_str().ok()?;
+        Some(match range.split_once(',') {
+            Some((start, lines)) => (start.parse().ok()?, lines.parse().ok()?),
+            None => (range.parse().ok()?, 1),
+        })
+    };
+    let (old_start, old_lines) = parse_range(old).with_context(invalid)?;
+    let (new_start, new_lines) = parse_range(new).with_context(invalid)?;
+    Ok(HunkHeader {
+        old_start,
+        old_lines,
+        new_start,
+        new_lines,
+    })
+}
+
+/// Parse the paths of a `diff --git a/<previous> b/<path>` line, which are only used if the patch doesn't otherwise
+/// name the paths, as it's ambiguous if paths contain spaces.
+fn parse_git_header_paths(paths: &[u8]) -> anyhow::Result<(BString, BString)> {
+    let paths = trim_newline(paths);
+    let pos = paths
+        .rfind(" b/")
+        .with_context(|| format!("Invalid diff header: {:?}", paths.as_bstr()))?;
+    let (previous_path, path) = paths.split_at(pos);
+    let previous_path = previous_path.strip_prefix(b"a/").unwrap_or(previous_path);
+    let path = path.strip_prefix(b" b/").unwrap_or(path);
+    Ok((previous_path.into(), path.into()))
+}
+
+/// Parse the path in `---` and `+++` lines, or `None` for `/dev/null`.
+fn parse_path(path: &[u8], prefix: &[u8]) -> anyhow::Result<Option<BString>> {
+    let path = trim_newline(path);
+    // Plain diffs may follow the path with a tab and a timestamp, and Git adds a tab to paths with spaces.
+    let path = path.split_str("\t").next().unwrap_or(path);
+    if path == b"/dev/null" {
+        return Ok(None);
+    }
+    if path.starts_with(b"\"") {
+        bail!("Quoted paths aren't supported yet: {:?}", path.as_bstr());
+    }
+    Ok(Some(path.strip_prefix(prefix).unwrap_or(path).into()))
+}
+
+fn parse_mode(mode: &[u8]) -> anyhow::Result<EntryKind> {
+    Ok(match trim_newline(mode) {
+        b"100644" => EntryKind::Blob,
+        b"100755" => EntryKind::BlobExecutable,
+        b"120000" => EntryKind::Link,
+        mode => bail!("Unsupported file mode in patch: {:?}", mode.as_bstr()),
+    })
+}
+
+fn trim_newline(line: &[u8]) -> &[u8] {
+    line.strip_suffix(b"\n")
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .unwrap_or(line)
+}
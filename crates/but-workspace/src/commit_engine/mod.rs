@@ -23,6 +23,21 @@ mod refs;
 mod hunks;
 pub use hunks::apply_hunks;
 
+mod branch;
+pub use branch::{BranchChanges, create_commit_on_branch, create_commit_on_branch_with_project};
+
+pub(crate) mod from_patch;
+pub use from_patch::{
+    create_commit_from_patch, create_commit_from_patch_and_update_refs,
+    create_commit_from_patch_and_update_refs_with_project,
+};
+
+mod split;
+pub use split::{
+    SplitCommitOutcome, SplitPiece, split_commit, split_commit_and_update_refs,
+    split_commit_and_update_refs_with_project,
+};
+
 /// Types for use in the frontend with serialization support.
 pub mod ui;
 
@@ -186,6 +201,9 @@ pub enum RejectionReason {
     /// The DiffSpec points to an actual change, or a subset of that change using a file path and optionally hunks into that file.
     /// However, at least one hunk was not fully contained..
     MissingDiffSpecAssociation,
+    /// A hunk of a patch couldn't be applied as its context and removed lines didn't match the file it should apply to,
+    /// or a file to be added by a patch already existed.
+    PatchDoesNotApply,
 }
 
 /// Alter the single `destination` in a given `frame` with as many `changes` as possible and write new objects into `repo`,
@@ -225,7 +243,31 @@ pub fn create_commit(
     changes: Vec<DiffSpec>,
     context_lines: u32,
 ) -> anyhow::Result<CreateCommitOutcome> {
-    let parents = match &destination {
+    let parents = destination_parents(repo, &destination)?;
+    let CreateTreeOutcome {
+        rejected_specs,
+        destination_tree,
+        changed_tree_pre_cherry_pick,
+    } = create_tree(repo, &destination, move_source, changes, context_lines)?;
+    let new_commit = destination_tree
+        .map(|new_tree| commit_tree_to_destination(repo, destination, parents, new_tree))
+        .transpose()?;
+    Ok(CreateCommitOutcome {
+        rejected_specs,
+        new_commit,
+        changed_tree_pre_cherry_pick,
+        references: Vec::new(),
+        rebase_output: None,
+        index: None,
+    })
+}
+
+/// Return the parents of the commit that will be created for `destination`.
+fn destination_parents(
+    repo: &gix::Repository,
+    destination: &Destination,
+) -> anyhow::Result<Vec<gix::ObjectId>> {
+    let parents = match destination {
         Destination::NewCommit {
             parent_commit_id: None,
             ..
@@ -246,51 +288,39 @@ pub fn create_commit(
     if !matches!(destination, Destination::AmendCommit(_)) && parents.len() > 1 {
         bail!("cannot currently handle more than 1 parent")
     }
+    Ok(parents)
+}
 
-    let CreateTreeOutcome {
-        rejected_specs,
-        destination_tree,
-        changed_tree_pre_cherry_pick,
-    } = create_tree(repo, &destination, move_source, changes, context_lines)?;
-    let new_commit = if let Some(new_tree) = destination_tree {
-        match destination {
-            Destination::NewCommit {
-                message,
-                parent_commit_id: _,
-                stack_segment: _,
-            } => {
-                let (author, committer) = repo.commit_signatures()?;
-                let new_commit = create_possibly_signed_commit(
-                    repo, author, committer, &message, new_tree, parents, None,
-                )?;
-                Some(new_commit)
-            }
-            Destination::AmendCommit(commit_id) => {
-                let mut commit = commit_id
-                    .attach(repo)
-                    .object()?
-                    .peel_to_commit()?
-                    .decode()?
-                    .to_owned();
-                commit.tree = new_tree;
-                Some(but_rebase::commit::create(
-                    repo,
-                    commit,
-                    CommitterMode::Update,
-                )?)
-            }
+/// Create a new commit with `new_tree` and `parents` for `destination`, either as new commit, or as the
+/// amended version of an existing one.
+fn commit_tree_to_destination(
+    repo: &gix::Repository,
+    destination: Destination,
+    parents: Vec<gix::ObjectId>,
+    new_tree: gix::ObjectId,
+) -> anyhow::Result<gix::ObjectId> {
+    match destination {
+        Destination::NewCommit {
+            message,
+            parent_commit_id: _,
+            stack_segment: _,
+        } => {
+            let (author, committer) = repo.commit_signatures()?;
+            create_possibly_signed_commit(
+                repo, author, committer, &message, new_tree, parents, None,
+            )
         }
-    } else {
-        None
-    };
-    Ok(CreateCommitOutcome {
-        rejected_specs,
-        new_commit,
-        changed_tree_pre_cherry_pick,
-        references: Vec::new(),
-        rebase_output: None,
-        index: None,
-    })
+        Destination::AmendCommit(commit_id) => {
+            let mut commit = commit_id
+                .attach(repo)
+                .object()?
+                .peel_to_commit()?
+                .decode()?
+                .to_owned();
+            commit.tree = new_tree;
+            but_rebase::commit::create(repo, commit, CommitterMode::Update)
+        }
+    }
 }
 
 /// All information to know where in the commit-graph the rewritten commit is located to figure out
@@ -343,121 +373,47 @@ pub fn create_commit_and_update_refs(
         context_lines,
     )?;
 
+    update_refs_and_index(repo, frame, vb, &destination, &mut out, false)?;
+    Ok(out)
+}
+
+/// Rewrite all descendants and references after `out.new_commit` was created for `destination`, and
+/// produce an index that matches the tree at `HEAD` which is also written to disk.
+///
+/// If `checkout_changes` is `true`, changes between the previous and the new tree at `HEAD` will also be written
+/// to the worktree, which is needed if they didn't originate there.
+fn update_refs_and_index(
+    repo: &gix::Repository,
+    frame: ReferenceFrame,
+    vb: &mut VirtualBranchesState,
+    destination: &Destination,
+    out: &mut CreateCommitOutcome,
+    checkout_changes: bool,
+) -> anyhow::Result<()> {
     let Some(new_commit) = out.new_commit else {
-        return Ok(out);
+        return Ok(());
     };
+    let previous_head_tree = repo.head_tree_id_or_empty()?.detach();
 
     let commit_to_find = match destination {
         Destination::NewCommit {
             parent_commit_id, ..
-        } => parent_commit_id,
-        Destination::AmendCommit(commit) => Some(commit),
+        } => *parent_commit_id,
+        Destination::AmendCommit(commit) => Some(*commit),
     };
 
-    if let Some(commit_in_graph) = commit_to_find {
-        let mut all_refs_by_id = gix::hashtable::HashMap::<_, Vec<_>>::default();
-        for (commit_id, git_reference) in repo
-            .references()?
-            .prefixed("refs/heads/")?
-            .chain(repo.references()?.prefixed("refs/gitbutler/")?)
-            .filter_map(Result::ok)
-            .filter_map(|r| r.try_id().map(|id| (id.detach(), r.inner.name)))
-        {
-            all_refs_by_id
-                .entry(commit_id)
-                .or_default()
-                .push(git_reference);
-        }
-
-        // Special case: commit/amend on top of `HEAD` and no merge above: no rebase necessary
-        if frame.workspace_tip.is_none()
-            && repo.head_id().ok().map(|id| id.detach()) == Some(commit_in_graph)
-            && all_refs_by_id.contains_key(&commit_in_graph)
-        {
-            refs::rewrite(
-                repo,
-                vb,
-                all_refs_by_id,
-                [(commit_in_graph, new_commit)],
-                &mut out.references,
-                destination.stack_segment(),
-            )?;
-        } else {
-            let Some(branch_tip) = frame.branch_tip else {
-                bail!(
-                    "HEAD isn't connected to the affected commit and a rebase is necessary, but no branch tip was provided"
-                );
-            };
-            // Use the branch tip to find all commits leading up to the one that was affected
-            // - these are the commits to rebase.
-            let mut found_marker = false;
-            let commits_to_rebase: Vec<_> = branch_tip
-                .attach(repo)
-                .ancestors()
-                .first_parent_only()
-                .all()?
-                .filter_map(Result::ok)
-                .take_while(|info| {
-                    if info.id == commit_in_graph {
-                        found_marker = true;
-                        false
-                    } else {
-                        true
-                    }
-                })
-                .map(|info| info.id)
-                .collect();
-            if !found_marker {
-                bail!(
-                    "Branch tip at {branch_tip} didn't contain the affected commit {commit_in_graph} - cannot rebase"
-                );
-            }
-
-            let workspace_tip = frame
-                .workspace_tip
-                .filter(|tip| !commits_to_rebase.contains(tip));
-            let rebase = {
-                // Set commits leading up to the tip on top of the new commit, serving as base.
-                let mut builder = but_rebase::Rebase::new(repo, new_commit, Some(commit_in_graph))?;
-                builder.steps(commits_to_rebase.into_iter().rev().map(|commit_id| {
-                    but_rebase::RebaseStep::Pick {
-                        commit_id,
-                        new_message: None,
-                    }
-                }))?;
-                if let Some(workspace_tip) = workspace_tip {
-                    // We can assume the workspace tip is connected to a pick (or else the rebase will fail)
-                    builder.steps([but_rebase::RebaseStep::Pick {
-                        commit_id: workspace_tip,
-                        new_message: None,
-                    }])?;
-                }
-                builder.rebase()?
-            };
-            refs::rewrite(
-                repo,
-                vb,
-                all_refs_by_id,
-                rebase
-                    .commit_mapping
-                    .iter()
-                    .map(|(_base, old, new)| (*old, *new))
-                    .chain(Some((commit_in_graph, new_commit))),
-                &mut out.references,
-                destination.stack_segment(),
-            )?;
-            out.rebase_output = Some(rebase);
-        }
-        // Assume an index to be present and adjust it to match the new tree.
-
-        let tree_index = repo.index_from_tree(&repo.head_tree_id()?)?;
-        let mut disk_index = repo.open_index()?;
-        index::apply_lhs_to_rhs(
-            repo.workdir().expect("non-bare"),
-            &tree_index,
-            &mut disk_index,
+    let mut index = if let Some(commit_in_graph) = commit_to_find {
+        out.rebase_output = rewrite_descendants_and_refs(
+            repo,
+            frame,
+            vb,
+            commit_in_graph,
+            new_commit,
+            &mut out.references,
+            destination.stack_segment(),
         )?;
-        out.index = disk_index.into();
+        // Assume an index to be present and adjust it to match the new tree.
+        repo.open_index()?
     } else {
         // unborn branch special case.
         repo.reference(
@@ -476,13 +432,20 @@ pub fn create_commit_and_update_refs(
             ),
         )?;
         let new_tree = new_commit.attach(repo).object()?.into_commit().tree_id()?;
-        out.index = repo.index_from_tree(&new_tree)?.into();
-    }
+        repo.index_from_tree(&new_tree)?
+    };
 
-    if let Some(index) = out.index.as_mut() {
-        index.write(Default::default())?;
+    let head_tree = repo.head_tree_id()?.detach();
+    if checkout_changes && head_tree != previous_head_tree {
+        from_patch::checkout_tree_changes(repo, previous_head_tree, head_tree, &mut index)?;
     }
-    Ok(out)
+    if commit_to_find.is_some() {
+        let tree_index = repo.index_from_tree(&head_tree)?;
+        index::apply_lhs_to_rhs(repo.workdir().expect("non-bare"), &tree_index, &mut index)?;
+    }
+    index.write(Default::default())?;
+    out.index = index.into();
+    Ok(())
 }
 
 /// Like [`create_commit_and_update_refs()`], but integrates with an existing GitButler `project`
@@ -533,9 +496,121 @@ pub fn create_commit_and_update_refs_with_project(
     Ok(out)
 }
 
+/// Rebase all descendants of `commit_in_graph` within `frame` onto `new_commit`, and rewrite all references and virtual
+/// branches in `vb` that pointed to a rewritten commit, recording them in `updated_refs`.
+///
+/// Return the output of the rebase, if one was needed.
+pub(crate) fn rewrite_descendants_and_refs(
+    repo: &gix::Repository,
+    frame: ReferenceFrame,
+    vb: &mut VirtualBranchesState,
+    commit_in_graph: gix::ObjectId,
+    new_commit: gix::ObjectId,
+    updated_refs: &mut Vec<UpdatedReference>,
+    stack_segment: Option<&StackSegmentId>,
+) -> anyhow::Result<Option<RebaseOutput>> {
+    let mut all_refs_by_id = gix::hashtable::HashMap::<_, Vec<_>>::default();
+    for (commit_id, git_reference) in repo
+        .references()?
+        .prefixed("refs/heads/")?
+        .chain(repo.references()?.prefixed("refs/gitbutler/")?)
+        .filter_map(Result::ok)
+        .filter_map(|r| r.try_id().map(|id| (id.detach(), r.inner.name)))
+    {
+        all_refs_by_id
+            .entry(commit_id)
+            .or_default()
+            .push(git_reference);
+    }
+
+    // Special case: commit/amend on top of `HEAD` or the branch tip and no merge above: no rebase necessary
+    if frame.workspace_tip.is_none()
+        && (repo.head_id().ok().map(|id| id.detach()) == Some(commit_in_graph)
+            || frame.branch_tip == Some(commit_in_graph))
+        && all_refs_by_id.contains_key(&commit_in_graph)
+    {
+        refs::rewrite(
+            repo,
+            vb,
+            all_refs_by_id,
+            [(commit_in_graph, new_commit)],
+            updated_refs,
+            stack_segment,
+        )?;
+        Ok(None)
+    } else {
+        let Some(branch_tip) = frame.branch_tip else {
+            bail!(
+                "HEAD isn't connected to the affected commit and a rebase is necessary, but no branch tip was provided"
+            );
+        };
+        // Use the branch tip to find all commits leading up to the one that was affected
+        // - these are the commits to rebase.
+        let mut found_marker = false;
+        let commits_to_rebase: Vec<_> = branch_tip
+            .attach(repo)
+            .ancestors()
+            .first_parent_only()
+            .all()?
+            .filter_map(Result::ok)
+            .take_while(|info| {
+                if info.id == commit_in_graph {
+                    found_marker = true;
+                    false
+                } else {
+                    true
+                }
+            })
+            .map(|info| info.id)
+            .collect();
+        if !found_marker {
+            bail!(
+                "Branch tip at {branch_tip} didn't contain the affected commit {commit_in_graph} - cannot rebase"
+            );
+        }
+
+        let workspace_tip = frame
+            .workspace_tip
+            .filter(|tip| !commits_to_rebase.contains(tip));
+        let rebase = {
+            // Set commits leading up to the tip on top of the new commit, serving as base.
+            let mut builder = but_rebase::Rebase::new(repo, new_commit, Some(commit_in_graph))?;
+            builder.steps(commits_to_rebase.into_iter().rev().map(|commit_id| {
+                but_rebase::RebaseStep::Pick {
+                    commit_id,
+                    new_message: None,
+                    base: None,
+                }
+            }))?;
+            if let Some(workspace_tip) = workspace_tip {
+                // We can assume the workspace tip is connected to a pick (or else the rebase will fail)
+                builder.steps([but_rebase::RebaseStep::Pick {
+                    commit_id: workspace_tip,
+                    new_message: None,
+                    base: None,
+                }])?;
+            }
+            builder.rebase()?
+        };
+        refs::rewrite(
+            repo,
+            vb,
+            all_refs_by_id,
+            rebase
+                .commit_mapping
+                .iter()
+                .map(|(_base, old, new)| (*old, *new))
+                .chain(Some((commit_in_graph, new_commit))),
+            updated_refs,
+            stack_segment,
+        )?;
+        Ok(Some(rebase))
+    }
+}
+
 /// Create a commit exactly as specified, and sign it depending on Git and GitButler specific Git configuration.
 #[allow(clippy::too_many_arguments)]
-fn create_possibly_signed_commit(
+pub(crate) fn create_possibly_signed_commit(
     repo: &gix::Repository,
     author: gix::actor::Signature,
     committer: gix::actor::Signature,
@@ -0,0 +1,192 @@
+use crate::commit_engine::reference_frame::InferenceMode;
+use crate::commit_engine::tree::{PossibleChange, apply_tree_changes};
+use crate::commit_engine::{
+    DiffSpec, ReferenceFrame, RejectionReason, UpdatedReference, rewrite_descendants_and_refs,
+};
+use anyhow::bail;
+use but_rebase::RebaseOutput;
+use but_rebase::commit::CommitterMode;
+use gitbutler_project::access::WorktreeWritePermission;
+use gitbutler_stack::{StackId, VirtualBranchesHandle, VirtualBranchesState};
+use gix::prelude::ObjectIdExt as _;
+
+/// One of the commits that a commit should be split into.
+#[derive(Debug, Clone)]
+pub struct SplitPiece {
+    /// The message of the new commit.
+    pub message: String,
+    /// The changes of the split commit that should go into the new commit, with hunks as seen in the diff
+    /// between the split commit and its parent.
+    pub changes: Vec<DiffSpec>,
+}
+
+/// Additional information about the outcome of a [`split_commit()`] call.
+#[derive(Debug)]
+pub struct SplitCommitOutcome {
+    /// Changes that couldn't be associated with the changes of the split commit, and thus went into the last new commit.
+    pub rejected_specs: Vec<(RejectionReason, DiffSpec)>,
+    /// The newly created commits, one per [piece](SplitPiece), from the bottom to the top.
+    pub new_commits: Vec<gix::ObjectId>,
+    /// The rewritten references, along with their `old` and `new` commit location.
+    pub references: Vec<UpdatedReference>,
+    /// `Some(_)` if a rebase of descendants was performed.
+    pub rebase_output: Option<RebaseOutput>,
+}
+
+/// Split the commit with `commit_id` into as many commits as there are `pieces`, with the first piece
+/// placed directly on top of the parent of `commit_id`, and each subsequent piece on top of the previous one.
+///
+/// Each new commit receives the changes of its piece in addition to the changes of all previous pieces, while
+/// the last new commit receives all remaining changes so its tree is the one of `commit_id`.
+/// `context_lines` is the amount of lines of context included in each [`HunkHeader`](crate::commit_engine::HunkHeader),
+/// and the value that will be used to recover the hunks of the split commit.
+///
+/// All new commits keep the author and additional headers of `commit_id`, and receive a new `Change-Id`, except for the
+/// last one which keeps the `Change-Id` of `commit_id` as it takes its place.
+///
+/// No reference is touched in the process.
+pub fn split_commit(
+    repo: &gix::Repository,
+    commit_id: gix::ObjectId,
+    pieces: Vec<SplitPiece>,
+    context_lines: u32,
+) -> anyhow::Result<SplitCommitOutcome> {
+    if pieces.len() < 2 {
+        bail!("A commit must be split into at least two commits");
+    }
+    let commit = but_core::Commit::from_id(commit_id.attach(repo))?;
+    if commit.is_conflicted() {
+        bail!("Cannot split commit {commit_id} as it is conflicted");
+    }
+    let parent_id = match commit.inner.parents.as_slice() {
+        [parent_id] => *parent_id,
+        _ => bail!("Can only split commits with exactly one parent, {commit_id} doesn't qualify"),
+    };
+    let base_tree = but_core::Commit::from_id(parent_id.attach(repo))?
+        .tree_id()?
+        .detach();
+    let commit_tree = commit.tree_id()?.detach();
+    let change_id = commit.headers().map(|hdr| hdr.change_id);
+
+    let mut rejected_specs = Vec::new();
+    let mut accepted_specs = Vec::<DiffSpec>::new();
+    let mut new_commits = Vec::with_capacity(pieces.len());
+    let mut parent = (parent_id, base_tree);
+    let num_pieces = pieces.len();
+    for (idx, piece) in pieces.into_iter().enumerate() {
+        let is_last = idx + 1 == num_pieces;
+        let tree = if is_last {
+            commit_tree
+        } else {
+            let mut changes: Vec<PossibleChange> = piece.changes.iter().cloned().map(Ok).collect();
+            apply_tree_changes(repo, base_tree, commit_tree, &mut changes, context_lines)?;
+            for (spec, outcome) in piece.changes.into_iter().zip(changes) {
+                match outcome {
+                    Ok(_) => merge_spec(&mut accepted_specs, spec),
+                    Err(rejected) => rejected_specs.push(rejected),
+                }
+            }
+            let mut changes: Vec<PossibleChange> = accepted_specs.iter().cloned().map(Ok).collect();
+            apply_tree_changes(repo, base_tree, commit_tree, &mut changes, context_lines)?
+                .unwrap_or(base_tree)
+        };
+        if tree == parent.1 {
+            bail!(
+                "Commit {num} of the split wouldn't contain any changes",
+                num = idx + 1
+            );
+        }
+
+        let mut new_commit = commit.inner.clone();
+        new_commit.message = piece.message.into();
+        new_commit.tree = tree;
+        new_commit.parents = std::iter::once(parent.0).collect();
+        let mut headers = but_core::commit::HeadersV2::default();
+        if is_last {
+            if let Some(change_id) = change_id.clone() {
+                headers.change_id = change_id;
+            }
+        }
+        headers.set_in_commit(&mut new_commit);
+        let new_commit_id = but_rebase::commit::create(repo, new_commit, CommitterMode::Update)?;
+        new_commits.push(new_commit_id);
+        parent = (new_commit_id, tree);
+    }
+
+    Ok(SplitCommitOutcome {
+        rejected_specs,
+        new_commits,
+        references: Vec::new(),
+        rebase_output: None,
+    })
+}
+
+/// Like [`split_commit()`], but also rebases all descendants of `commit_id` within `frame` onto the last new commit,
+/// and updates virtual branches in `vb` and git references that pointed to rewritten commits.
+///
+/// As the last new commit has the same tree as `commit_id`, neither the worktree nor the index are affected.
+pub fn split_commit_and_update_refs(
+    repo: &gix::Repository,
+    frame: ReferenceFrame,
+    vb: &mut VirtualBranchesState,
+    commit_id: gix::ObjectId,
+    pieces: Vec<SplitPiece>,
+    context_lines: u32,
+) -> anyhow::Result<SplitCommitOutcome> {
+    let mut out = split_commit(repo, commit_id, pieces, context_lines)?;
+    let last_commit = *out
+        .new_commits
+        .last()
+        .expect("there are always at least two new commits");
+    out.rebase_output = rewrite_descendants_and_refs(
+        repo,
+        frame,
+        vb,
+        commit_id,
+        last_commit,
+        &mut out.references,
+        None,
+    )?;
+    Ok(out)
+}
+
+/// Like [`split_commit_and_update_refs()`], but integrates with an existing GitButler `project`
+/// to find the stack that contains `commit_id`, unless `maybe_stackid` is given.
+/// Note that virtual branches will be updated and written back after this call.
+pub fn split_commit_and_update_refs_with_project(
+    repo: &gix::Repository,
+    project: &gitbutler_project::Project,
+    maybe_stackid: Option<StackId>,
+    commit_id: gix::ObjectId,
+    pieces: Vec<SplitPiece>,
+    context_lines: u32,
+    _perm: &mut WorktreeWritePermission,
+) -> anyhow::Result<SplitCommitOutcome> {
+    let vbh = VirtualBranchesHandle::new(project.gb_dir());
+    let mut vb = vbh.read_file()?;
+    let frame = match maybe_stackid {
+        None => ReferenceFrame::infer(repo, &vb, InferenceMode::CommitIdInStack(commit_id))?,
+        Some(stack_id) => ReferenceFrame::infer(repo, &vb, InferenceMode::StackId(stack_id))?,
+    };
+    let out = split_commit_and_update_refs(repo, frame, &mut vb, commit_id, pieces, context_lines)?;
+
+    vbh.write_file(&vb)?;
+    Ok(out)
+}
+
+/// Add `spec` to `specs`, combining it with the spec for the same change if there is one.
+fn merge_spec(specs: &mut Vec<DiffSpec>, spec: DiffSpec) {
+    let Some(existing) = specs
+        .iter_mut()
+        .find(|s| s.path == spec.path && s.previous_path == spec.previous_path)
+    else {
+        specs.push(spec);
+        return;
+    };
+    if existing.hunk_headers.is_empty() || spec.hunk_headers.is_empty() {
+        // The whole change was selected.
+        existing.hunk_headers.clear();
+    } else {
+        existing.hunk_headers.extend(spec.hunk_headers);
+    }
+}
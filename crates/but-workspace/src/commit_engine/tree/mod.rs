@@ -3,7 +3,7 @@ use crate::commit_engine::{
 };
 use anyhow::bail;
 use bstr::{BStr, ByteSlice};
-use but_core::{RepositoryExt, UnifiedDiff};
+use but_core::{ChangeState, RepositoryExt, UnifiedDiff};
 use gix::filter::plumbing::pipeline::convert::ToGitOutcome;
 use gix::merge::tree::TreatAsUnresolved;
 use gix::object::tree::EntryKind;
@@ -135,7 +135,7 @@ pub fn create_tree(
     })
 }
 
-fn into_err_spec(input: &mut PossibleChange, reason: RejectionReason) {
+pub(crate) fn into_err_spec(input: &mut PossibleChange, reason: RejectionReason) {
     *input = match std::mem::replace(input, Ok(Default::default())) {
         // What we thought was a good change turned out to be a no-op, rejected.
         Ok(inner) => Err((reason, inner)),
@@ -143,7 +143,7 @@ fn into_err_spec(input: &mut PossibleChange, reason: RejectionReason) {
     };
 }
 
-type PossibleChange = Result<DiffSpec, (RejectionReason, DiffSpec)>;
+pub(crate) type PossibleChange = Result<DiffSpec, (RejectionReason, DiffSpec)>;
 
 /// Apply `changes` to `changes_base_tree` and return the newly written tree as `(maybe_new_tree, actual_base_tree, maybe_new_index)`.
 /// All `changes` are expected to originate from `changes_base_tree`, and will be applied `changes_base_tree`.
@@ -320,6 +320,127 @@ fn apply_worktree_changes<'repo>(
     Ok((maybe_new_tree, actual_base_tree))
 }
 
+/// Apply `changes`, which are expected to be a selection of the changes that turn `base_tree` into `changes_tree`,
+/// to `base_tree` and return the newly written tree, or `None` if `base_tree` didn't change.
+///
+/// Unlike [`apply_worktree_changes()`], the new state of each change is obtained from `changes_tree`, and the hunks of
+/// a change can be given in any order as they will be sorted by their position in the diff first.
+pub(crate) fn apply_tree_changes(
+    repo: &gix::Repository,
+    base_tree: gix::ObjectId,
+    changes_tree: gix::ObjectId,
+    changes: &mut [PossibleChange],
+    context_lines: u32,
+) -> anyhow::Result<Option<gix::ObjectId>> {
+    let tree_changes = but_core::diff::tree_changes(repo, Some(base_tree), changes_tree)?;
+    let mut base_tree_editor = base_tree.attach(repo).object()?.peel_to_tree()?.edit()?;
+    'each_change: for possible_change in changes.iter_mut() {
+        let change_request = match possible_change {
+            Ok(change) => change,
+            Err(_) => continue,
+        };
+        let Some(tree_change) = tree_changes.iter().find(|c| {
+            c.path == change_request.path
+                && c.previous_path() == change_request.previous_path.as_ref().map(|p| p.as_bstr())
+        }) else {
+            into_err_spec(possible_change, RejectionReason::NoEffectiveChanges);
+            continue;
+        };
+        if let Some(previous_path) = tree_change.previous_path() {
+            base_tree_editor.remove(previous_path)?;
+        }
+        let current_state = tree_change.status.state();
+        if change_request.hunk_headers.is_empty() {
+            match current_state {
+                Some(state) => {
+                    base_tree_editor.upsert(change_request.path.as_bstr(), state.kind, state.id)?;
+                }
+                None => {
+                    base_tree_editor.remove(change_request.path.as_bstr())?;
+                }
+            }
+            continue;
+        }
+
+        let previous_state = tree_change
+            .status
+            .previous_state_and_path()
+            .map(|(state, _)| state);
+        let blob_data = |state: Option<ChangeState>| -> anyhow::Result<Option<Vec<u8>>> {
+            Ok(match state {
+                None => Some(Vec::new()),
+                Some(state) => match state.kind {
+                    EntryKind::Tree | EntryKind::Commit => None,
+                    EntryKind::Blob | EntryKind::BlobExecutable | EntryKind::Link => {
+                        Some(repo.find_blob(state.id)?.detach().data)
+                    }
+                },
+            })
+        };
+        let (Some(old_image), Some(new_image)) =
+            (blob_data(previous_state)?, blob_data(current_state)?)
+        else {
+            into_err_spec(possible_change, RejectionReason::UnsupportedTreeEntry);
+            continue;
+        };
+
+        let UnifiedDiff::Patch { hunks, .. } = tree_change.unified_diff(repo, context_lines)?
+        else {
+            into_err_spec(possible_change, RejectionReason::FileToLargeOrBinary);
+            continue;
+        };
+        let UnifiedDiff::Patch {
+            hunks: hunks_no_context,
+            ..
+        } = tree_change.unified_diff(repo, 0)?
+        else {
+            into_err_spec(possible_change, RejectionReason::FileToLargeOrBinary);
+            continue;
+        };
+        let diff_hunks: Vec<HunkHeader> = hunks.into_iter().map(Into::into).collect();
+        let diff_hunks_no_context: Vec<HunkHeader> =
+            hunks_no_context.into_iter().map(Into::into).collect();
+
+        // Selections of added lines are placed at the beginning of the hunk they are in,
+        // everything else is ordered by the old lines it affects.
+        let mut selected_hunks = std::mem::take(&mut change_request.hunk_headers);
+        selected_hunks.sort_by_key(|h| {
+            let old_start = if h.old_range().is_null() {
+                diff_hunks_no_context
+                    .iter()
+                    .find(|dh| dh.new_range().contains(h.new_range()))
+                    .map_or(h.old_start, |dh| dh.old_start)
+            } else {
+                h.old_start
+            };
+            (old_start, h.new_start)
+        });
+        selected_hunks.dedup();
+
+        let (hunks_to_commit, rejected) =
+            to_additive_hunks(selected_hunks, &diff_hunks, &diff_hunks_no_context);
+        change_request.hunk_headers = rejected;
+        if hunks_to_commit.is_empty() && !change_request.hunk_headers.is_empty() {
+            into_err_spec(possible_change, RejectionReason::MissingDiffSpecAssociation);
+            continue 'each_change;
+        }
+        let Some(kind) = current_state.or(previous_state).map(|state| state.kind) else {
+            unreachable!("a change always has at least one state")
+        };
+        let base_with_patches =
+            apply_hunks(old_image.as_bstr(), new_image.as_bstr(), &hunks_to_commit)?;
+        let blob_with_selected_patches = repo.write_blob(base_with_patches.as_slice())?;
+        base_tree_editor.upsert(
+            change_request.path.as_bstr(),
+            kind,
+            blob_with_selected_patches,
+        )?;
+    }
+
+    let altered_base_tree_id = base_tree_editor.write()?.detach();
+    Ok((altered_base_tree_id != base_tree).then_some(altered_base_tree_id))
+}
+
 pub(crate) fn worktree_file_to_git_in_buf(
     buf: &mut Vec<u8>,
     rela_path: &BStr,
@@ -0,0 +1,354 @@
+//! Resolve the conflicts of commits that were left conflicted by cherry-picks, all without touching the worktree.
+//!
+//! A conflicted commit stores the *base*, *ours* and *theirs* trees of the conflicting cherry-pick, along with the
+//! auto-resolved tree that its descendants are based on, and the paths that conflicted.
+//! Resolving it means to provide the resolved content for each of these paths, which yields a clean commit in its place.
+
+use crate::commit_engine::reference_frame::InferenceMode;
+use crate::commit_engine::{ReferenceFrame, UpdatedReference, rewrite_descendants_and_refs};
+use anyhow::{Context, bail};
+use bstr::{BString, ByteSlice};
+use but_core::commit::TreeKind;
+use but_core::{ChangeState, RepositoryExt};
+use but_rebase::RebaseOutput;
+use but_rebase::cherry_pick::ConflictEntries;
+use but_rebase::commit::CommitterMode;
+use gitbutler_oxidize::OidExt;
+use gitbutler_project::access::WorktreeWritePermission;
+use gitbutler_stack::{StackId, VirtualBranchesHandle, VirtualBranchesState};
+use gix::object::tree::EntryKind;
+use gix::prelude::ObjectIdExt as _;
+use std::path::Path;
+
+/// A conflicted commit along with the paths that conflicted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConflictedCommit {
+    /// The id of the conflicted commit.
+    pub id: gix::ObjectId,
+    /// The paths that conflicted, sorted and without duplicates.
+    pub paths: Vec<BString>,
+}
+
+/// The versions of a file that conflicted in a commit.
+#[derive(Debug, Clone)]
+pub struct ConflictedFile {
+    /// The path of the file.
+    pub path: BString,
+    /// The version of the file in the commit both sides are based on, or `None` if it didn't exist there.
+    pub base: Option<ChangeState>,
+    /// The version of the file in the commit that was picked onto, or `None` if it didn't exist there.
+    pub ours: Option<ChangeState>,
+    /// The version of the file in the picked commit, or `None` if it didn't exist there.
+    pub theirs: Option<ChangeState>,
+}
+
+/// How to present the conflicts of a commit in a scratch tree with [`materialize_conflicts()`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Materialization {
+    /// The whole tree of the commit, with conflicting files containing conflict markers.
+    Markers,
+    /// A tree with the `base`, `ours` and `theirs` directories, each containing its version of the conflicting files.
+    Sides,
+}
+
+/// The resolution of a single conflicting path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolution {
+    /// The path that conflicted.
+    pub path: BString,
+    /// The resolved content of the file, or `None` if the file should be deleted.
+    pub content: Option<BString>,
+}
+
+/// Additional information about the outcome of a [`resolve_commit_and_update_refs()`] call.
+#[derive(Debug)]
+pub struct ResolveCommitOutcome {
+    /// The resolved commit which took the place of the conflicted one.
+    pub new_commit: gix::ObjectId,
+    /// The rewritten references, along with their `old` and `new` commit location.
+    pub references: Vec<UpdatedReference>,
+    /// `Some(_)` if a rebase of descendants was performed.
+    pub rebase_output: Option<RebaseOutput>,
+}
+
+/// Return all conflicted commits reachable from `tip` by following the first parent, but not reachable from `base`,
+/// from the top to the bottom.
+pub fn conflicted_commits(
+    repo: &gix::Repository,
+    tip: gix::ObjectId,
+    base: Option<gix::ObjectId>,
+) -> anyhow::Result<Vec<ConflictedCommit>> {
+    let mut out = Vec::new();
+    for info in tip
+        .attach(repo)
+        .ancestors()
+        .first_parent_only()
+        .with_hidden(base)
+        .all()?
+    {
+        let commit = but_core::Commit::from_id(info?.id())?;
+        if commit.is_conflicted() {
+            out.push(ConflictedCommit {
+                id: commit.id.detach(),
+                paths: conflicted_paths(&commit)?,
+            });
+        }
+    }
+    Ok(out)
+}
+
+/// Like [`conflicted_commits()`], but for the stack with `stack_id` in the workspace whose GitButler state is in `gb_dir`,
+/// stopping at the merge-base of the stack with the target branch.
+pub fn conflicted_commits_in_stack(
+    repo: &gix::Repository,
+    gb_dir: &Path,
+    stack_id: StackId,
+) -> anyhow::Result<Vec<ConflictedCommit>> {
+    let state = VirtualBranchesHandle::new(gb_dir);
+    let tip = state.get_stack_in_workspace(stack_id)?.head(repo)?.to_gix();
+    let target = state.get_default_target()?.sha.to_gix();
+    let base = repo.merge_base(tip, target)?.detach();
+    conflicted_commits(repo, tip, Some(base))
+}
+
+/// Return all versions of the files that conflicted in the commit with `commit_id`.
+pub fn conflicted_files(
+    repo: &gix::Repository,
+    commit_id: gix::ObjectId,
+) -> anyhow::Result<Vec<ConflictedFile>> {
+    let commit = conflicted_commit(repo, commit_id)?;
+    let [base, ours, theirs] =
+        [TreeKind::Base, TreeKind::Ours, TreeKind::Theirs].map(|kind| -> anyhow::Result<_> {
+            Ok(commit.tree_id_by_kind_or_ours(kind)?.object()?.into_tree())
+        });
+    let (base, ours, theirs) = (base?, ours?, theirs?);
+    conflicted_paths(&commit)?
+        .into_iter()
+        .map(|path| {
+            Ok(ConflictedFile {
+                base: state_at(&base, path.as_ref())?,
+                ours: state_at(&ours, path.as_ref())?,
+                theirs: state_at(&theirs, path.as_ref())?,
+                path,
+            })
+        })
+        .collect()
+}
+
+/// Write a scratch tree that presents the conflicts of the commit with `commit_id` as described by `how`, and return its id.
+///
+/// The tree isn't referenced by anything, and the worktree is left untouched.
+pub fn materialize_conflicts(
+    repo: &gix::Repository,
+    commit_id: gix::ObjectId,
+    how: Materialization,
+) -> anyhow::Result<gix::ObjectId> {
+    Ok(match how {
+        Materialization::Markers => {
+            let commit = conflicted_commit(repo, commit_id)?;
+            let [base, ours, theirs] = [TreeKind::Base, TreeKind::Ours, TreeKind::Theirs]
+                .map(|kind| commit.tree_id_by_kind_or_ours(kind));
+            let mut merge = repo.merge_trees(
+                base?,
+                ours?,
+                theirs?,
+                repo.default_merge_labels(),
+                repo.tree_merge_options()?,
+            )?;
+            merge.tree.write()?.detach()
+        }
+        Materialization::Sides => {
+            let mut editor = repo.empty_tree().edit()?;
+            for file in conflicted_files(repo, commit_id)? {
+                for (side, state) in [
+                    ("base", file.base),
+                    ("ours", file.ours),
+                    ("theirs", file.theirs),
+                ] {
+                    if let Some(state) = state {
+                        let mut path = BString::from(side);
+                        path.push(b'/');
+                        path.extend_from_slice(&file.path);
+                        editor.upsert(path.as_bstr(), state.kind, state.id)?;
+                    }
+                }
+            }
+            editor.write()?.detach()
+        }
+    })
+}
+
+/// Write a commit in place of the conflicted commit with `commit_id` which has all of its conflicting paths set to their
+/// `resolutions`, and return its id.
+///
+/// The new commit is like the conflicted one, but with its auto-resolved tree adjusted by `resolutions`, and without the
+/// marker that makes it conflicted. It's an error if not all conflicting paths are resolved,
+/// or if a resolution is provided for a path that didn't conflict.
+///
+/// No reference is touched in the process.
+pub fn resolve_commit(
+    repo: &gix::Repository,
+    commit_id: gix::ObjectId,
+    resolutions: Vec<Resolution>,
+) -> anyhow::Result<gix::ObjectId> {
+    let commit = conflicted_commit(repo, commit_id)?;
+    let conflicted = conflicted_paths(&commit)?;
+    let not_conflicted: Vec<_> = resolutions
+        .iter()
+        .filter(|r| !conflicted.contains(&r.path))
+        .map(|r| r.path.to_str_lossy())
+        .collect();
+    if !not_conflicted.is_empty() {
+        bail!(
+            "Commit {commit_id} can't be resolved as these paths aren't conflicted: {}",
+            not_conflicted.join(", ")
+        );
+    }
+    let unresolved: Vec<_> = conflicted
+        .into_iter()
+        .filter(|path| !resolutions.iter().any(|r| r.path == *path))
+        .collect();
+    if !unresolved.is_empty() {
+        bail!(
+            "Commit {commit_id} can't be resolved as these paths remain conflicted: {}",
+            unresolved
+                .iter()
+                .map(|path| path.to_str_lossy())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    let auto_resolution = commit
+        .tree_id_by_kind_or_ours(TreeKind::AutoResolution)?
+        .object()?
+        .into_tree();
+    let ours = commit
+        .tree_id_by_kind_or_ours(TreeKind::Ours)?
+        .object()?
+        .into_tree();
+    let theirs = commit
+        .tree_id_by_kind_or_ours(TreeKind::Theirs)?
+        .object()?
+        .into_tree();
+    let mut editor = auto_resolution.edit()?;
+    for Resolution { path, content } in resolutions {
+        match content {
+            None => {
+                editor.remove(path.as_bstr())?;
+            }
+            Some(content) => {
+                let mut kind = EntryKind::Blob;
+                for tree in [&auto_resolution, &theirs, &ours] {
+                    if let Some(state) = state_at(tree, path.as_ref())? {
+                        kind = state.kind;
+                        break;
+                    }
+                }
+                let blob = repo.write_blob(content.as_slice())?;
+                editor.upsert(path.as_bstr(), kind, blob)?;
+            }
+        }
+    }
+
+    let mut headers = commit.headers().unwrap_or_default();
+    headers.conflicted = None;
+    let mut new_commit = commit.inner;
+    new_commit.tree = editor.write()?.detach();
+    headers.set_in_commit(&mut new_commit);
+    but_rebase::commit::create(repo, new_commit, CommitterMode::Update)
+}
+
+/// Like [`resolve_commit()`], but also rebases all descendants of `commit_id` within `frame` onto the resolved commit,
+/// and updates virtual branches in `vb` and git references that pointed to rewritten commits.
+///
+/// Note that the worktree and the index are never touched.
+pub fn resolve_commit_and_update_refs(
+    repo: &gix::Repository,
+    frame: ReferenceFrame,
+    vb: &mut VirtualBranchesState,
+    commit_id: gix::ObjectId,
+    resolutions: Vec<Resolution>,
+) -> anyhow::Result<ResolveCommitOutcome> {
+    let new_commit = resolve_commit(repo, commit_id, resolutions)?;
+    let mut references = Vec::new();
+    let rebase_output = rewrite_descendants_and_refs(
+        repo,
+        frame,
+        vb,
+        commit_id,
+        new_commit,
+        &mut references,
+        None,
+    )?;
+    Ok(ResolveCommitOutcome {
+        new_commit,
+        references,
+        rebase_output,
+    })
+}
+
+/// Like [`resolve_commit_and_update_refs()`], but integrates with an existing GitButler `project`
+/// to find the stack that contains `commit_id`, unless `maybe_stackid` is given.
+/// Note that virtual branches will be updated and written back after this call.
+pub fn resolve_commit_and_update_refs_with_project(
+    repo: &gix::Repository,
+    project: &gitbutler_project::Project,
+    maybe_stackid: Option<StackId>,
+    commit_id: gix::ObjectId,
+    resolutions: Vec<Resolution>,
+    _perm: &mut WorktreeWritePermission,
+) -> anyhow::Result<ResolveCommitOutcome> {
+    let vbh = VirtualBranchesHandle::new(project.gb_dir());
+    let mut vb = vbh.read_file()?;
+    let frame = match maybe_stackid {
+        None => ReferenceFrame::infer(repo, &vb, InferenceMode::CommitIdInStack(commit_id))?,
+        Some(stack_id) => ReferenceFrame::infer(repo, &vb, InferenceMode::StackId(stack_id))?,
+    };
+    let out = resolve_commit_and_update_refs(repo, frame, &mut vb, commit_id, resolutions)?;
+
+    vbh.write_file(&vb)?;
+    Ok(out)
+}
+
+fn conflicted_commit(
+    repo: &gix::Repository,
+    commit_id: gix::ObjectId,
+) -> anyhow::Result<but_core::Commit<'_>> {
+    let commit = but_core::Commit::from_id(commit_id.attach(repo))?;
+    if !commit.is_conflicted() {
+        bail!("Commit {commit_id} isn't conflicted");
+    }
+    Ok(commit)
+}
+
+fn conflicted_paths(commit: &but_core::Commit<'_>) -> anyhow::Result<Vec<BString>> {
+    let repo = commit.id.repo;
+    let tree = repo.find_tree(commit.tree)?;
+    let entry = tree.find_entry(".conflict-files").with_context(|| {
+        format!(
+            "Conflicted commit {} doesn't list its conflicting paths",
+            commit.id
+        )
+    })?;
+    let entries: ConflictEntries = toml::from_str(entry.object()?.data.to_str()?)?;
+    let mut paths: Vec<BString> = entries
+        .ancestor_entries
+        .into_iter()
+        .chain(entries.our_entries)
+        .chain(entries.their_entries)
+        .map(|path| gix::path::into_bstr(path).into_owned())
+        .collect();
+    paths.sort();
+    paths.dedup();
+    Ok(paths)
+}
+
+fn state_at(tree: &gix::Tree<'_>, path: &bstr::BStr) -> anyhow::Result<Option<ChangeState>> {
+    Ok(tree
+        .lookup_entry(path.split(|b| *b == b'/'))?
+        .map(|entry| ChangeState {
+            id: entry.object_id(),
+            kind: entry.mode().kind(),
+        }))
+}
@@ -308,7 +308,7 @@ pub fn purge_and_restore_from_head_tree(
     Ok(())
 }
 
-pub(super) mod index {
+pub(crate) mod index {
     use bstr::BStr;
     use gix::index::entry::Stage;
 
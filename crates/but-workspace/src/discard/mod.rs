@@ -43,7 +43,7 @@ pub mod ui {
     pub type DiscardSpec = crate::commit_engine::ui::DiffSpec;
 }
 
-mod file;
+pub(crate) mod file;
 pub(crate) mod hunk;
 
 #[cfg(unix)]
@@ -26,11 +26,13 @@
 use anyhow::{Context, Result};
 use author::Author;
 use bstr::{BStr, BString};
+use but_rebase::commit::verify_signature;
 use gitbutler_command_context::CommandContext;
 use gitbutler_commit::commit_ext::CommitExt;
 use gitbutler_id::id::Id;
 use gitbutler_oxidize::{OidExt, git2_signature_to_gix_signature};
 use gitbutler_stack::{Stack, StackBranch, VirtualBranchesHandle};
+use gix::prelude::ObjectIdExt as _;
 use integrated::IsCommitIntegrated;
 use itertools::Itertools;
 use serde::Serialize;
@@ -42,8 +44,10 @@ mod author;
 mod integrated;
 
 pub mod commit_engine;
+pub mod conflict;
 pub mod discard;
 pub use discard::function::discard_workspace_changes;
+pub mod mbox;
 
 /// 🚧utilities for applying and unapplying branches 🚧.
 pub mod branch;
@@ -59,6 +63,7 @@ mod stash {
         Orphaned,
     }
 }
+pub use but_rebase::commit::SignatureStatus;
 pub use stash::StashStatus;
 
 mod commit;
@@ -251,10 +256,11 @@ pub fn stack_info(gb_dir: &Path, stack_id: StackId, ctx: &CommandContext) -> Res
     let stack = state.get_stack(stack_id)?;
     let branches = stack.branches();
     let repo = ctx.gix_repo()?;
-    let remote = state
-        .get_default_target()
-        .context("failed to get default target")?
-        .push_remote_name();
+    let remote = stack.push_remote_name(
+        &state
+            .get_default_target()
+            .context("failed to get default target")?,
+    );
 
     let mut stack_state = BranchState::default();
     let mut stack_is_conflicted = false;
@@ -419,13 +425,13 @@ pub struct Branch {
 /// The entries are ordered from newest to oldest.
 pub fn stack_branches(stack_id: String, ctx: &CommandContext) -> Result<Vec<Branch>> {
     let state = state_handle(&ctx.project().gb_dir());
-    let remote = state
-        .get_default_target()
-        .context("failed to get default target")?
-        .push_remote_name();
-
     let mut stack_branches = vec![];
     let mut stack = state.get_stack(Id::from_str(&stack_id)?)?;
+    let remote = stack.push_remote_name(
+        &state
+            .get_default_target()
+            .context("failed to get default target")?,
+    );
     let mut current_base = stack.merge_base(ctx)?.to_gix();
     let repo = ctx.gix_repo()?;
     for internal in stack.branches() {
@@ -617,6 +623,21 @@ fn local_and_remote_commits(
     Ok(local_and_remote)
 }
 
+/// Verify the signature of the commit with `commit_id` in `repo`.
+///
+/// This isn't done when listing commits as it spawns `gpg`, `gpgsm` or `ssh-keygen` for each signed commit,
+/// and the outcome depends on keys and configuration that may change at any time. Instead, call it for each
+/// commit whose signature should be shown.
+pub fn commit_signature_status(
+    repo: &gix::Repository,
+    commit_id: gix::ObjectId,
+) -> Result<SignatureStatus> {
+    Ok(verify_signature(
+        repo,
+        &but_core::Commit::from_id(commit_id.attach(repo))?,
+    ))
+}
+
 fn state_handle(gb_state_path: &Path) -> VirtualBranchesHandle {
     VirtualBranchesHandle::new(gb_state_path)
 }
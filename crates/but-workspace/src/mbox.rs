@@ -0,0 +1,813 @@
+//! Export the commits of a branch as a series of emails in `mbox` format like `git format-patch` does,
+//! and turn such a mailbox back into commits like `git am` would.
+use crate::commit_engine::{create_possibly_signed_commit, from_patch::patch};
+use anyhow::{Context, bail};
+use bstr::{BStr, BString, ByteSlice, ByteVec};
+use but_core::{RefMetadata, RepositoryExt, TreeStatus, UnifiedDiff};
+use gitbutler_repo::commit_message::CommitMessage;
+use gix::object::tree::EntryKind;
+use gix::prelude::ObjectIdExt;
+use gix::refs::transaction::PreviousValue;
+
+/// The amount of context lines around each hunk, matching the default of Git.
+const CONTEXT_LINES: u32 = 3;
+
+/// The subject that Git uses in cover letters that still have to be filled in.
+const COVER_LETTER_SUBJECT_PLACEHOLDER: &str = "*** SUBJECT HERE ***";
+
+/// A series of patches along with its optional cover letter, as parsed from a mailbox.
+#[derive(Debug, Default, Clone)]
+pub struct PatchSeries {
+    /// The subject and body of the cover letter, separated by an empty line, if the series had one.
+    pub cover_letter: Option<String>,
+    /// The patches in the order in which they have to be applied.
+    pub patches: Vec<MailPatch>,
+}
+
+/// A single patch as parsed from an email.
+#[derive(Debug, Clone)]
+pub struct MailPatch {
+    /// The author of the patch, along with the time it was authored at.
+    pub author: gix::actor::Signature,
+    /// The commit message, including its trailers.
+    pub message: BString,
+    /// The changes to apply, as unified diff.
+    pub patch: BString,
+}
+
+impl MailPatch {
+    /// Return the first line of the commit message.
+    pub fn title(&self) -> &BStr {
+        self.message.lines().next().unwrap_or_default().as_bstr()
+    }
+}
+
+/// Produce a series of emails in `mbox` format with one patch for each commit between `base` and `tip`, just like
+/// `git format-patch` would. If `base` is `None`, all commits reachable from `tip` are included.
+///
+/// Only the first-parent ancestry of `tip` is followed, and merge commits are skipped.
+/// If `cover_letter` is given, its first line is used as subject and the remaining lines as body of an additional
+/// email that precedes all patches.
+pub fn format_patches(
+    repo: &gix::Repository,
+    base: Option<gix::ObjectId>,
+    tip: gix::ObjectId,
+    cover_letter: Option<&str>,
+) -> anyhow::Result<BString> {
+    let mut commit_ids = Vec::new();
+    for info in tip
+        .attach(repo)
+        .ancestors()
+        .first_parent_only()
+        .with_hidden(base)
+        .all()?
+    {
+        let info = info?;
+        if info.parent_ids.len() < 2 {
+            commit_ids.push(info.id);
+        }
+    }
+    commit_ids.reverse();
+    if commit_ids.is_empty() {
+        bail!("There are no commits between the base and {tip} to format as patches");
+    }
+
+    let mut commits = Vec::new();
+    for commit_id in commit_ids {
+        let commit = but_core::Commit::from_id(commit_id.attach(repo))?;
+        if commit.is_conflicted() {
+            bail!("Cannot format commit {commit_id} as patch as it is conflicted");
+        }
+        commits.push(commit);
+    }
+
+    let num_patches = commits.len();
+    let numbered = num_patches > 1 || cover_letter.is_some();
+    let mut out = BString::default();
+    if let Some(cover_letter) = cover_letter {
+        let (_author, committer) = repo.commit_signatures()?;
+        let (subject, body) = cover_letter
+            .trim()
+            .split_once('\n')
+            .unwrap_or((cover_letter.trim(), ""));
+        let mut body = BString::from(body.trim());
+        if !body.is_empty() {
+            body.push_str("\n\n");
+        }
+        body.push_str("---\n");
+        write_shortlog(&mut body, &commits);
+        write_mail(
+            &mut out,
+            gix::ObjectId::null(repo.object_hash()),
+            &committer,
+            &format!("[PATCH 0/{num_patches}] {subject}"),
+            body.as_bstr(),
+        );
+    }
+    for (idx, commit) in commits.iter().enumerate() {
+        let message = gix::objs::commit::MessageRef::from_bytes(&commit.message);
+        let subject = message.title.to_str_lossy().replace('\n', " ");
+        let subject = if numbered {
+            format!("[PATCH {}/{num_patches}] {subject}", idx + 1)
+        } else {
+            format!("[PATCH] {subject}")
+        };
+
+        let mut body = BString::default();
+        if let Some(message_body) = message.body {
+            body.push_str(message_body.trim_end());
+            body.push_str("\n");
+        }
+        body.push_str("---\n");
+        write_commit_diff(&mut body, repo, commit)?;
+        write_mail(
+            &mut out,
+            commit.id.detach(),
+            &commit.author,
+            &subject,
+            body.as_bstr(),
+        );
+    }
+    Ok(out)
+}
+
+/// Like [`format_patches()`], but formats all commits of the branch `ref_name` that aren't reachable from `base`,
+/// using the description of the branch as stored in `meta` as cover letter.
+pub fn format_patches_for_branch(
+    repo: &gix::Repository,
+    meta: &impl RefMetadata,
+    ref_name: &gix::refs::FullNameRef,
+    base: Option<gix::ObjectId>,
+) -> anyhow::Result<BString> {
+    let tip = repo
+        .find_reference(ref_name)?
+        .peel_to_id_in_place()?
+        .detach();
+    let description = meta.branch(ref_name)?.description.clone();
+    format_patches(repo, base, tip, description.as_deref())
+}
+
+/// Parse `mbox`, a mailbox with one or more emails as produced by `git format-patch`, into a series of patches.
+///
+/// The first email without a patch is considered the cover letter. Authors, dates and commit messages including
+/// their trailers are preserved, while prefixes like `[PATCH 1/2]` are removed from the subjects.
+pub fn parse_mbox(mbox: &[u8]) -> anyhow::Result<PatchSeries> {
+    let mut series = PatchSeries::default();
+    for (idx, mail) in split_mbox(mbox).into_iter().enumerate() {
+        let mail = parse_mail(mail)?;
+        let message = CommitMessage::from_bstr(
+            format!("{}\n\n{}", mail.subject, mail.message.to_str_lossy())
+                .as_bytes()
+                .as_bstr(),
+        )
+        .to_bstring_without_empty_sections();
+        match mail.patch {
+            Some(patch) => {
+                let mut message = message;
+                message.push(b'\n');
+                series.patches.push(MailPatch {
+                    author: mail.author,
+                    message,
+                    patch,
+                });
+            }
+            None if idx == 0 => {
+                series.cover_letter =
+                    (mail.subject != COVER_LETTER_SUBJECT_PLACEHOLDER).then(|| message.to_string());
+            }
+            None => bail!("The email '{}' doesn't contain a patch", mail.subject),
+        }
+    }
+    Ok(series)
+}
+
+/// Create a commit for each patch in `patches`, with the first one being based on `base`, and return their ids
+/// in order. Authors and messages are taken from the patches, and new change-ids are assigned.
+///
+/// It's an error if any part of a patch doesn't apply.
+pub fn commit_patch_series(
+    repo: &gix::Repository,
+    base: gix::ObjectId,
+    patches: &[MailPatch],
+) -> anyhow::Result<Vec<gix::ObjectId>> {
+    let mut parent = base;
+    let mut commit_ids = Vec::with_capacity(patches.len());
+    for (idx, mail) in patches.iter().enumerate() {
+        let base_tree = but_core::Commit::from_id(parent.attach(repo))?
+            .tree_id()?
+            .detach();
+        let (new_tree, rejected) = patch::apply(repo, base_tree, patch::parse(&mail.patch)?)?;
+        if let Some((_reason, spec)) = rejected.first() {
+            bail!(
+                "Patch {}/{} '{}' doesn't apply to '{}'",
+                idx + 1,
+                patches.len(),
+                mail.title(),
+                spec.path
+            );
+        }
+        let (_author, committer) = repo.commit_signatures()?;
+        parent = create_possibly_signed_commit(
+            repo,
+            mail.author.clone(),
+            committer,
+            &mail.message.to_str_lossy(),
+            new_tree.unwrap_or(base_tree),
+            [parent],
+            None,
+        )?;
+        commit_ids.push(parent);
+    }
+    Ok(commit_ids)
+}
+
+/// Turn all patches in `mbox` into commits on top of `base`, and create the branch `ref_name` pointing to the last of them.
+/// The cover letter of the series, if present, is stored as description of the branch in `meta`.
+///
+/// Return the ids of the new commits in order.
+pub fn create_stack_from_mbox(
+    repo: &gix::Repository,
+    meta: &mut impl RefMetadata,
+    ref_name: &gix::refs::FullNameRef,
+    base: gix::ObjectId,
+    mbox: &[u8],
+) -> anyhow::Result<Vec<gix::ObjectId>> {
+    let series = parse_mbox(mbox)?;
+    if series.patches.is_empty() {
+        bail!("The mailbox didn't contain any patches");
+    }
+    let commit_ids = commit_patch_series(repo, base, &series.patches)?;
+    let tip = *commit_ids.last().expect("one commit per patch");
+    repo.reference(
+        ref_name,
+        tip,
+        PreviousValue::MustNotExist,
+        "GitButler: import patches",
+    )
+    .with_context(|| format!("Could not create branch '{}'", ref_name.shorten()))?;
+
+    let mut branch = meta.branch(ref_name)?;
+    if series.cover_letter.is_some() {
+        branch.description = series.cover_letter;
+    }
+    meta.set_branch(&branch)?;
+    Ok(commit_ids)
+}
+
+fn write_mail(
+    out: &mut BString,
+    id: gix::ObjectId,
+    author: &gix::actor::Signature,
+    subject: &str,
+    body: &BStr,
+) {
+    out.push_str(format!("From {id} Mon Sep 17 00:00:00 2001\n"));
+    out.push_str("From: ");
+    out.push_str(encode_display_name(author.name.as_bstr()));
+    out.push_str(format!(" <{}>\n", author.email));
+    out.push_str(format!(
+        "Date: {}\n",
+        author.time.format(gix::date::time::format::RFC2822)
+    ));
+    out.push_str(format!("Subject: {}\n", encode_header_text(subject)));
+    out.push_str(
+        "MIME-Version: 1.0\nContent-Type: text/plain; charset=UTF-8\nContent-Transfer-Encoding: 8bit\n\n",
+    );
+    out.push_str(body);
+    out.push_str("\n");
+}
+
+/// Write the subjects of all `commits` grouped by author, like `git shortlog` would.
+fn write_shortlog(out: &mut BString, commits: &[but_core::Commit<'_>]) {
+    let mut by_author = Vec::<(&BStr, Vec<&BStr>)>::new();
+    for commit in commits {
+        let title = commit.message.lines().next().unwrap_or_default().as_bstr();
+        let name = commit.author.name.as_bstr();
+        match by_author.iter_mut().find(|(author, _)| *author == name) {
+            Some((_, titles)) => titles.push(title),
+            None => by_author.push((name, vec![title])),
+        }
+    }
+    for (author, titles) in by_author {
+        out.push_str(format!("{author} ({}):\n", titles.len()));
+        for title in titles {
+            out.push_str(format!("  {title}\n"));
+        }
+        out.push_str("\n");
+    }
+}
+
+/// Write the changes introduced by `commit` compared to its first parent as Git-style unified diff.
+fn write_commit_diff(
+    out: &mut BString,
+    repo: &gix::Repository,
+    commit: &but_core::Commit<'_>,
+) -> anyhow::Result<()> {
+    let changes =
+        but_core::diff::commit_changes(repo, commit.parents.first().copied(), commit.id.detach())?;
+    for change in changes {
+        let path = change.path.as_bstr();
+        let previous = change.status.previous_state_and_path();
+        let previous_path = previous.and_then(|(_, path)| path).unwrap_or(path);
+        let previous_state = previous.map(|(state, _)| state);
+        let state = change.status.state();
+
+        out.push_str(format!("diff --git a/{previous_path} b/{path}\n"));
+        match &change.status {
+            TreeStatus::Addition { state, .. } => {
+                out.push_str(format!("new file mode {}\n", mode(state.kind)));
+            }
+            TreeStatus::Deletion { previous_state } => {
+                out.push_str(format!("deleted file mode {}\n", mode(previous_state.kind)));
+            }
+            TreeStatus::Modification { .. } | TreeStatus::Rename { .. } => {
+                if let (Some(previous_state), Some(state)) = (previous_state, state) {
+                    if previous_state.kind != state.kind {
+                        out.push_str(format!(
+                            "old mode {}\nnew mode {}\n",
+                            mode(previous_state.kind),
+                            mode(state.kind)
+                        ));
+                    }
+                }
+                if let TreeStatus::Rename { previous_path, .. } = &change.status {
+                    out.push_str(format!("rename from {previous_path}\nrename to {path}\n"));
+                }
+            }
+        }
+
+        let previous_id = previous_state.map(|state| state.id);
+        let id = state.map(|state| state.id);
+        if previous_id == id {
+            // Only the mode or the location changed.
+            continue;
+        }
+        let abbreviated = |id: Option<gix::ObjectId>| {
+            id.map_or_else(
+                || "0000000".to_owned(),
+                |id| id.to_hex_with_len(7).to_string(),
+            )
+        };
+        out.push_str(format!(
+            "index {}..{}",
+            abbreviated(previous_id),
+            abbreviated(id)
+        ));
+        if let TreeStatus::Modification { .. } | TreeStatus::Rename { .. } = &change.status {
+            if let (Some(previous_state), Some(state)) = (previous_state, state) {
+                if previous_state.kind == state.kind {
+                    out.push_str(format!(" {}", mode(state.kind)));
+                }
+            }
+        }
+        out.push_str("\n");
+
+        let old_label = match previous_state {
+            Some(_) => format!("a/{previous_path}"),
+            None => "/dev/null".to_owned(),
+        };
+        let new_label = match state {
+            Some(_) => format!("b/{path}"),
+            None => "/dev/null".to_owned(),
+        };
+        let is_submodule = |state: Option<but_core::ChangeState>| {
+            state.is_some_and(|state| state.kind == EntryKind::Commit)
+        };
+        if is_submodule(previous_state) || is_submodule(state) {
+            out.push_str(format!("--- {old_label}\n+++ {new_label}\n"));
+            match (previous_state, state) {
+                (Some(previous_state), Some(state)) => out.push_str(format!(
+                    "@@ -1 +1 @@\n-Subproject commit {}\n+Subproject commit {}\n",
+                    previous_state.id, state.id
+                )),
+                (None, Some(state)) => {
+                    out.push_str(format!("@@ -0,0 +1 @@\n+Subproject commit {}\n", state.id))
+                }
+                (Some(previous_state), None) => out.push_str(format!(
+                    "@@ -1 +0,0 @@\n-Subproject commit {}\n",
+                    previous_state.id
+                )),
+                (None, None) => {}
+            }
+            continue;
+        }
+
+        match change.unified_diff(repo, CONTEXT_LINES)? {
+            UnifiedDiff::Binary | UnifiedDiff::TooLarge { .. } => {
+                out.push_str(format!("Binary files {old_label} and {new_label} differ\n"));
+            }
+            UnifiedDiff::Patch { hunks, .. } => {
+                out.push_str(format!("--- {old_label}\n+++ {new_label}\n"));
+                let old_lines = lines_without_final_newline(repo, previous_id)?;
+                let new_lines = lines_without_final_newline(repo, id)?;
+                for hunk in hunks {
+                    write_hunk(out, &hunk, old_lines, new_lines);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Return the amount of lines in the blob with `id` if it doesn't end with a newline, or `None` if it does or doesn't exist.
+fn lines_without_final_newline(
+    repo: &gix::Repository,
+    id: Option<gix::ObjectId>,
+) -> anyhow::Result<Option<u32>> {
+    let Some(id) = id else {
+        return Ok(None);
+    };
+    let blob = repo.find_blob(id)?;
+    Ok(match blob.data.last() {
+        None | Some(b'\n') => None,
+        Some(_) => Some(blob.data.lines().count() as u32),
+    })
+}
+
+/// Write `hunk` and mark its last old or new line as lacking a newline if it's the last line of a file
+/// that doesn't end with a newline, as indicated by `old_lines` and `new_lines`.
+fn write_hunk(
+    out: &mut BString,
+    hunk: &but_core::unified_diff::DiffHunk,
+    old_lines: Option<u32>,
+    new_lines: Option<u32>,
+) {
+    let reaches_end = |lines: Option<u32>, start: u32, len: u32| {
+        lines.is_some_and(|lines| len > 0 && start + len - 1 == lines)
+    };
+    let mut lines = hunk.diff.lines_with_terminator();
+    if let Some(header) = lines.next() {
+        out.push_str(header);
+    }
+    let lines: Vec<_> = lines.collect();
+    let last_line_with = |kind: u8| {
+        lines.iter().rposition(|line| {
+            line.first()
+                .is_some_and(|first| *first == kind || *first == b' ')
+        })
+    };
+    let old_end = reaches_end(old_lines, hunk.old_start, hunk.old_lines)
+        .then(|| last_line_with(b'-'))
+        .flatten();
+    let new_end = reaches_end(new_lines, hunk.new_start, hunk.new_lines)
+        .then(|| last_line_with(b'+'))
+        .flatten();
+    for (idx, line) in lines.iter().enumerate() {
+        out.push_str(line);
+        if Some(idx) == old_end || Some(idx) == new_end {
+            out.push_str("\\ No newline at end of file\n");
+        }
+    }
+}
+
+fn mode(kind: EntryKind) -> &'static str {
+    match kind {
+        EntryKind::Tree => "040000",
+        EntryKind::Blob => "100644",
+        EntryKind::BlobExecutable => "100755",
+        EntryKind::Link => "120000",
+        EntryKind::Commit => "160000",
+    }
+}
+
+/// Encode `name` so it can be used as display name in an address header.
+fn encode_display_name(name: &BStr) -> String {
+    let name = name.to_str_lossy();
+    if !name.is_ascii() {
+        encode_header_text(&name)
+    } else if name.contains(|c: char| "()<>[]:;@\\,.\"".contains(c)) {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        name.into_owned()
+    }
+}
+
+/// Encode `text` as RFC 2047 encoded words if it contains characters that can't be used in headers as is.
+/// As encoded words must not be longer than 75 characters, they are folded onto continuation lines as needed.
+fn encode_header_text(text: &str) -> String {
+    /// The longest an encoded word may be, including its `=?UTF-8?q?` prefix and `?=` suffix.
+    const MAX_ENCODED_WORD_LEN: usize = 75;
+    const PREFIX: &str = "=?UTF-8?q?";
+    const SUFFIX: &str = "?=";
+    if text.is_ascii() {
+        return text.to_owned();
+    }
+    let mut out = String::from(PREFIX);
+    let mut word_len = PREFIX.len();
+    let mut buf = [0; 4];
+    for char in text.chars() {
+        // Characters are encoded as a whole so their bytes are never split across encoded words.
+        let mut encoded = String::new();
+        for byte in char.encode_utf8(&mut buf).bytes() {
+            match byte {
+                b' ' => encoded.push('_'),
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'!' | b'*' | b'+' | b'-' | b'/' => {
+                    encoded.push(byte as char)
+                }
+                _ => encoded.push_str(&format!("={byte:02X}")),
+            }
+        }
+        if word_len + encoded.len() + SUFFIX.len() > MAX_ENCODED_WORD_LEN {
+            out.push_str(SUFFIX);
+            out.push_str("\n ");
+            out.push_str(PREFIX);
+            word_len = PREFIX.len();
+        }
+        out.push_str(&encoded);
+        word_len += encoded.len();
+    }
+    out.push_str(SUFFIX);
+    out
+}
+
+/// Split `mbox` into its emails, each without the line starting with `From ` that separates them.
+/// If there is no such line, `mbox` is considered to be a single email.
+fn split_mbox(mbox: &[u8]) -> Vec<&[u8]> {
+    let mut mails = Vec::new();
+    let mut start = None;
+    let mut pos = 0;
+    let mut previous_line_is_empty = true;
+    let mut lines = mbox.lines_with_terminator().peekable();
+    while let Some(line) = lines.next() {
+        let line_end = pos + line.len();
+        let is_separator = previous_line_is_empty
+            && line.starts_with(b"From ")
+            && lines.peek().is_some_and(|next| is_header_line(next));
+        if is_separator {
+            if let Some(start) = start {
+                mails.extend(mbox.get(start..pos));
+            }
+            start = Some(line_end);
+        }
+        previous_line_is_empty = line.trim().is_empty();
+        pos = line_end;
+    }
+    match start {
+        Some(start) => mails.extend(mbox.get(start..)),
+        None if !mbox.trim().is_empty() => mails.push(mbox),
+        None => {}
+    }
+    mails
+}
+
+fn is_header_line(line: &[u8]) -> bool {
+    line.find_byte(b':').is_some_and(|colon| {
+        colon > 0
+            && line
+                .get(..colon)
+                .is_some_and(|key| !key.contains(&b' ') && !key.contains(&b'\t'))
+    })
+}
+
+/// The parts of a single email that are relevant to create a commit from it.
+struct Mail {
+    author: gix::actor::Signature,
+    subject: String,
+    message: BString,
+    patch: Option<BString>,
+}
+
+fn parse_mail(mail: &[u8]) -> anyhow::Result<Mail> {
+    let mut lines = mail.lines_with_terminator().peekable();
+    let mut headers = Vec::<(String, String)>::new();
+    while let Some(line) = lines.next() {
+        if line.trim().is_empty() {
+            break;
+        }
+        let mut line = line.trim_end().to_str_lossy().into_owned();
+        while let Some(continuation) = lines.next_if(|next| {
+            next.first()
+                .is_some_and(|first| *first == b' ' || *first == b'\t')
+        }) {
+            line.push(' ');
+            line.push_str(&continuation.trim().to_str_lossy());
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            headers.push((key.trim().to_ascii_lowercase(), value.trim().to_owned()));
+        }
+    }
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    if let Some(encoding) = header("content-transfer-encoding") {
+        if !["7bit", "8bit", "binary"].contains(&encoding.to_ascii_lowercase().as_str()) {
+            bail!("Emails with content transfer encoding '{encoding}' are not supported");
+        }
+    }
+    let mut from = header("from").map(ToOwned::to_owned);
+    let mut date = header("date").map(ToOwned::to_owned);
+    let mut subject = header("subject").map(ToOwned::to_owned);
+
+    // Headers at the beginning of the body override those of the email, for instance if it was sent by someone else.
+    while lines.next_if(|line| line.trim().is_empty()).is_some() {}
+    let mut in_body_headers = Vec::new();
+    while let Some(line) = lines.next_if(|line| {
+        [&b"From: "[..], b"Date: ", b"Subject: "]
+            .iter()
+            .any(|prefix| line.starts_with(prefix))
+    }) {
+        in_body_headers.push(line.trim_end().to_str_lossy().into_owned());
+    }
+    let mut message = BString::default();
+    if lines.peek().is_none_or(|line| line.trim().is_empty()) {
+        for line in in_body_headers {
+            let (key, value) = line.split_once(':').expect("checked by prefix");
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            let value = Some(value.to_owned());
+            match key {
+                "From" => from = value,
+                "Date" => date = value,
+                _ => subject = value,
+            }
+        }
+    } else {
+        // They are just the first lines of the message.
+        for line in in_body_headers {
+            message.push_str(line);
+            message.push(b'\n');
+        }
+    }
+
+    let mut patch = None;
+    let mut in_message = true;
+    while let Some(line) = lines.next() {
+        let starts_diff = line.starts_with(b"diff ")
+            || (line.starts_with(b"--- ")
+                && lines.peek().is_some_and(|next| next.starts_with(b"+++ ")));
+        if in_message {
+            if line.trim_end() == b"---" || line.starts_with(b"Index: ") {
+                in_message = false;
+                continue;
+            }
+            if !starts_diff {
+                message.push_str(line);
+                continue;
+            }
+            in_message = false;
+        }
+        if starts_diff && patch.is_none() {
+            patch = Some(BString::default());
+        }
+        if let Some(patch) = patch.as_mut() {
+            patch.push_str(line);
+        }
+    }
+
+    let subject = strip_subject_prefixes(&decode_header_text(
+        subject
+            .as_deref()
+            .context("The email doesn't have a subject")?,
+    ))
+    .to_owned();
+    let (name, email) =
+        parse_address(&decode_header_text(from.as_deref().with_context(|| {
+            format!("The email '{subject}' doesn't have a sender")
+        })?))
+        .with_context(|| format!("Couldn't parse the sender of the email '{subject}'"))?;
+    let time = match date {
+        Some(date) => gix::date::parse(&date, Some(std::time::SystemTime::now()))
+            .with_context(|| format!("Couldn't parse date '{date}' of the email '{subject}'"))?,
+        None => gix::date::Time::now_local_or_utc(),
+    };
+    Ok(Mail {
+        author: gix::actor::Signature {
+            name: name.into(),
+            email: email.into(),
+            time,
+        },
+        subject,
+        message,
+        patch,
+    })
+}
+
+/// Remove prefixes like `Re:` or `[PATCH v2 1/3]` from `subject`.
+fn strip_subject_prefixes(mut subject: &str) -> &str {
+    loop {
+        subject = subject.trim_start();
+        if let Some(rest) = subject
+            .strip_prefix('[')
+            .and_then(|rest| rest.split_once(']'))
+            .map(|(_, rest)| rest)
+        {
+            subject = rest;
+        } else if subject
+            .get(..3)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case("re:"))
+        {
+            subject = subject.get(3..).unwrap_or_default();
+        } else {
+            return subject.trim_end();
+        }
+    }
+}
+
+/// Split an address like `"Name" <email>` into the name and the email.
+fn parse_address(address: &str) -> Option<(String, String)> {
+    let Some((name, rest)) = address.split_once('<') else {
+        let email = address.trim();
+        return email
+            .contains('@')
+            .then(|| (String::new(), email.to_owned()));
+    };
+    let email = rest.split_once('>')?.0.trim();
+    let name = name.trim();
+    let name = match name
+        .strip_prefix('"')
+        .and_then(|name| name.strip_suffix('"'))
+    {
+        Some(quoted) => quoted.replace("\\\"", "\"").replace("\\\\", "\\"),
+        None => name.to_owned(),
+    };
+    Some((name, email.to_owned()))
+}
+
+/// Decode all RFC 2047 encoded words in `text`, like `=?UTF-8?q?J=C3=B6rg?=`.
+fn decode_header_text(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    let mut previous_was_encoded = false;
+    while let Some(start) = rest.find("=?") {
+        let (before, candidate) = rest.split_at(start);
+        let Some((decoded, len)) = decode_encoded_word(candidate) else {
+            out.push_str(before);
+            out.push_str("=?");
+            rest = candidate.get(2..).unwrap_or_default();
+            previous_was_encoded = false;
+            continue;
+        };
+        // Whitespace between adjacent encoded words is ignored.
+        if !(previous_was_encoded && before.trim().is_empty()) {
+            out.push_str(before);
+        }
+        out.push_str(&decoded);
+        rest = candidate.get(len..).unwrap_or_default();
+        previous_was_encoded = true;
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Decode the encoded word at the beginning of `text` and return it along with the amount of bytes it occupied.
+fn decode_encoded_word(text: &str) -> Option<(String, usize)> {
+    let inner = text.strip_prefix("=?")?;
+    let (charset, inner) = inner.split_once('?')?;
+    let (encoding, inner) = inner.split_once('?')?;
+    let (encoded, _) = inner.split_once("?=")?;
+    let len = 2 + charset.len() + 1 + encoding.len() + 1 + encoded.len() + 2;
+    let bytes = match encoding {
+        "q" | "Q" => {
+            let mut bytes = Vec::new();
+            let mut input = encoded.bytes();
+            while let Some(byte) = input.next() {
+                match byte {
+                    b'_' => bytes.push(b' '),
+                    b'=' => {
+                        let hex = [input.next()?, input.next()?];
+                        bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+                    }
+                    _ => bytes.push(byte),
+                }
+            }
+            bytes
+        }
+        "b" | "B" => decode_base64(encoded)?,
+        _ => return None,
+    };
+    let decoded = if charset.eq_ignore_ascii_case("iso-8859-1") {
+        bytes.iter().map(|byte| *byte as char).collect()
+    } else {
+        bytes.to_str_lossy().into_owned()
+    };
+    Some((decoded, len))
+}
+
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut buf = 0u32;
+    let mut bits = 0;
+    for byte in encoded.bytes().filter(|byte| *byte != b'=') {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buf = (buf << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buf >> bits) as u8);
+            buf &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
@@ -84,6 +84,37 @@ impl VirtualBranchesTomlMetadata {
     pub fn path(&self) -> &Path {
         &self.snapshot.path
     }
+
+    /// Rename the branch `ref_name` to `new_ref_name` within the stack it belongs to, so the stack and all data
+    /// associated with the branch are kept. The references themselves are not touched.
+    ///
+    /// Return `false` if there was no data for `ref_name`.
+    pub fn rename_branch(
+        &mut self,
+        ref_name: &FullNameRef,
+        new_ref_name: &FullNameRef,
+    ) -> anyhow::Result<bool> {
+        if !self.branch(new_ref_name)?.is_default() {
+            bail!(
+                "There already is a branch named '{}'",
+                new_ref_name.shorten()
+            );
+        }
+        let short_name = ref_name.shorten();
+        let Some(branch) = self
+            .snapshot
+            .content
+            .branches
+            .values_mut()
+            .flat_map(|stack| stack.heads.iter_mut())
+            .find(|branch| short_name == branch.name().as_str())
+        else {
+            return Ok(false);
+        };
+        branch.name = new_ref_name.shorten().to_string();
+        self.snapshot.changed_at = Some(Instant::now());
+        Ok(true)
+    }
 }
 
 // Emergency-behaviour in case the application winds down, we don't want data-loss (at least a chance).
@@ -193,7 +224,9 @@ impl RefMetadata for VirtualBranchesTomlMetadata {
 
         // Find exactly one stack-id per branch name, and assign all branches to it.
         // `stacks` is the target state, and we have to make an actual stack look like it.
+        let branches_before = self.snapshot.content.branches.clone();
         let mut seen_stack_ids = HashSet::new();
+        let mut stack_ids_in_order = Vec::new();
         for stack in &value.stacks {
             let stack_branches = &stack.branches;
             let mut branches_to_create = Vec::new();
@@ -258,6 +291,7 @@ impl RefMetadata for VirtualBranchesTomlMetadata {
                 ))
             }
             stack.in_workspace = !stack.heads.is_empty();
+            stack_ids_in_order.push(stack.id);
             stack.heads.sort_by_key(|head| {
                 stack_branches.iter().enumerate().find_map(|(idx, branch)| {
                     (branch.ref_name.shorten() == head.name().as_str()).then_some(idx)
@@ -277,12 +311,27 @@ impl RefMetadata for VirtualBranchesTomlMetadata {
             stack.heads.reverse()
         }
 
+        // Stacks are ordered like in the workspace, reusing the order values they already have.
+        let mut orders: Vec<_> = stack_ids_in_order
+            .iter()
+            .filter_map(|id| self.snapshot.content.branches.get(id).map(|s| s.order))
+            .collect();
+        orders.sort();
+        for (stack_id, order) in stack_ids_in_order.iter().zip(orders) {
+            if let Some(stack) = self.snapshot.content.branches.get_mut(stack_id) {
+                stack.order = order;
+            }
+        }
+
         for (key, stack) in &mut self.snapshot.content.branches {
             if seen_stack_ids.contains(key) {
                 continue;
             }
             stack.in_workspace = false;
         }
+        if self.snapshot.content.branches != branches_before {
+            self.snapshot.changed_at = Some(Instant::now());
+        }
         Ok(())
     }
 
@@ -0,0 +1,19 @@
+#!/usr/bin/env bash
+
+### Description
+# Two branches, `A` and `B`, which change the same line in `file` differently, with `B` having another commit on top
+# that adds `other-file`. Picking the first commit of `B` onto `A` yields a conflicted commit.
+set -eu -o pipefail
+
+git init
+printf '1\n2\n3\n' >file
+git add . && git commit -m init
+
+git checkout -b A
+printf '1\na\n3\n' >file
+git commit -am "change second line to a"
+
+git checkout -b B main
+printf '1\nb\n3\n' >file
+git commit -am "change second line to b"
+echo other >other-file && git add . && git commit -m "add other-file"
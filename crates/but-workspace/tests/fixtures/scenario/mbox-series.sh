@@ -0,0 +1,20 @@
+#!/usr/bin/env bash
+
+### Description
+# A branch `feature` on top of `main` with commits that exercise what patch emails can express: an author with a
+# non-ASCII name, a message with trailers, an executable file, a file without trailing newline and a rename.
+# `feature.mbox` is the untracked output of `git format-patch` for the branch, with a cover letter that wasn't filled in.
+set -eu -o pipefail
+
+git init
+printf 'one\ntwo\nthree\n' >file && git add . && git commit -m "init"
+
+git checkout -b feature
+printf 'one\ntwo\nthree\nfour' >file && git add . &&
+  GIT_AUTHOR_NAME="Jörg Müller" GIT_AUTHOR_EMAIL="joerg@example.com" git commit -m "add a line without newline"
+printf '#!/bin/sh\n' >script && chmod +x script && git add . &&
+  git commit -m "add an executable script" -m "It doesn't do much yet." -m "Signed-off-by: Author <author@example.com>"
+git mv file renamed && git commit -m "rename file"
+
+git checkout main
+git format-patch --stdout --cover-letter main..feature >feature.mbox
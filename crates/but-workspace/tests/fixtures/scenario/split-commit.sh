@@ -0,0 +1,16 @@
+#!/usr/bin/env bash
+
+### Description
+# A single branch with a commit that changes two lines far apart in `file` and adds `new-file`,
+# followed by another commit on top.
+set -eu -o pipefail
+
+git init
+seq 20 >file
+git add . && git commit -m init
+
+{ echo 1; echo two; seq 3 17; echo eighteen; seq 19 20; } >file
+echo new >new-file
+git add . && git commit -m "mixed changes"
+
+echo other >other-file && git add . && git commit -m "top"
@@ -0,0 +1,16 @@
+#!/usr/bin/env bash
+
+### Description
+# Two branches A and B on top of `main`, each adding a file of its own, with `HEAD` on `main`.
+set -eu -o pipefail
+
+git init
+echo base >base && git add . && git commit -m "init"
+
+git checkout -b A
+echo a >a && git add . && git commit -m "add a"
+
+git checkout -b B main
+echo b >b && git add . && git commit -m "add b"
+
+git checkout main
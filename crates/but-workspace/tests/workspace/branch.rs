@@ -0,0 +1,158 @@
+use crate::utils::{read_only_in_memory_scenario, writable_scenario};
+use but_core::RefMetadata;
+use but_core::ref_metadata::ValueInfo;
+use but_testsupport::gix_testtools::tempfile;
+use but_workspace::VirtualBranchesTomlMetadata;
+use but_workspace::branch::{
+    add_branch_to_workspace, add_branch_to_workspace_and_update_refs, remove_branch_from_workspace,
+    remove_branch_from_workspace_and_update_refs,
+};
+use gix::prelude::ObjectIdExt;
+
+#[test]
+fn add_and_remove_branches_by_tip() -> anyhow::Result<()> {
+    let repo = read_only_in_memory_scenario("two-branches-with-distinct-files")?;
+    let (mut meta, _tmp) = empty_metadata()?;
+    let main = repo.rev_parse_single("main")?.detach();
+    let a = repo.rev_parse_single("A")?.detach();
+    let b = repo.rev_parse_single("B")?.detach();
+
+    let outcome = add_branch_to_workspace(&repo, a, main, Some(main), &mut meta)?;
+    assert!(outcome.rebase_output.is_none());
+    let ws = workspace_commit(&repo, outcome.workspace_tip)?;
+    assert!(ws.is_managed());
+    assert_eq!(
+        ws.parents.as_slice(),
+        [a],
+        "the target isn't a stack of the workspace"
+    );
+
+    let outcome = add_branch_to_workspace(&repo, b, outcome.workspace_tip, Some(main), &mut meta)?;
+    let ws = workspace_commit(&repo, outcome.workspace_tip)?;
+    assert_eq!(ws.parents.as_slice(), [a, b]);
+    assert_eq!(
+        tree_entries(&repo, outcome.workspace_tip)?,
+        ["a", "b", "base"],
+        "the trees of all stacks are merged"
+    );
+
+    let err = add_branch_to_workspace(&repo, main, outcome.workspace_tip, Some(main), &mut meta)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Cannot add branch that is already integrated"
+    );
+
+    let outcome =
+        remove_branch_from_workspace(&repo, a, outcome.workspace_tip, Some(main), &mut meta)?;
+    let ws = workspace_commit(&repo, outcome.workspace_tip)?;
+    assert_eq!(ws.parents.as_slice(), [b]);
+    assert_eq!(tree_entries(&repo, outcome.workspace_tip)?, ["b", "base"]);
+
+    let outcome =
+        remove_branch_from_workspace(&repo, b, outcome.workspace_tip, Some(main), &mut meta)?;
+    let ws = workspace_commit(&repo, outcome.workspace_tip)?;
+    assert_eq!(
+        ws.parents.as_slice(),
+        [main],
+        "without stacks, the workspace commit sits on top of the target"
+    );
+    assert_eq!(tree_entries(&repo, outcome.workspace_tip)?, ["base"]);
+
+    let err = remove_branch_from_workspace(&repo, a, outcome.workspace_tip, Some(main), &mut meta)
+        .unwrap_err();
+    assert!(err.to_string().contains("isn't a stack of the workspace"));
+    Ok(())
+}
+
+#[test]
+fn add_and_remove_branches_by_name() -> anyhow::Result<()> {
+    let (repo, _tmp) = writable_scenario("two-branches-with-distinct-files");
+    let (mut meta, _meta_tmp) = empty_metadata()?;
+    let main = repo.rev_parse_single("main")?.detach();
+    let a = repo.rev_parse_single("A")?.detach();
+    let workspace_ref: gix::refs::FullName = "refs/heads/gitbutler/workspace".try_into()?;
+    repo.reference(
+        workspace_ref.as_ref(),
+        main,
+        gix::refs::transaction::PreviousValue::MustNotExist,
+        "create workspace",
+    )?;
+
+    let outcome = add_branch_to_workspace_and_update_refs(
+        &repo,
+        "A".try_into()?,
+        workspace_ref.clone(),
+        Some("main".try_into()?),
+        &mut meta,
+    )?;
+    assert_eq!(
+        repo.head_id()?,
+        outcome.workspace_tip,
+        "HEAD is on the new tip"
+    );
+    assert_eq!(
+        repo.head_name()?.as_ref().map(|name| name.as_bstr()),
+        Some(workspace_ref.as_bstr()),
+        "HEAD points to the workspace reference"
+    );
+    assert_eq!(
+        workspace_commit(&repo, outcome.workspace_tip)?
+            .parents
+            .as_slice(),
+        [a]
+    );
+    let ws = meta.workspace(workspace_ref.as_ref())?;
+    assert_eq!(ws.stacks.len(), 1);
+    assert_eq!(
+        ws.stacks[0].ref_name().map(|name| name.as_bstr()),
+        Some("refs/heads/A".into()),
+        "the branch is now a stack in the workspace"
+    );
+
+    let outcome = remove_branch_from_workspace_and_update_refs(
+        &repo,
+        "A".try_into()?,
+        workspace_ref.clone(),
+        Some("main".try_into()?),
+        &mut meta,
+    )?;
+    assert_eq!(repo.head_id()?, outcome.workspace_tip);
+    assert_eq!(
+        workspace_commit(&repo, outcome.workspace_tip)?
+            .parents
+            .as_slice(),
+        [main]
+    );
+    let ws = meta.workspace(workspace_ref.as_ref())?;
+    assert!(
+        ws.stacks.is_empty(),
+        "the stack was removed from the workspace"
+    );
+    assert!(
+        !meta.branch("refs/heads/A".try_into()?)?.is_default(),
+        "but the branch metadata is kept"
+    );
+    Ok(())
+}
+
+fn empty_metadata() -> anyhow::Result<(VirtualBranchesTomlMetadata, tempfile::TempDir)> {
+    let tmp = tempfile::tempdir()?;
+    let meta = VirtualBranchesTomlMetadata::from_path(tmp.path().join("vb.toml"))?;
+    Ok((meta, tmp))
+}
+
+fn workspace_commit(
+    repo: &gix::Repository,
+    id: gix::ObjectId,
+) -> anyhow::Result<but_workspace::WorkspaceCommit<'_>> {
+    but_workspace::WorkspaceCommit::from_id(id.attach(repo))
+}
+
+fn tree_entries(repo: &gix::Repository, commit_id: gix::ObjectId) -> anyhow::Result<Vec<String>> {
+    let tree = commit_id.attach(repo).object()?.peel_to_tree()?;
+    Ok(tree
+        .iter()
+        .map(|entry| Ok(entry?.filename().to_string()))
+        .collect::<anyhow::Result<_>>()?)
+}
@@ -0,0 +1,204 @@
+use crate::utils::{CONTEXT_LINES, diff_spec, writable_scenario};
+use but_testsupport::assure_stable_env;
+use but_workspace::commit_engine::{BranchChanges, RejectionReason, create_commit_on_branch};
+use gitbutler_stack::{CommitOrChangeId, Stack, StackBranch, VirtualBranchesState};
+use gix::prelude::ObjectIdExt;
+
+#[test]
+fn patch_onto_branch_that_is_not_checked_out() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let (repo, _tmp) = writable_scenario("two-branches-with-distinct-files");
+    let head_id = repo.head_id()?.detach();
+    let a_id = repo.rev_parse_single("A")?.detach();
+    let mut vb = VirtualBranchesState::default();
+    let outcome = create_commit_on_branch(
+        &repo,
+        &mut vb,
+        "refs/heads/A".try_into()?,
+        "apply a patch",
+        BranchChanges::Patch(
+            "diff --git a/a b/a
+index 7898192..f2ba8f8 100644
+--- a/a
++++ b/a
+@@ -1 +1,2 @@
+ a
++more
+diff --git a/c b/c
+new file mode 100644
+index 0000000..f2ad6c7
+--- /dev/null
++++ b/c
+@@ -0,0 +1 @@
++c
+"
+            .into(),
+        ),
+        CONTEXT_LINES,
+    )?;
+    assert_eq!(outcome.rejected_specs, []);
+    let new_commit = outcome.new_commit.expect("the patch applies");
+    assert_eq!(repo.rev_parse_single("A")?.detach(), new_commit);
+    assert_eq!(repo.head_id()?.detach(), head_id, "HEAD isn't affected");
+    assert_eq!(outcome.references.len(), 1);
+
+    let commit = but_core::Commit::from_id(new_commit.attach(&repo))?;
+    assert_eq!(commit.inner.parents[..], [a_id]);
+    assert_eq!(commit.inner.message, "apply a patch");
+    assert_eq!(file(&repo, new_commit, "a")?.as_deref(), Some("a\nmore\n"));
+    assert_eq!(file(&repo, new_commit, "c")?.as_deref(), Some("c\n"));
+    assert_eq!(file(&repo, new_commit, "base")?.as_deref(), Some("base\n"));
+    Ok(())
+}
+
+#[test]
+fn patch_hunks_that_do_not_apply_are_rejected() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let (repo, _tmp) = writable_scenario("two-branches-with-distinct-files");
+    let mut vb = VirtualBranchesState::default();
+    let outcome = create_commit_on_branch(
+        &repo,
+        &mut vb,
+        "refs/heads/A".try_into()?,
+        "partially apply a patch",
+        BranchChanges::Patch(
+            "--- a/a
++++ b/a
+@@ -1 +1 @@
+-not a
++b
+--- a/base
++++ b/base
+@@ -1 +1 @@
+-base
++new base
+"
+            .into(),
+        ),
+        CONTEXT_LINES,
+    )?;
+    assert_eq!(outcome.rejected_specs.len(), 1);
+    let (reason, spec) = &outcome.rejected_specs[0];
+    assert_eq!(*reason, RejectionReason::PatchDoesNotApply);
+    assert_eq!(spec.path, "a");
+    assert_eq!(spec.hunk_headers.len(), 1);
+
+    let new_commit = outcome.new_commit.expect("the other file applies");
+    assert_eq!(file(&repo, new_commit, "a")?.as_deref(), Some("a\n"));
+    assert_eq!(
+        file(&repo, new_commit, "base")?.as_deref(),
+        Some("new base\n")
+    );
+    Ok(())
+}
+
+#[test]
+fn tree_changes_are_picked_onto_the_branch() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let (repo, _tmp) = writable_scenario("two-branches-with-distinct-files");
+    let tree_of = |spec: &str| -> anyhow::Result<gix::ObjectId> {
+        Ok(repo.rev_parse_single(spec)?.object()?.peel_to_tree()?.id)
+    };
+    let mut vb = VirtualBranchesState::default();
+    let outcome = create_commit_on_branch(
+        &repo,
+        &mut vb,
+        "refs/heads/A".try_into()?,
+        "add b from B",
+        BranchChanges::Trees {
+            previous_tree: tree_of("main")?,
+            tree: tree_of("B")?,
+            changes: vec![diff_spec(None, "b", None)],
+        },
+        CONTEXT_LINES,
+    )?;
+    assert_eq!(outcome.rejected_specs, []);
+    let new_commit = outcome.new_commit.expect("b can be added");
+    assert_eq!(repo.rev_parse_single("A")?.detach(), new_commit);
+    assert_eq!(file(&repo, new_commit, "a")?.as_deref(), Some("a\n"));
+    assert_eq!(file(&repo, new_commit, "b")?.as_deref(), Some("b\n"));
+    Ok(())
+}
+
+#[test]
+fn unapplied_stack_head_and_tree_follow_the_new_commit() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let (repo, _tmp) = writable_scenario("two-branches-with-distinct-files");
+    let a_id = repo.rev_parse_single("A")?.detach();
+    let stack = Stack::new_with_just_heads(
+        vec![StackBranch::new(
+            CommitOrChangeId::CommitId(a_id.to_string()),
+            "A".into(),
+            None,
+            &repo,
+        )?],
+        0,
+        0,
+        false,
+    );
+    let stack_id = stack.id;
+    let mut vb = VirtualBranchesState::default();
+    vb.branches.insert(stack_id, stack);
+
+    let outcome = create_commit_on_branch(
+        &repo,
+        &mut vb,
+        "refs/heads/A".try_into()?,
+        "change a",
+        BranchChanges::Patch("--- a/a\n+++ b/a\n@@ -1 +1 @@\n-a\n+changed\n".into()),
+        CONTEXT_LINES,
+    )?;
+    let new_commit = outcome.new_commit.expect("the patch applies");
+    assert_eq!(outcome.references.len(), 1);
+
+    let stack = &vb.branches[&stack_id];
+    assert_eq!(
+        stack.head(&repo)?.to_string(),
+        new_commit.to_string(),
+        "the segment was moved to the new commit"
+    );
+    assert_eq!(
+        stack.tree.to_string(),
+        repo.find_commit(new_commit)?.tree_id()?.to_string(),
+        "the stack tree is the one of its new head"
+    );
+    Ok(())
+}
+
+#[test]
+fn checked_out_branch_is_rejected() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let (repo, _tmp) = writable_scenario("two-branches-with-distinct-files");
+    let mut vb = VirtualBranchesState::default();
+    let err = create_commit_on_branch(
+        &repo,
+        &mut vb,
+        "refs/heads/main".try_into()?,
+        "won't work",
+        BranchChanges::Patch("--- a/base\n+++ b/base\n@@ -1 +1 @@\n-base\n+changed\n".into()),
+        CONTEXT_LINES,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Branch 'refs/heads/main' is checked out and can't be committed to without the worktree"
+    );
+    Ok(())
+}
+
+fn file(
+    repo: &gix::Repository,
+    commit_id: gix::ObjectId,
+    path: &str,
+) -> anyhow::Result<Option<String>> {
+    let tree = commit_id.attach(repo).object()?.peel_to_tree()?;
+    let Some(entry) = tree.lookup_entry_by_path(path)? else {
+        return Ok(None);
+    };
+    Ok(Some(String::from_utf8(entry.object()?.detach().data)?))
+}
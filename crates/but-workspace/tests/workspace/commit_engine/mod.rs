@@ -1,3 +1,6 @@
 mod amend_commit;
+mod branch_commit;
 mod new_commit;
+mod patch_commit;
 mod refs_update;
+mod split_commit;
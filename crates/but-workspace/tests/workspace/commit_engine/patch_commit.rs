@@ -0,0 +1,139 @@
+use crate::utils::writable_scenario;
+use but_testsupport::assure_stable_env;
+use but_workspace::commit_engine::{
+    Destination, ReferenceFrame, RejectionReason, create_commit_from_patch_and_update_refs,
+};
+use gitbutler_stack::VirtualBranchesState;
+
+#[test]
+fn patch_on_top_of_head_is_checked_out() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let (repo, _tmp) = writable_scenario("two-branches-with-distinct-files");
+    let head_id = repo.head_id()?.detach();
+    let mut vb = VirtualBranchesState::default();
+    let outcome = create_commit_from_patch_and_update_refs(
+        &repo,
+        ReferenceFrame::default(),
+        &mut vb,
+        Destination::NewCommit {
+            parent_commit_id: Some(head_id),
+            stack_segment: None,
+            message: "from a patch".into(),
+        },
+        b"diff --git a/base b/base
+--- a/base
++++ b/base
+@@ -1 +1 @@
+-not base
++changed
+diff --git a/c b/c
+new file mode 100644
+--- /dev/null
++++ b/c
+@@ -0,0 +1 @@
++c
+",
+    )?;
+    assert_eq!(outcome.rejected_specs.len(), 1);
+    let (reason, spec) = &outcome.rejected_specs[0];
+    assert_eq!(*reason, RejectionReason::PatchDoesNotApply);
+    assert_eq!(spec.path, "base");
+
+    let new_commit = outcome.new_commit.expect("c can be added");
+    assert_eq!(repo.head_id()?.detach(), new_commit, "HEAD moved along");
+    assert_eq!(worktree_file(&repo, "c")?.as_deref(), Some("c\n"));
+    assert_eq!(worktree_file(&repo, "base")?.as_deref(), Some("base\n"));
+    assert_eq!(
+        but_core::diff::worktree_changes(&repo)?.changes.len(),
+        0,
+        "the worktree and index match the new commit"
+    );
+    Ok(())
+}
+
+#[test]
+fn patch_to_branch_outside_of_workspace_leaves_worktree_alone() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let (repo, _tmp) = writable_scenario("two-branches-with-distinct-files");
+    let head_id = repo.head_id()?.detach();
+    let a_id = repo.rev_parse_single("A")?.detach();
+    let mut vb = VirtualBranchesState::default();
+    let outcome = create_commit_from_patch_and_update_refs(
+        &repo,
+        ReferenceFrame {
+            workspace_tip: None,
+            branch_tip: Some(a_id),
+        },
+        &mut vb,
+        Destination::NewCommit {
+            parent_commit_id: Some(a_id),
+            stack_segment: None,
+            message: "from a patch".into(),
+        },
+        b"--- a/a
++++ b/a
+@@ -1 +1,2 @@
+ a
++more
+--- a/base
++++ /dev/null
+@@ -1 +0,0 @@
+-base
+",
+    )?;
+    assert_eq!(outcome.rejected_specs, []);
+    let new_commit = outcome.new_commit.expect("the patch applies");
+    assert_eq!(repo.rev_parse_single("A")?.detach(), new_commit);
+    assert_eq!(repo.head_id()?.detach(), head_id, "HEAD isn't affected");
+
+    assert_eq!(worktree_file(&repo, "a")?, None, "A isn't checked out");
+    assert_eq!(worktree_file(&repo, "base")?.as_deref(), Some("base\n"));
+    assert_eq!(but_core::diff::worktree_changes(&repo)?.changes.len(), 0);
+    Ok(())
+}
+
+#[test]
+fn uncommitted_changes_touched_by_patch_are_not_overwritten() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let (repo, _tmp) = writable_scenario("two-branches-with-distinct-files");
+    let head_id = repo.head_id()?.detach();
+    std::fs::write(
+        repo.workdir_path("base").expect("non-bare"),
+        "uncommitted\n",
+    )?;
+    let mut vb = VirtualBranchesState::default();
+    let err = create_commit_from_patch_and_update_refs(
+        &repo,
+        ReferenceFrame::default(),
+        &mut vb,
+        Destination::NewCommit {
+            parent_commit_id: Some(head_id),
+            stack_segment: None,
+            message: "from a patch".into(),
+        },
+        b"--- a/base\n+++ b/base\n@@ -1 +1 @@\n-base\n+changed\n",
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Cannot apply patch as 'base' has uncommitted changes in the worktree that would be overwritten"
+    );
+    assert_eq!(repo.head_id()?.detach(), head_id, "nothing was committed");
+    assert_eq!(
+        worktree_file(&repo, "base")?.as_deref(),
+        Some("uncommitted\n")
+    );
+    Ok(())
+}
+
+fn worktree_file(repo: &gix::Repository, rela_path: &str) -> anyhow::Result<Option<String>> {
+    let path = repo.workdir_path(rela_path).expect("non-bare");
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
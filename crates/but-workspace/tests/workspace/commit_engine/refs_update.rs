@@ -364,6 +364,7 @@ fn insert_commit_into_single_stack_with_signatures() -> anyhow::Result<()> {
                         Sha1(3d1262e63b945d97e1eaeb736b48cf4dcdb3e9cf),
                     ),
                 ],
+                dropped: [],
             },
         ),
         index: None,
@@ -474,6 +475,50 @@ fn branch_tip_below_non_merge_workspace_commit() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// A commit on top of a branch tip that isn't `HEAD` has nothing to rebase, and the branch is simply moved to the
+/// new commit. This used to fail as a rebase without any commit to pick was attempted.
+#[test]
+fn new_commit_on_top_of_branch_tip_that_is_not_head() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let (repo, _tmp) = writable_scenario("two-branches-with-distinct-files");
+    let head_id = repo.head_id()?.detach();
+    let a_id = repo.rev_parse_single("A")?.detach();
+    let mut vb = VirtualBranchesState::default();
+
+    write_worktree_file(&repo, "new-file", "new\n")?;
+    let outcome = but_workspace::commit_engine::create_commit_and_update_refs(
+        &repo,
+        ReferenceFrame {
+            workspace_tip: None,
+            branch_tip: Some(a_id),
+        },
+        &mut vb,
+        Destination::NewCommit {
+            parent_commit_id: Some(a_id),
+            message: "on top of A".into(),
+            stack_segment: None,
+        },
+        None,
+        to_change_specs_whole_file(but_core::diff::worktree_changes(&repo)?),
+        CONTEXT_LINES,
+    )?;
+
+    assert_eq!(outcome.rejected_specs, []);
+    assert!(
+        outcome.rebase_output.is_none(),
+        "there is nothing above the branch tip to rebase"
+    );
+    let new_commit = outcome.new_commit.expect("the new file can be committed");
+    assert_eq!(
+        repo.rev_parse_single("A")?.detach(),
+        new_commit,
+        "the branch moved to the new commit"
+    );
+    assert_eq!(repo.head_id()?.detach(), head_id, "HEAD isn't affected");
+    Ok(())
+}
+
 #[test]
 fn deletions() -> anyhow::Result<()> {
     assure_stable_env();
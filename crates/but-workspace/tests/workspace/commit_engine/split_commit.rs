@@ -0,0 +1,203 @@
+use crate::utils::{CONTEXT_LINES, diff_spec, hunk_header, writable_scenario};
+use but_testsupport::assure_stable_env;
+use but_workspace::commit_engine::{ReferenceFrame, SplitPiece, split_commit};
+use gitbutler_stack::VirtualBranchesState;
+use gix::prelude::ObjectIdExt;
+
+#[test]
+fn by_file_and_by_hunk() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let (repo, _tmp) = writable_scenario("split-commit");
+    let commit_id = repo.rev_parse_single("@~1")?.detach();
+    let outcome = split_commit(
+        &repo,
+        commit_id,
+        vec![
+            piece("add new-file", [diff_spec(None, "new-file", None)]),
+            piece(
+                "change second line",
+                [diff_spec(None, "file", [hunk_header("-2,1", "+2,1")])],
+            ),
+            piece("the rest", []),
+        ],
+        CONTEXT_LINES,
+    )?;
+    assert!(outcome.rejected_specs.is_empty());
+    assert!(outcome.references.is_empty());
+    assert_eq!(outcome.new_commits.len(), 3);
+
+    let [first, second, third] = outcome.new_commits[..] else {
+        unreachable!("checked above")
+    };
+    assert_eq!(file(&repo, first, "new-file")?.as_deref(), Some("new\n"));
+    assert_eq!(file(&repo, first, "file")?, Some(lines(1..=20, [])));
+    assert_eq!(
+        file(&repo, second, "file")?,
+        Some(lines(1..=20, [(2, "two")]))
+    );
+    assert_eq!(
+        file(&repo, third, "file")?,
+        Some(lines(1..=20, [(2, "two"), (18, "eighteen")]))
+    );
+    assert_eq!(
+        tree_of(&repo, third)?,
+        tree_of(&repo, commit_id)?,
+        "the last commit has all the remaining changes"
+    );
+
+    let original = but_core::Commit::from_id(commit_id.attach(&repo))?;
+    let mut expected_parent = original.inner.parents[0];
+    let mut change_ids = Vec::new();
+    for (id, message) in
+        outcome
+            .new_commits
+            .iter()
+            .zip(["add new-file", "change second line", "the rest"])
+    {
+        let commit = but_core::Commit::from_id(id.attach(&repo))?;
+        assert_eq!(commit.inner.parents[..], [expected_parent]);
+        assert_eq!(commit.inner.message, message);
+        assert_eq!(
+            commit.inner.author, original.inner.author,
+            "authorship is kept"
+        );
+        change_ids.push(commit.headers().expect("change-id is assigned").change_id);
+        expected_parent = *id;
+    }
+    change_ids.sort();
+    change_ids.dedup();
+    assert_eq!(change_ids.len(), 3, "each commit has its own change-id");
+    Ok(())
+}
+
+#[test]
+fn descendants_and_references_are_rebased() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let (repo, _tmp) = writable_scenario("split-commit");
+    let head_id = repo.head_id()?.detach();
+    let commit_id = repo.rev_parse_single("@~1")?.detach();
+    let mut vb = VirtualBranchesState::default();
+    let outcome = but_workspace::commit_engine::split_commit_and_update_refs(
+        &repo,
+        ReferenceFrame {
+            workspace_tip: None,
+            branch_tip: Some(head_id),
+        },
+        &mut vb,
+        commit_id,
+        vec![
+            piece(
+                "second line",
+                [diff_spec(None, "file", [hunk_header("-2,1", "+2,1")])],
+            ),
+            piece(
+                "eighteenth line",
+                [diff_spec(None, "file", [hunk_header("-18,1", "+18,1")])],
+            ),
+            piece("add new-file", []),
+        ],
+        CONTEXT_LINES,
+    )?;
+    assert!(outcome.rejected_specs.is_empty());
+    assert_eq!(
+        file(&repo, outcome.new_commits[1], "file")?,
+        Some(lines(1..=20, [(2, "two"), (18, "eighteen")])),
+        "selections accumulate"
+    );
+    assert_eq!(file(&repo, outcome.new_commits[1], "new-file")?, None);
+
+    let new_head = but_core::Commit::from_id(repo.head_id()?)?;
+    assert_ne!(new_head.id.detach(), head_id, "the branch was rebased");
+    assert_eq!(new_head.inner.message, "top\n");
+    assert_eq!(
+        new_head.inner.parents[..],
+        [*outcome.new_commits.last().expect("three commits")]
+    );
+    assert_eq!(
+        tree_of(&repo, new_head.id.detach())?,
+        tree_of(&repo, head_id)?
+    );
+    assert_eq!(outcome.references.len(), 1, "only the checked-out branch");
+    Ok(())
+}
+
+#[test]
+fn empty_pieces_are_rejected() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let (repo, _tmp) = writable_scenario("split-commit");
+    let commit_id = repo.rev_parse_single("@~1")?.detach();
+    let err = split_commit(
+        &repo,
+        commit_id,
+        vec![
+            piece("nothing", [diff_spec(None, "other-file", None)]),
+            piece("everything", []),
+        ],
+        CONTEXT_LINES,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Commit 1 of the split wouldn't contain any changes"
+    );
+
+    let err = split_commit(
+        &repo,
+        commit_id,
+        vec![piece("everything", [])],
+        CONTEXT_LINES,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "A commit must be split into at least two commits"
+    );
+    Ok(())
+}
+
+fn piece(
+    message: &str,
+    changes: impl IntoIterator<Item = but_workspace::commit_engine::DiffSpec>,
+) -> SplitPiece {
+    SplitPiece {
+        message: message.into(),
+        changes: changes.into_iter().collect(),
+    }
+}
+
+fn tree_of(repo: &gix::Repository, commit_id: gix::ObjectId) -> anyhow::Result<gix::ObjectId> {
+    Ok(but_core::Commit::from_id(commit_id.attach(repo))?
+        .tree_id()?
+        .detach())
+}
+
+fn file(
+    repo: &gix::Repository,
+    commit_id: gix::ObjectId,
+    path: &str,
+) -> anyhow::Result<Option<String>> {
+    let tree = tree_of(repo, commit_id)?.attach(repo).object()?.into_tree();
+    let Some(entry) = tree.lookup_entry_by_path(path)? else {
+        return Ok(None);
+    };
+    Ok(Some(String::from_utf8(entry.object()?.detach().data)?))
+}
+
+/// Produce a file with a line for each number in `range`, with `replacements` replacing the line at the given number.
+fn lines<const N: usize>(
+    range: std::ops::RangeInclusive<usize>,
+    replacements: [(usize, &str); N],
+) -> String {
+    range
+        .map(|num| {
+            let line = replacements
+                .iter()
+                .find_map(|(rnum, line)| (*rnum == num).then(|| line.to_string()))
+                .unwrap_or_else(|| num.to_string());
+            format!("{line}\n")
+        })
+        .collect()
+}
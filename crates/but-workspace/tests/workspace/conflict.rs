@@ -0,0 +1,218 @@
+use crate::utils::{read_only_in_memory_scenario, writable_scenario};
+use bstr::ByteSlice;
+use but_rebase::cherry_pick::{EmptyCommit, PickMode};
+use but_testsupport::assure_stable_env;
+use but_workspace::commit_engine::ReferenceFrame;
+use but_workspace::conflict::{
+    Materialization, Resolution, conflicted_commits, conflicted_files, materialize_conflicts,
+    resolve_commit, resolve_commit_and_update_refs,
+};
+use gitbutler_stack::VirtualBranchesState;
+use gix::prelude::ObjectIdExt;
+
+#[test]
+fn enumerate_and_inspect() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let repo = read_only_in_memory_scenario("conflicting-branches")?;
+    let (conflicted, top) = pick_b_onto_a(&repo)?;
+
+    let base = repo.rev_parse_single("main")?.detach();
+    let commits = conflicted_commits(&repo, top, Some(base))?;
+    assert_eq!(
+        commits.len(),
+        1,
+        "only the first picked commit is conflicted"
+    );
+    assert_eq!(commits[0].id, conflicted);
+    assert_eq!(commits[0].paths, ["file"]);
+
+    let files = conflicted_files(&repo, conflicted)?;
+    assert_eq!(files.len(), 1);
+    let blob = |state: Option<but_core::ChangeState>| -> anyhow::Result<String> {
+        let id = state.expect("all sides have the file").id;
+        Ok(repo.find_blob(id)?.data.to_str()?.to_owned())
+    };
+    assert_eq!(files[0].path, "file");
+    assert_eq!(blob(files[0].base)?, "1\n2\n3\n");
+    assert_eq!(blob(files[0].ours)?, "1\na\n3\n");
+    assert_eq!(blob(files[0].theirs)?, "1\nb\n3\n");
+
+    let err = conflicted_files(&repo, top).unwrap_err();
+    assert_eq!(err.to_string(), format!("Commit {top} isn't conflicted"));
+    Ok(())
+}
+
+#[test]
+fn materialize() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let repo = read_only_in_memory_scenario("conflicting-branches")?;
+    let (conflicted, _top) = pick_b_onto_a(&repo)?;
+
+    let tree = materialize_conflicts(&repo, conflicted, Materialization::Sides)?;
+    assert_eq!(
+        file_in_tree(&repo, tree, "base/file")?.as_deref(),
+        Some("1\n2\n3\n")
+    );
+    assert_eq!(
+        file_in_tree(&repo, tree, "ours/file")?.as_deref(),
+        Some("1\na\n3\n")
+    );
+    assert_eq!(
+        file_in_tree(&repo, tree, "theirs/file")?.as_deref(),
+        Some("1\nb\n3\n")
+    );
+    assert_eq!(file_in_tree(&repo, tree, "file")?, None);
+
+    let tree = materialize_conflicts(&repo, conflicted, Materialization::Markers)?;
+    let file = file_in_tree(&repo, tree, "file")?.expect("the file is present");
+    assert!(
+        file.contains("<<<<<<<"),
+        "conflict markers are written: {file}"
+    );
+    assert!(file.contains("\na\n") && file.contains("\nb\n"));
+    Ok(())
+}
+
+#[test]
+fn resolve_requires_all_paths() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let repo = read_only_in_memory_scenario("conflicting-branches")?;
+    let (conflicted, _top) = pick_b_onto_a(&repo)?;
+
+    let err = resolve_commit(&repo, conflicted, vec![]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("Commit {conflicted} can't be resolved as these paths remain conflicted: file")
+    );
+
+    let err = resolve_commit(
+        &repo,
+        conflicted,
+        vec![
+            resolution("1\nab\n3\n"),
+            Resolution {
+                path: "other-file".into(),
+                content: Some("changed\n".into()),
+            },
+        ],
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Commit {conflicted} can't be resolved as these paths aren't conflicted: other-file"
+        ),
+        "only conflicting paths can be resolved"
+    );
+
+    let resolved = resolve_commit(&repo, conflicted, vec![resolution("1\nab\n3\n")])?;
+    let commit = but_core::Commit::from_id(resolved.attach(&repo))?;
+    assert!(!commit.is_conflicted());
+    let original = but_core::Commit::from_id(conflicted.attach(&repo))?;
+    assert_eq!(commit.inner.parents, original.inner.parents);
+    assert_eq!(commit.inner.message, original.inner.message);
+    assert_eq!(
+        commit.headers().map(|hdr| hdr.change_id),
+        original.headers().map(|hdr| hdr.change_id),
+        "the change-id is kept"
+    );
+    let tree = commit.tree_id()?.detach();
+    assert_eq!(
+        file_in_tree(&repo, tree, "file")?.as_deref(),
+        Some("1\nab\n3\n")
+    );
+    assert_eq!(
+        file_in_tree(&repo, tree, ".conflict-files")?,
+        None,
+        "the conflict bookkeeping is gone"
+    );
+    Ok(())
+}
+
+#[test]
+fn resolve_rebases_descendants_and_references() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let (repo, _tmp) = writable_scenario("conflicting-branches");
+    let (conflicted, top) = pick_b_onto_a(&repo)?;
+    repo.reference(
+        "refs/heads/picked",
+        top,
+        gix::refs::transaction::PreviousValue::Any,
+        "the branch with the conflicted commit",
+    )?;
+
+    let mut vb = VirtualBranchesState::default();
+    let outcome = resolve_commit_and_update_refs(
+        &repo,
+        ReferenceFrame {
+            workspace_tip: None,
+            branch_tip: Some(top),
+        },
+        &mut vb,
+        conflicted,
+        vec![resolution("1\nab\n3\n")],
+    )?;
+    assert!(outcome.rebase_output.is_some());
+    assert_eq!(outcome.references.len(), 1);
+
+    let new_top = but_core::Commit::from_id(repo.rev_parse_single("picked")?)?;
+    assert_ne!(new_top.id, top, "the branch was rebased");
+    assert!(!new_top.is_conflicted());
+    assert_eq!(new_top.inner.parents[..], [outcome.new_commit]);
+    let tree = new_top.tree_id()?.detach();
+    assert_eq!(
+        file_in_tree(&repo, tree, "file")?.as_deref(),
+        Some("1\nab\n3\n")
+    );
+    assert_eq!(
+        file_in_tree(&repo, tree, "other-file")?.as_deref(),
+        Some("other\n")
+    );
+
+    let base = repo.rev_parse_single("main")?.detach();
+    assert!(conflicted_commits(&repo, new_top.id.detach(), Some(base))?.is_empty());
+    Ok(())
+}
+
+/// Pick both commits of `B` onto `A`, and return the conflicted first commit and the clean one on top of it.
+fn pick_b_onto_a(repo: &gix::Repository) -> anyhow::Result<(gix::ObjectId, gix::ObjectId)> {
+    let a = repo.rev_parse_single("A")?.detach();
+    let conflicted = but_rebase::cherry_pick_one(
+        repo,
+        a,
+        repo.rev_parse_single("B~1")?.detach(),
+        PickMode::Unconditionally,
+        EmptyCommit::Keep,
+    )?;
+    let top = but_rebase::cherry_pick_one(
+        repo,
+        conflicted,
+        repo.rev_parse_single("B")?.detach(),
+        PickMode::Unconditionally,
+        EmptyCommit::Keep,
+    )?;
+    Ok((conflicted, top))
+}
+
+fn resolution(content: &str) -> Resolution {
+    Resolution {
+        path: "file".into(),
+        content: Some(content.into()),
+    }
+}
+
+fn file_in_tree(
+    repo: &gix::Repository,
+    tree_id: gix::ObjectId,
+    path: &str,
+) -> anyhow::Result<Option<String>> {
+    let tree = tree_id.attach(repo).object()?.into_tree();
+    let Some(entry) = tree.lookup_entry_by_path(path)? else {
+        return Ok(None);
+    };
+    Ok(Some(String::from_utf8(entry.object()?.detach().data)?))
+}
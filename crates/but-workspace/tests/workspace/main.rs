@@ -1,5 +1,9 @@
+mod branch;
 mod commit_engine;
+mod conflict;
 mod discard;
 mod head_info;
+mod mbox;
 mod ref_metadata;
+mod signature;
 mod utils;
@@ -0,0 +1,260 @@
+use crate::utils::{read_only_in_memory_scenario, writable_scenario};
+use bstr::ByteSlice;
+use but_core::RefMetadata;
+use but_testsupport::assure_stable_env;
+use but_workspace::VirtualBranchesTomlMetadata;
+use but_workspace::mbox::{commit_patch_series, format_patches, parse_mbox};
+use gix::prelude::ObjectIdExt;
+
+#[test]
+fn exported_patches_round_trip() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let repo = read_only_in_memory_scenario("mbox-series")?;
+    let base = repo.rev_parse_single("main")?.detach();
+    let tip = repo.rev_parse_single("feature")?.detach();
+    let mbox = format_patches(
+        &repo,
+        Some(base),
+        tip,
+        Some("The feature\n\nIt's described here."),
+    )?;
+    for expected_line in [
+        "Subject: [PATCH 0/3] The feature",
+        "From: =?UTF-8?q?J=C3=B6rg_M=C3=BCller?= <joerg@example.com>",
+        "Subject: [PATCH 1/3] add a line without newline",
+        "\\ No newline at end of file",
+        "new file mode 100755",
+        "rename from file",
+        "rename to renamed",
+    ] {
+        assert!(
+            mbox.lines().any(|line| line == expected_line.as_bytes()),
+            "'{expected_line}' is missing in:\n{mbox}"
+        );
+    }
+
+    let series = parse_mbox(&mbox)?;
+    assert_eq!(
+        series.cover_letter.as_deref(),
+        Some("The feature\n\nIt's described here.")
+    );
+    assert_eq!(series.patches.len(), 3);
+    let new_commits = commit_patch_series(&repo, base, &series.patches)?;
+    assert_commits_match(&repo, base, tip, &new_commits)?;
+    Ok(())
+}
+
+#[test]
+fn mailbox_produced_by_git_can_be_committed() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let repo = read_only_in_memory_scenario("mbox-series")?;
+    let mbox = std::fs::read(repo.workdir_path("feature.mbox").expect("non-bare"))?;
+    let series = parse_mbox(&mbox)?;
+    assert_eq!(
+        series.cover_letter, None,
+        "cover letters that weren't filled in are ignored"
+    );
+    let titles: Vec<_> = series.patches.iter().map(|patch| patch.title()).collect();
+    assert_eq!(
+        titles,
+        [
+            "add a line without newline",
+            "add an executable script",
+            "rename file"
+        ]
+    );
+    assert_eq!(
+        series.patches[1].message,
+        "add an executable script\n\nIt doesn't do much yet.\n\nSigned-off-by: Author <author@example.com>\n"
+    );
+
+    let base = repo.rev_parse_single("main")?.detach();
+    let tip = repo.rev_parse_single("feature")?.detach();
+    let new_commits = commit_patch_series(&repo, base, &series.patches)?;
+    assert_commits_match(&repo, base, tip, &new_commits)?;
+    Ok(())
+}
+
+#[test]
+fn long_non_ascii_subjects_are_folded() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let repo = read_only_in_memory_scenario("mbox-series")?;
+    let base = repo.rev_parse_single("main")?.detach();
+    let tip = repo.rev_parse_single("feature")?.detach();
+    let cover_letter =
+        "Überarbeitung der Größenberechnung für sehr lange Überschriften mit Umlauten äöü";
+    let mbox = format_patches(&repo, Some(base), tip, Some(cover_letter))?;
+
+    let subject_lines: Vec<_> = mbox
+        .lines()
+        .skip_while(|line| !line.starts_with(b"Subject: [PATCH 0/3]"))
+        .take_while(|line| line.starts_with(b"Subject: ") || line.starts_with(b" "))
+        .collect();
+    assert!(subject_lines.len() > 1, "the subject is folded:\n{mbox}");
+    for line in subject_lines {
+        for word in line.split_str(" ").filter(|word| word.starts_with(b"=?")) {
+            assert!(
+                word.len() <= 75,
+                "encoded word {word:?} is longer than 75 characters"
+            );
+        }
+    }
+
+    let series = parse_mbox(&mbox)?;
+    assert_eq!(series.cover_letter.as_deref(), Some(cover_letter));
+    Ok(())
+}
+
+#[test]
+fn empty_in_body_headers_are_ignored() -> anyhow::Result<()> {
+    let mbox = b"From 0000000000000000000000000000000000000000 Mon Sep 17 00:00:00 2001
+From: Author <author@example.com>
+Subject: [PATCH] add a file
+
+Subject:\x20
+From: Other <other@example.com>
+
+The body.
+---
+diff --git a/file b/file
+new file mode 100644
+index 0000000..257cc56
+--- /dev/null
++++ b/file
+@@ -0,0 +1 @@
++foo
+";
+    let series = parse_mbox(mbox)?;
+    assert_eq!(series.patches.len(), 1);
+    let patch = &series.patches[0];
+    assert_eq!(
+        patch.message, "add a file\n\nThe body.\n",
+        "the subject of the email is kept"
+    );
+    assert_eq!(
+        patch.author.name, "Other",
+        "other in-body headers still apply"
+    );
+    Ok(())
+}
+
+#[test]
+fn patches_that_do_not_apply_are_rejected() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let repo = read_only_in_memory_scenario("mbox-series")?;
+    let tip = repo.rev_parse_single("feature")?.detach();
+    let mbox = format_patches(&repo, None, tip, None)?;
+    let series = parse_mbox(&mbox)?;
+    assert_eq!(
+        series.patches.len(),
+        4,
+        "all commits are included without base"
+    );
+
+    let err = commit_patch_series(&repo, tip, &series.patches).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Patch 3/4 'add an executable script' doesn't apply to 'script'",
+        "the script already exists"
+    );
+    Ok(())
+}
+
+#[test]
+fn stack_from_mbox_keeps_cover_letter_as_description() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let (repo, tmp) = writable_scenario("mbox-series");
+    let base = repo.rev_parse_single("main")?.detach();
+    let tip = repo.rev_parse_single("feature")?.detach();
+    let mut meta = VirtualBranchesTomlMetadata::from_path(tmp.path().join("vb.toml"))?;
+    let ref_name: gix::refs::FullName = "refs/heads/imported".try_into()?;
+    let mbox = but_workspace::mbox::format_patches(
+        &repo,
+        Some(base),
+        tip,
+        Some("The feature\n\nIt's described here."),
+    )?;
+    let new_commits = but_workspace::mbox::create_stack_from_mbox(
+        &repo,
+        &mut meta,
+        ref_name.as_ref(),
+        base,
+        &mbox,
+    )?;
+    assert_commits_match(&repo, base, tip, &new_commits)?;
+    assert_eq!(
+        repo.find_reference(ref_name.as_ref())?
+            .peel_to_id_in_place()?
+            .detach(),
+        *new_commits.last().expect("three commits")
+    );
+    assert_eq!(
+        meta.branch(ref_name.as_ref())?.description.as_deref(),
+        Some("The feature\n\nIt's described here.")
+    );
+
+    let exported = but_workspace::mbox::format_patches_for_branch(
+        &repo,
+        &meta,
+        ref_name.as_ref(),
+        Some(base),
+    )?;
+    let series = parse_mbox(&exported)?;
+    assert_eq!(
+        series.cover_letter.as_deref(),
+        Some("The feature\n\nIt's described here."),
+        "the description is used as cover letter"
+    );
+    assert_eq!(series.patches.len(), 3);
+
+    let err = but_workspace::mbox::create_stack_from_mbox(
+        &repo,
+        &mut meta,
+        ref_name.as_ref(),
+        base,
+        &mbox,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Could not create branch 'imported'",
+        "existing branches are never overwritten"
+    );
+    Ok(())
+}
+
+/// Assert that `new_commits` are copies of the commits between `base` and `tip`, with their own change-ids.
+fn assert_commits_match(
+    repo: &gix::Repository,
+    base: gix::ObjectId,
+    tip: gix::ObjectId,
+    new_commits: &[gix::ObjectId],
+) -> anyhow::Result<()> {
+    let mut expected: Vec<_> = tip
+        .attach(repo)
+        .ancestors()
+        .with_hidden(Some(base))
+        .all()?
+        .map(|info| info.map(|info| info.id))
+        .collect::<Result<_, _>>()?;
+    expected.reverse();
+    assert_eq!(expected.len(), new_commits.len());
+
+    let mut parent = base;
+    for (expected, actual) in expected.into_iter().zip(new_commits) {
+        let expected = but_core::Commit::from_id(expected.attach(repo))?;
+        let actual = but_core::Commit::from_id(actual.attach(repo))?;
+        assert_eq!(actual.tree, expected.tree);
+        assert_eq!(actual.author, expected.author);
+        assert_eq!(actual.message, expected.message);
+        assert_eq!(actual.parents.as_slice(), [parent]);
+        assert!(actual.headers().is_some(), "new commits get a change-id");
+        parent = actual.id.detach();
+    }
+    Ok(())
+}
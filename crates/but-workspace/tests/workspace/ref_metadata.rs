@@ -923,6 +923,94 @@ mod virtual_branches_toml {
         Ok(())
     }
 
+    #[test]
+    fn reorder_stacks_in_workspace() -> anyhow::Result<()> {
+        let (mut store, _tmp) = vb_store_rw("virtual-branches-01")?;
+        let workspace_name = "refs/heads/gitbutler/workspace".try_into()?;
+        let toml_path = store.path().to_owned();
+        let mut ws = store.workspace(workspace_name)?;
+        let stack_tips = |ws: &but_core::ref_metadata::Workspace| {
+            ws.stacks
+                .iter()
+                .map(|stack| stack.ref_name().map(|rn| rn.shorten().to_string()))
+                .collect::<Vec<_>>()
+        };
+        let tips_before = stack_tips(&ws);
+        assert!(tips_before.len() > 1, "need multiple stacks to reorder");
+
+        std::fs::remove_file(&toml_path)?;
+        store.set_workspace(&ws)?;
+        drop(store);
+        assert!(
+            !toml_path.exists(),
+            "setting the workspace as it is doesn't count as change, so nothing is written"
+        );
+
+        let mut store = VirtualBranchesTomlMetadata::from_path(&toml_path)?;
+        ws.stacks.reverse();
+        store.set_workspace(&ws)?;
+        let stored_ws = store.workspace(workspace_name)?;
+        let mut expected_tips = tips_before.clone();
+        expected_tips.reverse();
+        assert_eq!(
+            stack_tips(&stored_ws),
+            expected_tips,
+            "the order of the stacks is stored"
+        );
+        drop(store);
+        assert!(toml_path.exists(), "the new order was written");
+
+        let store = VirtualBranchesTomlMetadata::from_path(&toml_path)?;
+        assert_eq!(
+            stack_tips(&store.workspace(workspace_name)?),
+            expected_tips,
+            "the order survives a roundtrip"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rename_branch_keeps_its_stack() -> anyhow::Result<()> {
+        let (mut store, _tmp) = vb_store_rw("virtual-branches-01")?;
+        let workspace_name = "refs/heads/gitbutler/workspace".try_into()?;
+        let old_name: &gix::refs::FullNameRef = "refs/heads/A".try_into()?;
+        let new_name: &gix::refs::FullNameRef = "refs/heads/A-renamed".try_into()?;
+        let ws_before = store.workspace(workspace_name)?;
+        let branch_before = store.branch(old_name)?;
+
+        assert!(store.rename_branch(old_name, new_name)?);
+        assert!(store.branch(old_name)?.is_default(), "the old name is gone");
+        let branch = store.branch(new_name)?;
+        assert_eq!(
+            branch.ref_info, branch_before.ref_info,
+            "the branch is still part of the same stack"
+        );
+        assert_eq!(branch.description, branch_before.description);
+        assert_eq!(branch.review, branch_before.review);
+
+        let ws = store.workspace(workspace_name)?;
+        assert_eq!(ws.stacks.len(), ws_before.stacks.len());
+        assert_eq!(
+            ws.stacks[0].ref_name().map(|rn| rn.as_bstr().to_owned()),
+            Some(new_name.as_bstr().to_owned()),
+            "the stack keeps its place in the workspace"
+        );
+
+        assert!(
+            !store.rename_branch(old_name, new_name)?,
+            "there is nothing to rename anymore"
+        );
+        let other_name: &gix::refs::FullNameRef = "refs/heads/B".try_into()?;
+        assert_eq!(
+            store
+                .rename_branch(other_name, new_name)
+                .unwrap_err()
+                .to_string(),
+            "There already is a branch named 'A-renamed'"
+        );
+        Ok(())
+    }
+
     fn vb_fixture(name: &str) -> PathBuf {
         format!("tests/fixtures/{name}.toml").into()
     }
@@ -0,0 +1,223 @@
+use crate::utils::{writable_scenario, writable_scenario_with_ssh_key};
+use but_rebase::commit::{SignatureFormat, SignatureStatus, sign_buffer, verify_signature};
+use but_testsupport::assure_stable_env;
+
+#[test]
+fn ssh_signatures_are_verified_against_allowed_signers() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let (mut repo, _tmp) = writable_scenario_with_ssh_key("two-signed-commits-with-line-offset");
+    let head = but_core::Commit::from_id(repo.head_id()?)?;
+    assert!(
+        matches!(
+            verify_signature(&repo, &head),
+            SignatureStatus::Unverifiable { reason } if reason.contains("gpg.ssh.allowedSignersFile")
+        ),
+        "allowed signers are needed to verify SSH signatures"
+    );
+
+    let workdir = repo.workdir().expect("non-bare").to_owned();
+    let allowed_signers = workdir.join("allowed_signers");
+    std::fs::write(&allowed_signers, "")?;
+    repo.config_snapshot_mut().set_raw_value(
+        &"gpg.ssh.allowedSignersFile",
+        gix::path::into_bstr(allowed_signers.as_path()).as_ref(),
+    )?;
+    assert_eq!(
+        verify_signature(&repo, &head),
+        SignatureStatus::Untrusted { signer: None },
+        "the signature is valid, but its key isn't allowed"
+    );
+
+    let public_key = std::fs::read_to_string(workdir.join("signature.key.pub"))?;
+    std::fs::write(&allowed_signers, format!("test@example.com {public_key}"))?;
+    assert_eq!(
+        verify_signature(&repo, &head),
+        SignatureStatus::Good {
+            signer: "test@example.com".into()
+        }
+    );
+
+    let mut tampered = head.inner.clone();
+    tampered.message = "a different message".into();
+    assert_eq!(verify_signature(&repo, &tampered), SignatureStatus::Bad);
+
+    let mut unsigned = head.inner.clone();
+    let pos = unsigned
+        .extra_headers()
+        .find_pos(gix::objs::commit::SIGNATURE_FIELD_NAME)
+        .expect("signed");
+    unsigned.extra_headers.remove(pos);
+    assert_eq!(
+        verify_signature(&repo, &unsigned),
+        SignatureStatus::Unsigned
+    );
+    Ok(())
+}
+
+#[test]
+fn unknown_signature_format_falls_back_to_openpgp() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let (mut repo, _tmp) = writable_scenario("two-commits-with-line-offset");
+    repo.config_snapshot_mut()
+        .set_raw_value(&"gpg.format", "unknown")?;
+    assert_eq!(
+        SignatureFormat::from_config(&repo.config_snapshot()),
+        SignatureFormat::OpenPgp
+    );
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn ssh_signing_key_from_default_key_command() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let (mut repo, tmp) = writable_scenario("two-commits-with-line-offset");
+    // Sign by copying the key into the signature, ssh-keygen would need the private key.
+    let program = write_program(
+        tmp.path(),
+        "fake-ssh-keygen",
+        r#"for arg; do
+  if [ "$previous" = "-f" ]; then key="$arg"; fi
+  previous="$arg"
+done
+cat "$key" >"$previous.sig""#,
+    )?;
+    let mut config = repo.config_snapshot_mut();
+    config.set_raw_value(&"gpg.format", "ssh")?;
+    config.set_raw_value(&"gpg.ssh.program", gix::path::into_bstr(program).as_ref())?;
+    config.set_raw_value(
+        &"gpg.ssh.defaultKeyCommand",
+        "echo key::ssh-ed25519 AAAAdefault",
+    )?;
+    config.commit()?;
+
+    assert_eq!(
+        sign_buffer(&repo, b"payload")?,
+        "ssh-ed25519 AAAAdefault",
+        "the key of the default key command is used to sign"
+    );
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn x509_signing_uses_configured_program() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let (mut repo, tmp) = writable_scenario("two-commits-with-line-offset");
+    let program = write_program(
+        tmp.path(),
+        "fake-gpgsm",
+        r#"cat >/dev/null
+echo "-----BEGIN SIGNED MESSAGE-----"
+echo "$@""#,
+    )?;
+    let mut config = repo.config_snapshot_mut();
+    config.set_raw_value(&"gpg.format", "x509")?;
+    config.set_raw_value(&"gpg.x509.program", gix::path::into_bstr(program).as_ref())?;
+    config.set_raw_value(&"user.signingKey", "signer")?;
+    config.commit()?;
+
+    assert_eq!(
+        sign_buffer(&repo, b"payload")?,
+        "-----BEGIN SIGNED MESSAGE-----\n--status-fd=2 -bsau signer -\n"
+    );
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn gpg_status_is_interpreted() -> anyhow::Result<()> {
+    assure_stable_env();
+
+    let (mut repo, tmp) = writable_scenario("two-commits-with-line-offset");
+    let failing = write_program(tmp.path(), "failing-gpg", "exit 1")?;
+    let status_file = tmp.path().join("status");
+    let program = write_program(
+        tmp.path(),
+        "fake-gpg",
+        &format!("cat >/dev/null\ncat {}", status_file.display()),
+    )?;
+    let mut config = repo.config_snapshot_mut();
+    config.set_raw_value(&"gpg.program", gix::path::into_bstr(failing).as_ref())?;
+    config.set_raw_value(
+        &"gpg.openpgp.program",
+        gix::path::into_bstr(program).as_ref(),
+    )?;
+    config.commit()?;
+
+    let mut commit = but_core::Commit::from_id(repo.head_id()?)?.inner;
+    commit.extra_headers.push((
+        gix::objs::commit::SIGNATURE_FIELD_NAME.into(),
+        "-----BEGIN PGP SIGNATURE-----\n\nsignature\n-----END PGP SIGNATURE-----".into(),
+    ));
+
+    let signer = "Signer <signer@example.com>";
+    for (status, expected) in [
+        (
+            format!("[GNUPG:] GOODSIG 0123456789ABCDEF {signer}\n[GNUPG:] TRUST_FULLY 0 pgp\n"),
+            SignatureStatus::Good {
+                signer: signer.into(),
+            },
+        ),
+        (
+            format!("[GNUPG:] GOODSIG 0123456789ABCDEF {signer}\n[GNUPG:] TRUST_UNDEFINED 0 pgp\n"),
+            SignatureStatus::Untrusted {
+                signer: Some(signer.into()),
+            },
+        ),
+        (
+            format!("[GNUPG:] EXPKEYSIG 0123456789ABCDEF {signer}\n"),
+            SignatureStatus::Expired {
+                signer: signer.into(),
+            },
+        ),
+        (
+            format!("[GNUPG:] REVKEYSIG 0123456789ABCDEF {signer}\n"),
+            SignatureStatus::Revoked {
+                signer: signer.into(),
+            },
+        ),
+        (
+            format!("[GNUPG:] BADSIG 0123456789ABCDEF {signer}\n"),
+            SignatureStatus::Bad,
+        ),
+        (
+            "[GNUPG:] ERRSIG 0123456789ABCDEF 1 8 00 1700000000 9\n[GNUPG:] NO_PUBKEY 0123456789ABCDEF\n"
+                .into(),
+            SignatureStatus::Unverifiable {
+                reason: "The key that made the signature isn't available".into(),
+            },
+        ),
+    ] {
+        std::fs::write(&status_file, &status)?;
+        assert_eq!(verify_signature(&repo, &commit), expected, "{status}");
+    }
+
+    std::fs::write(&status_file, "gpg: no valid OpenPGP data found.\n")?;
+    assert!(
+        matches!(
+            verify_signature(&repo, &commit),
+            SignatureStatus::Unverifiable { .. }
+        ),
+        "without a verdict, the signature couldn't be checked"
+    );
+    Ok(())
+}
+
+/// Write an executable shell script named `name` with `body` into `dir` and return its path.
+#[cfg(unix)]
+fn write_program(
+    dir: &std::path::Path,
+    name: &str,
+    body: &str,
+) -> anyhow::Result<std::path::PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
+    let path = dir.join(name);
+    std::fs::write(&path, format!("#!/bin/sh\n{body}\n"))?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+    Ok(path)
+}
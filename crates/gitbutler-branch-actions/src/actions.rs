@@ -28,7 +28,7 @@ use gitbutler_diff::DiffByPathMap;
 use gitbutler_operating_modes::assure_open_workspace_mode;
 use gitbutler_oplog::{
     entry::{OperationKind, SnapshotDetails},
-    OplogExt, SnapshotExt,
+    OplogExt, RestoreSelection, SnapshotExt,
 };
 use gitbutler_oxidize::{ObjectIdExt, OidExt};
 use gitbutler_project::FetchResult;
@@ -371,6 +371,36 @@ pub fn move_commit_file(
     vbranch::move_commit_file(ctx, stack_id, from_commit_oid, to_commit_oid, ownership)
 }
 
+/// Restore only the worktree paths and stacks chosen by `selection` from the snapshot at `snapshot_id`,
+/// and update the workspace commit if an applied stack was restored.
+///
+/// Returns the id of the snapshot that records the state before the restoration.
+pub fn restore_snapshot_selection(
+    ctx: &CommandContext,
+    snapshot_id: git2::Oid,
+    selection: &RestoreSelection,
+) -> Result<git2::Oid> {
+    ctx.verify()?;
+    assure_open_workspace_mode(ctx)
+        .context("Restoring parts of a snapshot requires open workspace mode")?;
+    let mut guard = ctx.project().exclusive_worktree_access();
+    let restore_snapshot_id = ctx.project().restore_snapshot_selection(
+        snapshot_id,
+        selection,
+        guard.write_permission(),
+    )?;
+
+    let vb_state = ctx.project().virtual_branches();
+    let mut restored_applied_stack = false;
+    for stack_id in &selection.stacks {
+        restored_applied_stack |= vb_state.try_stack_in_workspace(*stack_id)?.is_some();
+    }
+    if restored_applied_stack {
+        crate::integration::update_workspace_commit(&vb_state, ctx)?;
+    }
+    Ok(restore_snapshot_id)
+}
+
 pub fn undo_commit(ctx: &CommandContext, stack_id: StackId, commit_oid: git2::Oid) -> Result<()> {
     ctx.verify()?;
     assure_open_workspace_mode(ctx).context("Undoing a commit requires open workspace mode")?;
@@ -495,6 +525,24 @@ pub fn squash_commits(
     )
 }
 
+/// Fold all `fixup!`, `squash!` and `amend!` commits of the stack with `stack_id` into the commits they refer to,
+/// returning the amount of commits that were folded.
+pub fn autosquash_stack(ctx: &CommandContext, stack_id: StackId) -> Result<usize> {
+    ctx.verify()?;
+    assure_open_workspace_mode(ctx).context("Squashing a commit requires open workspace mode")?;
+    let mut guard = ctx.project().exclusive_worktree_access();
+    let snap = ctx.project().create_snapshot(
+        SnapshotDetails::new(OperationKind::SquashCommit),
+        guard.write_permission(),
+    )?;
+    let result = crate::autosquash::autosquash_stack(ctx, stack_id, guard.write_permission());
+    if result.is_err() {
+        ctx.project()
+            .restore_snapshot(snap, guard.write_permission())?;
+    }
+    result
+}
+
 pub fn update_commit_message(
     ctx: &CommandContext,
     stack_id: StackId,
@@ -0,0 +1,123 @@
+use anyhow::{bail, Context, Result};
+use but_rebase::RebaseStep;
+use gitbutler_command_context::CommandContext;
+use gitbutler_oxidize::{ObjectIdExt, OidExt};
+use gitbutler_project::access::WorktreeWritePermission;
+use gitbutler_repo::logging::{LogUntil, RepositoryExt};
+use gitbutler_stack::StackId;
+#[allow(deprecated)]
+use gitbutler_workspace::{
+    branch_trees::{update_uncommited_changes, WorkspaceState},
+    checkout_branch_trees, compute_updated_branch_head,
+};
+use gix::prelude::ObjectIdExt as _;
+use itertools::Itertools;
+
+use crate::VirtualBranchesExt;
+
+/// Fold all `fixup!`, `squash!` and `amend!` commits in the stack with `stack_id` into the commits they refer to,
+/// and return the amount of commits that were folded.
+pub(crate) fn autosquash_stack(
+    ctx: &CommandContext,
+    stack_id: StackId,
+    perm: &mut WorktreeWritePermission,
+) -> Result<usize> {
+    let old_workspace = WorkspaceState::create(ctx, perm.read_permission())?;
+    let vb_state = ctx.project().virtual_branches();
+    let mut stack = vb_state.get_stack_in_workspace(stack_id)?;
+    let gix_repo = ctx.gix_repo()?;
+
+    let default_target = vb_state.get_default_target()?;
+    let merge_base = ctx
+        .repo()
+        .merge_base(stack.head(&gix_repo)?, default_target.sha)?;
+    let branch_commit_oids =
+        ctx.repo()
+            .l(stack.head(&gix_repo)?, LogUntil::Commit(merge_base), false)?;
+
+    let mut steps: Vec<RebaseStep> = Vec::new();
+    for head in stack.heads_by_commit(ctx.repo().find_commit(merge_base)?, &gix_repo) {
+        steps.push(RebaseStep::Reference(but_core::Reference::Virtual(head)));
+    }
+    for oid in branch_commit_oids.iter().rev() {
+        steps.push(RebaseStep::Pick {
+            commit_id: oid.to_gix(),
+            new_message: None,
+            base: None,
+        });
+        for head in stack.heads_by_commit(ctx.repo().find_commit(*oid)?, &gix_repo) {
+            steps.push(RebaseStep::Reference(but_core::Reference::Virtual(head)));
+        }
+    }
+
+    let steps = but_rebase::autosquash::plan(&gix_repo, steps)?;
+    // The first commit that something is folded into is the first one to be rewritten.
+    let mut first_target = None;
+    let mut folded = 0;
+    let mut last_pick = None;
+    for step in &steps {
+        match step {
+            RebaseStep::Pick { commit_id, .. } => last_pick = Some(commit_id.to_git2()),
+            RebaseStep::SquashIntoPreceding { .. } => {
+                first_target = first_target.or(last_pick);
+                folded += 1;
+            }
+            _ => {}
+        }
+    }
+    let Some(first_target) = first_target else {
+        return Ok(0);
+    };
+
+    if !stack.allow_rebasing {
+        let remote_commits = stack
+            .branches()
+            .iter()
+            .flat_map(|b| b.commits(ctx, &stack))
+            .flat_map(|c| c.remote_commits)
+            .map(|c| c.id())
+            .collect_vec();
+        if let Some(pushed) = branch_commit_oids
+            .iter()
+            .rev()
+            .skip_while(|id| **id != first_target)
+            .find(|id| remote_commits.contains(id))
+        {
+            bail!("Force push is not allowed. Commit with id {pushed} has already been pushed");
+        }
+    }
+
+    let mut builder = but_rebase::Rebase::new(&gix_repo, merge_base.to_gix(), None)?;
+    let builder = builder.steps(steps)?;
+    builder.rebase_noops(false);
+    let output = builder.rebase()?;
+    for (_base, _old, new) in &output.commit_mapping {
+        if but_core::Commit::from_id(new.attach(&gix_repo))?.is_conflicted() {
+            bail!("Autosquashing would cause conflicts, the commits have to be squashed manually");
+        }
+    }
+
+    let new_stack_head = output.top_commit.to_git2();
+
+    let (new_head_oid, new_tree_oid) = if ctx.app_settings().feature_flags.v3 {
+        (new_stack_head, None)
+    } else {
+        #[allow(deprecated)]
+        let res = compute_updated_branch_head(ctx.repo(), &gix_repo, &stack, new_stack_head)?;
+        (res.head, Some(res.tree))
+    };
+
+    stack.set_stack_head(&vb_state, &gix_repo, new_head_oid, new_tree_oid)?;
+
+    let new_workspace = WorkspaceState::create(ctx, perm.read_permission())?;
+    if ctx.app_settings().feature_flags.v3 {
+        update_uncommited_changes(ctx, old_workspace, new_workspace, perm)?;
+    } else {
+        #[allow(deprecated)]
+        checkout_branch_trees(ctx, perm)?;
+    }
+    crate::integration::update_workspace_commit(&vb_state, ctx)
+        .context("failed to update gitbutler workspace")?;
+    stack.set_heads_from_rebase_output(ctx, output.references)?;
+    Ok(folded)
+}
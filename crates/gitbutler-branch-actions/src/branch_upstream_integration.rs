@@ -43,7 +43,7 @@ pub fn integrate_upstream_commits_for_series(
     let branches = stack.branches();
 
     let default_target = vb_state.get_default_target()?;
-    let remote = default_target.push_remote_name();
+    let remote = stack.push_remote_name(&default_target);
 
     let subject_branch = branches
         .iter()
@@ -163,6 +163,7 @@ impl IntegrateUpstreamContext<'_, '_> {
                                 RebaseStep::Pick {
                                     commit_id: merge_commit.id().to_gix(),
                                     new_message: None,
+                                    base: None,
                                 },
                             );
                             break;
@@ -215,6 +216,7 @@ impl IntegrateUpstreamContext<'_, '_> {
                     .map(|commit_id| RebaseStep::Pick {
                         commit_id: commit_id.to_gix(),
                         new_message: None,
+                        base: None,
                     })
                     .collect();
                 let updated_steps = flatten_buckets(buckets);
@@ -524,19 +526,23 @@ mod test {
                 RebaseStep::Pick {
                     commit_id: local_a.id().to_gix(),
                     new_message: None,
+                    base: None,
                 },
                 RebaseStep::Pick {
                     commit_id: local_b.id().to_gix(),
                     new_message: None,
+                    base: None,
                 },
                 RebaseStep::Reference(but_core::Reference::Virtual("One".to_string())),
                 RebaseStep::Pick {
                     commit_id: local_c.id().to_gix(),
                     new_message: None,
+                    base: None,
                 },
                 RebaseStep::Pick {
                     commit_id: local_d.id().to_gix(),
                     new_message: None,
+                    base: None,
                 },
                 RebaseStep::Reference(but_core::Reference::Virtual("Two".to_string())),
             ];
@@ -9,12 +9,16 @@ use gitbutler_command_context::CommandContext;
 use gitbutler_commit::commit_ext::CommitExt;
 use gitbutler_error::error::Marker;
 use gitbutler_operating_modes::OPEN_WORKSPACE_REFS;
-use gitbutler_oxidize::{git2_to_gix_object_id, gix_to_git2_oid, GixRepositoryExt, RepoExt};
+use gitbutler_oxidize::{
+    git2_signature_to_gix_signature, git2_to_gix_object_id, gix_to_git2_oid, GixRepositoryExt,
+    RepoExt,
+};
 use gitbutler_project::access::WorktreeWritePermission;
 use gitbutler_repo::logging::{LogUntil, RepositoryExt as _};
 use gitbutler_repo::RepositoryExt;
 use gitbutler_repo::SignaturePurpose;
 use gitbutler_stack::{Stack, VirtualBranchesHandle};
+use gix::prelude::ObjectIdExt as _;
 use tracing::instrument;
 
 use crate::{branch_manager::BranchManagerExt, conflicts, VirtualBranchesExt};
@@ -39,11 +43,12 @@ pub(crate) fn get_workspace_head(ctx: &CommandContext) -> Result<git2::Oid> {
         .context("failed to get target")?;
     let repo: &git2::Repository = ctx.repo();
 
-    let mut stacks: Vec<Stack> = vb_state.list_stacks_in_workspace()?;
+    let stacks: Vec<Stack> = vb_state.list_stacks_in_workspace()?;
 
     let target_commit = repo.find_commit(target.sha)?;
     let mut workspace_tree = repo.find_real_tree(&target_commit, Default::default())?;
     let mut workspace_tree_id = git2_to_gix_object_id(workspace_tree.id());
+    let mut stacks_conflict = false;
 
     if conflicts::is_conflicting(ctx, None)? {
         let merge_parent = conflicts::merge_parent(ctx)?.ok_or(anyhow!("No merge parent"))?;
@@ -55,7 +60,7 @@ pub(crate) fn get_workspace_head(ctx: &CommandContext) -> Result<git2::Oid> {
         let gix_repo = ctx.gix_repo_for_merging()?;
         let (merge_options_fail_fast, conflict_kind) = gix_repo.merge_options_fail_fast()?;
         let merge_tree_id = git2_to_gix_object_id(repo.find_commit(target.sha)?.tree_id());
-        for stack in stacks.iter_mut() {
+        for stack in &stacks {
             let branch_head = repo.find_commit(stack.head(&gix_repo)?)?;
             let branch_tree_id =
                 git2_to_gix_object_id(repo.find_real_tree(&branch_head, Default::default())?.id());
@@ -71,10 +76,9 @@ pub(crate) fn get_workspace_head(ctx: &CommandContext) -> Result<git2::Oid> {
             if !merge.has_unresolved_conflicts(conflict_kind) {
                 workspace_tree_id = merge.tree.write()?.detach();
             } else {
-                // This branch should have already been unapplied during the "update" command but for some reason that failed
+                // The stacks are kept in the workspace, and the conflict is recorded in the workspace commit instead.
                 tracing::warn!("Merge conflict between base and {:?}", stack.name);
-                stack.in_workspace = false;
-                vb_state.set_stack(stack.clone())?;
+                stacks_conflict = true;
             }
         }
         workspace_tree = repo.find_tree(gix_to_git2_oid(workspace_tree_id))?;
@@ -98,6 +102,30 @@ pub(crate) fn get_workspace_head(ctx: &CommandContext) -> Result<git2::Oid> {
     //       could make use of AsRef with the right traits.
     let head_refs: Vec<&git2::Commit<'_>> = heads.iter().collect();
 
+    if stacks_conflict {
+        let gix_repo = ctx.gix_repo_for_merging()?;
+        let workspace_head = gix::objs::Commit {
+            // The tree is replaced by the merge of the parents.
+            tree: gix::ObjectId::empty_tree(gix_repo.object_hash()),
+            parents: heads
+                .iter()
+                .map(|head| git2_to_gix_object_id(head.id()))
+                .collect(),
+            author: git2_signature_to_gix_signature(&author),
+            committer: git2_signature_to_gix_signature(&committer),
+            encoding: None,
+            message: WORKSPACE_HEAD.into(),
+            extra_headers: Vec::new(),
+        };
+        let workspace_head_id = but_rebase::merge::octopus(
+            &gix_repo,
+            workspace_head,
+            &mut gix_repo.revision_graph(None),
+            but_rebase::merge::ConflictMode::Record,
+        )?;
+        return Ok(gix_to_git2_oid(workspace_head_id));
+    }
+
     let workspace_head_id = repo.commit(
         None,
         &author,
@@ -228,16 +256,29 @@ pub fn update_workspace_commit(
     // requires committing to the tip of the branch, and we're mostly replacing the tip.
 
     let parents = workspace_head.parents().collect::<Vec<_>>();
-    let workspace_tree = workspace_head.tree()?;
-
-    let final_commit = repo.commit(
-        None,
-        &author,
-        &committer,
-        &message,
-        &workspace_tree,
-        parents.iter().collect::<Vec<_>>().as_slice(),
-    )?;
+    // The tree to check out, which for conflicting stacks is the auto-resolution.
+    let workspace_tree = repo.find_real_tree(&workspace_head, Default::default())?;
+
+    let final_commit = if workspace_head.is_conflicted() {
+        // Keep the conflicted tree and its header so the conflicts remain visible.
+        let mut commit = but_core::Commit::from_id(
+            git2_to_gix_object_id(workspace_head.id()).attach(&gix_repo),
+        )?
+        .inner;
+        commit.message = message.into();
+        commit.author = git2_signature_to_gix_signature(&author);
+        commit.committer = git2_signature_to_gix_signature(&committer);
+        gix_to_git2_oid(gix_repo.write_object(commit)?.detach())
+    } else {
+        repo.commit(
+            None,
+            &author,
+            &committer,
+            &message,
+            &workspace_tree,
+            parents.iter().collect::<Vec<_>>().as_slice(),
+        )?
+    };
 
     // Create or replace the workspace branch reference, then set as HEAD.
     repo.reference(
@@ -54,6 +54,7 @@ pub fn reorder_stack(
             steps.push(RebaseStep::Pick {
                 commit_id: oid.to_gix(),
                 new_message: None,
+                base: None,
             });
         }
         steps.push(RebaseStep::Reference(but_core::Reference::Virtual(
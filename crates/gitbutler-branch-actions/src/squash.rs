@@ -183,11 +183,13 @@ fn do_squash_commits(
             steps.push(RebaseStep::Pick {
                 commit_id: new_commit_oid.to_gix(),
                 new_message: None,
+                base: None,
             });
         } else {
             steps.push(RebaseStep::Pick {
                 commit_id: oid.to_gix(),
                 new_message: None,
+                base: None,
             });
         }
         for head in stack.heads_by_commit(commit, &gix_repo) {
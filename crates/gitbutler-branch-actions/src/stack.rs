@@ -1,18 +1,22 @@
 use std::collections::HashMap;
 
 use anyhow::{Context, Result};
+use bstr::BString;
 use gitbutler_command_context::CommandContext;
 use gitbutler_commit::commit_ext::CommitExt;
 use gitbutler_oplog::entry::{OperationKind, SnapshotDetails};
 use gitbutler_oplog::{OplogExt, SnapshotExt};
-use gitbutler_oxidize::RepoExt;
-use gitbutler_reference::normalize_branch_name;
+use gitbutler_oxidize::{OidExt, RepoExt};
+use gitbutler_reference::{normalize_branch_name, LocalRefname, Refname, RemoteRefname};
+use gitbutler_repo::RepositoryExt;
 use gitbutler_repo_actions::RepoActionsExt;
 use gitbutler_stack::{CommitOrChangeId, PatchReferenceUpdate, StackBranch};
 use gitbutler_stack::{Stack, StackId, Target};
+use gix::refs::transaction::PreviousValue;
 use serde::{Deserialize, Serialize};
 
 use crate::actions::Verify;
+use crate::branch_manager::BranchManagerExt;
 use crate::dependencies::{commit_dependencies_from_stack, StackDependencies};
 use crate::{
     commit::{commit_to_vbranch_commit, VirtualBranchCommit},
@@ -165,6 +169,183 @@ pub fn update_branch_pr_number(
     stack.set_pr_number(ctx, &branch_name, pr_number)
 }
 
+/// Sets the remote the branches of the stack are pushed to, overriding the push remote of the default target.
+/// Passing `None` reverts to the push remote of the default target.
+///
+/// # Errors
+/// This method will return an error if:
+///  - The remote does not exist
+///  - The stack cant be found
+///  - The project is not in workspace mode
+///  - Persisting the changes failed
+pub fn set_stack_push_remote(
+    ctx: &CommandContext,
+    stack_id: StackId,
+    push_remote_name: Option<String>,
+) -> Result<()> {
+    ctx.verify()?;
+    if let Some(name) = &push_remote_name {
+        ctx.repo()
+            .find_remote(name)
+            .with_context(|| format!("failed to find remote {name}"))?;
+    }
+    let mut guard = ctx.project().exclusive_worktree_access();
+    let _ = ctx.project().create_snapshot(
+        SnapshotDetails::new(OperationKind::UpdateBranchRemoteName),
+        guard.write_permission(),
+    );
+    assure_open_workspace_mode(ctx).context("Requires an open workspace mode")?;
+    let state = ctx.project().virtual_branches();
+    let mut stack = state.get_stack(stack_id)?;
+    stack.push_remote_name = push_remote_name;
+    state.set_stack(stack)
+}
+
+/// Sets the remote branch, like `upstream/main`, that the stack is checked against for integration, overriding
+/// the branch of the default target. Passing `None` reverts to the branch of the default target.
+///
+/// # Errors
+/// This method will return an error if:
+///  - The remote branch does not exist
+///  - The stack cant be found
+///  - The project is not in workspace mode
+///  - Persisting the changes failed
+pub fn set_stack_upstream_target(
+    ctx: &CommandContext,
+    stack_id: StackId,
+    upstream_target: Option<RemoteRefname>,
+) -> Result<()> {
+    ctx.verify()?;
+    if let Some(refname) = &upstream_target {
+        ctx.repo()
+            .find_branch_by_refname(&refname.into())
+            .with_context(|| format!("failed to find remote branch {refname}"))?;
+    }
+    let mut guard = ctx.project().exclusive_worktree_access();
+    let _ = ctx.project().create_snapshot(
+        SnapshotDetails::new(OperationKind::UpdateBranchRemoteName),
+        guard.write_permission(),
+    );
+    assure_open_workspace_mode(ctx).context("Requires an open workspace mode")?;
+    let state = ctx.project().virtual_branches();
+    let mut stack = state.get_stack(stack_id)?;
+    stack.upstream_target = upstream_target;
+    state.set_stack(stack)
+}
+
+/// Formats the commits of the series `branch_name` in the stack as a patch series in `mbox` format, like
+/// `git format-patch` would.
+/// Only commits that aren't part of the series below it, or of the target branch for the bottom-most series,
+/// are included. The description of the series is used as cover letter.
+pub fn format_branch_patches(
+    ctx: &CommandContext,
+    stack_id: StackId,
+    branch_name: &str,
+) -> Result<BString> {
+    ctx.verify()?;
+    let state = ctx.project().virtual_branches();
+    let stack = state.get_stack(stack_id)?;
+    let repo = ctx.gix_repo()?;
+    let branches = stack.branches();
+    let position = branches
+        .iter()
+        .position(|branch| branch.name() == branch_name)
+        .with_context(|| format!("Series '{branch_name}' does not exist in the stack"))?;
+    let branch = &branches[position];
+    let tip = branch.head_oid(&repo)?.to_gix();
+    let base = match position.checked_sub(1).and_then(|idx| branches.get(idx)) {
+        Some(branch_below) => branch_below.head_oid(&repo)?.to_gix(),
+        None => {
+            let default_target = state.get_default_target()?;
+            repo.merge_base(tip, default_target.sha.to_gix())?.detach()
+        }
+    };
+    but_workspace::mbox::format_patches(&repo, Some(base), tip, branch.description.as_deref())
+}
+
+/// Creates a new stack from the patches in `mbox`, a mailbox as produced by `git format-patch`, and applies it
+/// to the workspace.
+/// The commits are created on top of the target branch and keep the authors, dates and messages of the patches.
+/// The branch is named `branch_name`, or after the cover letter or the first patch if `None`,
+/// and the cover letter becomes its description.
+pub fn create_stack_from_mbox(
+    ctx: &CommandContext,
+    mbox: &[u8],
+    branch_name: Option<String>,
+) -> Result<StackId> {
+    ctx.verify()?;
+    assure_open_workspace_mode(ctx).context("Requires an open workspace mode")?;
+    let series = but_workspace::mbox::parse_mbox(mbox)?;
+    let first_patch = series
+        .patches
+        .first()
+        .context("The mailbox didn't contain any patches")?;
+    let branch_name = match branch_name {
+        Some(branch_name) => branch_name,
+        None => series
+            .cover_letter
+            .as_deref()
+            .and_then(|cover_letter| cover_letter.lines().next())
+            .map(ToOwned::to_owned)
+            .unwrap_or_else(|| first_patch.title().to_string()),
+    };
+    let refname = LocalRefname::new(&normalize_branch_name(&branch_name)?, None);
+
+    let mut guard = ctx.project().exclusive_worktree_access();
+    let repo = ctx.gix_repo()?;
+    let default_target = ctx.project().virtual_branches().get_default_target()?;
+    let commit_ids = but_workspace::mbox::commit_patch_series(
+        &repo,
+        default_target.sha.to_gix(),
+        &series.patches,
+    )?;
+    let tip = *commit_ids.last().expect("one commit per patch");
+    let reference = repo
+        .reference(
+            refname.to_string().as_str(),
+            tip,
+            PreviousValue::MustNotExist,
+            "GitButler: import patches",
+        )
+        .with_context(|| format!("Could not create branch '{}'", refname.branch()))?;
+
+    let stack_id = match ctx.branch_manager().create_virtual_branch_from_branch(
+        &Refname::Local(refname),
+        None,
+        None,
+        guard.write_permission(),
+    ) {
+        Ok(stack_id) => stack_id,
+        Err(err) => {
+            // Don't leave the branch behind so importing the patches again doesn't fail as it already exists.
+            if let Err(delete_err) = reference.delete() {
+                tracing::warn!(
+                    "Could not delete branch '{}' after failing to apply it: {delete_err}",
+                    reference.name().as_bstr()
+                );
+            }
+            return Err(err);
+        }
+    };
+    if let Some(description) = series.cover_letter {
+        let mut stack = ctx.project().virtual_branches().get_stack(stack_id)?;
+        let head_name = stack
+            .heads(false)
+            .last()
+            .cloned()
+            .context("A stack always has at least one branch")?;
+        stack.update_branch(
+            ctx,
+            head_name,
+            &PatchReferenceUpdate {
+                description: Some(Some(description)),
+                ..Default::default()
+            },
+        )?;
+    }
+    Ok(stack_id)
+}
+
 /// Pushes all series in the stack to the remote.
 /// This operation will error out if the target has no push remote configured.
 pub fn push_stack(ctx: &CommandContext, stack_id: StackId, with_force: bool) -> Result<()> {
@@ -181,7 +362,7 @@ pub fn push_stack(ctx: &CommandContext, stack_id: StackId, with_force: bool) ->
 
     // First fetch, because we dont want to push integrated series
     ctx.fetch(
-        &default_target.push_remote_name(),
+        &stack.push_remote_name(&default_target),
         Some("push_stack".into()),
     )?;
     let gix_repo = ctx.gix_repo_for_merging_non_persisting()?;
@@ -283,7 +464,7 @@ fn stack_branch_to_api_branch(
     let mut requires_force = false;
     let repo = ctx.repo();
     let branch_commits = stack_branch.commits(ctx, stack)?;
-    let remote = default_target.push_remote_name();
+    let remote = stack.push_remote_name(default_target);
     let upstream_reference = if stack_branch.pushed(remote.as_str(), repo) {
         Some(stack_branch.remote_reference(remote.as_str()))
     } else {
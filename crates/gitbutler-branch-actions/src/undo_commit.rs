@@ -42,6 +42,7 @@ pub(crate) fn undo_commit(
             RebaseStep::Pick {
                 commit_id,
                 new_message: _,
+                base: _,
             } => commit_id != &commit_to_remove.to_gix(),
             _ => true,
         })
@@ -97,9 +97,9 @@ pub struct BaseBranchResolution {
 #[serde(rename_all = "camelCase")]
 pub struct IntegrationOutcome {
     /// This is the list of branch names that have become archived as a result of the upstream integration
-    archived_branches: Vec<String>,
+    pub archived_branches: Vec<String>,
     /// This is the list of review ids that have been closed as a result of the upstream integration
-    review_ids_to_close: Vec<String>,
+    pub review_ids_to_close: Vec<String>,
 }
 
 impl StackStatus {
@@ -296,6 +296,7 @@ fn get_stack_status(
             .map(|commit_id| RebaseStep::Pick {
                 commit_id: commit_id.to_gix(),
                 new_message: None,
+                base: None,
             })
             .collect();
         let mut rebase = but_rebase::Rebase::new(gix_repo, Some(rebase_base.to_gix()), None)?;
@@ -360,6 +361,26 @@ fn get_stack_status(
     StackStatus::create(tree_status, branch_statuses)
 }
 
+/// Returns the commit `stack` is integrated with, which is the head of its upstream target if it has one
+/// that exists, or `new_target` otherwise.
+fn stack_target_commit<'repo>(
+    repo: &'repo git2::Repository,
+    stack: &Stack,
+    target: &Target,
+    new_target: &git2::Commit<'repo>,
+) -> Result<git2::Commit<'repo>> {
+    let upstream_target = stack.upstream_target(target);
+    if upstream_target == target.branch {
+        return Ok(new_target.clone());
+    }
+    Ok(
+        match repo.maybe_find_branch_by_refname(&upstream_target.into())? {
+            Some(branch) => branch.get().peel_to_commit()?,
+            None => new_target.clone(),
+        },
+    )
+}
+
 pub fn upstream_integration_statuses(
     context: &UpstreamIntegrationContext,
 ) -> Result<StackStatuses> {
@@ -375,8 +396,33 @@ pub fn upstream_integration_statuses(
     let gix_repo = gitbutler_command_context::gix_repo_for_merging(repo.path())?;
     let gix_repo_in_memory = gix_repo.clone().with_object_memory();
 
+    let stack_target_commit_ids = stacks_in_workspace
+        .iter()
+        .map(|stack| {
+            stack_target_commit(repo, stack, target, new_target)
+                .map(|commit| git2_to_gix_object_id(commit.id()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
     if new_target.id() == old_target.id() {
-        return Ok(StackStatuses::UpToDate);
+        // Stacks with their own upstream target are up to date once they contain its head.
+        let mut all_up_to_date = true;
+        for (stack, stack_target_commit_id) in
+            stacks_in_workspace.iter().zip(&stack_target_commit_ids)
+        {
+            let stack_target_commit_id = gix_to_git2_oid(*stack_target_commit_id);
+            if stack_target_commit_id == old_target.id() {
+                continue;
+            }
+            let stack_head = stack.head(&gix_repo)?;
+            if repo.merge_base(stack_head, stack_target_commit_id)? != stack_target_commit_id {
+                all_up_to_date = false;
+                break;
+            }
+        }
+        if all_up_to_date {
+            return Ok(StackStatuses::UpToDate);
+        }
     };
 
     let heads = stacks_in_workspace
@@ -422,14 +468,15 @@ pub fn upstream_integration_statuses(
 
     let statuses = stacks_in_workspace
         .iter()
-        .map(|stack| {
+        .zip(stack_target_commit_ids)
+        .map(|(stack, stack_target_commit_id)| {
             Ok((
                 stack.id,
                 get_stack_status(
                     repo,
                     &gix_repo_in_memory,
                     target.clone(),
-                    git2_to_gix_object_id(new_target.id()),
+                    stack_target_commit_id,
                     stack,
                     context.ctx,
                 )?,
@@ -644,6 +691,7 @@ pub(crate) fn resolve_upstream_integration(
                 .map(|commit| RebaseStep::Pick {
                     commit_id: commit.to_gix(),
                     new_message: None,
+                    base: None,
                 })
                 .collect::<Vec<_>>();
             let mut rebase =
@@ -680,6 +728,8 @@ fn compute_resolutions(
             else {
                 bail!("Failed to find virtual branch");
             };
+            // Stacks are integrated with the same commit their status was computed against.
+            let stack_target = stack_target_commit(repo, branch_stack, target, new_target)?;
 
             match resolution.approach {
                 ResolutionApproach::Unapply => {
@@ -696,13 +746,13 @@ fn compute_resolutions(
                     let top_branch = branch_stack.heads.last().context("top branch not found")?;
 
                     // These two go into the merge commit message.
-                    let incoming_branch_name = target.branch.fullname();
+                    let incoming_branch_name = branch_stack.upstream_target(target).fullname();
                     let target_branch_name = &top_branch.name();
 
                     let new_head = gitbutler_merge_commits(
                         repo,
                         target_commit,
-                        new_target.clone(),
+                        stack_target.clone(),
                         target_branch_name,
                         &incoming_branch_name,
                     )?;
@@ -735,13 +785,13 @@ fn compute_resolutions(
                     let cache = gix_repo.commit_graph_if_enabled()?;
                     let mut graph = gix_repo.revision_graph(cache.as_ref());
                     let upstream_commit_oids =
-                        repo.l(new_target.id(), LogUntil::Commit(target.sha), true)?;
+                        repo.l(stack_target.id(), LogUntil::Commit(target.sha), true)?;
                     let mut check_commit = IsCommitIntegrated::new_basic(
                         &gix_repo,
                         repo,
                         &mut graph,
                         git2_to_gix_object_id(target.sha),
-                        git2_to_gix_object_id(new_target.tree_id()),
+                        git2_to_gix_object_id(stack_target.tree_id()),
                         upstream_commit_oids,
                     );
 
@@ -749,11 +799,14 @@ fn compute_resolutions(
                     // the tree ends up conflicted, commit the tree.
 
                     // If the base branch needs to resolve its divergence
-                    // pick only the commits that are ahead of the old target head
-                    let lower_bound = if base_branch_resolution_approach.is_some() {
+                    // pick only the commits that are ahead of the old target head.
+                    // Stacks with their own upstream target aren't affected by that.
+                    let lower_bound = if base_branch_resolution_approach.is_some()
+                        && stack_target.id() == new_target.id()
+                    {
                         target.sha
                     } else {
-                        new_target.id()
+                        stack_target.id()
                     };
 
                     let all_steps = branch_stack.as_rebase_steps(context.ctx, context.gix_repo)?;
@@ -765,6 +818,7 @@ fn compute_resolutions(
                             RebaseStep::Pick {
                                 commit_id,
                                 new_message: _,
+                                base: _,
                             } => {
                                 let commit = repo.find_commit(commit_id.to_git2()).ok()?;
                                 let is_integrated = check_commit.is_integrated(&commit).ok()?;
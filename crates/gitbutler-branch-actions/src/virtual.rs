@@ -207,11 +207,14 @@ pub fn unapply_ownership(
         .find_commit(workspace_commit_id)
         .context("failed to find target commit")?;
 
-    let base_tree_id = git2_to_gix_object_id(target_commit.tree_id());
+    let base_tree_id = git2_to_gix_object_id(
+        repo.find_real_tree(&target_commit, Default::default())?
+            .id(),
+    );
     let gix_repo = ctx.gix_repo_for_merging()?;
     let (merge_options_fail_fast, conflict_kind) = gix_repo.merge_options_fail_fast()?;
     let final_tree_id = applied_statuses.into_iter().try_fold(
-        git2_to_gix_object_id(target_commit.tree_id()),
+        base_tree_id,
         |final_tree_id, status| -> Result<_> {
             let files = status
                 .1
@@ -557,10 +560,7 @@ pub fn update_branch(ctx: &CommandContext, branch_update: &BranchUpdateRequest)
 
     if let Some(updated_upstream) = &branch_update.upstream {
         let default_target = vb_state.get_default_target()?;
-        let upstream_remote = match default_target.push_remote_name {
-            Some(remote) => remote.clone(),
-            None => default_target.branch.remote().to_owned(),
-        };
+        let upstream_remote = stack.push_remote_name(&default_target);
 
         let remote_branch = format!(
             "refs/remotes/{}/{}",
@@ -700,14 +700,8 @@ pub(crate) fn reset_branch(
     let repo = ctx.repo();
     let diff = trees(
         repo,
-        &repo
-            .find_commit(updated_head)?
-            .tree()
-            .map_err(anyhow::Error::from)?,
-        &repo
-            .find_commit(old_head)?
-            .tree()
-            .map_err(anyhow::Error::from)?,
+        &repo.find_real_tree(&repo.find_commit(updated_head)?, Default::default())?,
+        &repo.find_real_tree(&repo.find_commit(old_head)?, Default::default())?,
         true,
     )?;
 
@@ -844,13 +838,10 @@ pub(crate) fn push(
     let vb_state = ctx.project().virtual_branches();
 
     let default_target = vb_state.get_default_target()?;
-    let upstream_remote = match default_target.push_remote_name {
-        Some(remote) => remote.clone(),
-        None => default_target.branch.remote().to_owned(),
-    };
 
     let gix_repo = ctx.gix_repo()?;
     let mut stack = vb_state.get_stack_in_workspace(stack_id)?;
+    let upstream_remote = stack.push_remote_name(&default_target);
     let remote_branch = if let Some(upstream_branch) = &stack.upstream {
         upstream_branch.clone()
     } else {
@@ -1329,6 +1320,7 @@ pub(crate) fn insert_blank_commit(
                 updated_steps.push(RebaseStep::Pick {
                     commit_id: blank_commit_oid.to_gix(),
                     new_message: None,
+                    base: None,
                 });
             }
         }
@@ -1340,6 +1332,7 @@ pub(crate) fn insert_blank_commit(
             RebaseStep::Pick {
                 commit_id: blank_commit_oid.to_gix(),
                 new_message: None,
+                base: None,
             },
         );
     }
@@ -1404,6 +1397,7 @@ pub(crate) fn update_commit_message(
         if let RebaseStep::Pick {
             commit_id: id,
             new_message,
+            base: _,
         } = step
         {
             if *id == commit_id.to_gix() {
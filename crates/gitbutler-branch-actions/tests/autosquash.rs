@@ -0,0 +1,103 @@
+use anyhow::Result;
+use bstr::ByteSlice;
+use gitbutler_branch_actions::{autosquash_stack, internal::PatchSeries, list_virtual_branches};
+use gitbutler_command_context::CommandContext;
+use gitbutler_stack::VirtualBranchesHandle;
+use itertools::Itertools;
+use tempfile::TempDir;
+
+// Fold fixups into commits of the branches below them
+//
+// - squash! commit 1 (a-branch-3) ──┐
+// - fixup! commit 2              ──┐│
+// - commit 3 (a-branch-2)          ││
+// - commit 2                     ◄─┘│
+// - commit 1 (a-branch-1)         ◄─┘
+//
+// Result:
+// - commit 3 (a-branch-2, a-branch-3)
+// - commit 2+fixup
+// - commit 1+squash (a-branch-1)
+#[test]
+fn fold_into_lower_branches() -> Result<()> {
+    let (ctx, _temp_dir) = command_ctx()?;
+    let handle = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let stack = handle
+        .list_all_stacks()?
+        .into_iter()
+        .find(|b| b.name == "my_stack")
+        .unwrap();
+
+    assert_eq!(autosquash_stack(&ctx, stack.id)?, 2);
+
+    let branches = list_branches(&ctx)?;
+    // branch 1
+    assert_eq!(branches.b1.patches.len(), 1);
+    assert_eq!(
+        branches.b1.patches[0].description,
+        "commit 1\n\nmore about commit 1"
+    );
+    assert_eq!(
+        blob_content(ctx.repo(), branches.b1.patches[0].id, "file1")?,
+        "change1\nsquash1\n"
+    );
+
+    // branch 2
+    assert_eq!(branches.b2.patches.len(), 2);
+    assert_eq!(branches.b2.patches[0].description, "commit 3");
+    assert_eq!(branches.b2.patches[1].description, "commit 2");
+    assert_eq!(
+        blob_content(ctx.repo(), branches.b2.patches[1].id, "file2")?,
+        "change2\nfixup2\n"
+    );
+
+    // branch 3
+    assert_eq!(branches.b3.patches.len(), 0);
+
+    assert_eq!(
+        autosquash_stack(&ctx, stack.id)?,
+        0,
+        "nothing is left to fold"
+    );
+    Ok(())
+}
+
+fn command_ctx() -> Result<(CommandContext, TempDir)> {
+    gitbutler_testsupport::writable::fixture("autosquash.sh", "fixups")
+}
+
+/// Stack branches, but from the list API
+#[derive(Debug, PartialEq, Clone)]
+struct TestBranchListing {
+    b1: PatchSeries,
+    b2: PatchSeries,
+    b3: PatchSeries,
+}
+
+/// Stack branches from the API
+fn list_branches(ctx: &CommandContext) -> Result<TestBranchListing> {
+    let branches = list_virtual_branches(ctx)?
+        .branches
+        .first()
+        .unwrap()
+        .series
+        .iter()
+        .map(|s| s.clone().unwrap())
+        .collect_vec();
+    fn find(branches: &[PatchSeries], name: &str) -> PatchSeries {
+        branches.iter().find(|b| b.name == name).unwrap().clone()
+    }
+    Ok(TestBranchListing {
+        b1: find(&branches, "a-branch-1"),
+        b2: find(&branches, "a-branch-2"),
+        b3: find(&branches, "a-branch-3"),
+    })
+}
+
+fn blob_content(repo: &git2::Repository, commit_oid: git2::Oid, file: &str) -> Result<String> {
+    let tree = repo.find_commit(commit_oid)?.tree()?;
+    let entry = tree.get_name(file).unwrap();
+    let blob = repo.find_blob(entry.id())?;
+    let blob_content: &str = blob.content().to_str()?;
+    Ok(blob_content.to_string())
+}
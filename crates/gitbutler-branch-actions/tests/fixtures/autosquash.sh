@@ -0,0 +1,46 @@
+#!/usr/bin/env bash
+set -eu -o pipefail
+CLI=${1:?The first argument is the GitButler CLI}
+
+
+git init remote
+(cd remote
+  echo a > file
+  git add . && git commit -m "init"
+)
+
+export GITBUTLER_CLI_DATA_DIR=../user/gitbutler/app-data
+
+# Scenario:
+# - squash! commit 1 (a-branch-3)
+# - fixup! commit 2
+# - commit 3 (a-branch-2)
+# - commit 2
+# - commit 1 (a-branch-1)
+git clone remote fixups
+(cd fixups
+  git config user.name "Author"
+  git config user.email "author@example.com"
+
+  git branch existing-branch
+  $CLI project add --switch-to-workspace "$(git rev-parse --symbolic-full-name @{u})"
+
+  $CLI branch create --set-default my_stack
+
+  echo change1 >> file1
+  $CLI branch commit my_stack -m "commit 1"
+
+  $CLI branch series my_stack -s "a-branch-2"
+
+  echo change2 >> file2
+  $CLI branch commit my_stack -m "commit 2"
+  echo change3 >> file3
+  $CLI branch commit my_stack -m "commit 3"
+
+  $CLI branch series my_stack -s "a-branch-3"
+
+  echo fixup2 >> file2
+  $CLI branch commit my_stack -m "fixup! commit 2"
+  echo squash1 >> file1
+  $CLI branch commit my_stack -m "$(printf 'squash! commit 1\n\nmore about commit 1')"
+)
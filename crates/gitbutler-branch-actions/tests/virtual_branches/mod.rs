@@ -76,10 +76,12 @@ mod save_and_unapply_virtual_branch;
 mod selected_for_changes;
 mod set_base_branch;
 mod squash;
+mod stack_upstream_target;
 mod unapply_ownership;
 mod unapply_without_saving_virtual_branch;
 mod undo_commit;
 mod update_commit_message;
+mod update_workspace_commit;
 mod upstream;
 mod verify_branch;
 mod workspace_migration;
@@ -2,8 +2,17 @@ use std::{io::Write, path::Path, time::Duration};
 
 use gitbutler_branch::BranchCreateRequest;
 use gitbutler_branch_actions::list_commit_files;
-use gitbutler_oplog::OplogExt;
-use gitbutler_stack::VirtualBranchesHandle;
+use gitbutler_oplog::{
+    auto_snapshot::{create_branch_switch_snapshot, AutoSnapshotTrigger, HeadTracker},
+    bundle,
+    compare::HeadChange,
+    entry::{OperationKind, Snapshot, SnapshotDetails},
+    file_limits::ExclusionReason,
+    retention::PruneOutcome,
+    OplogExt, RestoreSelection,
+};
+use gitbutler_project::{AutoSnapshotPolicy, Project, SnapshotFileLimits, SnapshotRetention};
+use gitbutler_stack::{StackId, VirtualBranchesHandle};
 use itertools::Itertools;
 
 use super::*;
@@ -353,6 +362,685 @@ fn restores_gitbutler_workspace() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn prune_keeps_most_recent_snapshots() -> anyhow::Result<()> {
+    let Test {
+        repo, project, ctx, ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(ctx, &"refs/remotes/origin/master".parse()?)?;
+    let stack_entry =
+        gitbutler_branch_actions::create_virtual_branch(ctx, &BranchCreateRequest::default())?;
+    for round in 0..2 {
+        fs::write(repo.path().join("file.txt"), format!("content {round}"))?;
+        gitbutler_branch_actions::create_commit(ctx, stack_entry.id, "commit", None)?;
+    }
+
+    let snapshots = project.list_snapshots(10, None)?;
+    assert_eq!(snapshots.len(), 3, "create vbranch, two commits");
+
+    let mut guard = project.exclusive_worktree_access();
+    let retention = SnapshotRetention {
+        keep_last: Some(2),
+        ..Default::default()
+    };
+    let outcome = project.prune_snapshots(&retention, guard.write_permission())?;
+    assert_eq!(outcome.kept, 2);
+    assert_eq!(outcome.removed, [snapshots[2].commit_id]);
+    assert_eq!(
+        outcome.rewritten.len(),
+        2,
+        "the oldest kept snapshot lost its parent, so all newer ones are rewritten"
+    );
+
+    let pruned = project.list_snapshots(10, None)?;
+    assert_eq!(pruned.len(), 2);
+    assert_eq!(
+        project.oplog_head()?,
+        Some(pruned[0].commit_id),
+        "the oplog head points to the rewritten most recent snapshot"
+    );
+    for (before, after) in snapshots.iter().zip(&pruned) {
+        assert_eq!(before.details, after.details, "the content didn't change");
+    }
+
+    let outcome = project.prune_snapshots(&retention, guard.write_permission())?;
+    assert_eq!(
+        outcome,
+        PruneOutcome {
+            kept: 2,
+            ..Default::default()
+        },
+        "there is nothing to do the second time"
+    );
+
+    project
+        .restore_snapshot(pruned[1].commit_id, guard.write_permission())
+        .expect("pruned snapshots can still be restored");
+    assert_eq!(project.list_snapshots(10, None)?.len(), 3);
+    Ok(())
+}
+
+#[test]
+fn restore_selected_paths() -> anyhow::Result<()> {
+    let Test {
+        repo, project, ctx, ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(ctx, &"refs/remotes/origin/master".parse()?)?;
+    let stack_entry =
+        gitbutler_branch_actions::create_virtual_branch(ctx, &BranchCreateRequest::default())?;
+    for round in ["one", "two"] {
+        fs::write(repo.path().join("file.txt"), round)?;
+        fs::write(repo.path().join("other.txt"), round)?;
+        gitbutler_branch_actions::create_commit(ctx, stack_entry.id, round, None)?;
+    }
+
+    let snapshots = project.list_snapshots(10, None)?;
+    assert_eq!(snapshots.len(), 3, "create vbranch, two commits");
+
+    let mut guard = project.exclusive_worktree_access();
+    let restore_snapshot_id = project.restore_snapshot_selection(
+        snapshots[0].commit_id,
+        &RestoreSelection {
+            paths: vec!["file.txt".into()],
+            ..Default::default()
+        },
+        guard.write_permission(),
+    )?;
+    drop(guard);
+    // The snapshot before the second commit saw the changes of the second round.
+    assert_eq!(fs::read_to_string(repo.path().join("file.txt"))?, "two");
+
+    let mut guard = project.exclusive_worktree_access();
+    project.restore_snapshot_selection(
+        snapshots[1].commit_id,
+        &RestoreSelection {
+            paths: vec!["file.txt".into()],
+            ..Default::default()
+        },
+        guard.write_permission(),
+    )?;
+    assert_eq!(
+        fs::read_to_string(repo.path().join("file.txt"))?,
+        "one",
+        "the selected file is restored"
+    );
+    assert_eq!(
+        fs::read_to_string(repo.path().join("other.txt"))?,
+        "two",
+        "everything else remains unchanged"
+    );
+
+    let snapshots = project.list_snapshots(10, None)?;
+    assert_eq!(snapshots.len(), 5, "each restoration is recorded");
+    assert_eq!(snapshots[1].commit_id, restore_snapshot_id);
+    let details = snapshots[0].details.as_ref().expect("valid details");
+    assert_eq!(details.operation, OperationKind::RestoreFromSnapshot);
+    assert!(details
+        .trailers
+        .iter()
+        .any(|t| t.key == "restored_path" && t.value == "file.txt"));
+    Ok(())
+}
+
+#[test]
+fn restore_selected_stack() -> anyhow::Result<()> {
+    let Test {
+        repo, project, ctx, ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(ctx, &"refs/remotes/origin/master".parse()?)?;
+    let stack_entry =
+        gitbutler_branch_actions::create_virtual_branch(ctx, &BranchCreateRequest::default())?;
+    for round in ["one", "two"] {
+        fs::write(repo.path().join(format!("{round}.txt")), round)?;
+        gitbutler_branch_actions::create_commit(ctx, stack_entry.id, round, None)?;
+    }
+    let patch_count = || -> anyhow::Result<usize> {
+        let stack = gitbutler_branch_actions::list_virtual_branches(ctx)?
+            .branches
+            .into_iter()
+            .find(|b| b.id == stack_entry.id)
+            .expect("stack is present");
+        Ok(stack.series[0].clone()?.patches.len())
+    };
+    assert_eq!(patch_count()?, 2);
+
+    let snapshots = project.list_snapshots(10, None)?;
+    assert_eq!(
+        snapshots[0].details.as_ref().map(|d| d.operation),
+        Some(OperationKind::CreateCommit),
+        "this one was taken before the second commit"
+    );
+    gitbutler_branch_actions::restore_snapshot_selection(
+        ctx,
+        snapshots[0].commit_id,
+        &RestoreSelection {
+            stacks: vec![stack_entry.id],
+            ..Default::default()
+        },
+    )?;
+    assert_eq!(patch_count()?, 1, "the stack lost its second commit");
+    assert!(
+        repo.path().join("two.txt").exists(),
+        "the worktree isn't affected"
+    );
+
+    let err = gitbutler_branch_actions::restore_snapshot_selection(
+        ctx,
+        snapshots[0].commit_id,
+        &RestoreSelection {
+            stacks: vec![StackId::generate()],
+            ..Default::default()
+        },
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("isn't part of snapshot"));
+    Ok(())
+}
+
+#[test]
+fn auto_snapshot_policy_triggers() -> anyhow::Result<()> {
+    let Test {
+        repo, project, ctx, ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(ctx, &"refs/remotes/origin/master".parse()?)?;
+    let stack_entry =
+        gitbutler_branch_actions::create_virtual_branch(ctx, &BranchCreateRequest::default())?;
+    fs::write(repo.path().join("file.txt"), "content")?;
+    gitbutler_branch_actions::create_commit(ctx, stack_entry.id, "commit", None)?;
+    assert_eq!(
+        project.auto_snapshot_trigger(Duration::ZERO)?,
+        None,
+        "nothing changed since the snapshot that was taken before the commit"
+    );
+
+    let project = Project {
+        auto_snapshot_policy: Some(AutoSnapshotPolicy {
+            on_untracked_files: true,
+            on_deleted_files: true,
+            ..Default::default()
+        }),
+        ..project.clone()
+    };
+    assert_eq!(project.auto_snapshot_trigger(Duration::ZERO)?, None);
+
+    fs::write(repo.path().join("new.txt"), "new")?;
+    assert_eq!(
+        project.auto_snapshot_trigger(Duration::ZERO)?,
+        Some(AutoSnapshotTrigger::UntrackedFiles(1))
+    );
+    assert_eq!(
+        project.auto_snapshot_trigger(Duration::from_secs(60 * 60))?,
+        Some(AutoSnapshotTrigger::UntrackedFiles(1)),
+        "only the line-count trigger waits for the last snapshot to be old enough"
+    );
+
+    fs::remove_file(repo.path().join("file.txt"))?;
+    assert_eq!(
+        project.auto_snapshot_trigger(Duration::ZERO)?,
+        Some(AutoSnapshotTrigger::DeletedFiles(1)),
+        "deletions take precedence"
+    );
+
+    let mut guard = project.exclusive_worktree_access();
+    project.create_snapshot(
+        SnapshotDetails::new(OperationKind::FileChanges),
+        guard.write_permission(),
+    )?;
+    drop(guard);
+    assert_eq!(
+        project.auto_snapshot_trigger(Duration::ZERO)?,
+        None,
+        "only deletions and new files since the last snapshot count"
+    );
+
+    let project = Project {
+        auto_snapshot_policy: Some(AutoSnapshotPolicy {
+            touched_files_threshold: Some(2),
+            ..Default::default()
+        }),
+        ..project
+    };
+    assert_eq!(
+        project.auto_snapshot_trigger(Duration::ZERO)?,
+        Some(AutoSnapshotTrigger::FilesTouched(2))
+    );
+    assert!(project.should_auto_snapshot(Duration::ZERO)?);
+    Ok(())
+}
+
+#[test]
+fn branch_switch_snapshot_records_the_workspace_before_the_switch() -> anyhow::Result<()> {
+    let Test {
+        repo, project, ctx, ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(ctx, &"refs/remotes/origin/master".parse()?)?;
+    let stack_entry =
+        gitbutler_branch_actions::create_virtual_branch(ctx, &BranchCreateRequest::default())?;
+    fs::write(repo.path().join("file.txt"), "content")?;
+    gitbutler_branch_actions::create_commit(ctx, stack_entry.id, "commit", None)?;
+    fs::write(repo.path().join("new.txt"), "new")?;
+
+    let target = VirtualBranchesHandle::new(project.gb_dir()).get_default_target()?;
+    repo.checkout_commit(target.sha);
+    assert!(
+        !repo.path().join("file.txt").exists(),
+        "the switch removed the committed file"
+    );
+
+    let mut guard = project.exclusive_worktree_access();
+    let snapshot_id = create_branch_switch_snapshot(project, guard.write_permission())?;
+    let snapshot = repo.find_commit(snapshot_id)?;
+    assert!(snapshot
+        .message()
+        .unwrap_or_default()
+        .contains("AutoSnapshotTrigger: BranchSwitch"));
+    let tree = snapshot.tree()?;
+    assert!(
+        tree.get_path(Path::new("worktree/file.txt")).is_ok(),
+        "the worktree is recorded with the workspace checked out"
+    );
+    assert!(
+        tree.get_path(Path::new("worktree/new.txt")).is_ok(),
+        "changes that were carried over by the switch are kept"
+    );
+    assert!(
+        tree.get_path(Path::new("virtual_branches/workspace"))
+            .is_ok(),
+        "the workspace commit is recorded as well"
+    );
+    Ok(())
+}
+
+#[test]
+fn only_leaving_the_workspace_is_a_branch_switch() -> anyhow::Result<()> {
+    let Test { repo, ctx, .. } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(ctx, &"refs/remotes/origin/master".parse()?)?;
+    let stack_entry =
+        gitbutler_branch_actions::create_virtual_branch(ctx, &BranchCreateRequest::default())?;
+
+    let mut head_tracker = HeadTracker::default();
+    assert!(
+        !head_tracker.head_left_workspace(ctx.repo())?,
+        "the first call only remembers HEAD"
+    );
+
+    fs::write(repo.path().join("file.txt"), "content")?;
+    gitbutler_branch_actions::create_commit(ctx, stack_entry.id, "commit", None)?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    gitbutler_branch_actions::update_workspace_commit(&vb_state, ctx)?;
+    assert!(
+        !head_tracker.head_left_workspace(ctx.repo())?,
+        "GitButler rewriting HEAD to the new workspace commit isn't a switch"
+    );
+
+    repo.checkout_commit(vb_state.get_default_target()?.sha);
+    assert!(head_tracker.head_left_workspace(ctx.repo())?);
+    assert!(
+        !head_tracker.head_left_workspace(ctx.repo())?,
+        "only the switch itself counts"
+    );
+    Ok(())
+}
+
+#[test]
+fn snapshot_file_limits_record_excluded_files() -> anyhow::Result<()> {
+    let Test {
+        repo, project, ctx, ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(ctx, &"refs/remotes/origin/master".parse()?)?;
+    let project = Project {
+        snapshot_file_limits: Some(SnapshotFileLimits {
+            max_untracked_file_bytes: Some(10),
+            always_include: vec!["*.keep".into()],
+            never_include: vec!["secrets/*".into()],
+        }),
+        ..project.clone()
+    };
+    fs::write(repo.path().join("small.txt"), "small")?;
+    fs::write(repo.path().join("large.bin"), "x".repeat(100))?;
+    fs::write(repo.path().join("large.keep"), "y".repeat(100))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::write(repo.path().join("tool.keep"), "#!/bin/sh\n".repeat(10))?;
+        fs::set_permissions(
+            repo.path().join("tool.keep"),
+            fs::Permissions::from_mode(0o755),
+        )?;
+    }
+    fs::create_dir(repo.path().join("secrets"))?;
+    fs::write(repo.path().join("secrets").join("token"), "1234")?;
+
+    let mut guard = project.exclusive_worktree_access();
+    let snapshot =
+        project.create_checkpoint("limits".into(), None, false, guard.write_permission())?;
+    #[cfg(unix)]
+    assert_eq!(
+        repo.find_commit(snapshot)?
+            .tree()?
+            .get_path(Path::new("worktree/tool.keep"))?
+            .filemode(),
+        i32::from(git2::FileMode::BlobExecutable),
+        "explicitly included files keep their executable bit"
+    );
+
+    let excluded = project.excluded_files(snapshot)?;
+    assert_eq!(
+        excluded
+            .iter()
+            .map(|file| (file.path.to_str().unwrap(), file.size, file.reason))
+            .collect::<Vec<_>>(),
+        [
+            ("large.bin", 100, ExclusionReason::TooLarge),
+            ("secrets/token", 4, ExclusionReason::Denied)
+        ]
+    );
+    assert!(excluded.iter().all(|file| file.is_present_in(repo.path())));
+    assert!(excluded.iter().all(|file| file.modified_at.is_some()));
+
+    fs::write(repo.path().join("large.bin"), "z".repeat(100))?;
+    fs::remove_file(repo.path().join("small.txt"))?;
+    fs::remove_file(repo.path().join("large.keep"))?;
+    assert!(
+        !excluded[0].is_present_in(repo.path()),
+        "the content changed"
+    );
+
+    project.restore_snapshot(snapshot, guard.write_permission())?;
+    assert_eq!(fs::read_to_string(repo.path().join("small.txt"))?, "small");
+    assert_eq!(
+        fs::read_to_string(repo.path().join("large.keep"))?,
+        "y".repeat(100),
+        "large files can be included explicitly"
+    );
+    assert_eq!(
+        fs::read_to_string(repo.path().join("large.bin"))?,
+        "z".repeat(100),
+        "excluded files are left alone"
+    );
+    assert!(repo.path().join("secrets").join("token").exists());
+    Ok(())
+}
+
+#[test]
+fn pinned_checkpoints_survive_pruning() -> anyhow::Result<()> {
+    let Test {
+        repo, project, ctx, ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(ctx, &"refs/remotes/origin/master".parse()?)?;
+    let mut guard = project.exclusive_worktree_access();
+    let checkpoint = project.create_checkpoint(
+        "before big refactor".into(),
+        Some("all is well".into()),
+        true,
+        guard.write_permission(),
+    )?;
+    drop(guard);
+
+    let stack_entry =
+        gitbutler_branch_actions::create_virtual_branch(ctx, &BranchCreateRequest::default())?;
+    fs::write(repo.path().join("file.txt"), "content")?;
+    gitbutler_branch_actions::create_commit(ctx, stack_entry.id, "commit", None)?;
+
+    let snapshots = project.list_snapshots(10, None)?;
+    assert_eq!(snapshots.len(), 3);
+    let details = snapshots[2].details.as_ref().expect("valid details");
+    assert_eq!(snapshots[2].commit_id, checkpoint);
+    assert_eq!(details.operation, OperationKind::Checkpoint);
+    assert_eq!(details.title, "before big refactor");
+    assert_eq!(details.body.as_deref(), Some("all is well"));
+    assert!(details.is_pinned());
+
+    let mut guard = project.exclusive_worktree_access();
+    let retention = SnapshotRetention {
+        keep_last: Some(1),
+        ..Default::default()
+    };
+    let outcome = project.prune_snapshots(&retention, guard.write_permission())?;
+    assert_eq!(outcome.kept, 2, "the pinned checkpoint is kept");
+    assert_eq!(outcome.removed, [snapshots[1].commit_id]);
+
+    let snapshots = project.list_snapshots(10, None)?;
+    assert_eq!(snapshots[1].commit_id, checkpoint, "it wasn't rewritten");
+    let unpinned = project.set_snapshot_pinned(checkpoint, false, guard.write_permission())?;
+    assert_ne!(unpinned, checkpoint, "the snapshot was rewritten");
+    assert_eq!(
+        project.set_snapshot_pinned(unpinned, false, guard.write_permission())?,
+        unpinned,
+        "nothing changes if the pin state is the same"
+    );
+
+    let snapshots = project.list_snapshots(10, None)?;
+    assert_eq!(snapshots.len(), 2);
+    assert_eq!(snapshots[1].commit_id, unpinned);
+    assert!(!snapshots[1].details.as_ref().is_some_and(|d| d.is_pinned()));
+
+    let outcome = project.prune_snapshots(&retention, guard.write_permission())?;
+    assert_eq!(outcome.removed, [unpinned], "now it can be pruned");
+    Ok(())
+}
+
+#[test]
+fn only_oplog_commits_are_snapshots() -> anyhow::Result<()> {
+    let Test {
+        repo, project, ctx, ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(ctx, &"refs/remotes/origin/master".parse()?)?;
+    let head_commit = repo.local_repo.head()?.peel_to_commit()?.id();
+    assert!(!project.is_snapshot(head_commit)?, "there is no oplog yet");
+
+    let stack_entry =
+        gitbutler_branch_actions::create_virtual_branch(ctx, &BranchCreateRequest::default())?;
+    fs::write(repo.path().join("file.txt"), "content")?;
+    gitbutler_branch_actions::create_commit(ctx, stack_entry.id, "commit", None)?;
+
+    let snapshots = project.list_snapshots(10, None)?;
+    assert_eq!(snapshots.len(), 2);
+    for snapshot in &snapshots {
+        assert!(project.is_snapshot(snapshot.commit_id)?);
+    }
+    let head_commit = repo.local_repo.head()?.peel_to_commit()?.id();
+    assert!(
+        !project.is_snapshot(head_commit)?,
+        "commits of the repository aren't snapshots"
+    );
+    Ok(())
+}
+
+#[test]
+fn compare_snapshots_with_stack_changes() -> anyhow::Result<()> {
+    let Test {
+        repo, project, ctx, ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(ctx, &"refs/remotes/origin/master".parse()?)?;
+    let stack_entry =
+        gitbutler_branch_actions::create_virtual_branch(ctx, &BranchCreateRequest::default())?;
+    for round in ["one", "two"] {
+        fs::write(repo.path().join(format!("{round}.txt")), round)?;
+        gitbutler_branch_actions::create_commit(ctx, stack_entry.id, round, None)?;
+    }
+
+    let snapshots = project.list_snapshots(10, None)?;
+    assert_eq!(snapshots.len(), 3);
+    let (before_second_commit, before_first_commit, before_stack) = (
+        snapshots[0].commit_id,
+        snapshots[1].commit_id,
+        snapshots[2].commit_id,
+    );
+
+    let comparison = project.compare_snapshots(before_first_commit, before_second_commit)?;
+    assert!(comparison.stacks_added.is_empty());
+    assert!(comparison.stacks_removed.is_empty());
+    assert!(comparison.target.is_none(), "the target didn't change");
+    assert_eq!(comparison.stacks_changed.len(), 1);
+    let stack = &comparison.stacks_changed[0];
+    assert_eq!(stack.id, stack_entry.id);
+    assert_eq!(stack.renamed_from, None);
+    assert!(
+        matches!(
+            stack.heads.as_slice(),
+            [HeadChange::Moved { previous, current, .. }] if previous != current
+        ),
+        "the first commit moved the branch head"
+    );
+    assert_eq!(
+        comparison
+            .worktree_changes
+            .iter()
+            .map(|change| change.path.to_string())
+            .collect::<Vec<_>>(),
+        ["two.txt"]
+    );
+
+    let comparison = project.compare_snapshots(before_stack, before_second_commit)?;
+    assert_eq!(comparison.stacks_added.len(), 1);
+    assert_eq!(comparison.stacks_added[0].id, stack_entry.id);
+    assert!(comparison.stacks_changed.is_empty());
+
+    let comparison = project.compare_snapshots(before_second_commit, before_stack)?;
+    assert_eq!(
+        comparison.stacks_removed.len(),
+        1,
+        "comparisons work in both directions"
+    );
+
+    assert!(project
+        .compare_snapshots(before_second_commit, before_second_commit)?
+        .is_empty());
+    Ok(())
+}
+
+#[test]
+fn export_and_import_bundle() -> anyhow::Result<()> {
+    let Test {
+        repo, project, ctx, ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(ctx, &"refs/remotes/origin/master".parse()?)?;
+    let stack_entry =
+        gitbutler_branch_actions::create_virtual_branch(ctx, &BranchCreateRequest::default())?;
+    for round in ["one", "two"] {
+        fs::write(repo.path().join(format!("{round}.txt")), round)?;
+        gitbutler_branch_actions::create_commit(ctx, stack_entry.id, round, None)?;
+    }
+    let snapshots = project.list_snapshots(10, None)?;
+    assert_eq!(snapshots.len(), 3);
+
+    let bundle_dir = tempfile::tempdir()?;
+    let bundle_path = bundle_dir.path().join("oplog.bundle");
+    let manifest = {
+        let guard = project.exclusive_worktree_access();
+        project.export_oplog(&bundle_path, Some(2), guard.read_permission())?
+    };
+    assert_eq!(manifest.snapshot_count, 2);
+    assert!(manifest.truncated, "the oldest snapshot was left out");
+    assert_eq!(manifest.source_head, snapshots[0].commit_id);
+    assert_ne!(
+        manifest.head, snapshots[0].commit_id,
+        "the exported snapshots had to be rewritten"
+    );
+    assert!(bundle::manifest_path(&bundle_path).is_file());
+
+    let Test {
+        project: other_project,
+        ctx: other_ctx,
+        ..
+    } = &Test::default();
+    gitbutler_branch_actions::set_base_branch(other_ctx, &"refs/remotes/origin/master".parse()?)?;
+    let outcome = {
+        let mut guard = other_project.exclusive_worktree_access();
+        other_project.import_oplog(&bundle_path, guard.write_permission())?
+    };
+    assert_eq!(outcome.imported, 2);
+    assert_eq!(
+        outcome.head, manifest.head,
+        "there was nothing to graft onto"
+    );
+    let titles = |snapshots: &[Snapshot]| {
+        snapshots
+            .iter()
+            .map(|s| s.details.as_ref().map(|d| d.title.clone()))
+            .collect::<Vec<_>>()
+    };
+    let imported = other_project.list_snapshots(10, None)?;
+    assert_eq!(titles(&imported), titles(&snapshots[..2]));
+
+    let outcome = {
+        let mut guard = project.exclusive_worktree_access();
+        project.import_oplog(&bundle_path, guard.write_permission())?
+    };
+    assert_eq!(
+        outcome.imported, 0,
+        "the bundle only contains snapshots of this oplog"
+    );
+    assert_eq!(outcome.head, snapshots[0].commit_id);
+    assert_eq!(project.list_snapshots(10, None)?.len(), 3);
+    Ok(())
+}
+
+#[test]
+fn importing_a_bundle_twice_adds_its_snapshots_once() -> anyhow::Result<()> {
+    let Test {
+        repo, project, ctx, ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(ctx, &"refs/remotes/origin/master".parse()?)?;
+    let stack_entry =
+        gitbutler_branch_actions::create_virtual_branch(ctx, &BranchCreateRequest::default())?;
+    fs::write(repo.path().join("one.txt"), "one")?;
+    gitbutler_branch_actions::create_commit(ctx, stack_entry.id, "one", None)?;
+
+    let bundle_dir = tempfile::tempdir()?;
+    let bundle_path = bundle_dir.path().join("oplog.bundle");
+    {
+        let guard = project.exclusive_worktree_access();
+        project.export_oplog(&bundle_path, None, guard.read_permission())?;
+    }
+
+    let Test {
+        repo: other_repo,
+        project: other_project,
+        ctx: other_ctx,
+        ..
+    } = &Test::default();
+    gitbutler_branch_actions::set_base_branch(other_ctx, &"refs/remotes/origin/master".parse()?)?;
+    let other_stack_entry = gitbutler_branch_actions::create_virtual_branch(
+        other_ctx,
+        &BranchCreateRequest::default(),
+    )?;
+    fs::write(other_repo.path().join("other.txt"), "other")?;
+    gitbutler_branch_actions::create_commit(other_ctx, other_stack_entry.id, "other", None)?;
+    let first = {
+        let mut guard = other_project.exclusive_worktree_access();
+        other_project.import_oplog(&bundle_path, guard.write_permission())?
+    };
+    assert_ne!(first.imported, 0);
+    let snapshot_count = other_project.list_snapshots(10, None)?.len();
+
+    let second = {
+        let mut guard = other_project.exclusive_worktree_access();
+        other_project.import_oplog(&bundle_path, guard.write_permission())?
+    };
+    assert_eq!(second.imported, 0, "all snapshots are known already");
+    assert_eq!(second.head, first.head);
+    assert_eq!(
+        other_project.list_snapshots(10, None)?.len(),
+        snapshot_count,
+        "the history isn't duplicated"
+    );
+    Ok(())
+}
+
 // test operations-log.toml head is not a commit
 #[test]
 fn head_corrupt_is_recreated_automatically() {
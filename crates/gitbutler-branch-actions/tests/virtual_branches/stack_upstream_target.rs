@@ -0,0 +1,98 @@
+use std::fs;
+
+use gitbutler_branch::BranchCreateRequest;
+use gitbutler_branch_actions::upstream_integration::{
+    Resolution, ResolutionApproach, StackStatuses,
+};
+use gitbutler_stack::VirtualBranchesHandle;
+
+use super::Test;
+
+#[test]
+fn stacks_are_integrated_with_their_upstream_target() {
+    let Test { repo, ctx, .. } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(ctx, &"refs/remotes/origin/master".parse().unwrap())
+        .unwrap();
+
+    let stack_entry =
+        gitbutler_branch_actions::create_virtual_branch(ctx, &BranchCreateRequest::default())
+            .unwrap();
+    fs::write(repo.path().join("file.txt"), "one\n").unwrap();
+    gitbutler_branch_actions::create_commit(ctx, stack_entry.id, "one", None).unwrap();
+
+    // The upstream of a fork, which is ahead of the default target.
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let git_repo = ctx.repo();
+    let target = git_repo
+        .find_commit(vb_state.get_default_target().unwrap().sha)
+        .unwrap();
+    let tree = {
+        let blob = git_repo.blob(b"upstream\n").unwrap();
+        let mut builder = git_repo.treebuilder(Some(&target.tree().unwrap())).unwrap();
+        builder.insert("upstream.txt", blob, 0o100644).unwrap();
+        git_repo.find_tree(builder.write().unwrap()).unwrap()
+    };
+    let signature = git2::Signature::now("test", "test@example.com").unwrap();
+    let upstream_commit = git_repo
+        .commit(
+            Some("refs/remotes/upstream/master"),
+            &signature,
+            &signature,
+            "upstream",
+            &tree,
+            &[&target],
+        )
+        .unwrap();
+
+    gitbutler_branch_actions::stack::set_stack_upstream_target(
+        ctx,
+        stack_entry.id,
+        Some("refs/remotes/upstream/master".parse().unwrap()),
+    )
+    .unwrap();
+
+    let StackStatuses::UpdatesRequired { statuses, .. } =
+        gitbutler_branch_actions::upstream_integration_statuses(ctx, None).unwrap()
+    else {
+        panic!("the stack is behind its upstream target")
+    };
+    assert_eq!(statuses.len(), 1);
+    assert_eq!(statuses[0].0, stack_entry.id);
+
+    gitbutler_branch_actions::integrate_upstream(
+        ctx,
+        &[Resolution {
+            branch_id: stack_entry.id,
+            approach: ResolutionApproach::Rebase,
+            delete_integrated_branches: false,
+        }],
+        None,
+    )
+    .unwrap();
+
+    let stack = vb_state.get_stack(stack_entry.id).unwrap();
+    let head = git_repo
+        .find_commit(stack.head(&ctx.gix_repo().unwrap()).unwrap())
+        .unwrap();
+    assert_eq!(
+        head.parent_id(0).unwrap(),
+        upstream_commit,
+        "the stack is rebased onto its upstream target, not the default target"
+    );
+    assert_eq!(
+        vb_state.get_default_target().unwrap().sha,
+        target.id(),
+        "the default target didn't change"
+    );
+    assert_eq!(
+        fs::read_to_string(repo.path().join("upstream.txt")).unwrap(),
+        "upstream\n"
+    );
+
+    assert_eq!(
+        gitbutler_branch_actions::upstream_integration_statuses(ctx, None).unwrap(),
+        StackStatuses::UpToDate,
+        "the stack contains its upstream target now"
+    );
+}
@@ -0,0 +1,83 @@
+use std::fs;
+
+use gitbutler_branch::BranchCreateRequest;
+use gitbutler_cherry_pick::RepositoryExt as _;
+use gitbutler_commit::commit_ext::CommitExt;
+use gitbutler_stack::VirtualBranchesHandle;
+
+use super::Test;
+
+#[test]
+fn conflicting_stacks_are_recorded_in_the_workspace_commit() {
+    let Test { repo, ctx, .. } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(ctx, &"refs/remotes/origin/master".parse().unwrap())
+        .unwrap();
+
+    let stack_entry_1 =
+        gitbutler_branch_actions::create_virtual_branch(ctx, &BranchCreateRequest::default())
+            .unwrap();
+    fs::write(repo.path().join("file.txt"), "one\n").unwrap();
+    gitbutler_branch_actions::create_commit(ctx, stack_entry_1.id, "one", None).unwrap();
+
+    let stack_entry_2 =
+        gitbutler_branch_actions::create_virtual_branch(ctx, &BranchCreateRequest::default())
+            .unwrap();
+
+    // Put a commit that changes the same file onto the second stack, bypassing the worktree.
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let git_repo = ctx.repo();
+    let target = git_repo
+        .find_commit(vb_state.get_default_target().unwrap().sha)
+        .unwrap();
+    let tree = {
+        let blob = git_repo.blob(b"two\n").unwrap();
+        let mut builder = git_repo.treebuilder(Some(&target.tree().unwrap())).unwrap();
+        builder.insert("file.txt", blob, 0o100644).unwrap();
+        git_repo.find_tree(builder.write().unwrap()).unwrap()
+    };
+    let signature = git2::Signature::now("test", "test@example.com").unwrap();
+    let conflicting_commit = git_repo
+        .commit(None, &signature, &signature, "two", &tree, &[&target])
+        .unwrap();
+    let mut stack_2 = vb_state.get_stack(stack_entry_2.id).unwrap();
+    stack_2
+        .set_stack_head(
+            &vb_state,
+            &ctx.gix_repo().unwrap(),
+            conflicting_commit,
+            Some(tree.id()),
+        )
+        .unwrap();
+
+    let workspace_commit_id =
+        gitbutler_branch_actions::update_workspace_commit(&vb_state, ctx).unwrap();
+
+    assert_eq!(
+        vb_state.list_stacks_in_workspace().unwrap().len(),
+        2,
+        "both stacks remain applied"
+    );
+    let workspace_commit = git_repo.find_commit(workspace_commit_id).unwrap();
+    assert!(workspace_commit.is_conflicted());
+    assert_eq!(workspace_commit.parent_count(), 2);
+
+    let auto_resolution = git_repo
+        .find_real_tree(&workspace_commit, Default::default())
+        .unwrap();
+    let index = git_repo.index().unwrap();
+    assert!(
+        index
+            .iter()
+            .all(|entry| !entry.path.starts_with(b".conflict-")
+                && !entry.path.starts_with(b".auto-resolution")),
+        "the index only contains the auto-resolution, not the conflict trees"
+    );
+    assert_eq!(
+        index
+            .get_path(std::path::Path::new("file.txt"), 0)
+            .expect("resolved file is present")
+            .id,
+        auto_resolution.get_name("file.txt").unwrap().id(),
+    );
+}
@@ -9,11 +9,26 @@ pub struct Args {
     /// Run as if gitbutler-cli was started in PATH instead of the current working directory.
     #[clap(short = 'C', long, default_value = ".", value_name = "PATH")]
     pub current_dir: PathBuf,
+    /// How to print the results of a command.
+    ///
+    /// `json` wraps the result or the error into a versioned envelope, and is meant to be consumed by scripts.
+    #[clap(long, short = 'f', value_enum, default_value_t = OutputFormat::Human, global = true)]
+    pub format: OutputFormat,
 
     #[clap(subcommand)]
     pub cmd: Subcommands,
 }
 
+/// The way results of commands are printed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Output for humans, which isn't stable and may change at any time.
+    #[default]
+    Human,
+    /// A JSON object with `version` and either `data` or `error` on a single line.
+    Json,
+}
+
 #[derive(Debug, Clone, clap::ValueEnum)]
 pub enum UpdateMode {
     Rebase,
@@ -186,6 +201,12 @@ pub mod snapshot {
         Restore {
             /// The snapshot to restore
             snapshot_id: String,
+            /// Only restore the worktree files at or below the given path. May be repeated.
+            #[clap(long = "path", short = 'p', value_name = "PATH")]
+            paths: Vec<std::path::PathBuf>,
+            /// Only restore the stack with the given id. May be repeated.
+            #[clap(long = "stack", short = 's', value_name = "STACK_ID")]
+            stack_ids: Vec<String>,
         },
         /// Show what is stored in a given snapshot.
         Diff {
@@ -1,14 +1,24 @@
+use crate::args::OutputFormat;
+use gitbutler_error::envelope::Envelope;
+
 pub mod prepare;
 pub mod project;
 pub mod vbranch;
 pub mod snapshot {
-    use crate::command::debug_print;
+    use crate::args::OutputFormat;
+    use crate::command::{json_print, print};
     use anyhow::Result;
-    use gitbutler_oplog::OplogExt;
+    use but_settings::AppSettings;
+    use gitbutler_command_context::CommandContext;
+    use gitbutler_oplog::{OplogExt, RestoreSelection};
     use gitbutler_project::Project;
+    use std::path::PathBuf;
 
-    pub fn list(project: Project) -> Result<()> {
+    pub fn list(project: Project, format: OutputFormat) -> Result<()> {
         let snapshots = project.list_snapshots(100, None)?;
+        if format == OutputFormat::Json {
+            return json_print(snapshots);
+        }
         for snapshot in snapshots {
             let ts = chrono::DateTime::from_timestamp(snapshot.created_at.seconds(), 0);
             let details = snapshot.details;
@@ -19,15 +29,40 @@ pub mod snapshot {
         Ok(())
     }
 
-    pub fn restore(project: Project, snapshot_id: String) -> Result<()> {
+    pub fn restore(
+        project: Project,
+        snapshot_id: String,
+        paths: Vec<PathBuf>,
+        stack_ids: Vec<String>,
+        format: OutputFormat,
+    ) -> Result<()> {
         let _guard = project.try_exclusive_access()?;
-        let mut guard = project.exclusive_worktree_access();
-        project.restore_snapshot(snapshot_id.parse()?, guard.write_permission())?;
+        let selection = RestoreSelection {
+            paths,
+            stacks: stack_ids
+                .iter()
+                .map(|id| id.parse())
+                .collect::<Result<_, _>>()?,
+        };
+        if selection.is_empty() {
+            let mut guard = project.exclusive_worktree_access();
+            project.restore_snapshot(snapshot_id.parse()?, guard.write_permission())?;
+        } else {
+            let ctx = CommandContext::open(&project, AppSettings::default())?;
+            gitbutler_branch_actions::restore_snapshot_selection(
+                &ctx,
+                snapshot_id.parse()?,
+                &selection,
+            )?;
+        }
+        if format == OutputFormat::Json {
+            return json_print(());
+        }
         Ok(())
     }
 
-    pub fn diff(project: Project, snapshot_id: String) -> Result<()> {
-        debug_print(project.snapshot_diff(snapshot_id.parse()?))
+    pub fn diff(project: Project, snapshot_id: String, format: OutputFormat) -> Result<()> {
+        print(project.snapshot_diff(snapshot_id.parse()?)?, format)
     }
 }
 
@@ -36,7 +71,32 @@ fn debug_print(this: impl std::fmt::Debug) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Print `this` as `data` of the versioned JSON envelope.
+fn json_print(this: impl serde::Serialize) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string(&Envelope::data(this))?);
+    Ok(())
+}
+
+/// Print `this` in the given `format`, assuming that it is a type meant for the frontend.
+fn print<T>(this: T, format: OutputFormat) -> anyhow::Result<()>
+where
+    T: std::fmt::Debug + serde::Serialize,
+{
+    match format {
+        OutputFormat::Human => debug_print(this),
+        OutputFormat::Json => json_print(this),
+    }
+}
+
+/// Print `err` as `error` of the versioned JSON envelope, so scripts can learn about the kind of failure.
+pub fn json_print_error(err: &anyhow::Error) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string(&Envelope::error(err))?);
+    Ok(())
+}
+
 pub mod ownership {
+    use crate::args::OutputFormat;
+    use crate::command::json_print;
     use but_settings::AppSettings;
     use gitbutler_command_context::CommandContext;
     use gitbutler_diff::Hunk;
@@ -49,6 +109,7 @@ pub mod ownership {
         file_path: PathBuf,
         from_line: u32,
         to_line: u32,
+        format: OutputFormat,
     ) -> anyhow::Result<()> {
         let claims = BranchOwnershipClaims {
             claims: vec![OwnershipClaim {
@@ -63,18 +124,23 @@ pub mod ownership {
         };
 
         let ctx = CommandContext::open(&project, AppSettings::default())?;
-        gitbutler_branch_actions::unapply_ownership(&ctx, &claims)
+        gitbutler_branch_actions::unapply_ownership(&ctx, &claims)?;
+        if format == OutputFormat::Json {
+            return json_print(());
+        }
+        Ok(())
     }
 }
 
 pub mod workspace {
-    use crate::args::UpdateMode;
+    use crate::args::{OutputFormat, UpdateMode};
+    use crate::command::json_print;
     use but_settings::AppSettings;
     use gitbutler_branch_actions::upstream_integration;
     use gitbutler_command_context::CommandContext;
     use gitbutler_project::Project;
 
-    pub fn update(project: Project, mode: UpdateMode) -> anyhow::Result<()> {
+    pub fn update(project: Project, mode: UpdateMode, format: OutputFormat) -> anyhow::Result<()> {
         let approach = match mode {
             UpdateMode::Rebase => upstream_integration::ResolutionApproach::Rebase,
             UpdateMode::Merge => upstream_integration::ResolutionApproach::Merge,
@@ -93,6 +159,9 @@ pub mod workspace {
             .collect();
         gitbutler_branch_actions::integrate_upstream(&ctx, &resolutions, None)?;
 
+        if format == OutputFormat::Json {
+            return json_print(());
+        }
         Ok(())
     }
 }
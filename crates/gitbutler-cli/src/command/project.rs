@@ -6,10 +6,15 @@ use gitbutler_command_context::CommandContext;
 use gitbutler_project::Project;
 use gitbutler_reference::RemoteRefname;
 
-use crate::command::debug_print;
+use crate::args::OutputFormat;
+use crate::command::{json_print, print};
 
-pub fn list(ctrl: gitbutler_project::Controller) -> Result<()> {
-    for project in ctrl.list()? {
+pub fn list(ctrl: gitbutler_project::Controller, format: OutputFormat) -> Result<()> {
+    let projects = ctrl.list()?;
+    if format == OutputFormat::Json {
+        return json_print(projects);
+    }
+    for project in projects {
         println!(
             "{id} {name} {path}",
             id = project.id,
@@ -24,6 +29,7 @@ pub fn add(
     ctrl: gitbutler_project::Controller,
     path: PathBuf,
     refname: Option<RemoteRefname>,
+    format: OutputFormat,
 ) -> Result<()> {
     let path = gix::discover(path)?
         .workdir()
@@ -35,10 +41,17 @@ pub fn add(
     if let Some(refname) = refname {
         gitbutler_branch_actions::set_base_branch(&ctx, &refname)?;
     };
-    debug_print(project)
+    print(project, format)
 }
 
-pub fn switch_to_workspace(project: Project, refname: RemoteRefname) -> Result<()> {
+pub fn switch_to_workspace(
+    project: Project,
+    refname: RemoteRefname,
+    format: OutputFormat,
+) -> Result<()> {
     let ctx = CommandContext::open(&project, AppSettings::default())?;
-    debug_print(gitbutler_branch_actions::set_base_branch(&ctx, &refname)?)
+    print(
+        gitbutler_branch_actions::set_base_branch(&ctx, &refname)?,
+        format,
+    )
 }
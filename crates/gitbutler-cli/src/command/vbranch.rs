@@ -1,88 +1,151 @@
 use anyhow::{bail, Context, Result};
 use but_settings::AppSettings;
 use gitbutler_branch::{BranchCreateRequest, BranchIdentity, BranchUpdateRequest};
-use gitbutler_branch_actions::{get_branch_listing_details, list_branches, BranchManagerExt};
+use gitbutler_branch_actions::{
+    get_branch_listing_details, list_branches, BranchManagerExt, VirtualBranch,
+};
 use gitbutler_command_context::CommandContext;
+use gitbutler_diff::FileDiff;
 use gitbutler_oxidize::ObjectIdExt;
 use gitbutler_project::Project;
 use gitbutler_reference::{LocalRefname, Refname};
-use gitbutler_stack::{Stack, VirtualBranchesHandle};
+use gitbutler_stack::{Stack, StackId, VirtualBranchesHandle};
 
-use crate::command::debug_print;
+use crate::args::OutputFormat;
+use crate::command::{debug_print, json_print, print};
 
-pub fn list_commit_files(project: Project, commit_id_hex: String) -> Result<()> {
+pub fn list_commit_files(
+    project: Project,
+    commit_id_hex: String,
+    format: OutputFormat,
+) -> Result<()> {
     let ctx = CommandContext::open(&project, AppSettings::default())?;
     let commit_id = gix::ObjectId::from_hex(commit_id_hex.as_bytes())?;
-    debug_print(gitbutler_branch_actions::list_commit_files(
-        &ctx,
-        commit_id.to_git2(),
-    )?)
+    print(
+        gitbutler_branch_actions::list_commit_files(&ctx, commit_id.to_git2())?,
+        format,
+    )
 }
 
-pub fn set_base(project: Project, short_tracking_branch_name: String) -> Result<()> {
+pub fn set_base(
+    project: Project,
+    short_tracking_branch_name: String,
+    format: OutputFormat,
+) -> Result<()> {
     let ctx = CommandContext::open(&project, AppSettings::default())?;
     let branch_name = format!("refs/remotes/{}", short_tracking_branch_name)
         .parse()
         .context("Invalid branch name")?;
-    debug_print(gitbutler_branch_actions::set_base_branch(
-        &ctx,
-        &branch_name,
-    )?)
+    print(
+        gitbutler_branch_actions::set_base_branch(&ctx, &branch_name)?,
+        format,
+    )
 }
 
-pub fn list_all(project: Project) -> Result<()> {
+pub fn list_all(project: Project, format: OutputFormat) -> Result<()> {
     let ctx = CommandContext::open(&project, AppSettings::default())?;
-    debug_print(list_branches(&ctx, None, None)?)
+    print(list_branches(&ctx, None, None)?, format)
 }
 
-pub fn details(project: Project, branch_names: Vec<BranchIdentity>) -> Result<()> {
+pub fn details(
+    project: Project,
+    branch_names: Vec<BranchIdentity>,
+    format: OutputFormat,
+) -> Result<()> {
     let ctx = CommandContext::open(&project, AppSettings::default())?;
-    debug_print(get_branch_listing_details(&ctx, branch_names)?)
+    print(get_branch_listing_details(&ctx, branch_names)?, format)
 }
 
-pub fn list(project: Project) -> Result<()> {
-    let stacks = VirtualBranchesHandle::new(project.gb_dir()).list_all_stacks()?;
+/// A stack as listed by [`list()`].
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ListedStack {
+    id: StackId,
+    name: String,
+    in_workspace: bool,
+    upstream: Option<String>,
+}
+
+impl From<Stack> for ListedStack {
+    fn from(stack: Stack) -> Self {
+        ListedStack {
+            id: stack.id,
+            name: stack.name,
+            in_workspace: stack.in_workspace,
+            upstream: stack.upstream.map(|b| b.to_string()),
+        }
+    }
+}
+
+pub fn list(project: Project, format: OutputFormat) -> Result<()> {
+    let stacks: Vec<ListedStack> = VirtualBranchesHandle::new(project.gb_dir())
+        .list_all_stacks()?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    if format == OutputFormat::Json {
+        return json_print(stacks);
+    }
     for stack in stacks {
         println!(
             "{active} {id} {name} {upstream} {default}",
             active = if stack.in_workspace { "✔️" } else { "⛌" },
             id = stack.id,
             name = stack.name,
-            upstream = stack
-                .upstream
-                .map_or_else(Default::default, |b| b.to_string()),
+            upstream = stack.upstream.unwrap_or_default(),
             default = if stack.in_workspace { "🌟" } else { "" }
         );
     }
     Ok(())
 }
 
-pub fn status(project: Project) -> Result<()> {
+/// The virtual branches and the files that couldn't be assigned to them, as printed by [`status()`].
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Status<'a> {
+    branches: &'a [VirtualBranch],
+    skipped_files: &'a [FileDiff],
+}
+
+pub fn status(project: Project, format: OutputFormat) -> Result<()> {
     let ctx = CommandContext::open(&project, AppSettings::default())?;
-    debug_print(gitbutler_branch_actions::list_virtual_branches(&ctx)?)
+    let list_result = gitbutler_branch_actions::list_virtual_branches(&ctx)?;
+    match format {
+        OutputFormat::Human => debug_print(list_result),
+        OutputFormat::Json => json_print(Status {
+            branches: &list_result.branches,
+            skipped_files: &list_result.skipped_files,
+        }),
+    }
 }
 
-pub fn unapply(project: Project, branch_name: String) -> Result<()> {
+pub fn unapply(project: Project, branch_name: String, format: OutputFormat) -> Result<()> {
     let ctx = CommandContext::open(&project, AppSettings::default())?;
     let stack = stack_by_name(&project, &branch_name)?;
-    debug_print(gitbutler_branch_actions::save_and_unapply_virutal_branch(
-        &ctx, stack.id,
-    )?)
+    print(
+        gitbutler_branch_actions::save_and_unapply_virutal_branch(&ctx, stack.id)?,
+        format,
+    )
 }
 
-pub fn apply(project: Project, branch_name: String, from_branch: bool) -> Result<()> {
+pub fn apply(
+    project: Project,
+    branch_name: String,
+    from_branch: bool,
+    format: OutputFormat,
+) -> Result<()> {
     if from_branch {
-        apply_from_branch(project, branch_name)
+        apply_from_branch(project, branch_name, format)
     } else {
-        apply_by_name(project, branch_name)
+        apply_by_name(project, branch_name, format)
     }
 }
 
-fn apply_by_name(project: Project, branch_name: String) -> Result<()> {
+fn apply_by_name(project: Project, branch_name: String, format: OutputFormat) -> Result<()> {
     let stack = stack_by_name(&project, &branch_name)?;
     let ctx = CommandContext::open(&project, AppSettings::default())?;
     let mut guard = project.exclusive_worktree_access();
-    debug_print(
+    print(
         ctx.branch_manager().create_virtual_branch_from_branch(
             stack
                 .source_refname
@@ -92,10 +155,11 @@ fn apply_by_name(project: Project, branch_name: String) -> Result<()> {
             None,
             guard.write_permission(),
         )?,
+        format,
     )
 }
 
-fn apply_from_branch(project: Project, branch_name: String) -> Result<()> {
+fn apply_from_branch(project: Project, branch_name: String, format: OutputFormat) -> Result<()> {
     let refname = Refname::Local(LocalRefname::new(&branch_name, None));
     let target = if let Some(stack) = stack_by_refname(&project, &refname)? {
         stack
@@ -108,15 +172,23 @@ fn apply_from_branch(project: Project, branch_name: String) -> Result<()> {
     let ctx = CommandContext::open(&project, AppSettings::default())?;
 
     let mut guard = project.exclusive_worktree_access();
-    debug_print(ctx.branch_manager().create_virtual_branch_from_branch(
-        &target,
-        None,
-        None,
-        guard.write_permission(),
-    )?)
+    print(
+        ctx.branch_manager().create_virtual_branch_from_branch(
+            &target,
+            None,
+            None,
+            guard.write_permission(),
+        )?,
+        format,
+    )
 }
 
-pub fn create(project: Project, branch_name: String, set_default: bool) -> Result<()> {
+pub fn create(
+    project: Project,
+    branch_name: String,
+    set_default: bool,
+    format: OutputFormat,
+) -> Result<()> {
     let ctx = CommandContext::open(&project, AppSettings::default())?;
     let new_stack_entry = gitbutler_branch_actions::create_virtual_branch(
         &ctx,
@@ -129,12 +201,16 @@ pub fn create(project: Project, branch_name: String, set_default: bool) -> Resul
         let new = VirtualBranchesHandle::new(project.gb_dir()).get_stack(new_stack_entry.id)?;
         set_default_branch(&project, &new)?;
     }
-    debug_print(new_stack_entry)
+    print(new_stack_entry, format)
 }
 
-pub fn set_default(project: Project, branch_name: String) -> Result<()> {
+pub fn set_default(project: Project, branch_name: String, format: OutputFormat) -> Result<()> {
     let stack = stack_by_name(&project, &branch_name)?;
-    set_default_branch(&project, &stack)
+    set_default_branch(&project, &stack)?;
+    if format == OutputFormat::Json {
+        return json_print(());
+    }
+    Ok(())
 }
 
 fn set_default_branch(project: &Project, stack: &Stack) -> Result<()> {
@@ -154,14 +230,27 @@ fn set_default_branch(project: &Project, stack: &Stack) -> Result<()> {
     )
 }
 
-pub fn series(project: Project, stack_name: String, new_series_name: String) -> Result<()> {
+pub fn series(
+    project: Project,
+    stack_name: String,
+    new_series_name: String,
+    format: OutputFormat,
+) -> Result<()> {
     let mut stack = stack_by_name(&project, &stack_name)?;
     let ctx = CommandContext::open(&project, AppSettings::default())?;
     stack.add_series_top_of_stack(&ctx, new_series_name, None)?;
+    if format == OutputFormat::Json {
+        return json_print(());
+    }
     Ok(())
 }
 
-pub fn commit(project: Project, branch_name: String, message: String) -> Result<()> {
+pub fn commit(
+    project: Project,
+    branch_name: String,
+    message: String,
+    format: OutputFormat,
+) -> Result<()> {
     let ctx = CommandContext::open(&project, AppSettings::default())?;
     let stack = stack_by_name(&project, &branch_name)?;
     let list_result = gitbutler_branch_actions::list_virtual_branches(&ctx)?;
@@ -203,12 +292,18 @@ pub fn commit(project: Project, branch_name: String, message: String) -> Result<
         )
     }
 
-    debug_print(gitbutler_branch_actions::create_commit(
+    let commit_id = gitbutler_branch_actions::create_commit(
         &ctx,
         stack.id,
         &message,
         Some(&target_branch.ownership),
-    )?)
+    )?;
+    match format {
+        OutputFormat::Human => debug_print(commit_id),
+        OutputFormat::Json => json_print(serde_json::json!({
+            "commitId": commit_id.to_string(),
+        })),
+    }
 }
 
 fn stack_by_name(project: &Project, name: &str) -> Result<Stack> {
@@ -3,7 +3,7 @@ use anyhow::Result;
 mod args;
 use args::Args;
 
-use crate::args::{project, snapshot, vbranch};
+use crate::args::{project, snapshot, vbranch, OutputFormat};
 
 mod command;
 
@@ -16,10 +16,22 @@ fn main() -> Result<()> {
     }
     let _op_span = tracing::info_span!("cli-op").entered();
 
+    let format = args.format;
+    match (run(args), format) {
+        (Err(err), OutputFormat::Json) => {
+            command::json_print_error(&err)?;
+            std::process::exit(1);
+        }
+        (res, _) => res,
+    }
+}
+
+fn run(args: Args) -> Result<()> {
+    let format = args.format;
     match args.cmd {
         args::Subcommands::IntegrateUpstream { mode } => {
             let project = command::prepare::project_from_path(args.current_dir)?;
-            command::workspace::update(project, mode)
+            command::workspace::update(project, mode, format)
         }
         args::Subcommands::UnapplyOwnership {
             filepath,
@@ -27,42 +39,42 @@ fn main() -> Result<()> {
             to_line,
         } => {
             let project = command::prepare::project_from_path(args.current_dir)?;
-            command::ownership::unapply(project, filepath, from_line, to_line)
+            command::ownership::unapply(project, filepath, from_line, to_line, format)
         }
         args::Subcommands::Branch(vbranch::Platform { cmd }) => {
             let project = command::prepare::project_from_path(args.current_dir)?;
             match cmd {
                 Some(vbranch::SubCommands::ListCommitFiles { commit_id }) => {
-                    command::vbranch::list_commit_files(project, commit_id)
+                    command::vbranch::list_commit_files(project, commit_id, format)
                 }
                 Some(vbranch::SubCommands::SetBase {
                     short_tracking_branch_name,
-                }) => command::vbranch::set_base(project, short_tracking_branch_name),
-                Some(vbranch::SubCommands::List) => command::vbranch::list_all(project),
-                Some(vbranch::SubCommands::Status) => command::vbranch::status(project),
+                }) => command::vbranch::set_base(project, short_tracking_branch_name, format),
+                Some(vbranch::SubCommands::List) => command::vbranch::list_all(project, format),
+                Some(vbranch::SubCommands::Status) => command::vbranch::status(project, format),
                 Some(vbranch::SubCommands::Unapply { name }) => {
-                    command::vbranch::unapply(project, name)
+                    command::vbranch::unapply(project, name, format)
                 }
                 Some(vbranch::SubCommands::Apply { name, branch }) => {
-                    command::vbranch::apply(project, name, branch)
+                    command::vbranch::apply(project, name, branch, format)
                 }
                 Some(vbranch::SubCommands::SetDefault { name }) => {
-                    command::vbranch::set_default(project, name)
+                    command::vbranch::set_default(project, name, format)
                 }
                 Some(vbranch::SubCommands::Commit { message, name }) => {
-                    command::vbranch::commit(project, name, message)
+                    command::vbranch::commit(project, name, message, format)
                 }
                 Some(vbranch::SubCommands::Series { name, series_name }) => {
-                    command::vbranch::series(project, name, series_name)
+                    command::vbranch::series(project, name, series_name, format)
                 }
                 Some(vbranch::SubCommands::Create { set_default, name }) => {
-                    command::vbranch::create(project, name, set_default)
+                    command::vbranch::create(project, name, set_default, format)
                 }
                 Some(vbranch::SubCommands::Details { names }) => {
-                    command::vbranch::details(project, names)
+                    command::vbranch::details(project, names, format)
                 }
-                Some(vbranch::SubCommands::ListAll) => command::vbranch::list_all(project),
-                None => command::vbranch::list(project),
+                Some(vbranch::SubCommands::ListAll) => command::vbranch::list_all(project, format),
+                None => command::vbranch::list(project, format),
             }
         }
         args::Subcommands::Project(project::Platform {
@@ -72,30 +84,32 @@ fn main() -> Result<()> {
         }) => match cmd {
             Some(project::SubCommands::SwitchToWorkspace { remote_ref_name }) => {
                 let project = command::prepare::project_from_path(args.current_dir)?;
-                command::project::switch_to_workspace(project, remote_ref_name)
+                command::project::switch_to_workspace(project, remote_ref_name, format)
             }
             Some(project::SubCommands::Add {
                 switch_to_workspace,
                 path,
             }) => {
                 let ctrl = command::prepare::project_controller(app_suffix, app_data_dir)?;
-                command::project::add(ctrl, path, switch_to_workspace)
+                command::project::add(ctrl, path, switch_to_workspace, format)
             }
             None => {
                 let ctrl = command::prepare::project_controller(app_suffix, app_data_dir)?;
-                command::project::list(ctrl)
+                command::project::list(ctrl, format)
             }
         },
         args::Subcommands::Snapshot(snapshot::Platform { cmd }) => {
             let project = command::prepare::project_from_path(args.current_dir)?;
             match cmd {
-                Some(snapshot::SubCommands::Restore { snapshot_id }) => {
-                    command::snapshot::restore(project, snapshot_id)
-                }
+                Some(snapshot::SubCommands::Restore {
+                    snapshot_id,
+                    paths,
+                    stack_ids,
+                }) => command::snapshot::restore(project, snapshot_id, paths, stack_ids, format),
                 Some(snapshot::SubCommands::Diff { snapshot_id }) => {
-                    command::snapshot::diff(project, snapshot_id)
+                    command::snapshot::diff(project, snapshot_id, format)
                 }
-                None => command::snapshot::list(project),
+                None => command::snapshot::list(project, format),
             }
         }
     }
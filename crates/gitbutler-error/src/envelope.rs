@@ -0,0 +1,115 @@
+//! Types for the machine-readable output of the command-line tools when invoked with `--format json`.
+//!
+//! Each command prints exactly one [`Envelope`] to `stdout`.
+use serde::Serialize;
+
+use crate::error::AnyhowContextExt;
+
+/// The version of the [`Envelope`] format, to be incremented whenever its fields change incompatibly.
+///
+/// Note that changes to the types in `data` are not covered by it.
+pub const VERSION: u32 = 1;
+
+/// The JSON object that wraps the result of each command.
+#[derive(Serialize)]
+pub struct Envelope<T> {
+    /// The version of this format.
+    version: u32,
+    /// Either the `data` produced by the command, or the `error` that prevented it from succeeding.
+    #[serde(flatten)]
+    outcome: Outcome<T>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+enum Outcome<T> {
+    Data(T),
+    Error(Error),
+}
+
+/// A description of a failure.
+#[derive(Serialize)]
+struct Error {
+    /// The [`Code`](crate::error::Code) of the failure, like `errors.validation`,
+    /// or `errors.unknown` if there is no particular classification.
+    code: String,
+    /// The message that is most suitable to show to users.
+    message: String,
+    /// All errors in the chain, from the outermost to the root cause.
+    chain: Vec<String>,
+}
+
+impl<T> Envelope<T> {
+    /// Wrap the `data` that a command produced.
+    pub fn data(data: T) -> Self {
+        Envelope {
+            version: VERSION,
+            outcome: Outcome::Data(data),
+        }
+    }
+}
+
+impl Envelope<()> {
+    /// Describe `err` with its [`Code`](crate::error::Code), and with the message of its context or of its root cause.
+    pub fn error(err: &anyhow::Error) -> Self {
+        let ctx = err.custom_context_or_root_cause();
+        let message = ctx
+            .message
+            .map(Into::into)
+            .unwrap_or_else(|| err.root_cause().to_string());
+        Envelope {
+            version: VERSION,
+            outcome: Outcome::Error(Error {
+                code: ctx.code.to_string(),
+                message,
+                chain: err.chain().map(ToString::to_string).collect(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Envelope;
+    use crate::error::{Code, Context};
+    use anyhow::anyhow;
+
+    fn json<T: serde::Serialize>(envelope: Envelope<T>) -> String {
+        serde_json::to_string(&envelope).unwrap()
+    }
+
+    #[test]
+    fn data_is_wrapped_with_version() {
+        assert_eq!(
+            json(Envelope::data(["a", "b"])),
+            r#"{"version":1,"data":["a","b"]}"#
+        );
+    }
+
+    #[test]
+    fn errors_carry_their_code() {
+        let err = anyhow!("root cause")
+            .context("the operation failed")
+            .context(Code::Validation);
+        assert_eq!(
+            json(Envelope::error(&err)),
+            r#"{"version":1,"error":{"code":"errors.validation","message":"root cause","chain":["errors.validation","the operation failed","root cause"]}}"#,
+            "a code without message uses the root cause, as it's the most specific"
+        );
+
+        let err = anyhow!("root cause")
+            .context(Context::new("the operation failed").with_code(Code::Validation));
+        assert_eq!(
+            json(Envelope::error(&err)),
+            r#"{"version":1,"error":{"code":"errors.validation","message":"the operation failed","chain":["the operation failed","root cause"]}}"#,
+            "the message of the context is meant for users"
+        );
+
+        let err = anyhow!("root cause").context("the operation failed");
+        assert_eq!(
+            json(Envelope::error(&err)),
+            r#"{"version":1,"error":{"code":"errors.unknown","message":"root cause","chain":["the operation failed","root cause"]}}"#,
+            "without code, the root cause is the message"
+        );
+    }
+}
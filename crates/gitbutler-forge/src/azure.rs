@@ -0,0 +1,181 @@
+//! Pull requests on Azure DevOps, managed through its REST API.
+use anyhow::Result;
+use gitbutler_secret::Sensitive;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::forge::{
+    CreatePullRequest, Forge, ForgeName, ForgeRepository, PullRequest, PullRequestState,
+    UpdatePullRequest,
+};
+use crate::http::{Auth, Client};
+
+/// The version of the API all requests are made against.
+const API_VERSION: &str = "7.1";
+
+/// How many pull requests to ask for at once, as the API only returns a limited amount by default.
+const PAGE_SIZE: usize = 100;
+
+/// A repository on Azure DevOps.
+pub struct Azure {
+    client: Client,
+    /// The path to the pull requests of the repository in the API.
+    pulls_path: String,
+    /// The URL at which pull requests are viewed in a browser, without the number.
+    web_url: String,
+}
+
+impl Azure {
+    /// Manage the pull requests of `repo`, whose owner is `organization/project`, authenticating with the
+    /// personal access `token`.
+    pub fn new(repo: ForgeRepository, token: Sensitive<String>) -> Result<Self> {
+        let api_url = repo.api_url.trim_end_matches('/');
+        Ok(Azure {
+            client: Client::new(api_url, Auth::Basic(token))?,
+            pulls_path: format!(
+                "{}/_apis/git/repositories/{}/pullrequests",
+                repo.owner, repo.name
+            ),
+            web_url: format!("{api_url}/{}/_git/{}/pullrequest", repo.owner, repo.name),
+        })
+    }
+
+    fn pull_request_path(&self, number: usize) -> String {
+        format!("{}/{number}?api-version={API_VERSION}", self.pulls_path)
+    }
+
+    fn to_pull_request(&self, pr: ApiPullRequest) -> PullRequest {
+        let state = match pr.status.as_str() {
+            "completed" => PullRequestState::Merged,
+            "abandoned" => PullRequestState::Closed,
+            _ if pr.is_draft => PullRequestState::Draft,
+            _ => PullRequestState::Open,
+        };
+        PullRequest {
+            number: pr.pull_request_id,
+            url: format!("{}/{}", self.web_url, pr.pull_request_id),
+            title: pr.title,
+            body: pr.description,
+            source_branch: branch_name(pr.source_ref_name),
+            target_branch: branch_name(pr.target_ref_name),
+            state,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiPullRequest {
+    pull_request_id: usize,
+    title: String,
+    description: Option<String>,
+    status: String,
+    #[serde(default)]
+    is_draft: bool,
+    source_ref_name: String,
+    target_ref_name: String,
+}
+
+#[derive(Deserialize)]
+struct ApiList<T> {
+    value: Vec<T>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiUpdate<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_ref_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<&'a str>,
+}
+
+/// Azure DevOps refers to branches by their full reference name.
+fn ref_name(branch: &str) -> String {
+    format!("refs/heads/{branch}")
+}
+
+fn branch_name(ref_name: String) -> String {
+    match ref_name.strip_prefix("refs/heads/") {
+        Some(name) => name.to_owned(),
+        None => ref_name,
+    }
+}
+
+impl Forge for Azure {
+    fn name(&self) -> ForgeName {
+        ForgeName::Azure
+    }
+
+    fn create_pull_request(&self, pr: &CreatePullRequest) -> Result<PullRequest> {
+        let pr: ApiPullRequest = self.client.send(
+            Method::POST,
+            &format!("{}?api-version={API_VERSION}", self.pulls_path),
+            &serde_json::json!({
+                "title": pr.title,
+                "description": pr.body,
+                "sourceRefName": ref_name(&pr.source_branch),
+                "targetRefName": ref_name(&pr.target_branch),
+                "isDraft": pr.draft,
+            }),
+        )?;
+        Ok(self.to_pull_request(pr))
+    }
+
+    fn update_pull_request(
+        &self,
+        number: usize,
+        update: &UpdatePullRequest,
+    ) -> Result<PullRequest> {
+        let pr: ApiPullRequest = self.client.send(
+            Method::PATCH,
+            &self.pull_request_path(number),
+            &ApiUpdate {
+                title: update.title.as_deref(),
+                description: update.body.as_deref(),
+                target_ref_name: update.target_branch.as_deref().map(ref_name),
+                status: None,
+            },
+        )?;
+        Ok(self.to_pull_request(pr))
+    }
+
+    fn pull_request(&self, number: usize) -> Result<PullRequest> {
+        let pr: ApiPullRequest = self.client.get(&self.pull_request_path(number))?;
+        Ok(self.to_pull_request(pr))
+    }
+
+    fn list_pull_requests(&self) -> Result<Vec<PullRequest>> {
+        let mut pull_requests = Vec::new();
+        loop {
+            let page: ApiList<ApiPullRequest> = self.client.get(&format!(
+                "{}?searchCriteria.status=active&$top={PAGE_SIZE}&$skip={}&api-version={API_VERSION}",
+                self.pulls_path,
+                pull_requests.len()
+            ))?;
+            let is_last_page = page.value.len() < PAGE_SIZE;
+            pull_requests.extend(page.value.into_iter().map(|pr| self.to_pull_request(pr)));
+            if is_last_page {
+                return Ok(pull_requests);
+            }
+        }
+    }
+
+    fn close_pull_request(&self, number: usize) -> Result<PullRequest> {
+        let pr: ApiPullRequest = self.client.send(
+            Method::PATCH,
+            &self.pull_request_path(number),
+            &ApiUpdate {
+                title: None,
+                description: None,
+                target_ref_name: None,
+                status: Some("abandoned"),
+            },
+        )?;
+        Ok(self.to_pull_request(pr))
+    }
+}
@@ -0,0 +1,172 @@
+//! Pull requests on Bitbucket Cloud, managed through its REST API.
+use anyhow::Result;
+use gitbutler_secret::Sensitive;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::forge::{
+    CreatePullRequest, Forge, ForgeName, ForgeRepository, PullRequest, PullRequestState,
+    UpdatePullRequest,
+};
+use crate::http::{Auth, Client};
+
+/// A repository on Bitbucket Cloud.
+pub struct Bitbucket {
+    client: Client,
+    /// The path to the repository in the API, like `repositories/workspace/name`.
+    repo_path: String,
+}
+
+impl Bitbucket {
+    /// Manage the pull requests of `repo`, authenticating with `token`.
+    pub fn new(repo: ForgeRepository, token: Sensitive<String>) -> Result<Self> {
+        Ok(Bitbucket {
+            client: Client::new(&repo.api_url, Auth::Bearer(token))?,
+            repo_path: format!("repositories/{}/{}", repo.owner, repo.name),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct ApiPullRequest {
+    id: usize,
+    title: String,
+    description: Option<String>,
+    state: String,
+    #[serde(default)]
+    draft: bool,
+    source: ApiEndpoint,
+    destination: ApiEndpoint,
+    links: ApiLinks,
+}
+
+#[derive(Deserialize, Serialize)]
+struct ApiEndpoint {
+    branch: ApiBranch,
+}
+
+#[derive(Deserialize, Serialize)]
+struct ApiBranch {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct ApiLinks {
+    html: ApiLink,
+}
+
+#[derive(Deserialize)]
+struct ApiLink {
+    href: String,
+}
+
+/// A single page of a paginated listing.
+#[derive(Deserialize)]
+struct ApiPage<T> {
+    values: Vec<T>,
+    /// The absolute URL of the next page, if there is one.
+    next: Option<String>,
+}
+
+impl From<ApiPullRequest> for PullRequest {
+    fn from(pr: ApiPullRequest) -> Self {
+        let state = match pr.state.as_str() {
+            "MERGED" => PullRequestState::Merged,
+            "DECLINED" | "SUPERSEDED" => PullRequestState::Closed,
+            _ if pr.draft => PullRequestState::Draft,
+            _ => PullRequestState::Open,
+        };
+        PullRequest {
+            number: pr.id,
+            title: pr.title,
+            body: pr.description,
+            source_branch: pr.source.branch.name,
+            target_branch: pr.destination.branch.name,
+            state,
+            url: pr.links.html.href,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ApiUpdate<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    destination: Option<ApiEndpoint>,
+}
+
+fn endpoint(branch: &str) -> ApiEndpoint {
+    ApiEndpoint {
+        branch: ApiBranch {
+            name: branch.to_owned(),
+        },
+    }
+}
+
+impl Forge for Bitbucket {
+    fn name(&self) -> ForgeName {
+        ForgeName::Bitbucket
+    }
+
+    fn create_pull_request(&self, pr: &CreatePullRequest) -> Result<PullRequest> {
+        let pr: ApiPullRequest = self.client.send(
+            Method::POST,
+            &format!("{}/pullrequests", self.repo_path),
+            &serde_json::json!({
+                "title": pr.title,
+                "description": pr.body,
+                "source": endpoint(&pr.source_branch),
+                "destination": endpoint(&pr.target_branch),
+                "draft": pr.draft,
+            }),
+        )?;
+        Ok(pr.into())
+    }
+
+    fn update_pull_request(
+        &self,
+        number: usize,
+        update: &UpdatePullRequest,
+    ) -> Result<PullRequest> {
+        let pr: ApiPullRequest = self.client.send(
+            Method::PUT,
+            &format!("{}/pullrequests/{number}", self.repo_path),
+            &ApiUpdate {
+                title: update.title.as_deref(),
+                description: update.body.as_deref(),
+                destination: update.target_branch.as_deref().map(endpoint),
+            },
+        )?;
+        Ok(pr.into())
+    }
+
+    fn pull_request(&self, number: usize) -> Result<PullRequest> {
+        let pr: ApiPullRequest = self
+            .client
+            .get(&format!("{}/pullrequests/{number}", self.repo_path))?;
+        Ok(pr.into())
+    }
+
+    fn list_pull_requests(&self) -> Result<Vec<PullRequest>> {
+        let mut pull_requests = Vec::new();
+        let mut next = Some(format!("{}/pullrequests?state=OPEN", self.repo_path));
+        while let Some(url) = next {
+            let page: ApiPage<ApiPullRequest> = self.client.get(&url)?;
+            pull_requests.extend(page.values.into_iter().map(PullRequest::from));
+            next = page.next;
+        }
+        Ok(pull_requests)
+    }
+
+    fn close_pull_request(&self, number: usize) -> Result<PullRequest> {
+        let pr: ApiPullRequest = self.client.send(
+            Method::POST,
+            &format!("{}/pullrequests/{number}/decline", self.repo_path),
+            &serde_json::json!({}),
+        )?;
+        Ok(pr.into())
+    }
+}
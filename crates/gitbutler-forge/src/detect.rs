@@ -0,0 +1,285 @@
+//! Detect the forges that host the remotes of a project.
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use bstr::ByteSlice;
+use gitbutler_stack::Target;
+use serde::Serialize;
+
+use crate::forge::{ForgeName, ForgeRepository};
+
+/// The name of the forge that hosts all remotes, overriding the detection by host.
+const FORGE: &str = "gitbutler.forge";
+/// Patterns for hosts of self-hosted forges, as `<forge>:<host-pattern>`, like `gitlab:git.example.com`.
+/// This key may be given multiple times.
+const FORGE_HOST: &str = "gitbutler.forgeHost";
+
+/// A repository on a forge, as derived from the URL of a remote.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForgeRepoInfo {
+    /// The forge that hosts the repository.
+    pub forge: ForgeName,
+    /// The lower-case host name of the remote, like `github.com`.
+    pub host: String,
+    /// The user or organization on GitHub, the group and its subgroups on GitLab, like `group/subgroup`,
+    /// the workspace on Bitbucket, and the organization or collection on Azure DevOps.
+    pub owner: String,
+    /// The project that contains the repository on Azure DevOps.
+    pub project: Option<String>,
+    /// The name of the repository, without `.git` suffix.
+    pub repo: String,
+}
+
+impl ForgeRepoInfo {
+    /// The path that identifies the repository on its forge, like `owner/repo`, or `organization/project/repo`
+    /// on Azure DevOps.
+    pub fn project_path(&self) -> String {
+        match &self.project {
+            Some(project) => format!("{}/{project}/{}", self.owner, self.repo),
+            None => format!("{}/{}", self.owner, self.repo),
+        }
+    }
+
+    /// Return `true` if the repository is hosted on the public instance of its forge, like `github.com`,
+    /// instead of a self-hosted one.
+    pub fn is_public_instance(&self) -> bool {
+        is_public_host(&self.forge, &self.host)
+    }
+
+    /// The location of the repository in the API of its forge, suitable for [`crate::forge::forge_client()`].
+    ///
+    /// Self-hosted instances are assumed to serve their API over HTTPS at the usual location. Bitbucket
+    /// is always assumed to be Bitbucket Cloud.
+    pub fn forge_repository(&self) -> ForgeRepository {
+        let host = &self.host;
+        let (api_url, owner) = match &self.forge {
+            ForgeName::GitHub if host == "github.com" => {
+                (self.forge.default_api_url().to_owned(), self.owner.clone())
+            }
+            ForgeName::GitHub => (format!("https://{host}/api/v3"), self.owner.clone()),
+            ForgeName::GitLab => (format!("https://{host}/api/v4"), self.owner.clone()),
+            ForgeName::Bitbucket => (self.forge.default_api_url().to_owned(), self.owner.clone()),
+            ForgeName::Azure => {
+                let project = self.project.clone().unwrap_or_default();
+                if host.ends_with("visualstudio.com") {
+                    (format!("https://{}.visualstudio.com", self.owner), project)
+                } else if host.ends_with("dev.azure.com") {
+                    (
+                        self.forge.default_api_url().to_owned(),
+                        format!("{}/{project}", self.owner),
+                    )
+                } else {
+                    (
+                        format!("https://{host}"),
+                        format!("{}/{project}", self.owner),
+                    )
+                }
+            }
+        };
+        ForgeRepository {
+            api_url,
+            owner,
+            name: self.repo.clone(),
+        }
+    }
+}
+
+/// A pattern to recognize the hosts of a forge, typically of self-hosted instances.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostPattern {
+    /// The forge hosts matching `pattern` belong to.
+    pub forge: ForgeName,
+    /// The host name to match, ignoring case, in which `*` matches any amount of characters, like `*.example.com`.
+    pub pattern: String,
+}
+
+impl HostPattern {
+    /// Parse `value` as `<forge>:<host-pattern>`, like `gitlab:git.example.com`.
+    pub fn parse(value: &str) -> Result<Self> {
+        let (forge, pattern) = value
+            .split_once(':')
+            .with_context(|| format!("Expected '<forge>:<host-pattern>', got '{value}'"))?;
+        Ok(HostPattern {
+            forge: forge.trim().parse()?,
+            pattern: pattern.trim().to_lowercase(),
+        })
+    }
+
+    /// Return `true` if the lower-case `host` matches this pattern.
+    pub fn matches(&self, host: &str) -> bool {
+        let mut parts = self.pattern.split('*');
+        let Some(mut rest) = parts.next().and_then(|prefix| host.strip_prefix(prefix)) else {
+            return false;
+        };
+        let mut parts: Vec<_> = parts.collect();
+        let Some(suffix) = parts.pop() else {
+            return rest.is_empty();
+        };
+        for part in parts {
+            match rest.find(part) {
+                Some(pos) => rest = &rest[pos + part.len()..],
+                None => return false,
+            }
+        }
+        rest.ends_with(suffix)
+    }
+}
+
+/// Configures how forges are recognized from the URLs of remotes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ForgeDetection {
+    /// If set, all remotes are assumed to be hosted on this forge.
+    pub forge: Option<ForgeName>,
+    /// Patterns for the hosts of self-hosted forges, tried in order before the hosts of the public instances.
+    pub host_patterns: Vec<HostPattern>,
+}
+
+impl ForgeDetection {
+    /// Read `gitbutler.forge` and all values of `gitbutler.forgeHost` from `config`.
+    pub fn from_git_config(config: &gix::config::Snapshot<'_>) -> Result<Self> {
+        let forge = config
+            .string(FORGE)
+            .map(|name| {
+                name.to_str()
+                    .context("Forge names must be valid UTF-8")?
+                    .parse::<ForgeName>()
+                    .with_context(|| format!("Invalid value for {FORGE}"))
+            })
+            .transpose()?;
+        let host_patterns = config
+            .strings(FORGE_HOST)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|value| {
+                HostPattern::parse(&value.to_str_lossy())
+                    .with_context(|| format!("Invalid value for {FORGE_HOST}"))
+            })
+            .collect::<Result<_>>()?;
+        Ok(ForgeDetection {
+            forge,
+            host_patterns,
+        })
+    }
+
+    /// Determine the forge and repository behind `remote_url`, or `None` if it's not a URL of a known forge.
+    pub fn detect(&self, remote_url: &str) -> Option<ForgeRepoInfo> {
+        let url = gitbutler_url::parse(remote_url.into()).ok()?;
+        let host = url.host.as_deref()?.to_lowercase();
+        let forge = self.forge.clone().or_else(|| self.forge_by_host(&host))?;
+        repo_info(forge, host, url.path.to_str().ok()?)
+    }
+
+    /// Recognize `host` by the configured patterns, or as one of the public instances of the forges.
+    ///
+    /// Host names that merely contain the name of a forge aren't trusted, as access tokens are kept per forge.
+    fn forge_by_host(&self, host: &str) -> Option<ForgeName> {
+        if let Some(pattern) = self.host_patterns.iter().find(|p| p.matches(host)) {
+            return Some(pattern.forge.clone());
+        }
+        [
+            ForgeName::GitHub,
+            ForgeName::GitLab,
+            ForgeName::Bitbucket,
+            ForgeName::Azure,
+        ]
+        .into_iter()
+        .find(|forge| is_public_host(forge, host))
+    }
+}
+
+/// Return `true` if the lower-case `host` belongs to the public instance of `forge`.
+fn is_public_host(forge: &ForgeName, host: &str) -> bool {
+    match forge {
+        ForgeName::GitHub => host == "github.com",
+        ForgeName::GitLab => host == "gitlab.com",
+        ForgeName::Bitbucket => host == "bitbucket.org",
+        ForgeName::Azure => {
+            host == "dev.azure.com"
+                || host == "ssh.dev.azure.com"
+                || host
+                    .strip_suffix(".visualstudio.com")
+                    .is_some_and(|organization| {
+                        !organization.is_empty() && !organization.contains('.')
+                    })
+        }
+    }
+}
+
+/// Derive the repository on `forge` from the `path` of the URL of one of its remotes.
+fn repo_info(forge: ForgeName, host: String, path: &str) -> Option<ForgeRepoInfo> {
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let segments: Vec<_> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let (owner, project, repo) = match forge {
+        ForgeName::GitHub | ForgeName::Bitbucket => match segments.as_slice() {
+            [owner, repo] => (owner.to_string(), None, *repo),
+            _ => return None,
+        },
+        ForgeName::GitLab => match segments.split_last() {
+            Some((repo, owner)) if !owner.is_empty() => (owner.join("/"), None, *repo),
+            _ => return None,
+        },
+        ForgeName::Azure => {
+            let (owner, project, repo) = azure_repo_path(&host, &segments)?;
+            (owner, Some(project.to_owned()), repo)
+        }
+    };
+    Some(ForgeRepoInfo {
+        forge,
+        host,
+        owner,
+        project,
+        repo: repo.to_owned(),
+    })
+}
+
+/// Azure DevOps uses `v3/<organization>/<project>/<repo>` via SSH, and `<organization>/<project>/_git/<repo>` via HTTPS.
+/// With legacy `<organization>.visualstudio.com` hosts, the organization is only part of the host.
+fn azure_repo_path<'a>(host: &str, segments: &[&'a str]) -> Option<(String, &'a str, &'a str)> {
+    if let ["v3", organization, project, repo] = segments {
+        return Some((organization.to_string(), project, repo));
+    }
+    let git_pos = segments.iter().position(|segment| *segment == "_git")?;
+    let (owner_and_project, git_and_repo) = segments.split_at(git_pos);
+    let (Some((project, owner)), ["_git", repo]) = (owner_and_project.split_last(), git_and_repo)
+    else {
+        return None;
+    };
+    let owner = match host.strip_suffix(".visualstudio.com") {
+        Some(organization) => organization.to_owned(),
+        None if owner.is_empty() => return None,
+        None => owner.join("/"),
+    };
+    Some((owner, project, repo))
+}
+
+/// The repositories on forges behind the remotes of a project.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DetectedForges {
+    /// The repository behind the remote of the target branch, if it's on a known forge.
+    pub target: Option<ForgeRepoInfo>,
+    /// The repositories behind the push URL of each remote on a known forge, by remote name.
+    pub push_remotes: BTreeMap<String, ForgeRepoInfo>,
+}
+
+/// Detect the forges behind the remote of `target` and the push URLs of all remotes of `repo`,
+/// configured by the git configuration of `repo`.
+pub fn detect_forges(repo: &gix::Repository, target: &Target) -> Result<DetectedForges> {
+    let detection = ForgeDetection::from_git_config(&repo.config_snapshot())?;
+    let mut push_remotes = BTreeMap::new();
+    for name in repo.remote_names() {
+        let remote = repo.find_remote(name.as_bstr())?;
+        let Some(url) = remote.url(gix::remote::Direction::Push) else {
+            continue;
+        };
+        if let Some(info) = detection.detect(&url.to_bstring().to_str_lossy()) {
+            push_remotes.insert(name.to_string(), info);
+        }
+    }
+    Ok(DetectedForges {
+        target: detection.detect(&target.remote_url),
+        push_remotes,
+    })
+}
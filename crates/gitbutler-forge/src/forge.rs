@@ -1,6 +1,16 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use gitbutler_secret::{secret, Sensitive};
+use gitbutler_stack::StackBranch;
+use gitbutler_user::User;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+use crate::{
+    azure::Azure, bitbucket::Bitbucket, detect::ForgeRepoInfo, github::GitHub, gitlab::GitLab,
+};
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 #[serde(tag = "name", rename_all = "lowercase")]
 /// Supported git forge types
 pub enum ForgeName {
@@ -9,3 +19,273 @@ pub enum ForgeName {
     Bitbucket,
     Azure,
 }
+
+impl ForgeName {
+    /// The URL of the API of the public instance of this forge.
+    pub fn default_api_url(&self) -> &'static str {
+        match self {
+            ForgeName::GitHub => "https://api.github.com",
+            ForgeName::GitLab => "https://gitlab.com/api/v4",
+            ForgeName::Bitbucket => "https://api.bitbucket.org/2.0",
+            ForgeName::Azure => "https://dev.azure.com",
+        }
+    }
+
+    /// The handle under which the access token for this forge is kept in the secrets store.
+    fn access_token_handle(&self) -> &'static str {
+        match self {
+            ForgeName::GitHub => "github_access_token",
+            ForgeName::GitLab => "gitlab_access_token",
+            ForgeName::Bitbucket => "bitbucket_access_token",
+            ForgeName::Azure => "azure_access_token",
+        }
+    }
+}
+
+impl FromStr for ForgeName {
+    type Err = anyhow::Error;
+
+    /// Parse the lower-case name of a forge as it's serialized, ignoring case.
+    fn from_str(name: &str) -> Result<Self> {
+        Ok(match name.to_lowercase().as_str() {
+            "github" => ForgeName::GitHub,
+            "gitlab" => ForgeName::GitLab,
+            "bitbucket" => ForgeName::Bitbucket,
+            "azure" => ForgeName::Azure,
+            _ => {
+                bail!("Unknown forge '{name}', expected one of github, gitlab, bitbucket or azure")
+            }
+        })
+    }
+}
+
+/// The state of a pull request, which some forges call merge request.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum PullRequestState {
+    /// The pull request is open for review.
+    Open,
+    /// The pull request is open, but marked as not yet ready for review.
+    Draft,
+    /// The changes of the pull request were merged.
+    Merged,
+    /// The pull request was closed without merging it.
+    Closed,
+}
+
+/// A pull request as known to a forge.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PullRequest {
+    /// The number that identifies the pull request within its repository.
+    pub number: usize,
+    /// The title of the pull request.
+    pub title: String,
+    /// The description of the pull request, if it has one.
+    pub body: Option<String>,
+    /// The name of the branch with the changes to merge, like `feature`.
+    pub source_branch: String,
+    /// The name of the branch the changes should be merged into, like `main`.
+    pub target_branch: String,
+    /// Whether the pull request is still open.
+    pub state: PullRequestState,
+    /// The URL at which the pull request can be viewed in a browser.
+    pub url: String,
+}
+
+/// All information needed to open a new pull request.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CreatePullRequest {
+    /// The title of the pull request.
+    pub title: String,
+    /// The description of the pull request.
+    pub body: Option<String>,
+    /// The name of the branch with the changes to merge, like `feature`.
+    pub source_branch: String,
+    /// The name of the branch the changes should be merged into, like `main`.
+    pub target_branch: String,
+    /// If `true`, the pull request is marked as not yet ready for review.
+    pub draft: bool,
+}
+
+/// Changes to apply to an existing pull request. Fields that are `None` remain unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpdatePullRequest {
+    /// The new title of the pull request.
+    pub title: Option<String>,
+    /// The new description of the pull request.
+    pub body: Option<String>,
+    /// The name of the branch the changes should now be merged into.
+    pub target_branch: Option<String>,
+}
+
+/// The lifecycle of pull requests in a single repository on a forge.
+pub trait Forge {
+    /// The kind of forge this is.
+    fn name(&self) -> ForgeName;
+    /// Open a new pull request as described by `pr`.
+    fn create_pull_request(&self, pr: &CreatePullRequest) -> Result<PullRequest>;
+    /// Change the pull request with `number` as described by `update`, and return it in its new state.
+    fn update_pull_request(&self, number: usize, update: &UpdatePullRequest)
+        -> Result<PullRequest>;
+    /// Fetch the pull request with `number` in its current state.
+    fn pull_request(&self, number: usize) -> Result<PullRequest>;
+    /// List all open pull requests, including drafts.
+    fn list_pull_requests(&self) -> Result<Vec<PullRequest>>;
+    /// Close the pull request with `number` without merging it, and return it in its new state.
+    fn close_pull_request(&self, number: usize) -> Result<PullRequest>;
+}
+
+/// The location of a repository on a forge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForgeRepository {
+    /// The URL of the API to talk to, like `https://api.github.com`.
+    pub api_url: String,
+    /// The owner of the repository, like the user or organization on GitHub, the (sub-)group on GitLab,
+    /// the workspace on Bitbucket, or `organization/project` on Azure DevOps.
+    pub owner: String,
+    /// The name of the repository.
+    pub name: String,
+}
+
+impl ForgeRepository {
+    /// Identify the repository `owner/name` on the public instance of `forge`.
+    pub fn new(forge: &ForgeName, owner: impl Into<String>, name: impl Into<String>) -> Self {
+        ForgeRepository {
+            api_url: forge.default_api_url().to_owned(),
+            owner: owner.into(),
+            name: name.into(),
+        }
+    }
+}
+
+/// Create the [`Forge`] implementation for `forge` to manage pull requests of `repo`, authenticating with `token`.
+pub fn forge_client(
+    forge: &ForgeName,
+    repo: ForgeRepository,
+    token: Sensitive<String>,
+) -> Result<Box<dyn Forge>> {
+    Ok(match forge {
+        ForgeName::GitHub => Box::new(GitHub::new(repo, token)?),
+        ForgeName::GitLab => Box::new(GitLab::new(repo, token)?),
+        ForgeName::Bitbucket => Box::new(Bitbucket::new(repo, token)?),
+        ForgeName::Azure => Box::new(Azure::new(repo, token)?),
+    })
+}
+
+/// Obtain the access token to use with the forge that hosts `repo`, if there is one.
+///
+/// For GitHub, the token of the logged-in `user` is used if present. Otherwise, the token is taken from the secrets store.
+/// The tokens for the public instance of a forge are never handed out for self-hosted instances, which each have
+/// their own token instead.
+pub fn access_token(
+    repo: &ForgeRepoInfo,
+    user: Option<&User>,
+) -> Result<Option<Sensitive<String>>> {
+    if let (ForgeName::GitHub, Some(user)) = (&repo.forge, user) {
+        if repo.is_public_instance() {
+            if let Some(token) = user.github_access_token()? {
+                return Ok(Some(token));
+            }
+        }
+    }
+    secret::retrieve(&access_token_handle(repo), secret::Namespace::BuildKind)
+}
+
+/// Store `token` as access token for the forge that hosts `repo` in the secrets store, or delete it if it is empty.
+pub fn persist_access_token(repo: &ForgeRepoInfo, token: &Sensitive<String>) -> Result<()> {
+    secret::persist(
+        &access_token_handle(repo),
+        token,
+        secret::Namespace::BuildKind,
+    )
+}
+
+/// The handle of the access token for `repo`, which is specific to the host for self-hosted instances.
+fn access_token_handle(repo: &ForgeRepoInfo) -> String {
+    let handle = repo.forge.access_token_handle();
+    if repo.is_public_instance() {
+        handle.to_owned()
+    } else {
+        format!("{handle}@{}", repo.host)
+    }
+}
+
+/// A branch in a stack along with the information needed to open a pull request for it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StackedBranch {
+    /// The name of the branch, like `feature`.
+    pub name: String,
+    /// The title of a new pull request.
+    pub title: String,
+    /// The description of a new pull request.
+    pub body: Option<String>,
+    /// The number of the pull request that already exists for the branch.
+    pub pr_number: Option<usize>,
+    /// If `true`, the branch was integrated and its pull request isn't part of the stack anymore.
+    pub archived: bool,
+    /// The id of the GitButler review of the branch, if it has one.
+    pub review_id: Option<String>,
+}
+
+impl From<&StackBranch> for StackedBranch {
+    fn from(branch: &StackBranch) -> Self {
+        StackedBranch {
+            name: branch.name.clone(),
+            title: branch.name.clone(),
+            body: branch.description.clone(),
+            pr_number: branch.pr_number,
+            archived: branch.archived,
+            review_id: branch.review_id.clone(),
+        }
+    }
+}
+
+/// Make sure each of `branches`, ordered from the bottom of the stack to its top, has a pull request that targets
+/// the branch below it, with the bottom-most one targeting `target_branch`.
+///
+/// New pull requests are created for branches without one, marked as `draft` if requested, while open pull requests
+/// that target the wrong branch are retargeted. Branches whose pull request was merged are skipped when chaining,
+/// so the branch above them targets what they targeted.
+///
+/// Return the pull requests in the order of `branches`.
+pub fn chain_pull_requests(
+    forge: &dyn Forge,
+    target_branch: &str,
+    branches: &[StackedBranch],
+    draft: bool,
+) -> Result<Vec<PullRequest>> {
+    let mut pull_requests = Vec::with_capacity(branches.len());
+    let mut base = target_branch;
+    for branch in branches {
+        let pr = match branch.pr_number {
+            Some(number) => {
+                let pr = forge.pull_request(number)?;
+                let is_open = matches!(pr.state, PullRequestState::Open | PullRequestState::Draft);
+                if is_open && pr.target_branch != base {
+                    forge.update_pull_request(
+                        number,
+                        &UpdatePullRequest {
+                            target_branch: Some(base.to_owned()),
+                            ..Default::default()
+                        },
+                    )?
+                } else {
+                    pr
+                }
+            }
+            None => forge.create_pull_request(&CreatePullRequest {
+                title: branch.title.clone(),
+                body: branch.body.clone(),
+                source_branch: branch.name.clone(),
+                target_branch: base.to_owned(),
+                draft,
+            })?,
+        };
+        if pr.state != PullRequestState::Merged {
+            base = &branch.name;
+        }
+        pull_requests.push(pr);
+    }
+    Ok(pull_requests)
+}
@@ -0,0 +1,162 @@
+//! Pull requests on GitHub, managed through its REST API.
+use anyhow::Result;
+use gitbutler_secret::Sensitive;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::forge::{
+    CreatePullRequest, Forge, ForgeName, ForgeRepository, PullRequest, PullRequestState,
+    UpdatePullRequest,
+};
+use crate::http::{Auth, Client};
+
+/// The amount of pull requests to fetch per page, which is the maximum GitHub allows.
+const PAGE_SIZE: usize = 100;
+
+/// A repository on GitHub or GitHub Enterprise.
+pub struct GitHub {
+    client: Client,
+    /// The path to the repository in the API, like `repos/owner/name`.
+    repo_path: String,
+}
+
+impl GitHub {
+    /// Manage the pull requests of `repo`, authenticating with `token`.
+    pub fn new(repo: ForgeRepository, token: Sensitive<String>) -> Result<Self> {
+        Ok(GitHub {
+            client: Client::new(&repo.api_url, Auth::Bearer(token))?,
+            repo_path: format!("repos/{}/{}", repo.owner, repo.name),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct ApiPullRequest {
+    number: usize,
+    title: String,
+    body: Option<String>,
+    state: String,
+    #[serde(default)]
+    draft: bool,
+    merged_at: Option<String>,
+    html_url: String,
+    head: ApiBranch,
+    base: ApiBranch,
+}
+
+#[derive(Deserialize)]
+struct ApiBranch {
+    #[serde(rename = "ref")]
+    name: String,
+}
+
+impl From<ApiPullRequest> for PullRequest {
+    fn from(pr: ApiPullRequest) -> Self {
+        let state = if pr.merged_at.is_some() {
+            PullRequestState::Merged
+        } else if pr.state == "closed" {
+            PullRequestState::Closed
+        } else if pr.draft {
+            PullRequestState::Draft
+        } else {
+            PullRequestState::Open
+        };
+        PullRequest {
+            number: pr.number,
+            title: pr.title,
+            body: pr.body,
+            source_branch: pr.head.name,
+            target_branch: pr.base.name,
+            state,
+            url: pr.html_url,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ApiUpdate<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    base: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<&'a str>,
+}
+
+impl Forge for GitHub {
+    fn name(&self) -> ForgeName {
+        ForgeName::GitHub
+    }
+
+    fn create_pull_request(&self, pr: &CreatePullRequest) -> Result<PullRequest> {
+        let pr: ApiPullRequest = self.client.send(
+            Method::POST,
+            &format!("{}/pulls", self.repo_path),
+            &serde_json::json!({
+                "title": pr.title,
+                "body": pr.body,
+                "head": pr.source_branch,
+                "base": pr.target_branch,
+                "draft": pr.draft,
+            }),
+        )?;
+        Ok(pr.into())
+    }
+
+    fn update_pull_request(
+        &self,
+        number: usize,
+        update: &UpdatePullRequest,
+    ) -> Result<PullRequest> {
+        let pr: ApiPullRequest = self.client.send(
+            Method::PATCH,
+            &format!("{}/pulls/{number}", self.repo_path),
+            &ApiUpdate {
+                title: update.title.as_deref(),
+                body: update.body.as_deref(),
+                base: update.target_branch.as_deref(),
+                state: None,
+            },
+        )?;
+        Ok(pr.into())
+    }
+
+    fn pull_request(&self, number: usize) -> Result<PullRequest> {
+        let pr: ApiPullRequest = self
+            .client
+            .get(&format!("{}/pulls/{number}", self.repo_path))?;
+        Ok(pr.into())
+    }
+
+    fn list_pull_requests(&self) -> Result<Vec<PullRequest>> {
+        let mut pull_requests = Vec::new();
+        for page in 1.. {
+            let prs: Vec<ApiPullRequest> = self.client.get(&format!(
+                "{}/pulls?state=open&per_page={PAGE_SIZE}&page={page}",
+                self.repo_path
+            ))?;
+            let is_last_page = prs.len() < PAGE_SIZE;
+            pull_requests.extend(prs.into_iter().map(PullRequest::from));
+            if is_last_page {
+                break;
+            }
+        }
+        Ok(pull_requests)
+    }
+
+    fn close_pull_request(&self, number: usize) -> Result<PullRequest> {
+        let pr: ApiPullRequest = self.client.send(
+            Method::PATCH,
+            &format!("{}/pulls/{number}", self.repo_path),
+            &ApiUpdate {
+                title: None,
+                body: None,
+                base: None,
+                state: Some("closed"),
+            },
+        )?;
+        Ok(pr.into())
+    }
+}
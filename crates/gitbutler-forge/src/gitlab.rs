@@ -0,0 +1,160 @@
+//! Merge requests on GitLab, managed through its REST API.
+use anyhow::Result;
+use gitbutler_secret::Sensitive;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::forge::{
+    CreatePullRequest, Forge, ForgeName, ForgeRepository, PullRequest, PullRequestState,
+    UpdatePullRequest,
+};
+use crate::http::{encode_path_segment, Auth, Client};
+
+/// The amount of merge requests to fetch per page, which is the maximum GitLab allows.
+const PAGE_SIZE: usize = 100;
+
+/// A project on GitLab or a self-hosted GitLab instance.
+pub struct GitLab {
+    client: Client,
+    /// The path to the project in the API, like `projects/group%2Fname`.
+    project_path: String,
+}
+
+impl GitLab {
+    /// Manage the merge requests of `repo`, authenticating with `token`.
+    pub fn new(repo: ForgeRepository, token: Sensitive<String>) -> Result<Self> {
+        Ok(GitLab {
+            client: Client::new(&repo.api_url, Auth::Bearer(token))?,
+            project_path: format!(
+                "projects/{}",
+                encode_path_segment(&format!("{}/{}", repo.owner, repo.name))
+            ),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct ApiMergeRequest {
+    iid: usize,
+    title: String,
+    description: Option<String>,
+    state: String,
+    #[serde(default)]
+    draft: bool,
+    web_url: String,
+    source_branch: String,
+    target_branch: String,
+}
+
+impl From<ApiMergeRequest> for PullRequest {
+    fn from(mr: ApiMergeRequest) -> Self {
+        let state = match mr.state.as_str() {
+            "merged" => PullRequestState::Merged,
+            "closed" | "locked" => PullRequestState::Closed,
+            _ if mr.draft => PullRequestState::Draft,
+            _ => PullRequestState::Open,
+        };
+        PullRequest {
+            number: mr.iid,
+            title: mr.title,
+            body: mr.description,
+            source_branch: mr.source_branch,
+            target_branch: mr.target_branch,
+            state,
+            url: mr.web_url,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ApiUpdate<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_branch: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state_event: Option<&'a str>,
+}
+
+impl Forge for GitLab {
+    fn name(&self) -> ForgeName {
+        ForgeName::GitLab
+    }
+
+    fn create_pull_request(&self, pr: &CreatePullRequest) -> Result<PullRequest> {
+        // GitLab marks merge requests as draft by the prefix of their title.
+        let title = if pr.draft {
+            format!("Draft: {}", pr.title)
+        } else {
+            pr.title.clone()
+        };
+        let mr: ApiMergeRequest = self.client.send(
+            Method::POST,
+            &format!("{}/merge_requests", self.project_path),
+            &serde_json::json!({
+                "title": title,
+                "description": pr.body,
+                "source_branch": pr.source_branch,
+                "target_branch": pr.target_branch,
+            }),
+        )?;
+        Ok(mr.into())
+    }
+
+    fn update_pull_request(
+        &self,
+        number: usize,
+        update: &UpdatePullRequest,
+    ) -> Result<PullRequest> {
+        let mr: ApiMergeRequest = self.client.send(
+            Method::PUT,
+            &format!("{}/merge_requests/{number}", self.project_path),
+            &ApiUpdate {
+                title: update.title.as_deref(),
+                description: update.body.as_deref(),
+                target_branch: update.target_branch.as_deref(),
+                state_event: None,
+            },
+        )?;
+        Ok(mr.into())
+    }
+
+    fn pull_request(&self, number: usize) -> Result<PullRequest> {
+        let mr: ApiMergeRequest = self
+            .client
+            .get(&format!("{}/merge_requests/{number}", self.project_path))?;
+        Ok(mr.into())
+    }
+
+    fn list_pull_requests(&self) -> Result<Vec<PullRequest>> {
+        let mut pull_requests = Vec::new();
+        for page in 1.. {
+            let mrs: Vec<ApiMergeRequest> = self.client.get(&format!(
+                "{}/merge_requests?state=opened&per_page={PAGE_SIZE}&page={page}",
+                self.project_path
+            ))?;
+            let is_last_page = mrs.len() < PAGE_SIZE;
+            pull_requests.extend(mrs.into_iter().map(PullRequest::from));
+            if is_last_page {
+                break;
+            }
+        }
+        Ok(pull_requests)
+    }
+
+    fn close_pull_request(&self, number: usize) -> Result<PullRequest> {
+        let mr: ApiMergeRequest = self.client.send(
+            Method::PUT,
+            &format!("{}/merge_requests/{number}", self.project_path),
+            &ApiUpdate {
+                title: None,
+                description: None,
+                target_branch: None,
+                state_event: Some("close"),
+            },
+        )?;
+        Ok(mr.into())
+    }
+}
@@ -0,0 +1,115 @@
+//! A minimal JSON client for the REST APIs of forges.
+use anyhow::{bail, Context, Result};
+use gitbutler_secret::Sensitive;
+use reqwest::{header, Method, Url};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// How to authenticate with the API.
+pub(crate) enum Auth {
+    /// Send the token as bearer token.
+    Bearer(Sensitive<String>),
+    /// Send the token as password with an empty user name.
+    Basic(Sensitive<String>),
+}
+
+pub(crate) struct Client {
+    http: reqwest::blocking::Client,
+    api_url: String,
+    auth: Auth,
+}
+
+impl Client {
+    pub fn new(api_url: &str, auth: Auth) -> Result<Self> {
+        let http = reqwest::blocking::Client::builder()
+            .user_agent("GitButler")
+            .build()?;
+        Ok(Client {
+            http,
+            api_url: api_url.trim_end_matches('/').to_owned(),
+            auth,
+        })
+    }
+
+    /// Fetch the resource at `path`, which is relative to the API URL unless it's an absolute URL.
+    pub fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        self.request(Method::GET, path, None::<&()>)
+    }
+
+    /// Send `body` to the resource at `path` using `method`, and return the response.
+    pub fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: &impl Serialize,
+    ) -> Result<T> {
+        self.request(method, path, Some(body))
+    }
+
+    fn request<T: DeserializeOwned, B: Serialize>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&B>,
+    ) -> Result<T> {
+        let url = if path.starts_with("http://") || path.starts_with("https://") {
+            self.ensure_same_origin(path)?;
+            path.to_owned()
+        } else {
+            format!("{}/{path}", self.api_url)
+        };
+        let mut request = self
+            .http
+            .request(method.clone(), &url)
+            .header(header::ACCEPT, "application/json");
+        request = match &self.auth {
+            Auth::Bearer(token) => request.bearer_auth(&token.0),
+            Auth::Basic(token) => request.basic_auth("", Some(&token.0)),
+        };
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        let response = request
+            .send()
+            .with_context(|| format!("Failed to send {method} request to {url}"))?;
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().unwrap_or_default();
+            bail!("{method} {url} failed with status {status}: {message}");
+        }
+        response
+            .json()
+            .with_context(|| format!("Failed to parse the response to {method} {url}"))
+    }
+
+    /// Fail unless the absolute `url` has the same scheme, host and port as the API URL, as it may have been
+    /// provided by the server and the token must not be sent anywhere else.
+    fn ensure_same_origin(&self, url: &str) -> Result<()> {
+        let api_url = Url::parse(&self.api_url)
+            .with_context(|| format!("Invalid API URL {}", self.api_url))?;
+        let parsed = Url::parse(url).with_context(|| format!("Invalid URL {url}"))?;
+        if parsed.scheme() != api_url.scheme()
+            || parsed.host_str() != api_url.host_str()
+            || parsed.port_or_known_default() != api_url.port_or_known_default()
+        {
+            bail!(
+                "Refusing to send a request to {url} as it's not at {}",
+                self.api_url
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Percent-encode `segment` so it can be used as a single segment of a URL path, even if it contains slashes.
+pub(crate) fn encode_path_segment(segment: &str) -> String {
+    let mut out = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    out
+}
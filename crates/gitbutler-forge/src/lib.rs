@@ -1,2 +1,11 @@
+pub mod detect;
 pub mod forge;
 pub mod review;
+pub mod sync;
+
+mod http;
+
+pub mod azure;
+pub mod bitbucket;
+pub mod github;
+pub mod gitlab;
@@ -96,7 +96,13 @@ pub fn get_review_template_functions(forge_name: &ForgeName) -> ReviewTemplateFu
             is_review_template: is_review_template_azure,
             get_root: get_azure_directory_path,
             is_valid_review_template_path: is_valid_review_template_path_azure,
-            supported_template_directories: &[SupportedTemplateDirectory::ForgeRoot],
+            supported_template_directories: &[
+                SupportedTemplateDirectory::ForgeRoot,
+                SupportedTemplateDirectory::ProjectRoot,
+                SupportedTemplateDirectory::Custom("pull_request_template"),
+                SupportedTemplateDirectory::Custom(".vsts"),
+                SupportedTemplateDirectory::Custom("docs"),
+            ],
         },
     }
 }
@@ -121,48 +127,80 @@ fn is_valid_review_template_path_github(path: &path::Path) -> bool {
 }
 
 fn get_gitlab_directory_path(root_path: &path::Path) -> path::PathBuf {
-    // TODO: implement
-    root_path.to_path_buf()
+    let mut path = root_path.to_path_buf();
+    path.push(".gitlab");
+    path
 }
 
-fn is_review_template_gitlab(_path_str: &str) -> bool {
-    // TODO: implement
-    false
+/// GitLab offers all Markdown files in `.gitlab/merge_request_templates` as templates, but none of its subdirectories.
+fn is_review_template_gitlab(path_str: &str) -> bool {
+    path_str
+        .strip_prefix(".gitlab/merge_request_templates/")
+        .is_some_and(|name| !name.contains('/') && name.ends_with(".md"))
 }
 
-fn is_valid_review_template_path_gitlab(_path: &path::Path) -> bool {
-    // TODO: implement
-    false
+fn is_valid_review_template_path_gitlab(path: &path::Path) -> bool {
+    is_plain_relative_path(path) && is_review_template_gitlab(path.to_str().unwrap_or_default())
 }
 
 fn get_bitbucket_directory_path(root_path: &path::Path) -> path::PathBuf {
-    // TODO: implement
-    root_path.to_path_buf()
+    let mut path = root_path.to_path_buf();
+    path.push(".bitbucket");
+    path
 }
 
-fn is_review_template_bitbucket(_path_str: &str) -> bool {
-    // TODO: implement
-    false
+/// Bitbucket doesn't read templates from the repository, so we use the GitHub conventions within `.bitbucket`,
+/// i.e. `.bitbucket/pull_request_template.md` and Markdown files in `.bitbucket/pull_request_template/`.
+fn is_review_template_bitbucket(path_str: &str) -> bool {
+    let path_str = path_str.to_lowercase();
+    let Some(path_str) = path_str.strip_prefix(".bitbucket/") else {
+        return false;
+    };
+    path_str == "pull_request_template.md"
+        || path_str
+            .strip_prefix("pull_request_template/")
+            .is_some_and(|name| !name.contains('/') && name.ends_with(".md"))
 }
 
-fn is_valid_review_template_path_bitbucket(_path: &path::Path) -> bool {
-    // TODO: implement
-    false
+fn is_valid_review_template_path_bitbucket(path: &path::Path) -> bool {
+    is_plain_relative_path(path) && is_review_template_bitbucket(path.to_str().unwrap_or_default())
 }
 
 fn get_azure_directory_path(root_path: &path::Path) -> path::PathBuf {
-    // TODO: implement
-    root_path.to_path_buf()
+    let mut path = root_path.to_path_buf();
+    path.push(".azuredevops");
+    path
+}
+
+/// Azure DevOps uses `pull_request_template.md` as default template, offers more templates from a
+/// `pull_request_template` directory and branch-specific ones from `pull_request_template/branches`.
+/// Each of these may be in the project root, `.azuredevops`, `.vsts` or `docs`, be Markdown or text files,
+/// and their names are case-insensitive.
+fn is_review_template_azure(path_str: &str) -> bool {
+    let path_str = path_str.to_lowercase();
+    ["", ".azuredevops/", ".vsts/", "docs/"].iter().any(|dir| {
+        let Some(path_str) = path_str.strip_prefix(dir) else {
+            return false;
+        };
+        let file_name = if let Some(name) = path_str.strip_prefix("pull_request_template/") {
+            name.strip_prefix("branches/").unwrap_or(name)
+        } else if path_str.starts_with("pull_request_template.") {
+            path_str
+        } else {
+            return false;
+        };
+        !file_name.contains('/') && (file_name.ends_with(".md") || file_name.ends_with(".txt"))
+    })
 }
 
-fn is_review_template_azure(_path_str: &str) -> bool {
-    // TODO: implement
-    false
+fn is_valid_review_template_path_azure(path: &path::Path) -> bool {
+    is_plain_relative_path(path) && is_review_template_azure(path.to_str().unwrap_or_default())
 }
 
-fn is_valid_review_template_path_azure(_path: &path::Path) -> bool {
-    // TODO: implement
-    false
+/// Return `true` if `path` is relative and can't leave the directory it's relative to.
+fn is_plain_relative_path(path: &path::Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, path::Component::Normal(_)))
 }
 
 #[cfg(test)]
@@ -199,4 +237,116 @@ mod tests {
             invalid_review_template_path,
         ));
     }
+
+    #[test]
+    fn test_is_valid_review_template_path_gitlab() {
+        assert!(is_valid_review_template_path_gitlab(Path::new(
+            ".gitlab/merge_request_templates/Default.md"
+        )));
+        assert!(is_valid_review_template_path_gitlab(Path::new(
+            ".gitlab/merge_request_templates/Bug fix.md"
+        )));
+        assert!(!is_valid_review_template_path_gitlab(Path::new(
+            ".gitlab/merge_request_templates/nested/Default.md"
+        )));
+        assert!(!is_valid_review_template_path_gitlab(Path::new(
+            ".gitlab/merge_request_templates/../../README.md"
+        )));
+        assert!(!is_valid_review_template_path_gitlab(Path::new(
+            ".gitlab/issue_templates/Bug.md"
+        )));
+        assert!(!is_valid_review_template_path_gitlab(Path::new(
+            "PULL_REQUEST_TEMPLATE.md"
+        )));
+    }
+
+    #[test]
+    fn test_is_valid_review_template_path_bitbucket() {
+        assert!(is_valid_review_template_path_bitbucket(Path::new(
+            ".bitbucket/PULL_REQUEST_TEMPLATE.md"
+        )));
+        assert!(is_valid_review_template_path_bitbucket(Path::new(
+            ".bitbucket/pull_request_template/feature.md"
+        )));
+        assert!(!is_valid_review_template_path_bitbucket(Path::new(
+            ".bitbucket/pull_request_template/../secret.md"
+        )));
+        assert!(!is_valid_review_template_path_bitbucket(Path::new(
+            ".github/PULL_REQUEST_TEMPLATE.md"
+        )));
+        assert!(!is_valid_review_template_path_bitbucket(Path::new(
+            ".bitbucket/README.md"
+        )));
+    }
+
+    #[test]
+    fn test_is_valid_review_template_path_azure() {
+        assert!(is_valid_review_template_path_azure(Path::new(
+            "pull_request_template.md"
+        )));
+        assert!(is_valid_review_template_path_azure(Path::new(
+            ".azuredevops/pull_request_template.txt"
+        )));
+        assert!(is_valid_review_template_path_azure(Path::new(
+            ".vsts/Pull_Request_Template.md"
+        )));
+        assert!(is_valid_review_template_path_azure(Path::new(
+            "docs/pull_request_template/feature.md"
+        )));
+        assert!(is_valid_review_template_path_azure(Path::new(
+            ".azuredevops/pull_request_template/branches/main.md"
+        )));
+        assert!(!is_valid_review_template_path_azure(Path::new(
+            ".azuredevops/pull_request_template/other/main.md"
+        )));
+        assert!(!is_valid_review_template_path_azure(Path::new(
+            "src/pull_request_template.md"
+        )));
+        assert!(!is_valid_review_template_path_azure(Path::new(
+            "pull_request_template.rs"
+        )));
+        assert!(!is_valid_review_template_path_azure(Path::new(
+            "docs/pull_request_template/../../secret.md"
+        )));
+    }
+
+    #[test]
+    fn test_available_review_templates() -> std::io::Result<()> {
+        let root = tempfile::tempdir()?;
+        for file in [
+            "README.md",
+            "pull_request_template.md",
+            ".gitlab/merge_request_templates/Default.md",
+            ".gitlab/merge_request_templates/Feature.md",
+            ".gitlab/issue_templates/Bug.md",
+            ".bitbucket/pull_request_template.md",
+            ".azuredevops/pull_request_template/branches/main.md",
+            "docs/pull_request_template.txt",
+        ] {
+            let path = root.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(path, "template")?;
+        }
+
+        assert_eq!(
+            available_review_templates(root.path(), &ForgeName::GitLab),
+            [
+                ".gitlab/merge_request_templates/Default.md",
+                ".gitlab/merge_request_templates/Feature.md"
+            ]
+        );
+        assert_eq!(
+            available_review_templates(root.path(), &ForgeName::Bitbucket),
+            [".bitbucket/pull_request_template.md"]
+        );
+        assert_eq!(
+            available_review_templates(root.path(), &ForgeName::Azure),
+            [
+                ".azuredevops/pull_request_template/branches/main.md",
+                "pull_request_template.md",
+                "docs/pull_request_template.txt"
+            ]
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,224 @@
+//! Keep the pull requests of a stack in line with the order of its branches, after branches were reordered
+//! or integrated.
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::forge::{Forge, PullRequest, PullRequestState, StackedBranch, UpdatePullRequest};
+
+/// Marks the beginning of the footer that lists all pull requests of a stack.
+pub const STACKING_FOOTER_BOUNDARY_TOP: &str = "<!-- GitButler Footer Boundary Top -->";
+/// Marks the end of the footer that lists all pull requests of a stack.
+pub const STACKING_FOOTER_BOUNDARY_BOTTOM: &str = "<!-- GitButler Footer Boundary Bottom -->";
+
+/// A change to a pull request to bring it in line with its stack.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum SyncOperation {
+    /// Make the pull request merge into `target_branch`, the branch below its own in the stack.
+    #[serde(rename_all = "camelCase")]
+    Retarget {
+        pr_number: usize,
+        target_branch: String,
+    },
+    /// Close the pull request of a branch that was integrated without merging the pull request.
+    #[serde(rename_all = "camelCase")]
+    Close { pr_number: usize },
+    /// Replace the description of the pull request with `body`, which has an up-to-date stack footer.
+    #[serde(rename_all = "camelCase")]
+    UpdateBody { pr_number: usize, body: String },
+}
+
+/// Plan the operations that make the pull requests of `branches`, ordered from the bottom of the stack to its top,
+/// each target the branch below them, with the bottom-most one targeting `target_branch`.
+///
+/// `pull_requests` is the current state of the pull requests of `branches` on the forge. Branches without pull request
+/// still serve as base for the branch above, while those whose pull request was merged don't.
+/// Archived branches are skipped, and open pull requests of branches whose review id is in `review_ids_to_close`, as
+/// obtained from integrating upstream changes, are closed.
+/// If `update_footers` is `true`, the descriptions of all open pull requests get a footer that lists the whole stack.
+///
+/// Operations are ordered so that pull requests are retargeted before others are closed.
+pub fn plan_stack_sync(
+    branches: &[StackedBranch],
+    target_branch: &str,
+    pull_requests: &[PullRequest],
+    review_ids_to_close: &[String],
+    update_footers: bool,
+) -> Vec<SyncOperation> {
+    let mut retargets = Vec::new();
+    let mut closes = Vec::new();
+    let mut open_pull_requests = Vec::new();
+    let mut base = target_branch;
+    for branch in branches {
+        let pr = branch
+            .pr_number
+            .and_then(|number| pull_requests.iter().find(|pr| pr.number == number));
+        let is_open = pr
+            .is_some_and(|pr| matches!(pr.state, PullRequestState::Open | PullRequestState::Draft));
+        let to_close = branch
+            .review_id
+            .as_ref()
+            .is_some_and(|id| review_ids_to_close.contains(id));
+        if to_close || branch.archived {
+            if let Some(pr) = pr.filter(|_| to_close && is_open) {
+                closes.push(SyncOperation::Close {
+                    pr_number: pr.number,
+                });
+            }
+            continue;
+        }
+
+        match pr {
+            Some(pr) if pr.state == PullRequestState::Merged => continue,
+            Some(pr) if is_open => {
+                if pr.target_branch != base {
+                    retargets.push(SyncOperation::Retarget {
+                        pr_number: pr.number,
+                        target_branch: base.to_owned(),
+                    });
+                }
+                open_pull_requests.push(pr);
+            }
+            _ => {}
+        }
+        base = &branch.name;
+    }
+
+    let mut operations = retargets;
+    operations.append(&mut closes);
+    if update_footers {
+        // The footer lists the top of the stack first.
+        let numbers: Vec<_> = open_pull_requests
+            .iter()
+            .rev()
+            .map(|pr| pr.number)
+            .collect();
+        for pr in open_pull_requests {
+            let old_body = pr.body.as_deref().unwrap_or_default();
+            let body = if numbers.len() > 1 {
+                with_footer(old_body, &footer(pr.number, &numbers))
+            } else {
+                without_footer(old_body)
+            };
+            if body != old_body {
+                operations.push(SyncOperation::UpdateBody {
+                    pr_number: pr.number,
+                    body,
+                });
+            }
+        }
+    }
+    operations
+}
+
+/// Fetch the pull requests of `branches` from `forge`, then plan and apply the operations to bring them in line
+/// with their stack as described in [`plan_stack_sync()`].
+///
+/// Return the operations that were applied.
+pub fn sync_stack_pull_requests(
+    forge: &dyn Forge,
+    branches: &[StackedBranch],
+    target_branch: &str,
+    review_ids_to_close: &[String],
+    update_footers: bool,
+) -> Result<Vec<SyncOperation>> {
+    let pull_requests = branches
+        .iter()
+        .filter_map(|branch| branch.pr_number)
+        .map(|number| forge.pull_request(number))
+        .collect::<Result<Vec<_>>>()?;
+    let operations = plan_stack_sync(
+        branches,
+        target_branch,
+        &pull_requests,
+        review_ids_to_close,
+        update_footers,
+    );
+    apply_stack_sync(forge, &operations)?;
+    Ok(operations)
+}
+
+/// Perform `operations` on `forge` in order, and return the pull requests in their new state.
+pub fn apply_stack_sync(
+    forge: &dyn Forge,
+    operations: &[SyncOperation],
+) -> Result<Vec<PullRequest>> {
+    operations
+        .iter()
+        .map(|operation| match operation {
+            SyncOperation::Retarget {
+                pr_number,
+                target_branch,
+            } => forge.update_pull_request(
+                *pr_number,
+                &UpdatePullRequest {
+                    target_branch: Some(target_branch.clone()),
+                    ..Default::default()
+                },
+            ),
+            SyncOperation::Close { pr_number } => forge.close_pull_request(*pr_number),
+            SyncOperation::UpdateBody { pr_number, body } => forge.update_pull_request(
+                *pr_number,
+                &UpdatePullRequest {
+                    body: Some(body.clone()),
+                    ..Default::default()
+                },
+            ),
+        })
+        .collect()
+}
+
+/// Generate the footer for the pull request `pr_number` that lists `all_pr_numbers`, which start at the top of the stack.
+///
+/// This matches the footer the desktop application adds.
+fn footer(pr_number: usize, all_pr_numbers: &[usize]) -> String {
+    let stack_len = all_pr_numbers.len();
+    let index = all_pr_numbers
+        .iter()
+        .position(|number| *number == pr_number)
+        .unwrap_or_default();
+    let mut footer = format!("{STACKING_FOOTER_BOUNDARY_TOP}\n---\n");
+    footer.push_str(&format!(
+        "This is **part {} of {stack_len} in a stack** made with GitButler:\n",
+        stack_len - index
+    ));
+    for (i, number) in all_pr_numbers.iter().enumerate() {
+        let current = if i == index { "👈 " } else { "" };
+        footer.push_str(&format!(
+            "- <kbd>&nbsp;{}&nbsp;</kbd> #{number} {current}\n",
+            stack_len - i
+        ));
+    }
+    footer.push_str(STACKING_FOOTER_BOUNDARY_BOTTOM);
+    footer
+}
+
+/// Replace the footer in `body` with `footer`, or append it if there is none.
+fn with_footer(body: &str, footer: &str) -> String {
+    let (head, tail) = split_footer(body);
+    format!("{head}\n\n{footer}\n\n{tail}")
+}
+
+/// Remove the footer from `body`, if it has one.
+fn without_footer(body: &str) -> String {
+    if !body.contains(STACKING_FOOTER_BOUNDARY_TOP) {
+        return body.to_owned();
+    }
+    match split_footer(body) {
+        (head, "") => head.to_owned(),
+        (head, tail) => format!("{head}\n\n{tail}"),
+    }
+}
+
+/// Return the trimmed text before and after the footer in `body`.
+fn split_footer(body: &str) -> (&str, &str) {
+    let head = body
+        .split(STACKING_FOOTER_BOUNDARY_TOP)
+        .next()
+        .unwrap_or_default();
+    let tail = body
+        .split(STACKING_FOOTER_BOUNDARY_BOTTOM)
+        .nth(1)
+        .unwrap_or_default();
+    (head.trim(), tail.trim())
+}
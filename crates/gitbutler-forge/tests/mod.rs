@@ -0,0 +1,4 @@
+mod detect;
+mod pull_requests;
+mod stand_in;
+mod sync;
@@ -0,0 +1,367 @@
+use gitbutler_forge::forge::{
+    chain_pull_requests, forge_client, CreatePullRequest, ForgeName, ForgeRepository,
+    PullRequestState, StackedBranch, UpdatePullRequest,
+};
+use gitbutler_secret::Sensitive;
+use serde_json::json;
+
+use crate::stand_in::StandIn;
+
+fn client(
+    forge: ForgeName,
+    server: &StandIn,
+    owner: &str,
+) -> Box<dyn gitbutler_forge::forge::Forge> {
+    let repo = ForgeRepository {
+        api_url: server.url.clone(),
+        owner: owner.into(),
+        name: "repo".into(),
+    };
+    forge_client(&forge, repo, Sensitive("secret-token".into())).unwrap()
+}
+
+fn github_pr(number: usize, head: &str, base: &str, state: &str) -> serde_json::Value {
+    json!({
+        "number": number,
+        "title": format!("PR for {head}"),
+        "body": null,
+        "state": state,
+        "draft": false,
+        "merged_at": null,
+        "html_url": format!("https://github.com/owner/repo/pull/{number}"),
+        "head": { "ref": head },
+        "base": { "ref": base },
+    })
+}
+
+#[test]
+fn github_pull_request_lifecycle() -> anyhow::Result<()> {
+    let server = StandIn::start();
+    let mut created = github_pr(1, "feature", "main", "open");
+    created["draft"] = json!(true);
+    created["body"] = json!("description");
+    server.respond("POST", "/repos/owner/repo/pulls", created);
+    server.respond(
+        "PATCH",
+        "/repos/owner/repo/pulls/1",
+        github_pr(1, "feature", "develop", "open"),
+    );
+    let mut merged = github_pr(1, "feature", "develop", "closed");
+    merged["merged_at"] = json!("2025-01-01T00:00:00Z");
+    server.respond("GET", "/repos/owner/repo/pulls/1", merged);
+    server.respond(
+        "GET",
+        "/repos/owner/repo/pulls?state=open&per_page=100&page=1",
+        json!([github_pr(2, "other", "main", "open")]),
+    );
+
+    let forge = client(ForgeName::GitHub, &server, "owner");
+    let pr = forge.create_pull_request(&CreatePullRequest {
+        title: "PR for feature".into(),
+        body: Some("description".into()),
+        source_branch: "feature".into(),
+        target_branch: "main".into(),
+        draft: true,
+    })?;
+    assert_eq!(pr.number, 1);
+    assert_eq!(pr.state, PullRequestState::Draft);
+    assert_eq!(pr.body.as_deref(), Some("description"));
+    assert_eq!(pr.url, "https://github.com/owner/repo/pull/1");
+
+    let pr = forge.update_pull_request(
+        1,
+        &UpdatePullRequest {
+            target_branch: Some("develop".into()),
+            ..Default::default()
+        },
+    )?;
+    assert_eq!(pr.target_branch, "develop");
+    assert_eq!(forge.pull_request(1)?.state, PullRequestState::Merged);
+
+    let prs = forge.list_pull_requests()?;
+    assert_eq!(prs.len(), 1, "a short page is the last one");
+    assert_eq!(prs[0].source_branch, "other");
+
+    let err = forge.close_pull_request(2).unwrap_err();
+    assert!(
+        err.to_string().contains("failed with status 404 Not Found"),
+        "{err}"
+    );
+
+    let requests = server.requests();
+    assert_eq!(
+        requests[0].headers.get("authorization").map(String::as_str),
+        Some("Bearer secret-token")
+    );
+    assert_eq!(
+        requests[0].body,
+        json!({
+            "title": "PR for feature",
+            "body": "description",
+            "head": "feature",
+            "base": "main",
+            "draft": true,
+        })
+    );
+    assert_eq!(
+        requests[1].body,
+        json!({ "base": "develop" }),
+        "only changed fields are sent"
+    );
+    assert_eq!(requests[4].method, "PATCH");
+    assert_eq!(requests[4].body, json!({ "state": "closed" }));
+    Ok(())
+}
+
+#[test]
+fn gitlab_marks_drafts_by_title() -> anyhow::Result<()> {
+    let server = StandIn::start();
+    let mr = json!({
+        "iid": 7,
+        "title": "Draft: Add feature",
+        "description": null,
+        "state": "opened",
+        "draft": true,
+        "web_url": "https://gitlab.com/group/sub/repo/-/merge_requests/7",
+        "source_branch": "feature",
+        "target_branch": "main",
+    });
+    server.respond(
+        "POST",
+        "/projects/group%2Fsub%2Frepo/merge_requests",
+        mr.clone(),
+    );
+    let mut closed = mr;
+    closed["state"] = json!("closed");
+    server.respond(
+        "PUT",
+        "/projects/group%2Fsub%2Frepo/merge_requests/7",
+        closed,
+    );
+
+    let forge = client(ForgeName::GitLab, &server, "group/sub");
+    let pr = forge.create_pull_request(&CreatePullRequest {
+        title: "Add feature".into(),
+        body: None,
+        source_branch: "feature".into(),
+        target_branch: "main".into(),
+        draft: true,
+    })?;
+    assert_eq!(pr.number, 7);
+    assert_eq!(pr.state, PullRequestState::Draft);
+    assert_eq!(forge.close_pull_request(7)?.state, PullRequestState::Closed);
+
+    let requests = server.requests();
+    assert_eq!(requests[0].body["title"], "Draft: Add feature");
+    assert_eq!(requests[1].body, json!({ "state_event": "close" }));
+    Ok(())
+}
+
+#[test]
+fn bitbucket_follows_pagination_and_declines() -> anyhow::Result<()> {
+    let server = StandIn::start();
+    let pr = |id: usize, state: &str| {
+        json!({
+            "id": id,
+            "title": "title",
+            "description": "",
+            "state": state,
+            "source": { "branch": { "name": format!("branch-{id}") } },
+            "destination": { "branch": { "name": "main" } },
+            "links": { "html": { "href": format!("https://bitbucket.org/ws/repo/pull-requests/{id}") } },
+        })
+    };
+    server.respond(
+        "GET",
+        "/repositories/ws/repo/pullrequests?state=OPEN",
+        json!({
+            "values": [pr(1, "OPEN")],
+            "next": format!("{}/repositories/ws/repo/pullrequests?state=OPEN&page=2", server.url),
+        }),
+    );
+    server.respond(
+        "GET",
+        "/repositories/ws/repo/pullrequests?state=OPEN&page=2",
+        json!({ "values": [pr(2, "OPEN")] }),
+    );
+    server.respond(
+        "POST",
+        "/repositories/ws/repo/pullrequests/2/decline",
+        pr(2, "DECLINED"),
+    );
+
+    let forge = client(ForgeName::Bitbucket, &server, "ws");
+    let numbers: Vec<_> = forge
+        .list_pull_requests()?
+        .into_iter()
+        .map(|pr| pr.number)
+        .collect();
+    assert_eq!(numbers, [1, 2]);
+    let pr = forge.close_pull_request(2)?;
+    assert_eq!(pr.state, PullRequestState::Closed);
+    assert_eq!(pr.source_branch, "branch-2");
+    assert_eq!(pr.url, "https://bitbucket.org/ws/repo/pull-requests/2");
+    Ok(())
+}
+
+#[test]
+fn bitbucket_does_not_follow_pagination_to_other_hosts() -> anyhow::Result<()> {
+    let server = StandIn::start();
+    let elsewhere = StandIn::start();
+    server.respond(
+        "GET",
+        "/repositories/ws/repo/pullrequests?state=OPEN",
+        json!({
+            "values": [],
+            "next": format!("{}/repositories/ws/repo/pullrequests?state=OPEN&page=2", elsewhere.url),
+        }),
+    );
+
+    let forge = client(ForgeName::Bitbucket, &server, "ws");
+    let err = forge.list_pull_requests().unwrap_err();
+    assert!(
+        err.to_string().starts_with("Refusing to send a request to"),
+        "{err:#}"
+    );
+    assert!(
+        elsewhere.requests().is_empty(),
+        "the token isn't sent to another host"
+    );
+    Ok(())
+}
+
+#[test]
+fn azure_lists_pull_requests_page_by_page() -> anyhow::Result<()> {
+    let server = StandIn::start();
+    let pr = |id: usize| {
+        json!({
+            "pullRequestId": id,
+            "title": "title",
+            "description": null,
+            "status": "active",
+            "isDraft": false,
+            "sourceRefName": format!("refs/heads/branch-{id}"),
+            "targetRefName": "refs/heads/main",
+        })
+    };
+    let path = |skip: usize| {
+        format!("/org/project/_apis/git/repositories/repo/pullrequests?searchCriteria.status=active&$top=100&$skip={skip}&api-version=7.1")
+    };
+    server.respond(
+        "GET",
+        &path(0),
+        json!({ "value": (1..=100).map(pr).collect::<Vec<_>>() }),
+    );
+    server.respond("GET", &path(100), json!({ "value": [pr(101)] }));
+
+    let forge = client(ForgeName::Azure, &server, "org/project");
+    let numbers: Vec<_> = forge
+        .list_pull_requests()?
+        .into_iter()
+        .map(|pr| pr.number)
+        .collect();
+    assert_eq!(numbers, (1..=101).collect::<Vec<_>>());
+    assert_eq!(server.requests().len(), 2);
+    Ok(())
+}
+
+#[test]
+fn azure_uses_basic_auth_and_full_ref_names() -> anyhow::Result<()> {
+    let server = StandIn::start();
+    server.respond(
+        "PATCH",
+        "/org/project/_apis/git/repositories/repo/pullrequests/3?api-version=7.1",
+        json!({
+            "pullRequestId": 3,
+            "title": "title",
+            "description": null,
+            "status": "active",
+            "isDraft": false,
+            "sourceRefName": "refs/heads/feature",
+            "targetRefName": "refs/heads/develop",
+        }),
+    );
+
+    let forge = client(ForgeName::Azure, &server, "org/project");
+    let pr = forge.update_pull_request(
+        3,
+        &UpdatePullRequest {
+            target_branch: Some("develop".into()),
+            ..Default::default()
+        },
+    )?;
+    assert_eq!(pr.state, PullRequestState::Open);
+    assert_eq!(pr.source_branch, "feature");
+    assert_eq!(pr.target_branch, "develop");
+    assert_eq!(
+        pr.url,
+        format!("{}/org/project/_git/repo/pullrequest/3", server.url)
+    );
+
+    let requests = server.requests();
+    assert_eq!(
+        requests[0].headers.get("authorization").map(String::as_str),
+        Some("Basic OnNlY3JldC10b2tlbg=="),
+        "the token is the password of an empty user"
+    );
+    assert_eq!(
+        requests[0].body,
+        json!({ "targetRefName": "refs/heads/develop" })
+    );
+    Ok(())
+}
+
+#[test]
+fn stacked_pull_requests_target_the_branch_below() -> anyhow::Result<()> {
+    let server = StandIn::start();
+    let mut merged = github_pr(1, "bottom", "main", "closed");
+    merged["merged_at"] = json!("2025-01-01T00:00:00Z");
+    server.respond("GET", "/repos/owner/repo/pulls/1", merged);
+    server.respond(
+        "GET",
+        "/repos/owner/repo/pulls/2",
+        github_pr(2, "middle", "bottom", "open"),
+    );
+    server.respond(
+        "PATCH",
+        "/repos/owner/repo/pulls/2",
+        github_pr(2, "middle", "main", "open"),
+    );
+    server.respond(
+        "POST",
+        "/repos/owner/repo/pulls",
+        github_pr(3, "top", "middle", "open"),
+    );
+
+    let forge = client(ForgeName::GitHub, &server, "owner");
+    let branch = |name: &str, pr_number: Option<usize>| StackedBranch {
+        name: name.into(),
+        title: format!("PR for {name}"),
+        body: None,
+        pr_number,
+        ..Default::default()
+    };
+    let prs = chain_pull_requests(
+        forge.as_ref(),
+        "main",
+        &[
+            branch("bottom", Some(1)),
+            branch("middle", Some(2)),
+            branch("top", None),
+        ],
+        false,
+    )?;
+    let numbers: Vec<_> = prs.iter().map(|pr| pr.number).collect();
+    assert_eq!(numbers, [1, 2, 3]);
+
+    let requests = server.requests();
+    assert_eq!(
+        requests[2].body,
+        json!({ "base": "main" }),
+        "the branch above a merged one targets what the merged one targeted"
+    );
+    assert_eq!(requests[3].body["head"], "top");
+    assert_eq!(requests[3].body["base"], "middle");
+    assert_eq!(requests.len(), 4);
+    Ok(())
+}
@@ -0,0 +1,120 @@
+//! A local HTTP server that stands in for the API of a forge, replying with canned responses.
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+/// A request as received by the [`StandIn`].
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    /// The path of the request, including the query.
+    pub path: String,
+    /// All headers, with lower-case names.
+    pub headers: HashMap<String, String>,
+    /// The JSON body, or `Null` if there was none.
+    pub body: serde_json::Value,
+}
+
+type Responses = HashMap<(String, String), serde_json::Value>;
+
+pub struct StandIn {
+    pub url: String,
+    responses: Arc<Mutex<Responses>>,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl StandIn {
+    /// Listen on a random local port and serve requests in the background.
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("can bind to a local port");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let responses = Arc::new(Mutex::new(Responses::new()));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        std::thread::spawn({
+            let responses = responses.clone();
+            let requests = requests.clone();
+            move || {
+                for stream in listener.incoming() {
+                    let Ok(mut stream) = stream else { break };
+                    let Some(request) = read_request(&mut stream) else {
+                        continue;
+                    };
+                    let response = responses
+                        .lock()
+                        .unwrap()
+                        .get(&(request.method.clone(), request.path.clone()))
+                        .cloned();
+                    requests.lock().unwrap().push(request);
+                    let (status, body) = match response {
+                        Some(body) => ("200 OK", body.to_string()),
+                        None => ("404 Not Found", r#"{"message":"Not Found"}"#.to_owned()),
+                    };
+                    write!(
+                        stream,
+                        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                    .ok();
+                }
+            }
+        });
+        StandIn {
+            url,
+            responses,
+            requests,
+        }
+    }
+
+    /// Reply with `body` to all requests with `method` to `path`, which includes the query.
+    pub fn respond(&self, method: &str, path: &str, body: serde_json::Value) {
+        self.responses
+            .lock()
+            .unwrap()
+            .insert((method.to_owned(), path.to_owned()), body);
+    }
+
+    /// All requests received so far, in order.
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn read_request(stream: &mut impl Read) -> Option<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_owned();
+    let path = parts.next()?.to_owned();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':')?;
+        headers.insert(name.trim().to_lowercase(), value.trim().to_owned());
+    }
+
+    let len: usize = headers
+        .get("content-length")
+        .and_then(|len| len.parse().ok())
+        .unwrap_or_default();
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body).ok()?;
+    let body = if body.is_empty() {
+        serde_json::Value::Null
+    } else {
+        serde_json::from_slice(&body).ok()?
+    };
+    Some(Request {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
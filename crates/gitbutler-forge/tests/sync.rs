@@ -0,0 +1,219 @@
+use gitbutler_forge::forge::{
+    forge_client, ForgeName, ForgeRepository, PullRequest, PullRequestState, StackedBranch,
+};
+use gitbutler_forge::sync::{plan_stack_sync, sync_stack_pull_requests, SyncOperation};
+use gitbutler_secret::Sensitive;
+use serde_json::json;
+
+use crate::stand_in::StandIn;
+
+fn branch(name: &str, pr_number: Option<usize>) -> StackedBranch {
+    StackedBranch {
+        name: name.into(),
+        title: name.into(),
+        pr_number,
+        ..Default::default()
+    }
+}
+
+fn pr(number: usize, source: &str, target: &str, state: PullRequestState) -> PullRequest {
+    PullRequest {
+        number,
+        title: source.into(),
+        body: Some("Description".into()),
+        source_branch: source.into(),
+        target_branch: target.into(),
+        state,
+        url: format!("https://github.com/owner/repo/pull/{number}"),
+    }
+}
+
+#[test]
+fn reordered_branches_are_retargeted() {
+    let branches = [
+        branch("a", Some(1)),
+        branch("b", Some(2)),
+        branch("c", Some(3)),
+    ];
+    let prs = [
+        pr(1, "a", "main", PullRequestState::Open),
+        pr(2, "b", "c", PullRequestState::Draft),
+        pr(3, "c", "a", PullRequestState::Open),
+    ];
+    assert_eq!(
+        plan_stack_sync(&branches, "main", &prs, &[], false),
+        [
+            SyncOperation::Retarget {
+                pr_number: 2,
+                target_branch: "a".into()
+            },
+            SyncOperation::Retarget {
+                pr_number: 3,
+                target_branch: "b".into()
+            },
+        ]
+    );
+}
+
+#[test]
+fn integrated_branches_are_skipped_and_their_reviews_closed() {
+    let mut bottom = branch("bottom", Some(1));
+    bottom.archived = true;
+    bottom.review_id = Some("review-1".into());
+    let mut merged = branch("merged", Some(2));
+    merged.archived = true;
+    let branches = [
+        bottom,
+        merged,
+        branch("unpushed", None),
+        branch("top", Some(3)),
+    ];
+    let prs = [
+        pr(1, "bottom", "main", PullRequestState::Open),
+        pr(2, "merged", "bottom", PullRequestState::Merged),
+        pr(3, "top", "merged", PullRequestState::Open),
+    ];
+    assert_eq!(
+        plan_stack_sync(&branches, "main", &prs, &["review-1".into()], false),
+        [
+            SyncOperation::Retarget {
+                pr_number: 3,
+                target_branch: "unpushed".into()
+            },
+            SyncOperation::Close { pr_number: 1 },
+        ],
+        "branches without pull request still serve as base, and closing happens after retargeting"
+    );
+}
+
+#[test]
+fn merged_pull_requests_pass_their_base_on() {
+    let branches = [branch("bottom", Some(1)), branch("top", Some(2))];
+    let prs = [
+        pr(1, "bottom", "main", PullRequestState::Merged),
+        pr(2, "top", "bottom", PullRequestState::Open),
+    ];
+    assert_eq!(
+        plan_stack_sync(&branches, "main", &prs, &[], false),
+        [SyncOperation::Retarget {
+            pr_number: 2,
+            target_branch: "main".into()
+        }]
+    );
+}
+
+#[test]
+fn footers_list_the_open_pull_requests_of_the_stack() {
+    let branches = [branch("a", Some(1)), branch("b", Some(2))];
+    let mut prs = [
+        pr(1, "a", "main", PullRequestState::Open),
+        pr(2, "b", "a", PullRequestState::Open),
+    ];
+    let operations = plan_stack_sync(&branches, "main", &prs, &[], true);
+    assert_eq!(
+        operations,
+        [
+            SyncOperation::UpdateBody {
+                pr_number: 1,
+                body: "Description\n\n<!-- GitButler Footer Boundary Top -->\n---\n\
+                       This is **part 1 of 2 in a stack** made with GitButler:\n\
+                       - <kbd>&nbsp;2&nbsp;</kbd> #2 \n\
+                       - <kbd>&nbsp;1&nbsp;</kbd> #1 👈 \n\
+                       <!-- GitButler Footer Boundary Bottom -->\n\n"
+                    .into()
+            },
+            SyncOperation::UpdateBody {
+                pr_number: 2,
+                body: "Description\n\n<!-- GitButler Footer Boundary Top -->\n---\n\
+                       This is **part 2 of 2 in a stack** made with GitButler:\n\
+                       - <kbd>&nbsp;2&nbsp;</kbd> #2 👈 \n\
+                       - <kbd>&nbsp;1&nbsp;</kbd> #1 \n\
+                       <!-- GitButler Footer Boundary Bottom -->\n\n"
+                    .into()
+            },
+        ]
+    );
+
+    for (pr, operation) in prs.iter_mut().zip(operations) {
+        let SyncOperation::UpdateBody { body, .. } = operation else {
+            unreachable!("only bodies are updated")
+        };
+        pr.body = Some(body);
+    }
+    assert_eq!(
+        plan_stack_sync(&branches, "main", &prs, &[], true),
+        [],
+        "up-to-date footers are left alone"
+    );
+
+    prs[0].state = PullRequestState::Merged;
+    assert_eq!(
+        plan_stack_sync(&branches, "main", &prs, &[], true),
+        [
+            SyncOperation::Retarget {
+                pr_number: 2,
+                target_branch: "main".into()
+            },
+            SyncOperation::UpdateBody {
+                pr_number: 2,
+                body: "Description".into()
+            },
+        ],
+        "the footer is removed once the pull request is the only one left"
+    );
+}
+
+#[test]
+fn planned_operations_are_applied_to_the_forge() -> anyhow::Result<()> {
+    let server = StandIn::start();
+    let github_pr = |number: usize, head: &str, base: &str| {
+        json!({
+            "number": number,
+            "title": head,
+            "body": null,
+            "state": "open",
+            "draft": false,
+            "merged_at": null,
+            "html_url": format!("https://github.com/owner/repo/pull/{number}"),
+            "head": { "ref": head },
+            "base": { "ref": base },
+        })
+    };
+    server.respond(
+        "GET",
+        "/repos/owner/repo/pulls/1",
+        github_pr(1, "a", "main"),
+    );
+    server.respond("GET", "/repos/owner/repo/pulls/2", github_pr(2, "b", "old"));
+    server.respond("PATCH", "/repos/owner/repo/pulls/2", github_pr(2, "b", "a"));
+
+    let forge = forge_client(
+        &ForgeName::GitHub,
+        ForgeRepository {
+            api_url: server.url.clone(),
+            owner: "owner".into(),
+            name: "repo".into(),
+        },
+        Sensitive("token".into()),
+    )?;
+    let operations = sync_stack_pull_requests(
+        forge.as_ref(),
+        &[branch("a", Some(1)), branch("b", Some(2))],
+        "main",
+        &[],
+        false,
+    )?;
+    assert_eq!(
+        operations,
+        [SyncOperation::Retarget {
+            pr_number: 2,
+            target_branch: "a".into()
+        }]
+    );
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert_eq!(requests[2].method, "PATCH");
+    assert_eq!(requests[2].body, json!({ "base": "a" }));
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use std::{fmt, time::Duration};
+
+use anyhow::Result;
+use gitbutler_oxidize::{ObjectIdExt, OidExt};
+use gitbutler_project::{access::WorktreeWritePermission, Project};
+use gitbutler_repo::RepositoryExt;
+
+use crate::{
+    entry::{OperationKind, SnapshotDetails, Trailer},
+    file_limits,
+    oplog::{get_workdir_tree, lines_since_snapshot, prepare_snapshot_before_branch_switch},
+    state::OplogHandle,
+    OplogExt,
+};
+
+/// The reference of the workspace commit, which HEAD points to while the workspace is open.
+const WORKSPACE_REF: &str = "refs/heads/gitbutler/workspace";
+
+/// The key of the trailer that records why an automatic snapshot was created.
+pub const TRIGGER_TRAILER_KEY: &str = "AutoSnapshotTrigger";
+
+/// The reason for creating an automatic snapshot, as returned by
+/// [`OplogExt::auto_snapshot_trigger()`](crate::OplogExt::auto_snapshot_trigger()).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoSnapshotTrigger {
+    /// More lines than [`Project::snapshot_lines_threshold()`] were added or removed.
+    LinesChanged(usize),
+    /// At least [`touched_files_threshold`](gitbutler_project::AutoSnapshotPolicy::touched_files_threshold) files
+    /// were added, modified or deleted.
+    FilesTouched(usize),
+    /// The given amount of files were added that aren't tracked by Git.
+    UntrackedFiles(usize),
+    /// The given amount of files that are tracked by Git and were part of the last snapshot were deleted since.
+    DeletedFiles(usize),
+    /// A different branch was checked out.
+    BranchSwitch,
+    /// There are changes and the last snapshot is older than
+    /// [`max_interval_seconds`](gitbutler_project::AutoSnapshotPolicy::max_interval_seconds).
+    MaxInterval,
+}
+
+impl fmt::Display for AutoSnapshotTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AutoSnapshotTrigger::LinesChanged(count) => write!(f, "LinesChanged({count})"),
+            AutoSnapshotTrigger::FilesTouched(count) => write!(f, "FilesTouched({count})"),
+            AutoSnapshotTrigger::UntrackedFiles(count) => write!(f, "UntrackedFiles({count})"),
+            AutoSnapshotTrigger::DeletedFiles(count) => write!(f, "DeletedFiles({count})"),
+            AutoSnapshotTrigger::BranchSwitch => f.write_str("BranchSwitch"),
+            AutoSnapshotTrigger::MaxInterval => f.write_str("MaxInterval"),
+        }
+    }
+}
+
+impl From<AutoSnapshotTrigger> for Trailer {
+    fn from(trigger: AutoSnapshotTrigger) -> Self {
+        Trailer {
+            key: TRIGGER_TRAILER_KEY.into(),
+            value: trigger.to_string(),
+        }
+    }
+}
+
+/// Statistics about the worktree files that changed since the last snapshot.
+#[derive(Debug, Default)]
+struct FileChanges {
+    touched: usize,
+    untracked: usize,
+    deleted: usize,
+}
+
+/// Create a snapshot with the [`BranchSwitch`](AutoSnapshotTrigger::BranchSwitch) trigger once HEAD was moved
+/// to another branch, as detected by [`HeadTracker`], with the workspace and its worktree changes as they were
+/// before the switch.
+///
+/// Returns the sha of the created snapshot commit.
+pub fn create_branch_switch_snapshot(
+    ctx: &Project,
+    perm: &mut WorktreeWritePermission,
+) -> Result<git2::Oid> {
+    let tree_id = prepare_snapshot_before_branch_switch(ctx, perm.read_permission())?;
+    ctx.commit_snapshot(
+        tree_id,
+        SnapshotDetails::new(OperationKind::FileChanges)
+            .with_trailers(vec![AutoSnapshotTrigger::BranchSwitch.into()]),
+        perm,
+    )
+}
+
+/// Remembers what HEAD pointed to to tell switches away from the workspace apart from GitButler rewriting HEAD,
+/// which it does whenever the workspace commit changes.
+#[derive(Debug, Default, Clone)]
+pub struct HeadTracker {
+    /// The reference HEAD pointed to when it was last seen, or `HEAD` if it was detached.
+    previous: Option<String>,
+}
+
+impl HeadTracker {
+    /// Remember what HEAD in `repo` points to now, and return `true` if it moved from `gitbutler/workspace`
+    /// to another reference, or was detached, since the last call.
+    ///
+    /// The first call only remembers HEAD, as there is nothing to compare it with.
+    pub fn head_left_workspace(&mut self, repo: &git2::Repository) -> Result<bool> {
+        let head = repo.find_reference("HEAD")?;
+        let current = head.symbolic_target().unwrap_or("HEAD").to_owned();
+        let previous = self.previous.replace(current.clone());
+        Ok(previous.as_deref() == Some(WORKSPACE_REF) && current != WORKSPACE_REF)
+    }
+}
+
+/// Note that `check_if_last_snapshot_older_than` only debounces the line-count trigger, as it is checked on every
+/// change to the worktree. All other triggers compare with the last snapshot and stop firing once it was taken.
+pub(crate) fn auto_snapshot_trigger(
+    ctx: &Project,
+    check_if_last_snapshot_older_than: Duration,
+) -> Result<Option<AutoSnapshotTrigger>> {
+    let repo = git2::Repository::open(&ctx.path)?;
+    if repo.workspace_ref_from_head().is_err() {
+        return Ok(None);
+    }
+    let oplog_state = OplogHandle::new(&ctx.gb_dir());
+    let since_last_snapshot = oplog_state.modified_at()?.elapsed()?;
+    let lines_changed = if since_last_snapshot > check_if_last_snapshot_older_than {
+        let lines_changed = lines_since_snapshot(ctx, &repo)?;
+        if lines_changed > ctx.snapshot_lines_threshold() {
+            return Ok(Some(AutoSnapshotTrigger::LinesChanged(lines_changed)));
+        }
+        Some(lines_changed)
+    } else {
+        None
+    };
+
+    let Some(policy) = ctx
+        .auto_snapshot_policy
+        .filter(|policy| policy.needs_file_changes())
+    else {
+        return Ok(None);
+    };
+    let Some(oplog_head) = oplog_state.oplog_head()? else {
+        return Ok(None);
+    };
+    let changes = file_changes_since_snapshot(ctx, &repo, oplog_head)?;
+    let trigger = if policy.on_deleted_files && changes.deleted > 0 {
+        Some(AutoSnapshotTrigger::DeletedFiles(changes.deleted))
+    } else if policy.on_untracked_files && changes.untracked > 0 {
+        Some(AutoSnapshotTrigger::UntrackedFiles(changes.untracked))
+    } else if policy
+        .touched_files_threshold
+        .is_some_and(|threshold| changes.touched >= threshold.max(1))
+    {
+        Some(AutoSnapshotTrigger::FilesTouched(changes.touched))
+    } else if policy
+        .max_interval_seconds
+        .is_some_and(|max| since_last_snapshot > Duration::from_secs(max))
+        && (changes.touched > 0 || lines_changed.is_some_and(|lines| lines > 0))
+    {
+        Some(AutoSnapshotTrigger::MaxInterval)
+    } else {
+        None
+    };
+    Ok(trigger)
+}
+
+/// Compare the worktree of the snapshot at `snapshot_id` with the current worktree, ignoring files that
+/// would be too large to be part of a snapshot.
+///
+/// Only files that are part of the worktree of the snapshot can be deleted, so files that were already gone when the
+/// snapshot was taken aren't counted again. Of these, only deletions of files that are tracked by Git are counted.
+fn file_changes_since_snapshot(
+    ctx: &Project,
+    repo: &git2::Repository,
+    snapshot_id: git2::Oid,
+) -> Result<FileChanges> {
+    let gix_repo = gitbutler_command_context::gix_repo_for_merging(ctx.path.as_path())?;
+    let snapshot_worktree =
+        repo.find_tree(get_workdir_tree(None, snapshot_id.to_gix(), &gix_repo)?.to_git2())?;
+
+    file_limits::ignore_excluded_files(ctx, repo)?;
+    let mut diff_opts = git2::DiffOptions::new();
+    diff_opts
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .ignore_submodules(true)
+        .skip_binary_check(true);
+    let diff = repo.diff_tree_to_workdir(Some(&snapshot_worktree), Some(&mut diff_opts))?;
+
+    let index = repo.index()?;
+    let head_tree = repo.head()?.peel_to_tree()?;
+    let is_tracked = |path: &std::path::Path| {
+        index.get_path(path, 0).is_some() || head_tree.get_path(path).is_ok()
+    };
+
+    let mut changes = FileChanges::default();
+    for delta in diff.deltas() {
+        changes.touched += 1;
+        match delta.status() {
+            git2::Delta::Untracked => changes.untracked += 1,
+            git2::Delta::Deleted if delta.old_file().path().is_some_and(is_tracked) => {
+                changes.deleted += 1
+            }
+            _ => {}
+        }
+    }
+    Ok(changes)
+}
@@ -0,0 +1,253 @@
+use std::{
+    collections::HashSet,
+    io::Write,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{bail, Context, Result};
+use gitbutler_project::{
+    access::{WorktreeReadPermission, WorktreeWritePermission},
+    Project,
+};
+use gitbutler_stack::VirtualBranchesHandle;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    reflog::{set_reference_to_oplog, ReflogCommits},
+    retention::{rewrite_snapshot, snapshot_chain},
+    state::OplogHandle,
+};
+
+/// The first line of a git bundle in version 2, which is what we write.
+const BUNDLE_SIGNATURE: &str = "# v2 git bundle";
+/// The name of the reference in the bundle that points to the most recent exported snapshot.
+const BUNDLE_REF_NAME: &str = "refs/gitbutler/oplog";
+/// The current version of the [`BundleManifest`] format.
+const MANIFEST_VERSION: u32 = 1;
+
+/// Information about an exported oplog bundle, stored next to it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleManifest {
+    /// The version of the manifest format.
+    pub version: u32,
+    /// The most recent snapshot in the bundle.
+    #[serde(with = "gitbutler_serde::oid")]
+    pub head: git2::Oid,
+    /// The oplog head in the repository the bundle was exported from.
+    /// It differs from `head` if the exported history had to be cut off.
+    #[serde(with = "gitbutler_serde::oid")]
+    pub source_head: git2::Oid,
+    /// The amount of snapshots in the bundle.
+    pub snapshot_count: usize,
+    /// If `true`, there are older snapshots in the source repository that are not part of the bundle.
+    pub truncated: bool,
+    /// The time of the export in seconds since epoch.
+    pub exported_at: u64,
+}
+
+/// The result of [`OplogExt::import_oplog()`](crate::OplogExt::import_oplog()).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportOutcome {
+    /// The new oplog head, which is the most recent imported snapshot, or the previous head if all snapshots
+    /// were already known.
+    pub head: git2::Oid,
+    /// The amount of snapshots that were added to the oplog.
+    pub imported: usize,
+}
+
+/// Return the path of the manifest that belongs to the bundle at `bundle_path`.
+pub fn manifest_path(bundle_path: &Path) -> PathBuf {
+    let mut path = bundle_path.as_os_str().to_owned();
+    path.push(".manifest.toml");
+    path.into()
+}
+
+pub(crate) fn export_oplog(
+    ctx: &Project,
+    bundle_path: &Path,
+    depth: Option<usize>,
+    _shared_access: &WorktreeReadPermission,
+) -> Result<BundleManifest> {
+    let repo = git2::Repository::open(ctx.path.as_path())?;
+    let source_head = OplogHandle::new(&ctx.gb_dir())
+        .oplog_head()?
+        .context("There are no snapshots to export")?;
+    let snapshots = snapshot_chain(&repo, source_head, depth.map(|depth| depth.max(1)))?;
+    let truncated = snapshots
+        .last()
+        .is_some_and(|oldest| oldest.parent_count() != 0);
+
+    // A cut-off history is rewritten to start at the oldest exported snapshot so the bundle is self-contained.
+    let mut exported = Vec::with_capacity(snapshots.len());
+    if truncated {
+        let mut parent: Option<git2::Commit<'_>> = None;
+        for snapshot in snapshots.iter().rev() {
+            let new_snapshot_id = rewrite_snapshot(&repo, snapshot, parent.as_ref())?;
+            exported.push(new_snapshot_id);
+            parent = Some(repo.find_commit(new_snapshot_id)?);
+        }
+    } else {
+        exported.extend(snapshots.iter().rev().map(|snapshot| snapshot.id()));
+    }
+    let head = *exported
+        .last()
+        .context("BUG: there is at least one snapshot")?;
+
+    // Snapshot trees only contain trees and blobs, so each snapshot is complete with its tree.
+    let mut pack_builder = repo.packbuilder()?;
+    for snapshot_id in &exported {
+        pack_builder.insert_commit(*snapshot_id)?;
+    }
+    let mut pack = git2::Buf::new();
+    pack_builder.write_buf(&mut pack)?;
+
+    let mut bundle = format!("{BUNDLE_SIGNATURE}\n{head} {BUNDLE_REF_NAME}\n\n").into_bytes();
+    bundle.extend_from_slice(&pack);
+    std::fs::write(bundle_path, bundle)
+        .with_context(|| format!("Could not write bundle to {}", bundle_path.display()))?;
+
+    let manifest = BundleManifest {
+        version: MANIFEST_VERSION,
+        head,
+        source_head,
+        snapshot_count: exported.len(),
+        truncated,
+        exported_at: SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs(),
+    };
+    gitbutler_fs::write(manifest_path(bundle_path), toml::to_string(&manifest)?)?;
+    Ok(manifest)
+}
+
+pub(crate) fn import_oplog(
+    ctx: &Project,
+    bundle_path: &Path,
+    _exclusive_access: &mut WorktreeWritePermission,
+) -> Result<ImportOutcome> {
+    let manifest_path = manifest_path(bundle_path);
+    let manifest: BundleManifest = toml::from_str(
+        &std::fs::read_to_string(&manifest_path)
+            .with_context(|| format!("Could not read manifest at {}", manifest_path.display()))?,
+    )?;
+    if manifest.version > MANIFEST_VERSION {
+        bail!(
+            "The bundle was created with manifest version {}, but only version {MANIFEST_VERSION} is supported",
+            manifest.version
+        );
+    }
+    VirtualBranchesHandle::new(ctx.gb_dir())
+        .get_default_target()
+        .context("A target branch must be set before an oplog bundle can be imported")?;
+
+    let bundle = std::fs::read(bundle_path)
+        .with_context(|| format!("Could not read bundle at {}", bundle_path.display()))?;
+    let (bundle_head, pack) = parse_bundle(&bundle)?;
+    if bundle_head != manifest.head {
+        bail!(
+            "The bundle points to {bundle_head}, but its manifest expects {}",
+            manifest.head
+        );
+    }
+
+    let repo = git2::Repository::open(ctx.path.as_path())?;
+    let odb = repo.odb()?;
+    let mut pack_writer = odb.packwriter()?;
+    pack_writer.write_all(pack)?;
+    pack_writer.commit()?;
+
+    let snapshots = snapshot_chain(&repo, bundle_head, None)?;
+    if snapshots.len() != manifest.snapshot_count
+        || snapshots
+            .last()
+            .is_some_and(|oldest| oldest.parent_count() != 0)
+    {
+        bail!(
+            "Expected {} snapshots in the bundle, but found a different history",
+            manifest.snapshot_count
+        );
+    }
+    for snapshot in &snapshots {
+        if snapshot.tree()?.get_name("virtual_branches.toml").is_none() {
+            bail!("{} in the bundle is not an oplog snapshot", snapshot.id());
+        }
+    }
+
+    // Graft the imported snapshots on top of the local oplog so nothing is lost.
+    // Snapshots that are already part of it, like when importing the same bundle twice, are skipped.
+    let oplog_state = OplogHandle::new(&ctx.gb_dir());
+    let (head, imported) = match oplog_state.oplog_head()? {
+        None => (bundle_head, snapshots.len()),
+        Some(local_head) => {
+            let known_snapshots = snapshot_chain(&repo, local_head, None)?
+                .iter()
+                .map(SnapshotKey::new)
+                .collect::<HashSet<_>>();
+            let mut parent = repo.find_commit(local_head)?;
+            let mut imported = 0;
+            for snapshot in snapshots.iter().rev() {
+                if known_snapshots.contains(&SnapshotKey::new(snapshot)) {
+                    continue;
+                }
+                let new_snapshot_id = rewrite_snapshot(&repo, snapshot, Some(&parent))?;
+                parent = repo.find_commit(new_snapshot_id)?;
+                imported += 1;
+            }
+            (parent.id(), imported)
+        }
+    };
+    if imported != 0 {
+        oplog_state.set_oplog_head(head)?;
+        set_reference_to_oplog(&ctx.path, ReflogCommits::new(ctx)?)?;
+    }
+
+    Ok(ImportOutcome { head, imported })
+}
+
+/// What makes a snapshot the same as another one, even if it was rewritten onto a different parent.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct SnapshotKey {
+    tree: git2::Oid,
+    /// The message, which contains the trailers with the details of the snapshot.
+    message: Vec<u8>,
+    time: i64,
+}
+
+impl SnapshotKey {
+    fn new(snapshot: &git2::Commit<'_>) -> Self {
+        SnapshotKey {
+            tree: snapshot.tree_id(),
+            message: snapshot.message_raw_bytes().to_owned(),
+            time: snapshot.author().when().seconds(),
+        }
+    }
+}
+
+/// Parse the git bundle in `bundle` and return the id its only reference points to, along with the pack data.
+fn parse_bundle(bundle: &[u8]) -> Result<(git2::Oid, &[u8])> {
+    let header_end = bundle
+        .windows(2)
+        .position(|w| w == b"\n\n")
+        .context("The bundle header is incomplete")?;
+    let header = std::str::from_utf8(&bundle[..header_end]).context("Invalid bundle header")?;
+    let mut lines = header.lines();
+    if lines.next() != Some(BUNDLE_SIGNATURE) {
+        bail!("Not a git bundle in version 2");
+    }
+    let mut head = None;
+    for line in lines {
+        if line.starts_with('-') {
+            bail!("The bundle depends on commits that aren't part of it, which isn't supported");
+        }
+        let (id, ref_name) = line
+            .split_once(' ')
+            .with_context(|| format!("Invalid reference line in bundle: '{line}'"))?;
+        if ref_name == BUNDLE_REF_NAME {
+            head = Some(git2::Oid::from_str(id)?);
+        }
+    }
+    let head = head.with_context(|| format!("The bundle doesn't contain {BUNDLE_REF_NAME}"))?;
+    Ok((head, &bundle[header_end + 2..]))
+}
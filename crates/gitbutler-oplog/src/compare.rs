@@ -0,0 +1,252 @@
+use std::{collections::HashMap, str::from_utf8};
+
+use anyhow::{Context, Result};
+use gitbutler_oxidize::OidExt;
+use gitbutler_project::Project;
+use gitbutler_stack::{Stack, StackId, VirtualBranchesState};
+use serde::{Serialize, Serializer};
+
+use crate::oplog::get_workdir_tree;
+
+/// What changed between two snapshots, as returned by [`OplogExt::compare_snapshots()`](crate::OplogExt::compare_snapshots()).
+///
+/// Everything is described as the change needed to get from the `previous` snapshot to the `current` one.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotComparison {
+    /// The changes to the worktree, sorted by path.
+    #[serde(serialize_with = "serialize_tree_changes")]
+    pub worktree_changes: Vec<but_core::TreeChange>,
+    /// Stacks that only exist in the current snapshot, in stack order.
+    pub stacks_added: Vec<StackSummary>,
+    /// Stacks that only exist in the previous snapshot, in stack order.
+    pub stacks_removed: Vec<StackSummary>,
+    /// Stacks that exist in both snapshots, but were renamed, applied or unapplied, or had their heads changed,
+    /// in stack order of the current snapshot.
+    pub stacks_changed: Vec<StackChanges>,
+    /// Set if the target branch or the commit it points to changed.
+    pub target: Option<TargetChange>,
+}
+
+impl SnapshotComparison {
+    /// Return `true` if the two snapshots are equivalent.
+    pub fn is_empty(&self) -> bool {
+        self.worktree_changes.is_empty()
+            && self.stacks_added.is_empty()
+            && self.stacks_removed.is_empty()
+            && self.stacks_changed.is_empty()
+            && self.target.is_none()
+    }
+}
+
+/// A stack that was added or removed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StackSummary {
+    /// The id of the stack.
+    pub id: StackId,
+    /// The name of the stack.
+    pub name: String,
+    /// The names of the branches in the stack, from the bottom (closest to the target) to the top.
+    pub heads: Vec<String>,
+}
+
+/// The changes to a stack that is present in both snapshots.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StackChanges {
+    /// The id of the stack.
+    pub id: StackId,
+    /// The name of the stack in the current snapshot.
+    pub name: String,
+    /// The name of the stack in the previous snapshot, if it was renamed.
+    pub renamed_from: Option<String>,
+    /// If the stack was applied or unapplied, this is `true` or `false` respectively.
+    pub in_workspace: Option<bool>,
+    /// The changes to the branches of the stack.
+    pub heads: Vec<HeadChange>,
+}
+
+/// A change to a branch in a stack, identified by its name.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type", content = "subject")]
+pub enum HeadChange {
+    /// The branch was added to the stack.
+    Added {
+        name: String,
+        #[serde(with = "gitbutler_serde::oid_opt")]
+        head: Option<git2::Oid>,
+    },
+    /// The branch was removed from the stack.
+    Removed {
+        name: String,
+        #[serde(with = "gitbutler_serde::oid_opt")]
+        head: Option<git2::Oid>,
+    },
+    /// The branch points to a different commit now.
+    Moved {
+        name: String,
+        #[serde(with = "gitbutler_serde::oid_opt")]
+        previous: Option<git2::Oid>,
+        #[serde(with = "gitbutler_serde::oid_opt")]
+        current: Option<git2::Oid>,
+    },
+}
+
+/// A change of the default target.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TargetChange {
+    /// The name of the target branch in the previous snapshot, like `refs/remotes/origin/main`,
+    /// if it was set.
+    pub previous_branch: Option<String>,
+    /// The commit the target pointed to in the previous snapshot, if it was set.
+    #[serde(with = "gitbutler_serde::oid_opt")]
+    pub previous_sha: Option<git2::Oid>,
+    /// The name of the target branch in the current snapshot, if it is set.
+    pub branch: Option<String>,
+    /// The commit the target points to in the current snapshot, if it is set.
+    #[serde(with = "gitbutler_serde::oid_opt")]
+    pub sha: Option<git2::Oid>,
+}
+
+fn serialize_tree_changes<S>(changes: &[but_core::TreeChange], s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    s.collect_seq(changes.iter().cloned().map(but_core::ui::TreeChange::from))
+}
+
+pub(crate) fn compare_snapshots(
+    ctx: &Project,
+    previous: git2::Oid,
+    current: git2::Oid,
+) -> Result<SnapshotComparison> {
+    let repo = gitbutler_command_context::gix_repo_for_merging(ctx.path.as_path())?;
+
+    let previous_worktree = get_workdir_tree(None, previous.to_gix(), &repo)?;
+    let current_worktree = get_workdir_tree(None, current.to_gix(), &repo)?;
+    let worktree_changes =
+        but_core::diff::tree_changes(&repo, Some(previous_worktree), current_worktree)?;
+
+    let previous_state = virtual_branches_state(&repo, previous)?;
+    let current_state = virtual_branches_state(&repo, current)?;
+
+    let mut stacks_added = Vec::new();
+    let mut stacks_changed = Vec::new();
+    for stack in stacks_in_order(&current_state.branches) {
+        match previous_state.branches.get(&stack.id) {
+            None => stacks_added.push(summarize(stack)),
+            Some(previous_stack) => {
+                if let Some(changes) = stack_changes(previous_stack, stack) {
+                    stacks_changed.push(changes);
+                }
+            }
+        }
+    }
+    let stacks_removed = stacks_in_order(&previous_state.branches)
+        .filter(|stack| !current_state.branches.contains_key(&stack.id))
+        .map(summarize)
+        .collect();
+
+    let target = {
+        let branch_and_sha = |state: &VirtualBranchesState| {
+            state
+                .default_target
+                .as_ref()
+                .map(|target| (target.branch.to_string(), target.sha))
+                .unzip()
+        };
+        let (previous_branch, previous_sha) = branch_and_sha(&previous_state);
+        let (branch, sha) = branch_and_sha(&current_state);
+        (previous_branch != branch || previous_sha != sha).then_some(TargetChange {
+            previous_branch,
+            previous_sha,
+            branch,
+            sha,
+        })
+    };
+
+    Ok(SnapshotComparison {
+        worktree_changes,
+        stacks_added,
+        stacks_removed,
+        stacks_changed,
+        target,
+    })
+}
+
+/// Decode the `virtual_branches.toml` blob stored in the snapshot with `snapshot_id`.
+fn virtual_branches_state(
+    repo: &gix::Repository,
+    snapshot_id: git2::Oid,
+) -> Result<VirtualBranchesState> {
+    let snapshot_commit = repo.find_commit(snapshot_id.to_gix())?;
+    let vb_toml_entry = snapshot_commit
+        .tree()?
+        .lookup_entry_by_path("virtual_branches.toml")?
+        .with_context(|| format!("{snapshot_id} doesn't seem to be an oplog snapshot"))?;
+    let vb_toml_blob = repo.find_blob(vb_toml_entry.id())?;
+    Ok(toml::from_str(from_utf8(&vb_toml_blob.data)?)?)
+}
+
+fn stacks_in_order(stacks: &HashMap<StackId, Stack>) -> impl Iterator<Item = &Stack> {
+    let mut stacks: Vec<_> = stacks.values().collect();
+    stacks.sort_by(|a, b| a.order.cmp(&b.order).then_with(|| a.name.cmp(&b.name)));
+    stacks.into_iter()
+}
+
+fn summarize(stack: &Stack) -> StackSummary {
+    StackSummary {
+        id: stack.id,
+        name: stack.name.clone(),
+        heads: stack.heads.iter().map(|head| head.name.clone()).collect(),
+    }
+}
+
+fn stack_changes(previous: &Stack, current: &Stack) -> Option<StackChanges> {
+    let mut heads = Vec::new();
+    for head in &current.heads {
+        let current_head = head.persisted_head_oid();
+        match previous.heads.iter().find(|h| h.name == head.name) {
+            None => heads.push(HeadChange::Added {
+                name: head.name.clone(),
+                head: current_head,
+            }),
+            Some(previous_head) => {
+                let previous_head = previous_head.persisted_head_oid();
+                if previous_head != current_head {
+                    heads.push(HeadChange::Moved {
+                        name: head.name.clone(),
+                        previous: previous_head,
+                        current: current_head,
+                    });
+                }
+            }
+        }
+    }
+    heads.extend(
+        previous
+            .heads
+            .iter()
+            .filter(|head| !current.heads.iter().any(|h| h.name == head.name))
+            .map(|head| HeadChange::Removed {
+                name: head.name.clone(),
+                head: head.persisted_head_oid(),
+            }),
+    );
+
+    let renamed_from = (previous.name != current.name).then(|| previous.name.clone());
+    let in_workspace =
+        (previous.in_workspace != current.in_workspace).then_some(current.in_workspace);
+    if renamed_from.is_none() && in_workspace.is_none() && heads.is_empty() {
+        return None;
+    }
+    Some(StackChanges {
+        id: current.id,
+        name: current.name.clone(),
+        renamed_from,
+        in_workspace,
+        heads,
+    })
+}
@@ -64,8 +64,29 @@ impl SnapshotDetails {
         self.trailers = trailers;
         self
     }
+
+    /// Return `true` if the snapshot is pinned, which protects it from being pruned.
+    pub fn is_pinned(&self) -> bool {
+        self.trailers
+            .iter()
+            .any(|t| t.key == PINNED_TRAILER_KEY && t.value == "true")
+    }
+
+    /// Add or remove the trailer that marks the snapshot as pinned.
+    pub fn set_pinned(&mut self, pinned: bool) {
+        self.trailers.retain(|t| t.key != PINNED_TRAILER_KEY);
+        if pinned {
+            self.trailers.push(Trailer {
+                key: PINNED_TRAILER_KEY.into(),
+                value: "true".into(),
+            });
+        }
+    }
 }
 
+/// The key of the trailer that marks a snapshot as pinned if its value is `true`.
+pub const PINNED_TRAILER_KEY: &str = "pinned";
+
 impl FromStr for SnapshotDetails {
     type Err = anyhow::Error;
 
@@ -163,6 +184,8 @@ pub enum OperationKind {
     UpdateDependentBranchName,
     UpdateDependentBranchDescription,
     UpdateDependentBranchPrNumber,
+    /// A checkpoint that was created on request of the user, with a title and body of their choice.
+    Checkpoint,
     #[default]
     Unknown,
 }
@@ -0,0 +1,234 @@
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+use gitbutler_oxidize::{ObjectIdExt, OidExt};
+use gitbutler_project::{Project, SnapshotFileLimits};
+use gix::bstr::{BStr, ByteSlice};
+use serde::{Deserialize, Serialize};
+
+/// The name of the blob in the snapshot tree that lists the untracked files left out of the snapshot.
+pub const EXCLUDED_FILES_ENTRY: &str = "excluded_files.toml";
+
+/// Why an untracked file isn't part of a snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExclusionReason {
+    /// The file is larger than [`Project::snapshot_untracked_limit_bytes()`].
+    TooLarge,
+    /// The file matches a pattern in [`SnapshotFileLimits::never_include`].
+    Denied,
+}
+
+/// An untracked file that was left out of a snapshot, as recorded in the snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExcludedFile {
+    /// The path of the file relative to the worktree.
+    pub path: PathBuf,
+    /// The size of the file in bytes.
+    pub size: u64,
+    /// The time the file was last modified in seconds since epoch, if known.
+    pub modified_at: Option<u64>,
+    /// The id the file would have as blob, which allows to tell if the file is still the same.
+    #[serde(with = "gitbutler_serde::oid")]
+    pub hash: git2::Oid,
+    /// Why the file was left out.
+    pub reason: ExclusionReason,
+}
+
+impl ExcludedFile {
+    /// Return `true` if the file is still present in `worktree_dir` with the same content.
+    pub fn is_present_in(&self, worktree_dir: &Path) -> bool {
+        git2::Oid::hash_file(git2::ObjectType::Blob, worktree_dir.join(&self.path))
+            .is_ok_and(|id| id == self.hash)
+    }
+}
+
+/// The content of [`EXCLUDED_FILES_ENTRY`].
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ExcludedFiles {
+    #[serde(default)]
+    files: Vec<ExcludedFile>,
+}
+
+/// Adjust the `worktree` tree written for a snapshot of `ctx` to match its [`SnapshotFileLimits`], and return
+/// the new tree along with all untracked files that aren't part of it.
+pub(crate) fn apply_file_limits(
+    ctx: &Project,
+    worktree: git2::Oid,
+) -> Result<(git2::Oid, Vec<ExcludedFile>)> {
+    let limit = ctx.snapshot_untracked_limit_bytes();
+    let default_limits = SnapshotFileLimits::default();
+    let limits = ctx.snapshot_file_limits.as_ref().unwrap_or(&default_limits);
+    if limit == 0 && limits.never_include.is_empty() {
+        return Ok((worktree, Vec::new()));
+    }
+
+    let gix_repo = gitbutler_command_context::gix_repo_for_merging(ctx.path.as_path())?;
+    let (mut pipeline, index) = gix_repo.filter_pipeline(None)?;
+    let mut editor = gix_repo.edit_tree(worktree.to_gix())?;
+    let mut excluded = Vec::new();
+    for (rela_path, path) in untracked_files(&gix_repo)? {
+        let Ok(md) = path.metadata() else {
+            continue;
+        };
+        if !md.is_file() {
+            continue;
+        }
+        let reason = if matches_any(&limits.never_include, rela_path.as_ref()) {
+            ExclusionReason::Denied
+        } else if limit != 0 && md.len() > limit {
+            if matches_any(&limits.always_include, rela_path.as_ref()) {
+                // Larger files were skipped when creating the tree, so add them now just like all other files,
+                // through the worktree filters and with their executable bit.
+                if let Some((blob_id, kind, _md)) =
+                    pipeline.worktree_file_to_object(rela_path.as_bstr(), &index)?
+                {
+                    editor.upsert(rela_path.as_bstr(), kind, blob_id)?;
+                }
+                continue;
+            }
+            ExclusionReason::TooLarge
+        } else {
+            continue;
+        };
+
+        editor.remove(rela_path.as_bstr())?;
+        excluded.push(ExcludedFile {
+            path: gix::path::from_bstr(rela_path.as_bstr()).into_owned(),
+            size: md.len(),
+            modified_at: md
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+                .map(|time| time.as_secs()),
+            hash: git2::Oid::hash_file(git2::ObjectType::Blob, &path)?,
+            reason,
+        });
+    }
+    excluded.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok((editor.write()?.to_git2(), excluded))
+}
+
+/// Write the list of `excluded` files as blob to be stored at [`EXCLUDED_FILES_ENTRY`] in the snapshot tree.
+pub(crate) fn write_excluded_files(
+    repo: &git2::Repository,
+    excluded: Vec<ExcludedFile>,
+) -> Result<git2::Oid> {
+    let content = toml::to_string(&ExcludedFiles { files: excluded })?;
+    Ok(repo.blob(content.as_bytes())?)
+}
+
+/// Read the untracked files that were left out of the snapshot with `snapshot_tree`,
+/// which is empty for snapshots that were created before this was recorded.
+pub(crate) fn excluded_files(
+    repo: &git2::Repository,
+    snapshot_tree: &git2::Tree<'_>,
+) -> Result<Vec<ExcludedFile>> {
+    let Some(entry) = snapshot_tree.get_name(EXCLUDED_FILES_ENTRY) else {
+        return Ok(Vec::new());
+    };
+    let blob = repo.find_blob(entry.id())?;
+    let excluded: ExcludedFiles = toml::from_str(
+        std::str::from_utf8(blob.content()).context("excluded files must be valid UTF-8")?,
+    )?;
+    Ok(excluded.files)
+}
+
+/// Make sure that all untracked files which `ctx` wouldn't put into snapshots are ignored by `repo`,
+/// so they don't show up in diffs and are left alone when checking out a snapshot.
+pub(crate) fn ignore_excluded_files(ctx: &Project, repo: &git2::Repository) -> Result<()> {
+    let limit = ctx.snapshot_untracked_limit_bytes();
+    let default_limits = SnapshotFileLimits::default();
+    let limits = ctx.snapshot_file_limits.as_ref().unwrap_or(&default_limits);
+    if limit == 0 && limits.never_include.is_empty() {
+        return Ok(());
+    }
+
+    let gix_repo = gitbutler_command_context::gix_repo_for_merging(ctx.path.as_path())?;
+    for (rela_path, path) in untracked_files(&gix_repo)? {
+        let is_excluded = matches_any(&limits.never_include, rela_path.as_ref())
+            || (limit != 0
+                && path
+                    .metadata()
+                    .is_ok_and(|md| md.is_file() && md.len() > limit)
+                && !matches_any(&limits.always_include, rela_path.as_ref()));
+        if !is_excluded {
+            continue;
+        }
+        // In-memory, libgit2 internal ignore rule, anchored to the worktree root.
+        if let Ok(rela_path) = rela_path.to_str() {
+            repo.add_ignore_rule(&format!("/{rela_path}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Return the worktree-relative path and the full path of all untracked files.
+fn untracked_files(repo: &gix::Repository) -> Result<Vec<(gix::bstr::BString, PathBuf)>> {
+    let worktree_dir = repo
+        .workdir()
+        .context("All repos are expected to have a worktree")?;
+    Ok(repo
+        .dirwalk_iter(
+            repo.index_or_empty()?,
+            None::<gix::bstr::BString>,
+            Default::default(),
+            repo.dirwalk_options()?
+                .emit_ignored(None)
+                .emit_pruned(false)
+                .emit_untracked(gix::dir::walk::EmissionMode::Matching),
+        )?
+        .filter_map(Result::ok)
+        .filter(|item| item.entry.status == gix::dir::entry::Status::Untracked)
+        .map(|item| {
+            let path = worktree_dir.join(gix::path::from_bstr(item.entry.rela_path.as_bstr()));
+            (item.entry.rela_path, path)
+        })
+        .collect())
+}
+
+/// Return `true` if `rela_path` matches any of `patterns`.
+/// Patterns without a slash match the file name, all others match the whole path.
+fn matches_any(patterns: &[String], rela_path: &BStr) -> bool {
+    use gix::glob::wildmatch::Mode;
+    let file_name = rela_path
+        .rfind_byte(b'/')
+        .map_or(rela_path, |pos| rela_path[pos + 1..].as_bstr());
+    patterns.iter().any(|pattern| {
+        let pattern = pattern.trim_start_matches('/');
+        if pattern.contains('/') {
+            gix::glob::wildmatch(pattern.into(), rela_path, Mode::NO_MATCH_SLASH_LITERAL)
+        } else {
+            gix::glob::wildmatch(pattern.into(), file_name, Mode::NO_MATCH_SLASH_LITERAL)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches_any;
+
+    #[test]
+    fn patterns_without_slash_match_file_names() {
+        let patterns = ["*.bin".to_string()];
+        assert!(matches_any(&patterns, "large.bin".into()));
+        assert!(matches_any(&patterns, "dir/sub/large.bin".into()));
+        assert!(!matches_any(&patterns, "large.txt".into()));
+    }
+
+    #[test]
+    fn patterns_with_slash_match_paths() {
+        let patterns = ["assets/*.png".to_string(), "/build/**".to_string()];
+        assert!(matches_any(&patterns, "assets/logo.png".into()));
+        assert!(
+            !matches_any(&patterns, "assets/icons/logo.png".into()),
+            "a single star doesn't match slashes"
+        );
+        assert!(matches_any(&patterns, "build/out/app".into()));
+        assert!(!matches_any(&patterns, "src/build/app".into()));
+    }
+}
@@ -1,7 +1,12 @@
+pub mod auto_snapshot;
+pub mod bundle;
+pub mod compare;
 pub mod entry;
+pub mod file_limits;
 mod oplog;
-pub use oplog::OplogExt;
+pub use oplog::{OplogExt, RestoreSelection};
 pub mod reflog;
+pub mod retention;
 mod snapshot;
 pub use snapshot::SnapshotExt;
 mod state;
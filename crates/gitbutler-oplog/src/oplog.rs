@@ -1,12 +1,20 @@
 use std::{
     collections::{hash_map::Entry, HashMap},
     fs,
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::{from_utf8, FromStr},
     time::Duration,
 };
 
-use crate::{entry::Version, reflog::ReflogCommits};
+use crate::{
+    auto_snapshot::{self, AutoSnapshotTrigger},
+    bundle::{self, BundleManifest, ImportOutcome},
+    compare::{self, SnapshotComparison},
+    entry::Version,
+    file_limits::{self, ExcludedFile},
+    reflog::ReflogCommits,
+    retention::{self, PruneOutcome},
+};
 
 use super::{
     entry::{OperationKind, Snapshot, SnapshotDetails, Trailer},
@@ -15,7 +23,6 @@ use super::{
 };
 use anyhow::{anyhow, bail, Context, Result};
 use git2::FileMode;
-use gitbutler_command_context::RepositoryExtLite;
 use gitbutler_diff::{hunks_by_filepath, FileDiff};
 use gitbutler_oxidize::ObjectIdExt as _;
 use gitbutler_oxidize::RepoExt;
@@ -24,16 +31,32 @@ use gitbutler_oxidize::{
 };
 use gitbutler_project::{
     access::{WorktreeReadPermission, WorktreeWritePermission},
-    Project, AUTO_TRACK_LIMIT_BYTES,
+    Project, SnapshotRetention,
 };
 use gitbutler_repo::RepositoryExt;
 use gitbutler_repo::SignaturePurpose;
-use gitbutler_stack::{Stack, VirtualBranchesHandle, VirtualBranchesState};
+use gitbutler_stack::{Stack, StackId, VirtualBranchesHandle, VirtualBranchesState};
 use gix::object::tree::diff::Change;
 use gix::prelude::ObjectIdExt;
 use gix::{bstr::ByteSlice, ObjectId};
 use tracing::instrument;
 
+/// Determines which parts of a snapshot to restore with [`OplogExt::restore_snapshot_selection()`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RestoreSelection {
+    /// Worktree-relative paths to files or directories to restore from the snapshot.
+    pub paths: Vec<PathBuf>,
+    /// The ids of the stacks to restore from the snapshot.
+    pub stacks: Vec<StackId>,
+}
+
+impl RestoreSelection {
+    /// Return `true` if nothing is selected.
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty() && self.stacks.is_empty()
+    }
+}
+
 /// The Oplog allows for crating snapshots of the current state of the project as well as restoring to a previous snapshot.
 /// Snapshots include the state of the working directory as well as all additional GitButler state (e.g. virtual branches, conflict state).
 /// The data is stored as git trees in the following shape:
@@ -89,6 +112,30 @@ pub trait OplogExt {
         perm: &mut WorktreeWritePermission,
     ) -> Result<git2::Oid>;
 
+    /// Creates a snapshot on request of the user with operation [`OperationKind::Checkpoint`], using `title` and `body`
+    /// to describe it. If `pinned` is `true`, it won't be removed when the oplog is pruned.
+    ///
+    /// Returns the sha of the created snapshot commit.
+    fn create_checkpoint(
+        &self,
+        title: String,
+        body: Option<String>,
+        pinned: bool,
+        perm: &mut WorktreeWritePermission,
+    ) -> Result<git2::Oid>;
+
+    /// Pins or unpins the snapshot with `snapshot_commit_id` depending on `pinned`.
+    /// Pinned snapshots are never removed when the oplog is pruned.
+    ///
+    /// As the pin is stored as trailer of the snapshot, it and all more recent snapshots are rewritten.
+    /// Returns the new sha of the snapshot, which is unchanged if it already was in the desired state.
+    fn set_snapshot_pinned(
+        &self,
+        snapshot_commit_id: git2::Oid,
+        pinned: bool,
+        perm: &mut WorktreeWritePermission,
+    ) -> Result<git2::Oid>;
+
     /// Lists the snapshots that have been created for the given repository, up to the given limit,
     /// and with the most recent snapshot first, and at the end of the vec.
     ///
@@ -122,6 +169,26 @@ pub trait OplogExt {
         guard: &mut WorktreeWritePermission,
     ) -> Result<git2::Oid>;
 
+    /// Restores only the parts of the snapshot at `snapshot_commit_id` that are chosen by `selection`,
+    /// and leaves everything else as it is.
+    ///
+    /// This will restore the following:
+    ///  - The worktree files at or below each of `selection.paths`, as they were in the subtree `worktree` of the snapshot.
+    ///    Files that didn't exist at the time are removed. The index isn't changed.
+    ///  - The state of each stack in `selection.stacks` as it was in `virtual_branches.toml`, along with the references of
+    ///    its branches. Commits of the stack that don't exist anymore are recreated from `virtual_branches/<id>/commits`.
+    ///    A stack keeps its current state of being applied to the workspace or not, so the worktree isn't affected.
+    ///    Stacks that don't exist anymore are restored as unapplied stacks.
+    ///
+    /// Note that the workspace commit isn't updated, which is the responsibility of the caller if applied stacks were restored.
+    /// Returns the sha of the created restore snapshot commit, with trailers describing what was restored.
+    fn restore_snapshot_selection(
+        &self,
+        snapshot_commit_id: git2::Oid,
+        selection: &RestoreSelection,
+        guard: &mut WorktreeWritePermission,
+    ) -> Result<git2::Oid>;
+
     /// Determines if a new snapshot should be created due to file changes being created since the last snapshot.
     /// The needs for the automatic snapshotting are:
     ///  - It needs to facilitate backup of work in progress code
@@ -135,16 +202,83 @@ pub trait OplogExt {
     ///  - Head is pointing to the workspace branch.
     ///  - If it's been more than 5 minutes since the last snapshot,
     ///    check the sum of added and removed lines since the last snapshot, otherwise return `false`.
-    ///      * If the sum of added and removed lines is greater than a configured threshold, return `true`, otherwise return `false`.
+    ///      * If the sum of added and removed lines is greater than a configured threshold, return `true`.
+    ///      * Otherwise, return `true` if any trigger of [`Project::auto_snapshot_policy`] fires.
+    ///
+    /// Use [`auto_snapshot_trigger()`](Self::auto_snapshot_trigger()) to learn why a snapshot should be created.
     fn should_auto_snapshot(&self, check_if_last_snapshot_older_than: Duration) -> Result<bool>;
 
+    /// Like [`should_auto_snapshot()`](Self::should_auto_snapshot()), but returns the trigger that fired first,
+    /// or `None` if no snapshot should be created.
+    ///
+    /// Deletions are checked before all other file-based triggers so deleted files are never lost between snapshots.
+    fn auto_snapshot_trigger(
+        &self,
+        check_if_last_snapshot_older_than: Duration,
+    ) -> Result<Option<AutoSnapshotTrigger>>;
+
     /// Returns the diff of the snapshot and it's parent. It only includes the workdir changes.
     ///
     /// This is useful to show what has changed in this particular snapshot
     fn snapshot_diff(&self, sha: git2::Oid) -> Result<HashMap<PathBuf, FileDiff>>;
 
+    /// Compares the snapshot `previous` with the snapshot `current`, which can be any two snapshots of the oplog.
+    ///
+    /// Besides the changes to the worktree, this includes the changes to the stacks, their branches and the target,
+    /// as stored in the `virtual_branches.toml` of each snapshot. This describes what a sequence of operations
+    /// did to the workspace as a whole.
+    fn compare_snapshots(
+        &self,
+        previous: git2::Oid,
+        current: git2::Oid,
+    ) -> Result<SnapshotComparison>;
+
+    /// Returns the untracked files that were left out of the snapshot at `snapshot_commit_id` due to
+    /// [`Project::snapshot_file_limits`], sorted by path.
+    /// Use [`ExcludedFile::is_present_in()`] to learn if restoring the snapshot would lose them.
+    fn excluded_files(&self, snapshot_commit_id: git2::Oid) -> Result<Vec<ExcludedFile>>;
+
     /// Gets the sha of the last snapshot commit if present.
     fn oplog_head(&self) -> Result<Option<git2::Oid>>;
+
+    /// Returns `true` if `sha` is a snapshot of the oplog, i.e. the oplog head or one of its ancestors.
+    fn is_snapshot(&self, sha: git2::Oid) -> Result<bool>;
+
+    /// Removes all snapshots from the oplog that are not kept according to `retention`, so their objects
+    /// can eventually be garbage-collected by Git. The most recent snapshot and pinned snapshots are always kept.
+    ///
+    /// As snapshots are a chain of commits, all kept snapshots that are more recent than the oldest removed one
+    /// are rewritten and thus change their id. The oplog head and the reflog that protects it are updated accordingly.
+    ///
+    /// Note that this also happens automatically when creating snapshots, at most once a day,
+    /// if [`Project::snapshot_retention`] is set.
+    fn prune_snapshots(
+        &self,
+        retention: &SnapshotRetention,
+        perm: &mut WorktreeWritePermission,
+    ) -> Result<PruneOutcome>;
+
+    /// Writes the most recent `depth` snapshots, or all of them if `None`, into a git bundle at `bundle_path`,
+    /// along with a [manifest](bundle::manifest_path()) that describes it.
+    ///
+    /// If older snapshots are left out, the exported snapshots are rewritten so the bundle is self-contained,
+    /// which changes their ids. The oplog itself remains unchanged.
+    fn export_oplog(
+        &self,
+        bundle_path: &Path,
+        depth: Option<usize>,
+        perm: &WorktreeReadPermission,
+    ) -> Result<BundleManifest>;
+
+    /// Reads the snapshots from the bundle at `bundle_path` as written by [`export_oplog()`](Self::export_oplog()),
+    /// and makes them the most recent snapshots of the oplog so they can be listed and restored.
+    ///
+    /// Existing snapshots are kept, which is why the imported snapshots are rewritten on top of them.
+    fn import_oplog(
+        &self,
+        bundle_path: &Path,
+        perm: &mut WorktreeWritePermission,
+    ) -> Result<ImportOutcome>;
 }
 
 impl OplogExt for Project {
@@ -171,6 +305,33 @@ impl OplogExt for Project {
         commit_snapshot(self, tree_id, details, perm)
     }
 
+    #[instrument(skip(self, perm), err(Debug))]
+    fn create_checkpoint(
+        &self,
+        title: String,
+        body: Option<String>,
+        pinned: bool,
+        perm: &mut WorktreeWritePermission,
+    ) -> Result<git2::Oid> {
+        let mut details = SnapshotDetails {
+            title,
+            body,
+            ..SnapshotDetails::new(OperationKind::Checkpoint)
+        };
+        details.set_pinned(pinned);
+        self.create_snapshot(details, perm)
+    }
+
+    #[instrument(skip(self, perm), err(Debug))]
+    fn set_snapshot_pinned(
+        &self,
+        snapshot_commit_id: git2::Oid,
+        pinned: bool,
+        perm: &mut WorktreeWritePermission,
+    ) -> Result<git2::Oid> {
+        retention::set_snapshot_pinned(self, snapshot_commit_id, pinned, perm)
+    }
+
     #[instrument(skip(self), err(Debug))]
     fn list_snapshots(
         &self,
@@ -305,18 +466,28 @@ impl OplogExt for Project {
         restore_snapshot(self, snapshot_commit_id, guard)
     }
 
+    fn restore_snapshot_selection(
+        &self,
+        snapshot_commit_id: git2::Oid,
+        selection: &RestoreSelection,
+        guard: &mut WorktreeWritePermission,
+    ) -> Result<git2::Oid> {
+        restore_snapshot_selection(self, snapshot_commit_id, selection, guard)
+    }
+
     #[instrument(level = tracing::Level::DEBUG, skip(self), err(Debug))]
     fn should_auto_snapshot(&self, check_if_last_snapshot_older_than: Duration) -> Result<bool> {
-        let last_snapshot_time = OplogHandle::new(&self.gb_dir()).modified_at()?;
-        if last_snapshot_time.elapsed()? <= check_if_last_snapshot_older_than {
-            return Ok(false);
-        }
+        Ok(self
+            .auto_snapshot_trigger(check_if_last_snapshot_older_than)?
+            .is_some())
+    }
 
-        let repo = git2::Repository::open(&self.path)?;
-        if repo.workspace_ref_from_head().is_err() {
-            return Ok(false);
-        }
-        Ok(lines_since_snapshot(self, &repo)? > self.snapshot_lines_threshold())
+    #[instrument(level = tracing::Level::DEBUG, skip(self), err(Debug))]
+    fn auto_snapshot_trigger(
+        &self,
+        check_if_last_snapshot_older_than: Duration,
+    ) -> Result<Option<AutoSnapshotTrigger>> {
+        auto_snapshot::auto_snapshot_trigger(self, check_if_last_snapshot_older_than)
     }
 
     fn snapshot_diff(&self, sha: git2::Oid) -> Result<HashMap<PathBuf, FileDiff>> {
@@ -331,7 +502,7 @@ impl OplogExt for Project {
         let old_wd_tree_id = tree_from_applied_vbranches(&gix_repo, commit.parent(0)?.id())?;
         let old_wd_tree = repo.find_tree(old_wd_tree_id)?;
 
-        repo.ignore_large_files_in_diffs(AUTO_TRACK_LIMIT_BYTES)?;
+        file_limits::ignore_excluded_files(self, &repo)?;
 
         let mut diff_opts = git2::DiffOptions::new();
         diff_opts
@@ -348,15 +519,65 @@ impl OplogExt for Project {
         Ok(hunks)
     }
 
+    fn compare_snapshots(
+        &self,
+        previous: git2::Oid,
+        current: git2::Oid,
+    ) -> Result<SnapshotComparison> {
+        compare::compare_snapshots(self, previous, current)
+    }
+
+    fn excluded_files(&self, snapshot_commit_id: git2::Oid) -> Result<Vec<ExcludedFile>> {
+        let repo = git2::Repository::open(self.path.as_path())?;
+        let snapshot_tree = repo.find_commit(snapshot_commit_id)?.tree()?;
+        file_limits::excluded_files(&repo, &snapshot_tree)
+    }
+
     /// Gets the sha of the last snapshot commit if present.
     fn oplog_head(&self) -> Result<Option<git2::Oid>> {
         let oplog_state = OplogHandle::new(&self.gb_dir());
         oplog_state.oplog_head()
     }
+
+    fn is_snapshot(&self, sha: git2::Oid) -> Result<bool> {
+        let Some(head) = self.oplog_head()? else {
+            return Ok(false);
+        };
+        let repo = git2::Repository::open(self.path.as_path())?;
+        Ok(head == sha || repo.graph_descendant_of(head, sha)?)
+    }
+
+    #[instrument(skip(self, perm), err(Debug))]
+    fn prune_snapshots(
+        &self,
+        retention: &SnapshotRetention,
+        perm: &mut WorktreeWritePermission,
+    ) -> Result<PruneOutcome> {
+        retention::prune_snapshots(self, retention, perm)
+    }
+
+    #[instrument(skip(self, perm), err(Debug))]
+    fn export_oplog(
+        &self,
+        bundle_path: &Path,
+        depth: Option<usize>,
+        perm: &WorktreeReadPermission,
+    ) -> Result<BundleManifest> {
+        bundle::export_oplog(self, bundle_path, depth, perm)
+    }
+
+    #[instrument(skip(self, perm), err(Debug))]
+    fn import_oplog(
+        &self,
+        bundle_path: &Path,
+        perm: &mut WorktreeWritePermission,
+    ) -> Result<ImportOutcome> {
+        bundle::import_oplog(self, bundle_path, perm)
+    }
 }
 
 /// Get a tree of the working dir (applied branches merged)
-fn get_workdir_tree(
+pub(crate) fn get_workdir_tree(
     wd_trees_cache: Option<&mut HashMap<gix::ObjectId, gix::ObjectId>>,
     commit_id: impl Into<gix::ObjectId>,
     repo: &gix::Repository,
@@ -400,7 +621,24 @@ fn get_workdir_tree(
     }
 }
 
-fn prepare_snapshot(ctx: &Project, _shared_access: &WorktreeReadPermission) -> Result<git2::Oid> {
+fn prepare_snapshot(ctx: &Project, shared_access: &WorktreeReadPermission) -> Result<git2::Oid> {
+    prepare_snapshot_of_workspace(ctx, shared_access, false)
+}
+
+/// Like [`prepare_snapshot()`], but if HEAD was already moved from `gitbutler/workspace` to another branch,
+/// the worktree is recorded as it was before the switch, with the workspace still checked out.
+pub(crate) fn prepare_snapshot_before_branch_switch(
+    ctx: &Project,
+    shared_access: &WorktreeReadPermission,
+) -> Result<git2::Oid> {
+    prepare_snapshot_of_workspace(ctx, shared_access, true)
+}
+
+fn prepare_snapshot_of_workspace(
+    ctx: &Project,
+    _shared_access: &WorktreeReadPermission,
+    head_may_have_left_workspace: bool,
+) -> Result<git2::Oid> {
     let worktree_dir = ctx.path.as_path();
     let repo = git2::Repository::open(worktree_dir)?;
 
@@ -483,11 +721,33 @@ fn prepare_snapshot(ctx: &Project, _shared_access: &WorktreeReadPermission) -> R
     }
 
     // Add the worktree tree
-    let worktree = repo.create_wd_tree(AUTO_TRACK_LIMIT_BYTES)?;
-    tree_builder.insert("worktree", worktree.id(), FileMode::Tree.into())?;
+    let mut worktree_id = repo
+        .create_wd_tree(ctx.snapshot_untracked_limit_bytes())?
+        .id();
+    let mut head = repo.head()?;
+    if head_may_have_left_workspace && head.name() != Some("refs/heads/gitbutler/workspace") {
+        let workspace = repo.find_reference("refs/heads/gitbutler/workspace")?;
+        worktree_id = move_worktree_changes(
+            &repo,
+            &head.peel_to_tree()?,
+            worktree_id,
+            &workspace.peel_to_tree()?,
+        )?;
+        head = workspace;
+    }
+    let (worktree_id, excluded_files) = file_limits::apply_file_limits(ctx, worktree_id)?;
+    tree_builder.insert("worktree", worktree_id, FileMode::Tree.into())?;
+    // Remember which untracked files aren't part of the snapshot, so they can be reported when restoring.
+    if !excluded_files.is_empty() {
+        let excluded_files_blob = file_limits::write_excluded_files(&repo, excluded_files)?;
+        tree_builder.insert(
+            file_limits::EXCLUDED_FILES_ENTRY,
+            excluded_files_blob,
+            FileMode::Blob.into(),
+        )?;
+    }
 
     // also add the gitbutler/workspace commit to the branches tree
-    let head = repo.head()?;
     if head.name() == Some("refs/heads/gitbutler/workspace") {
         let head_commit = head.peel_to_commit()?;
         let head_tree = head_commit.tree()?;
@@ -525,12 +785,47 @@ fn prepare_snapshot(ctx: &Project, _shared_access: &WorktreeReadPermission) -> R
     Ok(tree_id)
 }
 
+/// Apply the changes between `head_tree` and the tree at `worktree_id` to `onto`, which yields the worktree
+/// as if `onto` was checked out instead of `head_tree`.
+fn move_worktree_changes(
+    repo: &git2::Repository,
+    head_tree: &git2::Tree,
+    worktree_id: git2::Oid,
+    onto: &git2::Tree,
+) -> Result<git2::Oid> {
+    let worktree = repo.find_tree(worktree_id)?;
+    let diff = repo.diff_tree_to_tree(Some(head_tree), Some(&worktree), None)?;
+    let mut editor = git2::build::TreeUpdateBuilder::new();
+    for delta in diff.deltas() {
+        if delta.status() == git2::Delta::Deleted {
+            if let Some(path) = delta.old_file().path() {
+                editor.remove(path);
+            }
+        } else if let Some(path) = delta.new_file().path() {
+            editor.upsert(path, delta.new_file().id(), delta.new_file().mode());
+        }
+    }
+    Ok(editor.create_updated(repo, onto)?)
+}
+
 fn commit_snapshot(
     ctx: &Project,
     snapshot_tree_id: git2::Oid,
     details: SnapshotDetails,
-    _exclusive_access: &mut WorktreeWritePermission,
+    exclusive_access: &mut WorktreeWritePermission,
 ) -> Result<git2::Oid> {
+    if let Some(retention) = ctx.snapshot_retention {
+        match retention::should_auto_prune(ctx) {
+            Ok(true) => {
+                if let Err(err) = retention::prune_snapshots(ctx, &retention, exclusive_access) {
+                    tracing::warn!("failed to prune the oplog - ignoring: {err}");
+                }
+            }
+            Ok(false) => {}
+            Err(err) => tracing::warn!("failed to check if the oplog should be pruned: {err}"),
+        }
+    }
+
     let repo = git2::Repository::open(ctx.path.as_path())?;
     let snapshot_tree = repo.find_tree(snapshot_tree_id)?;
 
@@ -614,15 +909,7 @@ fn restore_snapshot(
         for commit_entry in commits_tree.iter() {
             // for each commit, recreate the commit from the commit data if it doesn't exist
             if let Some(commit_id) = commit_entry.name() {
-                // check for the oid in the repo
-                let commit_oid = git2::Oid::from_str(commit_id)?;
-                if repo.find_commit(commit_oid).is_err() {
-                    // commit is not in the repo, let's build it from our data
-                    let new_commit_oid = deserialize_commit(&repo, &commit_entry)?;
-                    if new_commit_oid != commit_oid {
-                        bail!("commit id mismatch: failed to recreate a commit from its parts");
-                    }
-                }
+                let commit_oid = recreate_commit_if_missing(&repo, commit_id, &commit_entry)?;
 
                 // if branch_name is 'workspace', we need to create or update the gitbutler/workspace branch
                 if branch_name == Some("workspace") {
@@ -657,7 +944,17 @@ fn restore_snapshot(
     let workdir_tree =
         repo.find_tree(get_workdir_tree(None, snapshot_commit_id.to_gix(), &gix_repo)?.to_git2())?;
 
-    repo.ignore_large_files_in_diffs(AUTO_TRACK_LIMIT_BYTES)?;
+    file_limits::ignore_excluded_files(ctx, &repo)?;
+    let unrestorable_files = file_limits::excluded_files(&repo, &snapshot_tree)?
+        .into_iter()
+        .filter(|file| !file.is_present_in(worktree_dir));
+    for file in unrestorable_files {
+        tracing::warn!(
+            "{} ({} bytes) was excluded from snapshot {snapshot_commit_id} and can't be restored",
+            file.path.display(),
+            file.size
+        );
+    }
 
     // Define the checkout builder
     let mut checkout_builder = git2::build::CheckoutBuilder::new();
@@ -677,7 +974,13 @@ fn restore_snapshot(
     let stacks = vb_state.list_stacks_in_workspace()?;
     for stack in stacks {
         for branch in stack.heads {
-            branch.set_reference_to_head_value(&gix_repo).ok();
+            // This is best-effort, a single reference that can't be written must not leave a half-restored workspace.
+            if let Err(err) = branch.set_reference_to_head_value(&gix_repo) {
+                tracing::warn!(
+                    "Could not restore reference of branch '{}': {err:#}",
+                    branch.name()
+                );
+            }
         }
     }
 
@@ -691,34 +994,14 @@ fn restore_snapshot(
     let mut index = repo.index()?;
     index.read_tree(&index_tree)?;
 
-    let restored_operation = snapshot_commit
-        .message()
-        .and_then(|msg| SnapshotDetails::from_str(msg).ok())
-        .map(|d| d.operation.to_string())
-        .unwrap_or_default();
-
     // create new snapshot
     let before_restore_snapshot_tree_id = before_restore_snapshot_result?;
-    let restored_date_ms = snapshot_commit.time().seconds() * 1000;
     let details = SnapshotDetails {
         version: Default::default(),
         operation: OperationKind::RestoreFromSnapshot,
         title: "Restored from snapshot".to_string(),
         body: None,
-        trailers: vec![
-            Trailer {
-                key: "restored_from".to_string(),
-                value: snapshot_commit_id.to_string(),
-            },
-            Trailer {
-                key: "restored_operation".to_string(),
-                value: restored_operation,
-            },
-            Trailer {
-                key: "restored_date".to_string(),
-                value: restored_date_ms.to_string(),
-            },
-        ],
+        trailers: restored_from_trailers(&snapshot_commit),
     };
     commit_snapshot(
         ctx,
@@ -728,6 +1011,173 @@ fn restore_snapshot(
     )
 }
 
+fn restore_snapshot_selection(
+    ctx: &Project,
+    snapshot_commit_id: git2::Oid,
+    selection: &RestoreSelection,
+    exclusive_access: &mut WorktreeWritePermission,
+) -> Result<git2::Oid> {
+    if selection.is_empty() {
+        bail!("Nothing was selected to be restored from snapshot {snapshot_commit_id}");
+    }
+    let worktree_dir = ctx.path.as_path();
+    let repo = git2::Repository::open(worktree_dir)?;
+
+    let before_restore_snapshot_result = prepare_snapshot(ctx, exclusive_access.read_permission());
+    let snapshot_commit = repo.find_commit(snapshot_commit_id)?;
+    let snapshot_tree = snapshot_commit.tree()?;
+    let gix_repo = gitbutler_command_context::gix_repo_for_merging(worktree_dir)?;
+
+    if !selection.stacks.is_empty() {
+        let vb_toml_entry = snapshot_tree
+            .get_name("virtual_branches.toml")
+            .context("failed to get virtual_branches.toml blob")?;
+        let vb_toml_blob = repo
+            .find_blob(vb_toml_entry.id())
+            .context("failed to convert virtual_branches tree entry to blob")?;
+        let snapshot_vb_state: VirtualBranchesState =
+            toml::from_str(from_utf8(vb_toml_blob.content())?)?;
+        let vb_tree_entry = snapshot_tree
+            .get_name("virtual_branches")
+            .context("failed to get virtual_branches tree entry")?;
+        let vb_tree = repo
+            .find_tree(vb_tree_entry.id())
+            .context("failed to convert virtual_branches tree entry to tree")?;
+
+        let vb_state = VirtualBranchesHandle::new(ctx.gb_dir());
+        for stack_id in &selection.stacks {
+            let mut stack = snapshot_vb_state
+                .branches
+                .get(stack_id)
+                .cloned()
+                .with_context(|| {
+                    format!("Stack {stack_id} isn't part of snapshot {snapshot_commit_id}")
+                })?;
+
+            // Only stacks in the workspace have their commits stored, so this is optional.
+            if let Some(branch_entry) = vb_tree.get_name(&stack_id.to_string()) {
+                let branch_tree = repo
+                    .find_tree(branch_entry.id())
+                    .context("failed to convert virtual_branches tree entry to tree")?;
+                let commits_tree_entry = branch_tree
+                    .get_name("commits")
+                    .context("failed to get commits tree entry")?;
+                let commits_tree = repo
+                    .find_tree(commits_tree_entry.id())
+                    .context("failed to convert commits tree entry to tree")?;
+                for commit_entry in commits_tree.iter() {
+                    if let Some(commit_id) = commit_entry.name() {
+                        recreate_commit_if_missing(&repo, commit_id, &commit_entry)?;
+                    }
+                }
+            }
+
+            match vb_state.try_stack(*stack_id)? {
+                Some(current) => {
+                    // Uncommitted changes stay where they are as the worktree isn't affected.
+                    stack.in_workspace = current.in_workspace;
+                    if current.in_workspace {
+                        stack.tree = current.tree;
+                        stack.ownership = current.ownership;
+                    }
+                }
+                None => stack.in_workspace = false,
+            }
+            for branch in &stack.heads {
+                if let Err(err) = branch.set_reference_to_head_value(&gix_repo) {
+                    tracing::warn!(
+                        "Could not restore reference of branch '{}': {err:#}",
+                        branch.name()
+                    );
+                }
+            }
+            vb_state.set_stack(stack)?;
+        }
+    }
+
+    if !selection.paths.is_empty() {
+        let workdir_tree = repo
+            .find_tree(get_workdir_tree(None, snapshot_commit_id.to_gix(), &gix_repo)?.to_git2())?;
+        file_limits::ignore_excluded_files(ctx, &repo)?;
+
+        let mut checkout_builder = git2::build::CheckoutBuilder::new();
+        checkout_builder
+            .remove_untracked(true)
+            .force()
+            .update_index(false);
+        for path in &selection.paths {
+            checkout_builder.path(path.as_path());
+        }
+        repo.checkout_tree(workdir_tree.as_object(), Some(&mut checkout_builder))?;
+    }
+
+    let before_restore_snapshot_tree_id = before_restore_snapshot_result?;
+    let mut trailers = restored_from_trailers(&snapshot_commit);
+    trailers.extend(selection.paths.iter().map(|path| Trailer {
+        key: "restored_path".to_string(),
+        value: path.to_string_lossy().into_owned(),
+    }));
+    trailers.extend(selection.stacks.iter().map(|stack_id| Trailer {
+        key: "restored_stack".to_string(),
+        value: stack_id.to_string(),
+    }));
+    let details = SnapshotDetails {
+        version: Default::default(),
+        operation: OperationKind::RestoreFromSnapshot,
+        title: "Partially restored from snapshot".to_string(),
+        body: None,
+        trailers,
+    };
+    commit_snapshot(
+        ctx,
+        before_restore_snapshot_tree_id,
+        details,
+        exclusive_access,
+    )
+}
+
+/// Produce the trailers that identify `snapshot_commit` as the snapshot that was restored.
+fn restored_from_trailers(snapshot_commit: &git2::Commit<'_>) -> Vec<Trailer> {
+    let restored_operation = snapshot_commit
+        .message()
+        .and_then(|msg| SnapshotDetails::from_str(msg).ok())
+        .map(|d| d.operation.to_string())
+        .unwrap_or_default();
+    let restored_date_ms = snapshot_commit.time().seconds() * 1000;
+    vec![
+        Trailer {
+            key: "restored_from".to_string(),
+            value: snapshot_commit.id().to_string(),
+        },
+        Trailer {
+            key: "restored_operation".to_string(),
+            value: restored_operation,
+        },
+        Trailer {
+            key: "restored_date".to_string(),
+            value: restored_date_ms.to_string(),
+        },
+    ]
+}
+
+/// Recreate the commit named `commit_id` from the data in `commit_entry` of a snapshot if it doesn't exist
+/// in `repo` anymore, and return its id.
+fn recreate_commit_if_missing(
+    repo: &git2::Repository,
+    commit_id: &str,
+    commit_entry: &git2::TreeEntry,
+) -> Result<git2::Oid> {
+    let commit_oid = git2::Oid::from_str(commit_id)?;
+    if repo.find_commit(commit_oid).is_err() {
+        // commit is not in the repo, let's build it from our data
+        let new_commit_oid = deserialize_commit(repo, commit_entry)?;
+        if new_commit_oid != commit_oid {
+            bail!("commit id mismatch: failed to recreate a commit from its parts");
+        }
+    }
+    Ok(commit_oid)
+}
+
 /// Restore the state of .git/base_merge_parent and .git/conflicts from the snapshot
 /// Will remove those files if they are not present in the snapshot
 fn restore_conflicts_tree(snapshot_tree: &git2::Tree, repo: &git2::Repository) -> Result<()> {
@@ -799,11 +1249,11 @@ fn write_conflicts_tree(
 /// `repo` is an already opened project repository.
 ///
 /// If there are no snapshots, 0 is returned.
-fn lines_since_snapshot(project: &Project, repo: &git2::Repository) -> Result<usize> {
+pub(crate) fn lines_since_snapshot(project: &Project, repo: &git2::Repository) -> Result<usize> {
     // This looks at the diff between the tree of the currently selected as 'default' branch (where new changes go)
     // and that same tree in the last snapshot. For some reason, comparing workdir to the workdir subree from
     // the snapshot simply does not give us what we need here, so instead using tree to tree comparison.
-    repo.ignore_large_files_in_diffs(AUTO_TRACK_LIMIT_BYTES)?;
+    file_limits::ignore_excluded_files(project, repo)?;
 
     let oplog_state = OplogHandle::new(&project.gb_dir());
     let Some(oplog_commit_id) = oplog_state.oplog_head()? else {
@@ -0,0 +1,357 @@
+use std::{
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use gitbutler_project::{access::WorktreeWritePermission, Project, SnapshotRetention};
+
+use crate::{
+    entry::SnapshotDetails,
+    reflog::{set_reference_to_oplog, ReflogCommits},
+    state::OplogHandle,
+};
+
+/// The minimal time between two automatic prunes of the oplog, which happen when snapshots are created.
+pub(crate) const AUTO_PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60 * 24);
+
+const SECONDS_PER_DAY: i64 = 60 * 60 * 24;
+
+/// The result of [`OplogExt::prune_snapshots()`](crate::OplogExt::prune_snapshots()).
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PruneOutcome {
+    /// The amount of snapshots that remain in the oplog.
+    pub kept: usize,
+    /// The ids of the snapshots that were removed from the oplog, most recent first.
+    pub removed: Vec<git2::Oid>,
+    /// Snapshots that were kept, but had to be rewritten as `(old_id, new_id)` as their parent changed,
+    /// most recent first.
+    pub rewritten: Vec<(git2::Oid, git2::Oid)>,
+}
+
+/// Decide which of the snapshots with the given commit `times` (in seconds since epoch, most recent first)
+/// to keep according to `retention`, with `now` being the current time in seconds since epoch.
+///
+/// Returns a vec of the same length as `times`, with `true` for each snapshot to keep.
+/// Days and weeks are in UTC, and weeks start on Monday.
+pub fn snapshots_to_keep(times: &[i64], retention: &SnapshotRetention, now: i64) -> Vec<bool> {
+    if retention.keeps_everything() {
+        return vec![true; times.len()];
+    }
+
+    let SnapshotRetention {
+        keep_last,
+        keep_within_seconds,
+        daily,
+        weekly,
+    } = *retention;
+    let day_of = |time: i64| time.div_euclid(SECONDS_PER_DAY);
+    // The unix epoch was a Thursday, shift by three days to let weeks start on Monday.
+    let week_of = |time: i64| (day_of(time) + 3).div_euclid(7);
+
+    let (mut last_day, mut days) = (None, 0);
+    let (mut last_week, mut weeks) = (None, 0);
+    times
+        .iter()
+        .enumerate()
+        .map(|(idx, &time)| {
+            let mut keep = idx == 0 || keep_last.is_some_and(|n| idx < n);
+            keep |= keep_within_seconds.is_some_and(|max_age| {
+                now.saturating_sub(time) <= i64::try_from(max_age).unwrap_or(i64::MAX)
+            });
+            if let Some(daily) = daily {
+                let day = day_of(time);
+                if last_day != Some(day) && days < daily {
+                    last_day = Some(day);
+                    days += 1;
+                    keep = true;
+                }
+            }
+            if let Some(weekly) = weekly {
+                let week = week_of(time);
+                if last_week != Some(week) && weeks < weekly {
+                    last_week = Some(week);
+                    weeks += 1;
+                    keep = true;
+                }
+            }
+            keep
+        })
+        .collect()
+}
+
+/// Return `true` if the oplog of `project` should be pruned automatically as it has a retention policy,
+/// and it wasn't pruned recently.
+pub(crate) fn should_auto_prune(project: &Project) -> Result<bool> {
+    if project
+        .snapshot_retention
+        .is_none_or(|retention| retention.keeps_everything())
+    {
+        return Ok(false);
+    }
+    let Some(pruned_at) = OplogHandle::new(&project.gb_dir()).pruned_at()? else {
+        return Ok(true);
+    };
+    Ok(pruned_at.elapsed().unwrap_or_default() > AUTO_PRUNE_INTERVAL)
+}
+
+/// Remove all snapshots from the oplog of `ctx` that aren't kept by `retention` or pinned, and rewrite the remaining ones
+/// to form a chain again.
+pub(crate) fn prune_snapshots(
+    ctx: &Project,
+    retention: &SnapshotRetention,
+    _exclusive_access: &mut WorktreeWritePermission,
+) -> Result<PruneOutcome> {
+    let repo = git2::Repository::open(ctx.path.as_path())?;
+    let oplog_state = OplogHandle::new(&ctx.gb_dir());
+    let Some(head_id) = oplog_state.oplog_head()? else {
+        return Ok(PruneOutcome::default());
+    };
+
+    let snapshots = snapshot_chain(&repo, head_id, None)?;
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_secs() as i64;
+    let times: Vec<_> = snapshots.iter().map(|c| c.time().seconds()).collect();
+    let keep: Vec<_> = snapshots_to_keep(&times, retention, now)
+        .into_iter()
+        .zip(&snapshots)
+        .map(|(keep, snapshot)| keep || is_pinned(snapshot))
+        .collect();
+    if keep.iter().all(|keep| *keep) {
+        oplog_state.set_pruned_oplog_head(head_id)?;
+        return Ok(PruneOutcome {
+            kept: snapshots.len(),
+            ..Default::default()
+        });
+    }
+
+    // Recreate the chain from the oldest snapshot to keep, and reuse snapshots as long as their parent didn't change.
+    let mut outcome = PruneOutcome::default();
+    let mut parent: Option<git2::Commit<'_>> = None;
+    for (snapshot, keep) in snapshots.into_iter().zip(keep).rev() {
+        if !keep {
+            outcome.removed.insert(0, snapshot.id());
+            continue;
+        }
+        outcome.kept += 1;
+        let new_parent_id = parent.as_ref().map(|p| p.id());
+        if new_parent_id == snapshot.parent_ids().next() {
+            parent = Some(snapshot);
+            continue;
+        }
+        let new_snapshot_id = rewrite_snapshot(&repo, &snapshot, parent.as_ref())?;
+        outcome
+            .rewritten
+            .insert(0, (snapshot.id(), new_snapshot_id));
+        parent = Some(repo.find_commit(new_snapshot_id)?);
+    }
+
+    let new_head = parent.context("BUG: the most recent snapshot is always kept")?;
+    oplog_state.set_pruned_oplog_head(new_head.id())?;
+    set_reference_to_oplog(&ctx.path, ReflogCommits::new(ctx)?)?;
+
+    Ok(outcome)
+}
+
+/// Add or remove the pin of the snapshot with `snapshot_id` in the oplog of `ctx`, and return its new id.
+///
+/// As this changes the snapshot, it and all more recent snapshots are rewritten.
+pub(crate) fn set_snapshot_pinned(
+    ctx: &Project,
+    snapshot_id: git2::Oid,
+    pinned: bool,
+    _exclusive_access: &mut WorktreeWritePermission,
+) -> Result<git2::Oid> {
+    let repo = git2::Repository::open(ctx.path.as_path())?;
+    let oplog_state = OplogHandle::new(&ctx.gb_dir());
+    let head_id = oplog_state
+        .oplog_head()?
+        .context("The oplog doesn't have any snapshots")?;
+    let snapshots = snapshot_chain(&repo, head_id, None)?;
+    let pos = snapshots
+        .iter()
+        .position(|snapshot| snapshot.id() == snapshot_id)
+        .with_context(|| format!("{snapshot_id} is not a snapshot in the oplog"))?;
+
+    let snapshot = &snapshots[pos];
+    let mut details = snapshot
+        .message()
+        .and_then(|msg| SnapshotDetails::from_str(msg).ok())
+        .with_context(|| format!("Could not read the details of snapshot {snapshot_id}"))?;
+    if details.is_pinned() == pinned {
+        return Ok(snapshot_id);
+    }
+    details.set_pinned(pinned);
+    let parents: Vec<_> = snapshot.parents().collect();
+    let new_snapshot_id = repo.commit(
+        None,
+        &snapshot.author(),
+        &snapshot.committer(),
+        &details.to_string(),
+        &snapshot.tree()?,
+        &parents.iter().collect::<Vec<_>>(),
+    )?;
+
+    let mut parent = repo.find_commit(new_snapshot_id)?;
+    for more_recent in snapshots[..pos].iter().rev() {
+        let new_id = rewrite_snapshot(&repo, more_recent, Some(&parent))?;
+        parent = repo.find_commit(new_id)?;
+    }
+    oplog_state.set_rewritten_oplog_head(parent.id())?;
+    set_reference_to_oplog(&ctx.path, ReflogCommits::new(ctx)?)?;
+    Ok(new_snapshot_id)
+}
+
+fn is_pinned(snapshot: &git2::Commit<'_>) -> bool {
+    snapshot
+        .message()
+        .and_then(|msg| SnapshotDetails::from_str(msg).ok())
+        .is_some_and(|details| details.is_pinned())
+}
+
+/// Collect the chain of snapshots starting at `head_id`, most recent first, and stop where `list_snapshots()`
+/// would stop as well, or after `limit` snapshots.
+pub(crate) fn snapshot_chain(
+    repo: &git2::Repository,
+    head_id: git2::Oid,
+    limit: Option<usize>,
+) -> Result<Vec<git2::Commit<'_>>> {
+    let mut snapshots = Vec::new();
+    let mut next = Some(repo.find_commit(head_id)?);
+    while let Some(commit) = next {
+        if limit.is_some_and(|limit| snapshots.len() == limit) {
+            break;
+        }
+        next = if commit.parent_count() == 1 {
+            commit.parent(0).ok()
+        } else {
+            None
+        };
+        snapshots.push(commit);
+    }
+    Ok(snapshots)
+}
+
+/// Write a copy of `snapshot` that has `parent` as its only parent, or no parent at all, and return its id.
+pub(crate) fn rewrite_snapshot(
+    repo: &git2::Repository,
+    snapshot: &git2::Commit<'_>,
+    parent: Option<&git2::Commit<'_>>,
+) -> Result<git2::Oid> {
+    Ok(repo.commit(
+        None,
+        &snapshot.author(),
+        &snapshot.committer(),
+        snapshot
+            .message_raw()
+            .context("snapshot messages are always valid UTF-8")?,
+        &snapshot.tree()?,
+        parent.into_iter().collect::<Vec<_>>().as_slice(),
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use gitbutler_project::SnapshotRetention;
+
+    use super::{snapshots_to_keep, SECONDS_PER_DAY};
+
+    const NOW: i64 = 1_700_000_000;
+    const HOUR: i64 = 60 * 60;
+
+    #[test]
+    fn no_rules_keep_everything() {
+        let times = [NOW, NOW - HOUR, NOW - SECONDS_PER_DAY * 100];
+        assert_eq!(
+            snapshots_to_keep(&times, &SnapshotRetention::default(), NOW),
+            [true, true, true]
+        );
+    }
+
+    #[test]
+    fn most_recent_is_always_kept() {
+        let retention = SnapshotRetention {
+            keep_last: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(
+            snapshots_to_keep(&[NOW, NOW - HOUR], &retention, NOW),
+            [true, false]
+        );
+    }
+
+    #[test]
+    fn keep_last_and_within() {
+        let times = [
+            NOW,
+            NOW - HOUR,
+            NOW - 2 * HOUR,
+            NOW - 3 * HOUR,
+            NOW - 4 * HOUR,
+        ];
+        let retention = SnapshotRetention {
+            keep_last: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            snapshots_to_keep(&times, &retention, NOW),
+            [true, true, false, false, false]
+        );
+
+        let retention = SnapshotRetention {
+            keep_last: Some(2),
+            keep_within_seconds: Some(3 * HOUR as u64),
+            ..Default::default()
+        };
+        assert_eq!(
+            snapshots_to_keep(&times, &retention, NOW),
+            [true, true, true, true, false],
+            "the age limit is inclusive"
+        );
+    }
+
+    #[test]
+    fn daily_keeps_the_most_recent_snapshot_per_day() {
+        let today = NOW - NOW.rem_euclid(SECONDS_PER_DAY) + 12 * HOUR;
+        let times = [
+            today + HOUR,
+            today,
+            today - SECONDS_PER_DAY,
+            today - SECONDS_PER_DAY - HOUR,
+            today - 3 * SECONDS_PER_DAY,
+            today - 4 * SECONDS_PER_DAY,
+        ];
+        let retention = SnapshotRetention {
+            daily: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(
+            snapshots_to_keep(&times, &retention, NOW),
+            [true, false, true, false, true, false],
+            "days without snapshots don't count"
+        );
+    }
+
+    #[test]
+    fn weekly_keeps_the_most_recent_snapshot_per_week() {
+        // 2023-11-13 is a Monday.
+        let monday = 1_699_833_600;
+        let times = [
+            monday + SECONDS_PER_DAY,
+            monday,
+            monday - 1,
+            monday - 3 * SECONDS_PER_DAY,
+            monday - 7 * SECONDS_PER_DAY - 1,
+        ];
+        let retention = SnapshotRetention {
+            weekly: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            snapshots_to_keep(&times, &retention, monday + SECONDS_PER_DAY),
+            [true, false, true, false, false],
+            "the snapshot just before monday belongs to the previous week"
+        );
+    }
+}
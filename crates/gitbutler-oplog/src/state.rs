@@ -34,6 +34,9 @@ pub struct Oplog {
         default = "unix_epoch"
     )]
     pub modified_at: SystemTime,
+    /// The time when the oplog was last pruned, or `None` if it never was.
+    #[serde(default)]
+    pub pruned_at: Option<SystemTime>,
 }
 
 impl Default for Oplog {
@@ -41,6 +44,7 @@ impl Default for Oplog {
         Self {
             head_sha: None,
             modified_at: SystemTime::UNIX_EPOCH,
+            pruned_at: None,
         }
     }
 }
@@ -67,6 +71,26 @@ impl OplogHandle {
         Ok(())
     }
 
+    /// Persists the oplog head after the oplog history was rewritten by pruning it.
+    ///
+    /// Unlike [`set_oplog_head()`](Self::set_oplog_head()), the time of the last snapshot remains unchanged.
+    /// Errors if the file cannot be read or written.
+    pub fn set_pruned_oplog_head(&self, sha: git2::Oid) -> Result<()> {
+        let mut oplog = self.read_file()?;
+        oplog.head_sha = Some(sha);
+        oplog.pruned_at = Some(SystemTime::now());
+        gitbutler_fs::write(&self.file_path, toml::to_string(&oplog)?)
+    }
+
+    /// Persists the oplog head after the oplog history was rewritten, without changing the time of the last snapshot.
+    ///
+    /// Errors if the file cannot be read or written.
+    pub fn set_rewritten_oplog_head(&self, sha: git2::Oid) -> Result<()> {
+        let mut oplog = self.read_file()?;
+        oplog.head_sha = Some(sha);
+        gitbutler_fs::write(&self.file_path, toml::to_string(&oplog)?)
+    }
+
     /// Gets the oplog head sha for the given repository.
     ///
     /// Errors if the file cannot be read or written.
@@ -83,6 +107,14 @@ impl OplogHandle {
         Ok(oplog.modified_at)
     }
 
+    /// Gets the time when the oplog was last pruned, if ever.
+    ///
+    /// Errors if the file cannot be read or written.
+    pub fn pruned_at(&self) -> Result<Option<SystemTime>> {
+        let oplog = self.read_file()?;
+        Ok(oplog.pruned_at)
+    }
+
     /// Reads and parses the state file.
     ///
     /// If the file does not exist, it will be created.
@@ -175,3 +175,38 @@ mod snapshot_details {
         }
     }
 }
+
+mod pinned {
+    use std::str::FromStr;
+
+    use gitbutler_oplog::entry::{OperationKind, SnapshotDetails, Trailer};
+
+    #[test]
+    fn set_and_parse() {
+        let mut details =
+            SnapshotDetails::new(OperationKind::Checkpoint).with_trailers(vec![Trailer {
+                key: "foo".to_string(),
+                value: "bar".to_string(),
+            }]);
+        assert!(!details.is_pinned());
+
+        details.set_pinned(true);
+        details.set_pinned(true);
+        assert!(
+            details.to_string().contains("\npinned: true"),
+            "the key is snake-case like all other trailer keys"
+        );
+        let parsed = SnapshotDetails::from_str(&details.to_string()).unwrap();
+        assert!(parsed.is_pinned());
+        assert_eq!(
+            parsed.trailers.len(),
+            2,
+            "pinning twice adds only one trailer"
+        );
+        assert_eq!(parsed.operation, OperationKind::Checkpoint);
+
+        details.set_pinned(false);
+        assert!(!details.is_pinned());
+        assert_eq!(details.trailers.len(), 1, "other trailers are kept");
+    }
+}
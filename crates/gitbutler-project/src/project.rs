@@ -70,6 +70,83 @@ pub struct CodePushState {
     pub timestamp: time::SystemTime,
 }
 
+/// Determines which snapshots of the operations log are kept when it is pruned.
+///
+/// A snapshot is kept if any of the rules wants to keep it, and the most recent snapshot is always kept.
+/// If no rule is set, all snapshots are kept.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotRetention {
+    /// Keep this many of the most recent snapshots.
+    pub keep_last: Option<usize>,
+    /// Keep all snapshots that are younger than this amount of seconds.
+    pub keep_within_seconds: Option<u64>,
+    /// Keep the most recent snapshot of each of the last `daily` days that have snapshots.
+    pub daily: Option<usize>,
+    /// Keep the most recent snapshot of each of the last `weekly` weeks that have snapshots.
+    pub weekly: Option<usize>,
+}
+
+impl SnapshotRetention {
+    /// Return `true` if this policy would keep all snapshots.
+    pub fn keeps_everything(&self) -> bool {
+        let SnapshotRetention {
+            keep_last,
+            keep_within_seconds,
+            daily,
+            weekly,
+        } = self;
+        keep_last.is_none() && keep_within_seconds.is_none() && daily.is_none() && weekly.is_none()
+    }
+}
+
+/// Determines when automatic snapshots are created in addition to the line-count threshold in
+/// [`Project::snapshot_lines_threshold`].
+///
+/// An automatic snapshot is created if any of the triggers fire. Changes are always compared with the last snapshot.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AutoSnapshotPolicy {
+    /// Create a snapshot if at least this many files were added, modified or deleted.
+    pub touched_files_threshold: Option<usize>,
+    /// Create a snapshot if there are new files that aren't tracked by Git.
+    pub on_untracked_files: bool,
+    /// Create a snapshot if files that are tracked by Git were deleted since the last snapshot.
+    pub on_deleted_files: bool,
+    /// Create a snapshot when a different branch was checked out.
+    pub on_branch_switch: bool,
+    /// Create a snapshot if there are any changes and the last snapshot is older than this amount of seconds,
+    /// no matter how many lines changed.
+    pub max_interval_seconds: Option<u64>,
+}
+
+impl AutoSnapshotPolicy {
+    /// Return `true` if a trigger is configured that needs to know which files changed.
+    pub fn needs_file_changes(&self) -> bool {
+        self.touched_files_threshold.is_some()
+            || self.on_untracked_files
+            || self.on_deleted_files
+            || self.max_interval_seconds.is_some()
+    }
+}
+
+/// Determines which untracked files are part of oplog snapshots.
+///
+/// Tracked files are always part of snapshots. Untracked files that are left out are recorded in the snapshot
+/// so it's known what can't be restored.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SnapshotFileLimits {
+    /// Untracked files larger than this amount of bytes are left out of snapshots.
+    /// If `None`, [`AUTO_TRACK_LIMIT_BYTES`](crate::AUTO_TRACK_LIMIT_BYTES) is used, and `0` disables the limit.
+    pub max_untracked_file_bytes: Option<u64>,
+    /// Glob patterns of untracked files that are always part of snapshots, no matter their size.
+    /// Patterns without a `/` match the file name, and all others match the path relative to the worktree.
+    pub always_include: Vec<String>,
+    /// Glob patterns of untracked files that are never part of snapshots, with the same rules as `always_include`.
+    pub never_include: Vec<String>,
+}
+
 pub type ProjectId = Id<Project>;
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
@@ -98,6 +175,16 @@ pub struct Project {
     pub omit_certificate_check: Option<bool>,
     // The number of changed lines that will trigger a snapshot
     pub snapshot_lines_threshold: Option<usize>,
+    /// Which snapshots to keep when the operations log is pruned, or `None` to never prune it.
+    #[serde(default)]
+    pub snapshot_retention: Option<SnapshotRetention>,
+    /// Additional triggers for automatic snapshots, or `None` to only use the line-count threshold.
+    #[serde(default)]
+    pub auto_snapshot_policy: Option<AutoSnapshotPolicy>,
+    /// Which untracked files are part of snapshots, or `None` to include all untracked files up to
+    /// [`AUTO_TRACK_LIMIT_BYTES`](crate::AUTO_TRACK_LIMIT_BYTES).
+    #[serde(default)]
+    pub snapshot_file_limits: Option<SnapshotFileLimits>,
 }
 
 /// Instantiation
@@ -153,6 +240,14 @@ impl Project {
         self.snapshot_lines_threshold.unwrap_or(20)
     }
 
+    /// The size in bytes above which untracked files are left out of snapshots, or `0` if there is no limit.
+    pub fn snapshot_untracked_limit_bytes(&self) -> u64 {
+        self.snapshot_file_limits
+            .as_ref()
+            .and_then(|limits| limits.max_untracked_file_bytes)
+            .unwrap_or(crate::AUTO_TRACK_LIMIT_BYTES)
+    }
+
     pub fn worktree_path(&self) -> PathBuf {
         self.path.clone()
     }
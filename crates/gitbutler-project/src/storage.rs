@@ -3,7 +3,10 @@ use std::path::PathBuf;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
-use crate::{ApiProject, AuthKey, CodePushState, FetchResult, Project, ProjectId};
+use crate::{
+    ApiProject, AuthKey, AutoSnapshotPolicy, CodePushState, FetchResult, Project, ProjectId,
+    SnapshotFileLimits, SnapshotRetention,
+};
 
 const PROJECTS_FILE: &str = "projects.json";
 
@@ -29,6 +32,9 @@ pub struct UpdateRequest {
     pub omit_certificate_check: Option<bool>,
     pub use_diff_context: Option<bool>,
     pub snapshot_lines_threshold: Option<usize>,
+    pub snapshot_retention: Option<SnapshotRetention>,
+    pub auto_snapshot_policy: Option<AutoSnapshotPolicy>,
+    pub snapshot_file_limits: Option<SnapshotFileLimits>,
 }
 
 fn default_false() -> bool {
@@ -133,6 +139,18 @@ impl Storage {
             project.snapshot_lines_threshold = Some(snapshot_lines_threshold);
         }
 
+        if let Some(snapshot_retention) = update_request.snapshot_retention {
+            project.snapshot_retention = Some(snapshot_retention);
+        }
+
+        if let Some(auto_snapshot_policy) = update_request.auto_snapshot_policy {
+            project.auto_snapshot_policy = Some(auto_snapshot_policy);
+        }
+
+        if let Some(snapshot_file_limits) = &update_request.snapshot_file_limits {
+            project.snapshot_file_limits = Some(snapshot_file_limits.clone());
+        }
+
         self.inner
             .write(PROJECTS_FILE, &serde_json::to_string_pretty(&projects)?)?;
 
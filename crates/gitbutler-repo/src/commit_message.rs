@@ -1,6 +1,6 @@
 #![deny(rust_2018_idioms)]
 
-use bstr::{BString, ByteSlice as _, ByteVec as _};
+use bstr::{BStr, BString, ByteSlice as _, ByteVec as _};
 
 pub struct CommitMessage {
     pub title: BString,
@@ -19,6 +19,23 @@ impl CommitMessage {
         out
     }
 
+    /// Like [`to_bstring()`](Self::to_bstring()), but omit empty sections and trailing whitespace of the body,
+    /// which is how `git` would write the message.
+    pub fn to_bstring_without_empty_sections(&self) -> BString {
+        let mut out = BString::default();
+        out.push_str(self.title.clone());
+        let body = self.body.trim_end();
+        if !body.is_empty() {
+            out.push_str(b"\n\n");
+            out.push_str(body);
+        }
+        if !self.trailers.is_empty() {
+            out.push_str(b"\n\n");
+            out.push_str(self.trailers_as_bstring());
+        }
+        out
+    }
+
     fn trailers_as_bstring(&self) -> BString {
         let mut out = BString::default();
         for (index, trailer) in self.trailers.iter().enumerate() {
@@ -33,11 +50,19 @@ impl CommitMessage {
     }
 
     pub fn new(commit: gix::objs::CommitRef<'_>) -> Self {
-        let message_ref = commit.message();
+        Self::from_message_ref(commit.message())
+    }
+
+    /// Parse `message`, formatted like the message of a commit, into its title, body and trailers.
+    pub fn from_bstr(message: &BStr) -> Self {
+        Self::from_message_ref(gix::objs::commit::MessageRef::from_bytes(message))
+    }
+
+    fn from_message_ref(message_ref: gix::objs::commit::MessageRef<'_>) -> Self {
         let body_ref = message_ref.body();
 
         CommitMessage {
-            title: commit.message().title.to_owned(),
+            title: message_ref.title.to_owned(),
             body: body_ref
                 .map(|body_ref| body_ref.without_trailer().as_bstr().to_owned())
                 .unwrap_or_default(),
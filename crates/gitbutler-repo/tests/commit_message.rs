@@ -0,0 +1,34 @@
+use bstr::ByteSlice as _;
+use gitbutler_repo::commit_message::CommitMessage;
+
+#[test]
+fn to_bstring_keeps_all_sections() {
+    let message = CommitMessage {
+        title: "title".into(),
+        body: "".into(),
+        trailers: vec![],
+    };
+    assert_eq!(message.to_bstring(), "title\n\n\n\n");
+
+    let message = CommitMessage {
+        title: "title".into(),
+        body: "body\n".into(),
+        trailers: vec![("Change-Id".into(), "42".into())],
+    };
+    assert_eq!(message.to_bstring(), "title\n\nbody\n\n\nChange-Id: 42");
+}
+
+#[test]
+fn to_bstring_without_empty_sections() {
+    let message = CommitMessage::from_bstr(b"title".as_bstr());
+    assert_eq!(message.to_bstring_without_empty_sections(), "title");
+
+    let message = CommitMessage::from_bstr(b"title\n\nbody\n".as_bstr());
+    assert_eq!(message.to_bstring_without_empty_sections(), "title\n\nbody");
+
+    let message = CommitMessage::from_bstr(b"title\n\nbody\n\nChange-Id: 42\n".as_bstr());
+    assert_eq!(
+        message.to_bstring_without_empty_sections(),
+        "title\n\nbody\n\nChange-Id: 42"
+    );
+}
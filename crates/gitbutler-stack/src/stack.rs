@@ -29,6 +29,7 @@ use crate::heads::remove_head;
 use crate::stack_branch::remote_reference;
 use crate::stack_branch::CommitOrChangeId;
 use crate::StackBranch;
+use crate::Target;
 use crate::{ownership::BranchOwnershipClaims, VirtualBranchesHandle};
 
 pub type StackId = Id<Stack>;
@@ -89,6 +90,14 @@ pub struct Stack {
     pub heads: Vec<StackBranch>,
     #[serde(default = "default_false")]
     pub post_commits: bool,
+    /// The name of the remote the branches of this stack are pushed to, overriding the push remote of the
+    /// default target. Useful to push to a fork while integrating with the upstream repository.
+    #[serde(default)]
+    pub push_remote_name: Option<String>,
+    /// The remote branch, like `upstream/main`, that this stack is checked against for integration, overriding
+    /// the branch of the default target.
+    #[serde(default)]
+    pub upstream_target: Option<RemoteRefname>,
 }
 
 fn default_true() -> bool {
@@ -167,6 +176,8 @@ impl Stack {
             not_in_workspace_wip_change_id: None,
             heads: Default::default(),
             post_commits: false,
+            push_remote_name: None,
+            upstream_target: None,
         }
     }
 
@@ -203,6 +214,8 @@ impl Stack {
             // unclear
             post_commits: false,
             ownership: Default::default(),
+            push_remote_name: None,
+            upstream_target: None,
         }
     }
 
@@ -664,7 +677,7 @@ impl Stack {
         let (_, reference) = get_head(&self.heads, &branch_name)?;
         let oid = reference.head_oid(&ctx.gix_repo()?)?;
         let commit = ctx.repo().find_commit(oid)?;
-        let remote_name = branch_state(ctx).get_default_target()?.push_remote_name();
+        let remote_name = self.push_remote_name(&branch_state(ctx).get_default_target()?);
         let upstream_refname =
             RemoteRefname::from_str(&reference.remote_reference(remote_name.as_str()))?;
         Ok(PushDetails {
@@ -673,6 +686,22 @@ impl Stack {
         })
     }
 
+    /// Returns the name of the remote to push the branches of this stack to, which is the remote set for this
+    /// stack, or the push remote of `default_target`.
+    pub fn push_remote_name(&self, default_target: &Target) -> String {
+        self.push_remote_name
+            .clone()
+            .unwrap_or_else(|| default_target.push_remote_name())
+    }
+
+    /// Returns the remote branch this stack is checked against for integration, which is the one set for
+    /// this stack, or the branch of `default_target`.
+    pub fn upstream_target(&self, default_target: &Target) -> RemoteRefname {
+        self.upstream_target
+            .clone()
+            .unwrap_or_else(|| default_target.branch.clone())
+    }
+
     /// Returns the branch that precedes the given branch in the stack, if any.
     pub(crate) fn branch_predacessor(&self, branch: &StackBranch) -> Option<&StackBranch> {
         self.heads.iter().take_while(|head| *head != branch).last()
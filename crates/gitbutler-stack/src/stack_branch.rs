@@ -262,6 +262,17 @@ impl StackBranch {
         }
     }
 
+    /// Returns the head as persisted in this struct, or `None` if it is a legacy change id.
+    ///
+    /// Unlike [`Self::head_oid()`], this doesn't consult the reference, which makes it suitable to learn
+    /// where a branch was pointing to in an oplog snapshot.
+    pub fn persisted_head_oid(&self) -> Option<git2::Oid> {
+        match &self.head {
+            CommitOrChangeId::CommitId(id) => git2::Oid::from_str(id).ok(),
+            CommitOrChangeId::ChangeId(_) => None,
+        }
+    }
+
     /// Updates the git reference to reflect what the current head property is (the head value from the persisted struct)
     ///
     /// This is basically the opposite of `sync_with_reference` and is something to do only after restoring from a snapshot.
@@ -342,12 +353,12 @@ impl StackBranch {
         let default_target = virtual_branch_state.get_default_target()?;
         let mut remote_patches: Vec<Commit<'_>> = vec![];
 
-        // Use remote from upstream if available, otherwise default to push remote.
+        // Use remote from upstream if available, otherwise default to the push remote of the stack.
         let remote = stack
             .upstream
             .clone()
             .map(|ref_name| ref_name.remote().to_owned())
-            .unwrap_or(default_target.push_remote_name());
+            .unwrap_or_else(|| stack.push_remote_name(&default_target));
         if self.pushed(&remote, repo) {
             let upstream_head = repo
                 .find_reference(self.remote_reference(&remote).as_str())?
@@ -473,6 +473,27 @@ fn push_series_success() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn push_details_use_push_remote_of_stack() -> Result<()> {
+    let (ctx, _temp_dir) = command_ctx("multiple-commits")?;
+    let mut test_ctx = test_ctx(&ctx)?;
+
+    let state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let mut target = state.get_default_target()?;
+    target.push_remote_name = Some("origin".into());
+    state.set_default_target(target)?;
+
+    test_ctx.stack.push_remote_name = Some("fork".into());
+    let push_details = test_ctx.stack.push_details(&ctx, "a-branch-2".into())?;
+    assert_eq!(push_details.remote_refname.remote(), "fork");
+    assert_eq!(push_details.remote_refname.branch(), "a-branch-2");
+
+    test_ctx.stack.push_remote_name = None;
+    let push_details = test_ctx.stack.push_details(&ctx, "a-branch-2".into())?;
+    assert_eq!(push_details.remote_refname.remote(), "origin");
+    Ok(())
+}
+
 #[test]
 fn update_name_after_push() -> Result<()> {
     let (ctx, _temp_dir) = command_ctx("multiple-commits")?;
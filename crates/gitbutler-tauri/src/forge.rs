@@ -3,13 +3,16 @@ pub mod commands {
 
     use anyhow::Context;
     use gitbutler_forge::{
-        forge::ForgeName,
+        detect::{detect_forges, DetectedForges},
+        forge::{access_token, forge_client, ForgeName, StackedBranch},
         review::{
             available_review_templates, get_review_template_functions, ReviewTemplateFunctions,
         },
+        sync::{sync_stack_pull_requests, SyncOperation},
     };
     use gitbutler_project::{Controller, ProjectId};
     use gitbutler_repo::RepoCommands;
+    use gitbutler_stack::{StackId, VirtualBranchesHandle};
     use tauri::State;
     use tracing::instrument;
 
@@ -53,4 +56,62 @@ pub mod commands {
             .content
             .context("PR template was not valid UTF-8")?)
     }
+
+    #[tauri::command(async)]
+    #[instrument(skip(projects), err(Debug))]
+    pub fn get_detected_forges(
+        projects: State<'_, Controller>,
+        project_id: ProjectId,
+    ) -> Result<DetectedForges, Error> {
+        let project = projects.get_validated(project_id)?;
+        let repo = gix::open(&project.path).map_err(anyhow::Error::from)?;
+        let target = VirtualBranchesHandle::new(project.gb_dir()).get_default_target()?;
+        Ok(detect_forges(&repo, &target)?)
+    }
+
+    /// Retarget the pull requests of the stack with `stack_id` so each merges into the branch below it,
+    /// and close those of `review_ids_to_close`, as returned after integrating upstream changes.
+    ///
+    /// The forge client blocks, so it runs on a thread of its own instead of the async runtime.
+    #[tauri::command(async)]
+    #[instrument(skip(projects, users), err(Debug))]
+    pub async fn sync_stack_pull_requests_with_forge(
+        projects: State<'_, Controller>,
+        users: State<'_, gitbutler_user::Controller>,
+        project_id: ProjectId,
+        stack_id: StackId,
+        review_ids_to_close: Vec<String>,
+        update_footers: bool,
+    ) -> Result<Vec<SyncOperation>, Error> {
+        let project = projects.get_validated(project_id)?;
+        let repo = gix::open(&project.path).map_err(anyhow::Error::from)?;
+        let state = VirtualBranchesHandle::new(project.gb_dir());
+        let target = state.get_default_target()?;
+        let stack = state.get_stack(stack_id)?;
+
+        let mut forges = detect_forges(&repo, &target)?;
+        let info = forges
+            .push_remotes
+            .remove(&stack.push_remote_name(&target))
+            .or(forges.target)
+            .context("The push remote isn't hosted on a known forge")?;
+        let token = access_token(&info, users.get_user()?.as_ref())?
+            .with_context(|| format!("There is no access token for {}", info.host))?;
+
+        let branches: Vec<_> = stack.heads.iter().map(StackedBranch::from).collect();
+        let target_branch = stack.upstream_target(&target).branch().to_owned();
+        let operations = tauri::async_runtime::spawn_blocking(move || {
+            let forge = forge_client(&info.forge, info.forge_repository(), token)?;
+            sync_stack_pull_requests(
+                forge.as_ref(),
+                &branches,
+                &target_branch,
+                &review_ids_to_close,
+                update_footers,
+            )
+        })
+        .await
+        .map_err(anyhow::Error::from)??;
+        Ok(operations)
+    }
 }
@@ -238,13 +238,17 @@ fn main() {
                     stack::update_branch_name,
                     stack::update_branch_description,
                     stack::update_branch_pr_number,
+                    stack::set_stack_push_remote,
+                    stack::set_stack_upstream_target,
                     stack::push_stack,
                     stack::push_stack_to_review,
                     secret::secret_get_global,
                     secret::secret_set_global,
                     undo::list_snapshots,
                     undo::restore_snapshot,
+                    undo::restore_snapshot_selection,
                     undo::snapshot_diff,
+                    undo::compare_snapshots,
                     undo::take_synced_snapshot,
                     config::get_gb_config,
                     config::set_gb_config,
@@ -263,6 +267,8 @@ fn main() {
                     open::open_url,
                     forge::commands::get_available_review_templates,
                     forge::commands::get_review_template_contents,
+                    forge::commands::get_detected_forges,
+                    forge::commands::sync_stack_pull_requests_with_forge,
                     settings::get_app_settings,
                     settings::update_onboarding_complete,
                     settings::update_telemetry,
@@ -272,6 +278,7 @@ fn main() {
                     workspace::stack_branches,
                     workspace::stack_branch_local_and_remote_commits,
                     workspace::stack_branch_upstream_only_commits,
+                    workspace::commit_signature_status,
                     workspace::hunk_dependencies_for_workspace_changes,
                     workspace::create_commit_from_worktree_changes,
                     workspace::amend_commit_from_worktree_changes,
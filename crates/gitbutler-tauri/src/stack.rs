@@ -3,6 +3,7 @@ use gitbutler_branch_actions::stack::CreateSeriesRequest;
 use gitbutler_command_context::CommandContext;
 use gitbutler_project as projects;
 use gitbutler_project::ProjectId;
+use gitbutler_reference::RemoteRefname;
 use gitbutler_stack::StackId;
 use gitbutler_user::User;
 use tauri::State;
@@ -109,6 +110,40 @@ pub fn update_branch_pr_number(
     Ok(())
 }
 
+#[tauri::command(async)]
+#[instrument(skip(projects, windows, settings), err(Debug))]
+pub fn set_stack_push_remote(
+    windows: State<'_, WindowState>,
+    projects: State<'_, projects::Controller>,
+    settings: State<'_, AppSettingsWithDiskSync>,
+    project_id: ProjectId,
+    stack_id: StackId,
+    push_remote_name: Option<String>,
+) -> Result<(), Error> {
+    let project = projects.get(project_id)?;
+    let ctx = CommandContext::open(&project, settings.get()?.clone())?;
+    gitbutler_branch_actions::stack::set_stack_push_remote(&ctx, stack_id, push_remote_name)?;
+    emit_vbranches(&windows, project_id, ctx.app_settings());
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[instrument(skip(projects, windows, settings), err(Debug))]
+pub fn set_stack_upstream_target(
+    windows: State<'_, WindowState>,
+    projects: State<'_, projects::Controller>,
+    settings: State<'_, AppSettingsWithDiskSync>,
+    project_id: ProjectId,
+    stack_id: StackId,
+    upstream_target: Option<RemoteRefname>,
+) -> Result<(), Error> {
+    let project = projects.get(project_id)?;
+    let ctx = CommandContext::open(&project, settings.get()?.clone())?;
+    gitbutler_branch_actions::stack::set_stack_upstream_target(&ctx, stack_id, upstream_target)?;
+    emit_vbranches(&windows, project_id, ctx.app_settings());
+    Ok(())
+}
+
 #[tauri::command(async)]
 #[instrument(skip(projects, windows, settings), err(Debug))]
 pub fn push_stack(
@@ -4,7 +4,7 @@ use anyhow::Context;
 use but_settings::AppSettingsWithDiskSync;
 use gitbutler_command_context::CommandContext;
 use gitbutler_diff::FileDiff;
-use gitbutler_oplog::{entry::Snapshot, OplogExt};
+use gitbutler_oplog::{compare::SnapshotComparison, entry::Snapshot, OplogExt, RestoreSelection};
 use gitbutler_project as projects;
 use gitbutler_project::ProjectId;
 use gitbutler_stack::StackId;
@@ -47,6 +47,29 @@ pub fn restore_snapshot(
     Ok(())
 }
 
+#[tauri::command(async)]
+#[instrument(skip(projects, settings), err(Debug))]
+pub fn restore_snapshot_selection(
+    projects: State<'_, projects::Controller>,
+    settings: State<'_, AppSettingsWithDiskSync>,
+    project_id: ProjectId,
+    sha: String,
+    paths: Vec<PathBuf>,
+    stack_ids: Vec<StackId>,
+) -> Result<(), Error> {
+    let project = projects.get(project_id).context("failed to get project")?;
+    let ctx = CommandContext::open(&project, settings.get()?.clone())?;
+    gitbutler_branch_actions::restore_snapshot_selection(
+        &ctx,
+        sha.parse().map_err(anyhow::Error::from)?,
+        &RestoreSelection {
+            paths,
+            stacks: stack_ids,
+        },
+    )?;
+    Ok(())
+}
+
 #[tauri::command(async)]
 #[instrument(skip(projects), err(Debug))]
 pub fn snapshot_diff(
@@ -59,6 +82,22 @@ pub fn snapshot_diff(
     Ok(diff)
 }
 
+#[tauri::command(async)]
+#[instrument(skip(projects), err(Debug))]
+pub fn compare_snapshots(
+    projects: State<'_, projects::Controller>,
+    project_id: ProjectId,
+    previous_sha: String,
+    current_sha: String,
+) -> Result<SnapshotComparison, Error> {
+    let project = projects.get(project_id).context("failed to get project")?;
+    let comparison = project.compare_snapshots(
+        previous_sha.parse().map_err(anyhow::Error::from)?,
+        current_sha.parse().map_err(anyhow::Error::from)?,
+    )?;
+    Ok(comparison)
+}
+
 #[tauri::command(async)]
 #[instrument(skip(projects, settings), err(Debug))]
 pub fn take_synced_snapshot(
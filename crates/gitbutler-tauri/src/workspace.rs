@@ -70,6 +70,19 @@ pub fn stack_branch_local_and_remote_commits(
         .map_err(Into::into)
 }
 
+/// Verify the signature of the commit with `commit_id`, which is done on demand as it's expensive.
+#[tauri::command(async)]
+#[instrument(skip(projects), err(Debug))]
+pub fn commit_signature_status(
+    projects: State<'_, projects::Controller>,
+    project_id: ProjectId,
+    commit_id: HexHash,
+) -> Result<but_workspace::SignatureStatus, Error> {
+    let project = projects.get(project_id)?;
+    let repo = gix::open(&project.path).map_err(anyhow::Error::from)?;
+    but_workspace::commit_signature_status(&repo, commit_id.into()).map_err(Into::into)
+}
+
 #[tauri::command(async)]
 #[instrument(skip(projects, settings), err(Debug))]
 pub fn stack_branch_upstream_only_commits(
@@ -1,4 +1,8 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 
 use anyhow::{Context, Result};
 use but_settings::{AppSettings, AppSettingsWithDiskSync};
@@ -8,6 +12,7 @@ use gitbutler_diff::DiffByPathMap;
 use gitbutler_error::error::Marker;
 use gitbutler_operating_modes::{in_open_workspace_mode, operating_mode};
 use gitbutler_oplog::{
+    auto_snapshot::{create_branch_switch_snapshot, AutoSnapshotTrigger, HeadTracker},
     entry::{OperationKind, SnapshotDetails},
     OplogExt,
 };
@@ -30,6 +35,8 @@ pub struct Handler {
     // need extra protection.
     projects: projects::Controller,
     users: users::Controller,
+    /// What HEAD pointed to in each project, to only snapshot when leaving the workspace.
+    heads: Arc<Mutex<HashMap<ProjectId, HeadTracker>>>,
 
     /// A function to send events - decoupled from app-handle for testing purposes.
     #[allow(clippy::type_complexity)]
@@ -47,6 +54,7 @@ impl Handler {
         Handler {
             projects,
             users,
+            heads: Default::default(),
             send_event: Arc::new(send_event),
         }
     }
@@ -183,19 +191,28 @@ impl Handler {
     }
 
     fn maybe_create_snapshot(&self, project: &Project) -> anyhow::Result<()> {
-        if project
-            .should_auto_snapshot(std::time::Duration::from_secs(300))
+        if let Some(trigger) = project
+            .auto_snapshot_trigger(std::time::Duration::from_secs(300))
             .unwrap_or_default()
         {
-            let mut guard = project.exclusive_worktree_access();
-            project.create_snapshot(
-                SnapshotDetails::new(OperationKind::FileChanges),
-                guard.write_permission(),
-            )?;
+            self.create_auto_snapshot(project, trigger)?;
         }
         Ok(())
     }
 
+    fn create_auto_snapshot(
+        &self,
+        project: &Project,
+        trigger: AutoSnapshotTrigger,
+    ) -> anyhow::Result<()> {
+        let mut guard = project.exclusive_worktree_access();
+        project.create_snapshot(
+            SnapshotDetails::new(OperationKind::FileChanges).with_trailers(vec![trigger.into()]),
+            guard.write_permission(),
+        )?;
+        Ok(())
+    }
+
     pub fn git_files_change(&self, paths: Vec<PathBuf>, ctx: &CommandContext) -> Result<()> {
         for path in paths {
             let Some(file_name) = path.to_str() else {
@@ -215,6 +232,30 @@ impl Handler {
                     }
                 }
                 "HEAD" => {
+                    // GitButler rewrites HEAD whenever the workspace commit changes, which isn't a switch.
+                    let head_left_workspace = self
+                        .heads
+                        .lock()
+                        .expect("BUG: no panics while holding the lock")
+                        .entry(ctx.project().id)
+                        .or_default()
+                        .head_left_workspace(ctx.repo())?;
+                    if head_left_workspace
+                        && ctx
+                            .project()
+                            .auto_snapshot_policy
+                            .is_some_and(|policy| policy.on_branch_switch)
+                    {
+                        let mut guard = ctx.project().exclusive_worktree_access();
+                        if let Err(err) =
+                            create_branch_switch_snapshot(ctx.project(), guard.write_permission())
+                        {
+                            tracing::warn!(
+                                ?err,
+                                "failed to snapshot the workspace before the branch switch"
+                            );
+                        }
+                    }
                     let head_ref = ctx.repo().head().context("failed to get head")?;
                     if let Some(head) = head_ref.name() {
                         self.emit_app_event(Change::GitHead {
@@ -284,6 +284,7 @@ pub fn compute_updated_branch_head_for_commits(
     rebase.steps(Some(but_rebase::RebaseStep::Pick {
         commit_id: commited_tree.to_gix(),
         new_message: None,
+        base: None,
     }))?;
     rebase.rebase_noops(false);
     let output = rebase.rebase()?;